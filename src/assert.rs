@@ -1,11 +1,40 @@
-use crate::de::Deserializer;
-use crate::ser::Serializer;
-use crate::token::Token;
+use crate::capture::{capture_forbidding, capture_tokens};
+use crate::check::check_tokens;
+use crate::de::{Declared, Deserializer};
+use crate::diff::diff_tokens;
+use crate::display::DisplayTokens;
+use crate::error::{Error, ErrorKind};
+use crate::group;
+use crate::into_tokens::IntoTokens;
+use crate::located::Located;
+use crate::parse::parse_tokens;
+use crate::pattern::{assert_matches, ErrorPattern};
+use crate::pretty::{self, Pretty};
+use crate::schema::{self, TokenSchema};
+use crate::ser::{self, token_matches, Serializer};
+use crate::token::{OwnedToken, Token};
+use crate::validate::{validate_token_prefix, validate_tokens, TokenStreamError};
+use serde::de::{DeserializeOwned, DeserializeSeed};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "backtrace")]
+use std::backtrace::BacktraceStatus;
+use std::borrow::Cow;
+use std::env;
 use std::fmt::Debug;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::slice;
 
 /// Runs both `assert_ser_tokens` and `assert_de_tokens`.
 ///
+/// The serialize, deserialize and `deserialize_in_place` passes all run even
+/// if an earlier one fails, and the panic reports the outcome of each. A
+/// failed pass says whether the tokens did not match or the type returned
+/// its own error, and shows the numbered tokens on either side of where it
+/// failed, 3 by default or as many as the `SERDE_TEST_CONTEXT` environment
+/// variable says.
+///
 /// ```
 /// # use serde::{Deserialize, Serialize};
 /// # use serde_test::{assert_tokens, Token};
@@ -30,207 +59,3892 @@ use std::fmt::Debug;
 /// );
 /// ```
 #[track_caller]
-pub fn assert_tokens<'test, 'de, T>(value: &T, tokens: &[Token<'test, 'de>])
+pub fn assert_tokens<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>)
 where
     T: Serialize + Deserialize<'de> + PartialEq + Debug,
 {
-    assert_ser_tokens(value, tokens);
-    assert_de_tokens(value, tokens);
+    if let Err(msg) = tokens_case(value, &tokens.into_tokens()) {
+        panic!("{}", msg);
+    }
 }
 
-/// Asserts that `value` serializes to the given `tokens`.
+/// Runs [`assert_tokens`] on every `(value, tokens)` case, then panics once
+/// with the failures of all of them, numbered from 0.
+///
+/// ```
+/// # use serde_test::{assert_tokens_table, Token};
+/// #
+/// assert_tokens_table([
+///     (None, vec![Token::None]),
+///     (Some(0u8), vec![Token::Some, Token::U8(0)]),
+///     (Some(255u8), vec![Token::Some, Token::U8(255)]),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_tokens_table<'test, 'de: 'test, T, K>(cases: impl IntoIterator<Item = (T, K)>)
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+    K: IntoTokens<'test, 'de>,
+{
+    let mut count = 0;
+    let mut failures = Vec::new();
+    for (index, (value, tokens)) in cases.into_iter().enumerate() {
+        count += 1;
+        if let Err(msg) = tokens_case(&value, &tokens.into_tokens()) {
+            failures.push((index, msg));
+        }
+    }
+    report_table(&failures, count);
+}
+
+/// Asserts that `value` serializes to some token stream that deserializes
+/// back to an equal value, without spelling out the tokens.
+///
+/// The tokens are captured from `value` itself and then put through every
+/// pass of [`assert_tokens`]. This is a cheap smoke test for types whose
+/// exact representation is not the point.
 ///
 /// ```
 /// # use serde::{Deserialize, Serialize};
-/// # use serde_test::{assert_ser_tokens, Token};
+/// # use serde_test::assert_round_trip;
+/// # use std::collections::BTreeMap;
 /// #
 /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// struct S {
-///     a: u8,
-///     b: u8,
+/// enum E {
+///     A { names: BTreeMap<String, Vec<u8>> },
+///     B(Option<(i64, char)>),
 /// }
 ///
-/// let s = S { a: 0, b: 0 };
-/// assert_ser_tokens(
-///     &s,
-///     &[
-///         Token::Struct { name: "S", len: 2 },
-///         Token::Str("a"),
-///         Token::U8(0),
-///         Token::Str("b"),
-///         Token::U8(0),
-///         Token::StructEnd,
-///     ],
-/// );
+/// assert_round_trip(&E::A {
+///     names: BTreeMap::from([("x".to_owned(), vec![1, 2])]),
+/// });
+/// assert_round_trip(&E::B(Some((-1, 'y'))));
 /// ```
 #[track_caller]
-pub fn assert_ser_tokens<T: ?Sized>(value: &T, tokens: &[Token<'_, '_>])
+pub fn assert_round_trip<T>(value: &T)
 where
-    T: Serialize,
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
 {
-    let mut ser = Serializer::new(tokens);
-    match value.serialize(&mut ser) {
-        Ok(()) => {}
+    let captured = match capture_tokens(value) {
+        Ok(captured) => captured,
         Err(err) => panic!("value failed to serialize: {}", err),
+    };
+    let tokens: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+    if let Err(msg) = tokens_case(value, &tokens) {
+        panic!("{}\ncaptured tokens:\n{}", msg, DisplayTokens::new(&tokens));
     }
+}
 
-    if ser.remaining() > 0 {
-        panic!("{} remaining tokens", ser.remaining());
+/// Asserts that the struct, variant and field names `value` serializes with
+/// are the ones its `Deserialize` impl asks for.
+///
+/// The tokens are captured from `value` and deserialized back, recording the
+/// names passed to `deserialize_struct`, `deserialize_enum` and the other
+/// named `deserialize_*` methods. Every one of them is checked against the
+/// token at the same point of the stream: a struct or enum must have been
+/// serialized under the same name, with each of its fields among the
+/// declared `fields` and its variant among the declared `variants`. This
+/// catches hand-written impl pairs that have drifted apart, such as a field
+/// renamed on one side only. Declared fields that were not serialized are
+/// allowed, since they may be optional.
+///
+/// ```should_panic
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::assert_ser_de_symmetry;
+/// #
+/// #[derive(Serialize, Deserialize, Default)]
+/// struct Config {
+///     #[serde(rename(serialize = "timeout", deserialize = "timeout_secs"))]
+///     timeout: u32,
+/// }
+///
+/// // struct "Config" serialized fields ["timeout"] that deserialize_struct
+/// // does not expect, which are ["timeout_secs"]
+/// assert_ser_de_symmetry(&Config::default());
+/// ```
+#[track_caller]
+pub fn assert_ser_de_symmetry<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned,
+{
+    let captured = match capture_tokens(value) {
+        Ok(captured) => captured,
+        Err(err) => panic!("value failed to serialize: {}", err),
+    };
+    let tokens: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+    let mut de = Deserializer::new(&tokens).record_declared();
+    let result = T::deserialize(&mut de);
+    let mismatches: Vec<String> = de
+        .declared()
+        .iter()
+        .filter_map(|(position, declared)| asymmetry(&tokens, *position, declared))
+        .map(|(index, msg)| format!("{}\n{}", msg, around(&tokens, index)))
+        .collect();
+    match result {
+        Err(err) if mismatches.is_empty() => panic!(
+            "tokens failed to deserialize, so the names past index {} were not checked: {}\n{}",
+            failed_at(&err, de.position()),
+            err,
+            around(&tokens, failed_at(&err, de.position())),
+        ),
+        _ if mismatches.is_empty() => {}
+        Ok(_) => panic!("{}", mismatches.join("\n")),
+        Err(err) => panic!(
+            "{}\ntokens then failed to deserialize: {}",
+            mismatches.join("\n"),
+            err,
+        ),
     }
 }
 
-/// Asserts that `value` serializes to the given `tokens`, and then yields
-/// `error`.
+/// Asserts that `value` serializes to the token stream its [`TokenSchema`]
+/// describes, with `leaves` as its scalar tokens in order, and that those
+/// tokens deserialize back to an equal value.
+///
+/// Names, field names and lengths come from the schema, so only the leaf
+/// values need spelling out. Strings and bytes are serialized as
+/// [`Token::Str`] and [`Token::Bytes`].
 ///
 /// ```
-/// use serde::Serialize;
-/// use serde_test::{assert_ser_tokens_error, Token};
-/// use std::sync::{Arc, Mutex};
-/// use std::thread;
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::schema::{Schema, TokenSchema};
+/// # use serde_test::{assert_matches_schema, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+///     label: Option<String>,
+/// }
 ///
-/// #[derive(Serialize)]
-/// struct Example {
-///     lock: Arc<Mutex<u32>>,
+/// impl TokenSchema for Point {
+///     fn schema() -> Schema {
+///         Schema::structure("Point")
+///             .field("x", i32::schema())
+///             .field("y", i32::schema())
+///             .field("label", Option::<String>::schema())
+///     }
 /// }
 ///
-/// fn main() {
-///     let example = Example {
-///         lock: Arc::new(Mutex::new(0)),
-///     };
-///     let lock = example.lock.clone();
+/// assert_matches_schema(
+///     &vec![
+///         Point { x: 1, y: 2, label: None },
+///         Point { x: 3, y: 4, label: Some("far".to_owned()) },
+///     ],
+///     [
+///         Token::I32(1),
+///         Token::I32(2),
+///         Token::I32(3),
+///         Token::I32(4),
+///         Token::Str("far"),
+///     ],
+/// );
+/// ```
 ///
-///     let thread = thread::spawn(move || {
-///         // This thread will acquire the mutex first, unwrapping the result
-///         // of `lock` because the lock has not been poisoned.
-///         let _guard = lock.lock().unwrap();
+/// A `Serialize` impl that strays from the schema is caught even if it
+/// round trips:
 ///
-///         // This panic while holding the lock (`_guard` is in scope) will
-///         // poison the mutex.
-///         panic!()
-///     });
-///     thread.join();
+/// ```should_panic
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::schema::{Schema, TokenSchema};
+/// # use serde_test::{assert_matches_schema, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Point {
+///     #[serde(rename = "X")]
+///     x: i32,
+/// }
 ///
-///     let expected = &[
-///         Token::Struct {
-///             name: "Example",
-///             len: 1,
-///         },
-///         Token::Str("lock"),
-///     ];
-///     let error = "lock poison error while serializing";
-///     assert_ser_tokens_error(&example, expected, error);
+/// impl TokenSchema for Point {
+///     fn schema() -> Schema {
+///         Schema::structure("Point").field("x", i32::schema())
+///     }
 /// }
+///
+/// // expected one of the fields ["x"] but found Token::Str("X") at index 1
+/// assert_matches_schema(&Point { x: 1 }, [Token::I32(1)]);
 /// ```
 #[track_caller]
-pub fn assert_ser_tokens_error<T: ?Sized>(value: &T, tokens: &[Token<'_, '_>], error: &str)
+pub fn assert_matches_schema<'test, 'de: 'test, T>(value: &T, leaves: impl IntoTokens<'test, 'de>)
 where
-    T: Serialize,
+    T: TokenSchema + Serialize + DeserializeOwned + PartialEq + Debug,
 {
-    let mut ser = Serializer::new(tokens);
-    match value.serialize(&mut ser) {
-        Ok(()) => panic!("value serialized successfully"),
-        Err(e) => assert_eq!(e, *error),
+    let captured = match capture_tokens(value) {
+        Ok(captured) => captured,
+        Err(err) => panic!("value failed to serialize: {}", err),
+    };
+    let tokens: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+    if let Err((index, msg)) = schema::check(&T::schema(), &tokens, &leaves.into_tokens()) {
+        panic!("{}\n{}", msg, around(&tokens, index));
+    }
+    if let Err(msg) = tokens_case(value, &tokens) {
+        panic!("{}\ncaptured tokens:\n{}", msg, DisplayTokens::new(&tokens));
     }
+}
 
-    if ser.remaining() > 0 {
-        panic!("{} remaining tokens", ser.remaining());
+/// Like [`check_tokens`], but panics if anything mismatched, naming the
+/// source location of each token a mismatch was found at.
+///
+/// The tokens are made with [`Token::with_location`], which keeps the panic
+/// pointing at the offending line of a long token array.
+///
+/// ```should_panic
+/// # use serde_test::{assert_tokens_located, Token};
+/// #
+/// // serialize at index 1: value serialized as Token::U8(1)
+/// //   --> src/lib.rs:8:22
+/// assert_tokens_located(
+///     &Some(1u8),
+///     &[
+///         Token::Some.with_location(),
+///         Token::U8(0).with_location(),
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_located<'test, 'de: 'test, T>(value: &T, tokens: &[Located<'test, 'de>])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let report = check_tokens(value, tokens);
+    if report.is_ok() {
+        return;
+    }
+    let mut msg = format!("{} mismatches", report.mismatches().len());
+    for mismatch in report.mismatches() {
+        msg.push_str(&format!("\n{}", mismatch));
+        if let Some(located) = mismatch.position().and_then(|index| tokens.get(index)) {
+            msg.push_str(&format!("\n  --> {}", located.location()));
+        }
     }
+    panic!("{}", msg);
 }
 
-/// Asserts that the given `tokens` deserialize into `value`.
+/// Asserts that `value` serializes to `ser_tokens` and that `de_tokens`
+/// deserialize into `value`.
+///
+/// This is [`assert_tokens`] for types whose serialized form is only one of
+/// the forms they accept, such as types using `skip_serializing_if` or field
+/// aliases. Failures name the phase they happened in.
 ///
 /// ```
 /// # use serde::{Deserialize, Serialize};
-/// # use serde_test::{assert_de_tokens, Token};
+/// # use serde_test::{assert_tokens_asymmetric, Token};
 /// #
 /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
 /// struct S {
-///     a: u8,
-///     b: u8,
+///     #[serde(alias = "old")]
+///     new: u8,
 /// }
 ///
-/// let s = S { a: 0, b: 0 };
-/// assert_de_tokens(
-///     &s,
+/// assert_tokens_asymmetric(
+///     &S { new: 1 },
 ///     &[
-///         Token::Struct { name: "S", len: 2 },
-///         Token::Str("a"),
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("new"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("old"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_asymmetric<'ser, 'test, 'de: 'test, T>(
+    value: &T,
+    ser_tokens: impl IntoTokens<'ser, 'ser>,
+    de_tokens: impl IntoTokens<'test, 'de>,
+) where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let ser_tokens = ser_tokens.into_tokens();
+    let ser_tokens = &*ser_tokens;
+    let de_tokens = de_tokens.into_tokens();
+    let de_tokens = &*de_tokens;
+    if let Err(err) = validate_tokens(ser_tokens) {
+        panic!("serialize: invalid token stream: {}", err);
+    }
+    if let Err(err) = validate_tokens(de_tokens) {
+        panic!("deserialize: invalid token stream: {}", err);
+    }
+
+    let ser = ser_pass(value, ser_tokens, None);
+    let (place, de) = de_pass(value, de_tokens, None);
+    let de_in_place = place.map(|mut place| de_in_place_pass(value, de_tokens, &mut place, None));
+    report(&[
+        ("serialize", Some(ser)),
+        ("deserialize", Some(de)),
+        ("deserialize_in_place", de_in_place),
+    ]);
+}
+
+/// Asserts that `value` serializes to and deserializes from
+/// `readable_tokens` when `is_human_readable` answers `true`, and
+/// `compact_tokens` when it answers `false`.
+///
+/// Every pass runs in both modes, and failures name the mode and the pass.
+///
+/// ```
+/// # use serde_test::{assert_tokens_all_modes, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// assert_tokens_all_modes(
+///     &Ipv4Addr::LOCALHOST,
+///     &[Token::Str("127.0.0.1")],
+///     &[
+///         Token::Tuple { len: 4 },
+///         Token::U8(127),
 ///         Token::U8(0),
-///         Token::Str("b"),
 ///         Token::U8(0),
-///         Token::StructEnd,
+///         Token::U8(1),
+///         Token::TupleEnd,
 ///     ],
 /// );
 /// ```
 #[track_caller]
-pub fn assert_de_tokens<'test, 'de: 'test, T>(value: &T, tokens: &'test [Token<'test, 'de>])
+pub fn assert_tokens_all_modes<'test, 'de: 'test, T>(
+    value: &T,
+    readable_tokens: impl IntoTokens<'test, 'de>,
+    compact_tokens: impl IntoTokens<'test, 'de>,
+) where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let readable_tokens = readable_tokens.into_tokens();
+    let readable_tokens = &*readable_tokens;
+    let compact_tokens = compact_tokens.into_tokens();
+    let compact_tokens = &*compact_tokens;
+    if let Err(err) = validate_tokens(readable_tokens) {
+        panic!("readable: invalid token stream: {}", err);
+    }
+    if let Err(err) = validate_tokens(compact_tokens) {
+        panic!("compact: invalid token stream: {}", err);
+    }
+
+    let mut passes = Vec::new();
+    for (names, tokens, readable) in [
+        (
+            [
+                "readable serialize",
+                "readable deserialize",
+                "readable deserialize_in_place",
+            ],
+            readable_tokens,
+            true,
+        ),
+        (
+            [
+                "compact serialize",
+                "compact deserialize",
+                "compact deserialize_in_place",
+            ],
+            compact_tokens,
+            false,
+        ),
+    ] {
+        let ser = ser_pass(value, tokens, Some(readable));
+        let (place, de) = de_pass(value, tokens, Some(readable));
+        let de_in_place =
+            place.map(|mut place| de_in_place_pass(value, tokens, &mut place, Some(readable)));
+        passes.push((names[0], Some(ser)));
+        passes.push((names[1], Some(de)));
+        passes.push((names[2], de_in_place));
+    }
+    report(&passes);
+}
+
+/// The environment variable that makes [`assert_tokens_golden`] rewrite its
+/// file instead of comparing against it.
+const UPDATE_GOLDEN_VAR: &str = "SERDE_TEST_UPDATE";
+
+/// Asserts that `value` serializes to the token stream stored in the file at
+/// `path`, and that the stream deserializes back into `value`.
+///
+/// The file holds the stream in the format rendered by
+/// [`DisplayTokens`](crate::DisplayTokens). When the `SERDE_TEST_UPDATE`
+/// environment variable is set to anything but `0`, the file is instead
+/// (re)written from the serialized value, so a legitimate change of
+/// representation is accepted by rerunning the tests and reviewing the diff:
+///
+/// ```text
+/// SERDE_TEST_UPDATE=1 cargo test
+/// ```
+///
+/// ```
+/// # use serde_test::assert_tokens_golden;
+/// #
+/// # let path = std::env::temp_dir().join("serde_test_golden_doc.tokens");
+/// # std::fs::write(&path, "Tuple { len: 2 },\n    I32(1),\n    Str(\"a\"),\nTupleEnd,\n").unwrap();
+/// #
+/// // Tuple { len: 2 },
+/// //     I32(1),
+/// //     Str("a"),
+/// // TupleEnd,
+/// assert_tokens_golden(&(1, "a".to_owned()), &path);
+/// ```
+#[track_caller]
+pub fn assert_tokens_golden<T, P>(value: &T, path: P)
 where
-    T: Deserialize<'de> + PartialEq + Debug,
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+    P: AsRef<Path>,
 {
-    let mut de = Deserializer::new(tokens);
-    let mut deserialized_val = match T::deserialize(&mut de) {
-        Ok(v) => {
-            assert_eq!(v, *value);
-            v
-        }
-        Err(e) => panic!("tokens failed to deserialize: {}", e),
+    let path = path.as_ref();
+    let captured = match capture_tokens(value) {
+        Ok(tokens) => tokens,
+        Err(err) => panic!("value failed to serialize: {}", err),
     };
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
-    }
+    let captured: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
 
-    // Do the same thing for deserialize_in_place. This isn't *great* because a
-    // no-op impl of deserialize_in_place can technically succeed here. Still,
-    // this should catch a lot of junk.
-    let mut de = Deserializer::new(tokens);
-    match T::deserialize_in_place(&mut de, &mut deserialized_val) {
-        Ok(()) => {
-            assert_eq!(deserialized_val, *value);
+    let update = env::var_os(UPDATE_GOLDEN_VAR).map_or(false, |v| !v.is_empty() && v != "0");
+    if update {
+        if let Some(dir) = path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                panic!("failed to create {}: {}", dir.display(), err);
+            }
+        }
+        if let Err(err) = fs::write(path, DisplayTokens::new(&captured).to_string()) {
+            panic!("failed to write {}: {}", path.display(), err);
+        }
+    } else {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => panic!(
+                "failed to read {}: {}\nrerun with {}=1 to create it",
+                path.display(),
+                err,
+                UPDATE_GOLDEN_VAR,
+            ),
+        };
+        let golden = match parse_tokens(&text) {
+            Ok(tokens) => tokens,
+            Err(err) => panic!("failed to parse {}: {}", path.display(), err),
+        };
+        if let Some(index) = (0..=golden.len().max(captured.len()))
+            .find(|&i| golden.get(i).map(OwnedToken::as_token) != captured.get(i).copied())
+        {
+            let show = |token: Option<Token<'_, '_>>| match token {
+                Some(token) => format!("Token::{}", token),
+                None => "end of tokens".to_owned(),
+            };
+            let golden: Vec<Token<'_, '_>> = golden.iter().map(OwnedToken::as_token).collect();
+            panic!(
+                "serialized tokens differ from {} at index {}\n  golden: {}\n  actual: {}\n{}rerun with {}=1 to accept the new tokens",
+                path.display(),
+                index,
+                show(golden.get(index).copied()),
+                show(captured.get(index).copied()),
+                diff_tokens(&golden, &captured),
+                UPDATE_GOLDEN_VAR,
+            );
         }
-        Err(e) => panic!("tokens failed to deserialize_in_place: {}", e),
-    }
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
     }
+
+    assert_valid(validate_tokens(&captured));
+    let (place, de) = de_pass(value, &captured, None);
+    let de_in_place = place.map(|mut place| de_in_place_pass(value, &captured, &mut place, None));
+    report(&[
+        ("deserialize", Some(de)),
+        ("deserialize_in_place", de_in_place),
+    ]);
 }
 
-/// Asserts that the given `tokens` yield `error` when deserializing.
+/// Asserts that `value` serializes to the given `tokens`.
 ///
 /// ```
 /// # use serde::{Deserialize, Serialize};
-/// # use serde_test::{assert_de_tokens_error, Token};
+/// # use serde_test::{assert_ser_tokens, Token};
 /// #
 /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// #[serde(deny_unknown_fields)]
 /// struct S {
 ///     a: u8,
 ///     b: u8,
 /// }
 ///
-/// assert_de_tokens_error::<S>(
+/// let s = S { a: 0, b: 0 };
+/// assert_ser_tokens(
+///     &s,
 ///     &[
 ///         Token::Struct { name: "S", len: 2 },
-///         Token::Str("x"),
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::Str("b"),
+///         Token::U8(0),
+///         Token::StructEnd,
 ///     ],
-///     "unknown field `x`, expected `a` or `b`",
 /// );
 /// ```
+///
+/// On a mismatch, the value goes on serializing so that the failure can show
+/// the whole of what it serialized as. A value that never stops, such as one
+/// wrapping itself forever, is cut off after a few times as many tokens as
+/// expected, or [`NESTING_DEPTH`](crate::pathological::NESTING_DEPTH)
+/// levels deeper, and the original mismatch is reported.
+///
+/// ```should_panic
+/// # use serde::ser::{Serialize, Serializer};
+/// # use serde_test::{assert_ser_tokens, Token};
+/// #
+/// struct Recursive;
+///
+/// impl Serialize for Recursive {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         serializer.serialize_some(self)
+///     }
+/// }
+///
+/// // expected Token::U8(1) at index 1 of 2 but serialized as Some
+/// assert_ser_tokens(&Recursive, &[Token::Some, Token::U8(1)]);
+/// ```
 #[track_caller]
-pub fn assert_de_tokens_error<'de, T>(tokens: &[Token<'_, 'de>], error: &str)
+pub fn assert_ser_tokens<'test, T>(value: &T, tokens: impl IntoTokens<'test, 'test>)
 where
-    T: Deserialize<'de>,
+    T: ?Sized + Serialize,
 {
-    let mut de = Deserializer::new(tokens);
-    match T::deserialize(&mut de) {
-        Ok(_) => panic!("tokens deserialized successfully"),
-        Err(e) => assert_eq!(e.msg(), error),
+    if let Err(msg) = ser_tokens_case(value, &tokens.into_tokens()) {
+        panic!("{}", msg);
     }
+}
 
-    // FIXME ????
-    // There may be one token left if a peek caused the error
-    de.next_token_opt();
+/// Like [`assert_ser_tokens`], but also serializes `value` behind `&T`,
+/// `Box<&T>` and `Cow<&T>`, each of which must give the same tokens.
+///
+/// Serde's impls for these pointers forward to `T`'s, so this is close to
+/// running [`assert_ser_tokens`] once per wrapper. It only catches a
+/// `Serialize` impl that gives different tokens from one call to the next,
+/// or a serializer-side specialization for `Box` or `Cow` that departs from
+/// `T`'s impl. The pointers wrap a reference to `value`, so it need not be
+/// `Clone`, or even `Sized`. Every failing wrapper is reported together.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::{assert_ser_tokens_via_wrappers, Token};
+/// #
+/// #[derive(Serialize)]
+/// struct Meters(f64);
+///
+/// assert_ser_tokens_via_wrappers(
+///     &Meters(1.5),
+///     &[Token::NewtypeStruct { name: "Meters" }, Token::F64(1.5)],
+/// );
+/// assert_ser_tokens_via_wrappers("m", &[Token::Str("m")]);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_via_wrappers<'test, T>(value: &T, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    assert_valid(validate_tokens(&tokens));
 
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
+    report(&[
+        ("T", Some(ser_pass(value, &tokens, None))),
+        ("&T", Some(ser_pass(&value, &tokens, None))),
+        ("Box<&T>", Some(ser_pass(&Box::new(value), &tokens, None))),
+        (
+            "Cow<&T>",
+            Some(ser_pass(&Cow::<&T>::Borrowed(&value), &tokens, None)),
+        ),
+    ]);
+}
+
+/// Runs [`assert_ser_tokens`] on every `(value, tokens)` case, then panics
+/// once with the failures of all of them, numbered from 0.
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_table, Token};
+/// #
+/// assert_ser_tokens_table([
+///     ("a", vec![Token::Str("a")]),
+///     ("bc", vec![Token::Str("bc")]),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_table<'test, T, K>(cases: impl IntoIterator<Item = (T, K)>)
+where
+    T: Serialize,
+    K: IntoTokens<'test, 'test>,
+{
+    let mut count = 0;
+    let mut failures = Vec::new();
+    for (index, (value, tokens)) in cases.into_iter().enumerate() {
+        count += 1;
+        if let Err(msg) = ser_tokens_case(&value, &tokens.into_tokens()) {
+            failures.push((index, msg));
+        }
+    }
+    report_table(&failures, count);
+}
+
+/// Asserts that `value` serializes to `tokens` every one of `times` times in
+/// a row, to catch `Serialize` impls that change state as they run, such as
+/// ones draining a `Cell`-held iterator.
+///
+/// The panic names the first attempt that failed, counting from 1.
+///
+/// ```should_panic
+/// # use serde::ser::{Serialize, Serializer};
+/// # use serde_test::{assert_ser_tokens_repeat, Token};
+/// # use std::cell::Cell;
+/// #
+/// struct Once(Cell<Option<u8>>);
+///
+/// impl Serialize for Once {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         serializer.serialize_u8(self.0.take().unwrap_or_default())
+///     }
+/// }
+///
+/// // serialization 2 of 3 failed: value failed to serialize (token mismatch, fix the test): expected Token::U8(1) at index 0 of 1 ...
+/// assert_ser_tokens_repeat(&Once(Cell::new(Some(1))), &[Token::U8(1)], 3);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_repeat<'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'test>,
+    times: usize,
+) where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    for attempt in 1..=times {
+        if let Err(msg) = ser_tokens_case(value, &tokens) {
+            panic!("serialization {} of {} failed: {}", attempt, times, msg);
+        }
+    }
+}
+
+/// Asserts that serializing `value` never calls any of the `forbidden`
+/// `Serializer` methods, such as `"serialize_bytes"` or `"serialize_map"`.
+///
+/// No tokens are given, so this states a contract about the representation
+/// of values whose streams are too large or variable to spell out. The panic
+/// names the forbidden method and shows the tokens serialized up to its call.
+///
+/// ```should_panic
+/// # use serde_test::assert_ser_never_calls;
+/// # use std::collections::BTreeMap;
+/// #
+/// let mut value = BTreeMap::new();
+/// value.insert("a", vec![1.0, 2.5]);
+///
+/// assert_ser_never_calls(&value, &["serialize_bytes"]);
+///
+/// // serialize_f64 was called at index 3, but the test forbids it
+/// assert_ser_never_calls(&value, &["serialize_f64"]);
+/// ```
+#[track_caller]
+pub fn assert_ser_never_calls<T>(value: &T, forbidden: &[&str])
+where
+    T: ?Sized + Serialize,
+{
+    let (captured, result) = capture_forbidding(value, forbidden);
+    if let Err(err) = result {
+        let tokens: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+        let index = err.position().unwrap_or(tokens.len());
+        panic!("{}\n{}", err, around(&tokens, index));
+    }
+}
+
+/// Asserts that `value`, taken by value, serializes to the given `tokens`.
+///
+/// The value is serialized exactly once and then dropped, so `Serialize`
+/// impls that drain an iterator or a channel can be tested. Unlike
+/// [`assert_ser_tokens`], a failure does not serialize the value again to
+/// show what it produced, only the tokens around the failure.
+///
+/// ```
+/// # use serde::ser::{Serialize, Serializer};
+/// # use serde_test::{assert_ser_tokens_once, Token};
+/// # use std::cell::RefCell;
+/// #
+/// /// Serializes the items of an iterator, which it can only do once.
+/// struct Drain<I>(RefCell<Option<I>>);
+///
+/// impl<I> Serialize for Drain<I>
+/// where
+///     I: Iterator<Item = u8>,
+/// {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let iter = self.0.borrow_mut().take().expect("already serialized");
+///         serializer.collect_seq(iter)
+///     }
+/// }
+///
+/// assert_ser_tokens_once(
+///     Drain(RefCell::new(Some(1..3))),
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_once<'test, T>(value: T, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut ser = Serializer::new(tokens);
+    if let Err(err) = value.serialize(&mut ser) {
+        let index = err.position().unwrap_or(ser.position());
+        panic!(
+            "{}\n{}",
+            failure("value failed to serialize", &err, index, tokens.len()),
+            around(tokens, index),
+        );
+    }
+    if let Err(err) = ser.check_ended() {
+        panic!("{}", err);
+    }
+    if ser.remaining() > 0 {
+        panic!("{}", leftover(ser.position(), ser.remaining_tokens()));
+    }
+}
+
+/// Asserts that `value` serializes to a stream that starts with `tokens`.
+///
+/// Serialization is cut short after the last of `tokens`, so the rest of the
+/// value is never produced. This works for values too large to serialize in
+/// full, or that never end.
+///
+/// ```
+/// # use serde::ser::{Serialize, SerializeSeq, Serializer};
+/// # use serde_test::{assert_ser_tokens_prefix, Token};
+/// #
+/// /// Serializes the natural numbers, forever.
+/// struct Naturals;
+///
+/// impl Serialize for Naturals {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let mut seq = serializer.serialize_seq(None)?;
+///         for n in 0u64.. {
+///             seq.serialize_element(&n)?;
+///         }
+///         seq.end()
+///     }
+/// }
+///
+/// assert_ser_tokens_prefix(
+///     &Naturals,
+///     &[Token::Seq { len: None }, Token::U64(0), Token::U64(1)],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_prefix<'test, T>(value: &T, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_token_prefix(tokens));
+
+    let mut ser = Serializer::new(tokens);
+    match value.serialize(&mut ser) {
+        // Ran past the end of the prefix, which is where it is cut short.
+        Err(err) if err.kind() == ErrorKind::EndOfTokens && ser.remaining() == 0 => {}
+        Err(err) => panic!("{}", err),
+        Ok(()) => {
+            if let Err(err) = ser.check_ended() {
+                panic!("{}", err);
+            }
+            if ser.remaining() > 0 {
+                panic!("{}", leftover(ser.position(), ser.remaining_tokens()));
+            }
+        }
+    }
+}
+
+/// Asserts that `value` serializes to a stream containing the run of `tokens`
+/// somewhere, in order and without gaps.
+///
+/// Each token matches the way [`assert_ser_tokens`] would accept it: a
+/// [`Token::OneOf`] matches any of its alternatives, a [`Token::Matches`]
+/// whatever passes its predicate, a [`Token::Number`] an integer of any
+/// width, and floats by IEEE equality. Strings and bytes in the serialized
+/// stream are always [`Token::Str`] and [`Token::Bytes`], as with
+/// [`capture_tokens`](crate::capture_tokens), which the owned and borrowed
+/// forms also match.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::{assert_ser_tokens_contains, Token};
+/// #
+/// #[derive(Serialize)]
+/// struct Config {
+///     name: &'static str,
+///     retries: u8,
+///     hosts: Vec<&'static str>,
+/// }
+///
+/// let config = Config {
+///     name: "prod",
+///     retries: 3,
+///     hosts: vec!["a", "b", "c"],
+/// };
+/// assert_ser_tokens_contains(&config, &[Token::Str("retries"), Token::U8(3)]);
+/// assert_ser_tokens_contains(
+///     &config,
+///     &[Token::OneOf(&[Token::Str("b"), Token::Str("B")]), Token::Str("c")],
+/// );
+/// assert_ser_tokens_contains(&vec![-0.0f32], &[Token::F32(0.0)]);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_contains<'test, T>(value: &T, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let captured = match capture_tokens(value) {
+        Ok(tokens) => tokens,
+        Err(err) => panic!("value failed to serialize: {}", err),
+    };
+    let captured: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+
+    // The start of the longest run matching a prefix of `tokens`.
+    let mut closest = (0, 0);
+    for start in 0..captured.len() {
+        let matched = captured[start..]
+            .iter()
+            .zip(tokens)
+            .take_while(|&(&found, &expected)| token_matches(expected, found))
+            .count();
+        if matched == tokens.len() {
+            return;
+        }
+        if matched > closest.1 {
+            closest = (start, matched);
+        }
+    }
+    if tokens.is_empty() {
+        return;
+    }
+
+    let (start, matched) = closest;
+    let found = match captured.get(start + matched) {
+        Some(token) => format!("Token::{}", token),
+        None => "end of tokens".to_owned(),
+    };
+    panic!(
+        "tokens not found in the {} serialized tokens\nclosest match at index {} matches {} of {} tokens, then expected Token::{} but found {}\n  closest: {:#}",
+        captured.len(),
+        start,
+        matched,
+        tokens.len(),
+        tokens[matched],
+        found,
+        DisplayTokens::new(&captured[start..captured.len().min(start + tokens.len())]),
+    );
+}
+
+/// Asserts that none of the `tokens` appear anywhere in the stream `value`
+/// serializes to.
+///
+/// This checks `skip_serializing` and redaction without spelling out the
+/// rest of the stream. Tokens match as in [`assert_ser_tokens_contains`], so
+/// a [`Token::Matches`] excludes every value passing its predicate. Every
+/// occurrence is reported, with the tokens around the first.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::ser::{Event, Predicate};
+/// # use serde_test::{assert_ser_tokens_excludes, Token};
+/// #
+/// #[derive(Serialize)]
+/// struct Login {
+///     user: &'static str,
+///     #[serde(skip_serializing)]
+///     password: &'static str,
+/// }
+///
+/// let login = Login {
+///     user: "admin",
+///     password: "hunter2",
+/// };
+/// assert_ser_tokens_excludes(&login, &[Token::Str("password"), Token::Str("hunter2")]);
+///
+/// static SECRET: Predicate = Predicate::new("secret", |event| {
+///     matches!(event, Event::Str(s) if s.starts_with("hunter"))
+/// });
+/// assert_ser_tokens_excludes(&login, &[Token::Matches(&SECRET)]);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_excludes<'test, T>(value: &T, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let captured = match capture_tokens(value) {
+        Ok(tokens) => tokens,
+        Err(err) => panic!("value failed to serialize: {}", err),
+    };
+    let captured: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+
+    let found: Vec<usize> = (0..captured.len())
+        .filter(|&i| {
+            tokens
+                .iter()
+                .any(|&token| token_matches(token, captured[i]))
+        })
+        .collect();
+    if let Some(&first) = found.first() {
+        let found: Vec<String> = found
+            .iter()
+            .map(|&i| format!("Token::{} at index {}", captured[i], i))
+            .collect();
+        panic!(
+            "excluded tokens found in the {} serialized tokens: {}\n{}",
+            captured.len(),
+            found.join(", "),
+            around(&captured, first),
+        );
+    }
+}
+
+/// Asserts that `value` serializes to the given `tokens`, and then yields
+/// `error`.
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_test::{assert_ser_tokens_error, Token};
+/// use std::sync::{Arc, Mutex};
+/// use std::thread;
+///
+/// #[derive(Serialize)]
+/// struct Example {
+///     lock: Arc<Mutex<u32>>,
+/// }
+///
+/// fn main() {
+///     let example = Example {
+///         lock: Arc::new(Mutex::new(0)),
+///     };
+///     let lock = example.lock.clone();
+///
+///     let thread = thread::spawn(move || {
+///         // This thread will acquire the mutex first, unwrapping the result
+///         // of `lock` because the lock has not been poisoned.
+///         let _guard = lock.lock().unwrap();
+///
+///         // This panic while holding the lock (`_guard` is in scope) will
+///         // poison the mutex.
+///         panic!()
+///     });
+///     thread.join();
+///
+///     let expected = &[
+///         Token::Struct {
+///             name: "Example",
+///             len: 1,
+///         },
+///         Token::Str("lock"),
+///     ];
+///     let error = "lock poison error while serializing";
+///     assert_ser_tokens_error(&example, expected, error);
+/// }
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error<'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'test>,
+    error: impl ErrorMessages,
+) where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, consumed) = ser_error(value, tokens);
+    if !error.messages().contains(&err.msg()) {
+        panic!(
+            "{}, raised after {} of {} tokens were serialized\n{}",
+            unexpected_message(&err, error.messages()),
+            consumed,
+            tokens.len(),
+            around(tokens, consumed.saturating_sub(1)),
+        );
+    }
+    if let Some(rest) = unconsumed(tokens, consumed) {
+        panic!("{}", rest);
+    }
+}
+
+/// Asserts that `value` yields `error` after serializing exactly the first
+/// `consumed` of the given `tokens`.
+///
+/// [`assert_ser_tokens_error`] wants the tokens to end where the error was
+/// raised. This instead takes the full stream the value would serialize to,
+/// and states how much of it is consumed before the error.
+///
+/// ```
+/// # use serde::ser::{Error, Serialize, SerializeSeq, Serializer};
+/// # use serde_test::{assert_ser_tokens_error_consumed, Token};
+/// #
+/// struct Truncated;
+///
+/// impl Serialize for Truncated {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let mut seq = serializer.serialize_seq(Some(2))?;
+///         seq.serialize_element(&1u8)?;
+///         Err(S::Error::custom("disk full"))
+///     }
+/// }
+///
+/// let tokens = [
+///     Token::Seq { len: Some(2) },
+///     Token::U8(1),
+///     Token::U8(2),
+///     Token::SeqEnd,
+/// ];
+/// assert_ser_tokens_error_consumed(&Truncated, &tokens, "disk full", 2);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error_consumed<'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'test>,
+    error: impl ErrorMessages,
+    consumed: usize,
+) where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, actual) = ser_error(value, tokens);
+    assert_message(&err, error.messages());
+    if actual != consumed {
+        panic!(
+            "expected the error after {} tokens were serialized but it was raised after {}\n{}",
+            consumed,
+            actual,
+            around(tokens, actual.saturating_sub(1)),
+        );
+    }
+}
+
+/// Asserts that `value` serializes to the given `tokens`, and then yields an
+/// error matched by `pattern`.
+///
+/// ```
+/// # use serde::ser::{Error, Serialize, Serializer};
+/// # use serde_test::pattern::contains;
+/// # use serde_test::{assert_ser_tokens_error_matches, Token};
+/// #
+/// struct Flaky;
+///
+/// impl Serialize for Flaky {
+///     fn serialize<S>(&self, _: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         Err(S::Error::custom("connection reset (os error 104)"))
+///     }
+/// }
+///
+/// let tokens: [Token; 0] = [];
+/// assert_ser_tokens_error_matches(&Flaky, tokens, contains("connection reset"));
+/// assert_ser_tokens_error_matches(&Flaky, tokens, |msg: &str| msg.ends_with(')'));
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error_matches<'test, T, P>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'test>,
+    pattern: P,
+) where
+    T: ?Sized + Serialize,
+    P: ErrorPattern,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, consumed) = ser_error(value, tokens);
+    assert_matches(err.msg(), &pattern);
+    if let Some(rest) = unconsumed(tokens, consumed) {
+        panic!("{}", rest);
+    }
+}
+
+/// Asserts that `value` serializes to the given `tokens`, and then yields
+/// `error` of the given `kind`.
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_error_kind, ErrorKind, Token};
+/// #
+/// assert_ser_tokens_error_kind(
+///     &Some(1u8),
+///     &[Token::Some, Token::U16(1)],
+///     ErrorKind::Mismatch,
+///     "expected Token::U16(1) at index 1 of 2 but serialized as U8(1)",
+/// );
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error_kind<'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'test>,
+    kind: ErrorKind,
+    error: impl ErrorMessages,
+) where
+    T: ?Sized + Serialize,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, consumed) = ser_error(value, tokens);
+    assert_kind(&err, kind);
+    assert_message(&err, error.messages());
+    if let Some(rest) = unconsumed(tokens, consumed) {
+        panic!("{}", rest);
+    }
+}
+
+/// Asserts that a `serialize_with` function serializes `value` to the given
+/// `tokens`, without a wrapper type to call it through.
+///
+/// The function is given a [`Serializer`](crate::ser::Serializer) by value,
+/// which checks that no tokens are left over.
+///
+/// ```
+/// # use serde::Serializer;
+/// # use serde_test::{assert_ser_with, Token};
+/// #
+/// fn as_hex<S>(v: &u32, serializer: S) -> Result<S::Ok, S::Error>
+/// where
+///     S: Serializer,
+/// {
+///     serializer.serialize_str(&format!("{:x}", v))
+/// }
+///
+/// assert_ser_with(&255, as_hex, &[Token::Str("ff")]);
+/// ```
+#[track_caller]
+pub fn assert_ser_with<'test, T, F>(value: &T, serialize: F, tokens: impl IntoTokens<'test, 'test>)
+where
+    T: ?Sized,
+    F: FnOnce(&T, Serializer<'test>) -> Result<(), Error>,
+{
+    let tokens = leak_tokens(tokens);
+    assert_valid(validate_tokens(tokens));
+
+    if let Err(err) = ser_with_pass(value, serialize, tokens) {
+        panic!("{}", err);
+    }
+}
+
+/// Asserts that the given `tokens` deserialize into `value`.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_tokens, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// let s = S { a: 0, b: 0 };
+/// assert_de_tokens(
+///     &s,
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::Str("b"),
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    if let Err(msg) = de_tokens_case(value, &tokens.into_tokens()) {
+        panic!("{}", msg);
+    }
+}
+
+/// Like [`assert_de_tokens`], but without the `deserialize_in_place` pass.
+///
+/// For types whose `deserialize_in_place` is deliberately not equivalent to
+/// `deserialize`, such as one that merges into the existing value.
+///
+/// ```
+/// # use serde::{Deserialize, Deserializer};
+/// # use serde_test::{assert_de_tokens_no_in_place, Token};
+/// #
+/// // Deserializing in place adds to the count instead of replacing it.
+/// #[derive(PartialEq, Debug)]
+/// struct Counter(u32);
+///
+/// impl<'de> Deserialize<'de> for Counter {
+///     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+///         u32::deserialize(deserializer).map(Counter)
+///     }
+///
+///     fn deserialize_in_place<D: Deserializer<'de>>(
+///         deserializer: D,
+///         place: &mut Self,
+///     ) -> Result<(), D::Error> {
+///         place.0 += u32::deserialize(deserializer)?;
+///         Ok(())
+///     }
+/// }
+///
+/// assert_de_tokens_no_in_place(&Counter(3), &[Token::U32(3)]);
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_no_in_place<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'de>,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    if let (_, Err(msg)) = de_pass(value, tokens, None) {
+        panic!("{}", msg);
+    }
+}
+
+/// Asserts that `deserialize_in_place` from the given `tokens` turns
+/// `initial` into `expected`.
+///
+/// [`assert_de_tokens`] only deserializes in place into a value it has just
+/// deserialized from the same tokens, which hides stale state that an impl
+/// forgets to clear. Starting from a different `initial` value catches that.
+///
+/// ```
+/// # use serde_test::{assert_de_in_place_tokens, Token};
+/// #
+/// assert_de_in_place_tokens(
+///     vec!["stale".to_owned(), "state".to_owned(), "here".to_owned()],
+///     &[
+///         Token::Seq { len: Some(1) },
+///         Token::Str("fresh"),
+///         Token::SeqEnd,
+///     ],
+///     &vec!["fresh".to_owned()],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_in_place_tokens<'test, 'de: 'test, T>(
+    initial: T,
+    tokens: impl IntoTokens<'test, 'de>,
+    expected: &T,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    if let Err(msg) = de_in_place_case(initial, &tokens.into_tokens(), expected, None) {
+        panic!("{}", msg);
+    }
+}
+
+/// Like [`assert_de_in_place_tokens`], but also asserts that the allocation
+/// whose `capacity` is given was reused rather than replaced.
+///
+/// The capacity must be the same before and after, so the tokens should fit
+/// in what `initial` has already allocated.
+///
+/// ```
+/// # use serde_test::{assert_de_in_place_reuses_capacity, Token};
+/// #
+/// let mut initial = Vec::with_capacity(16);
+/// initial.extend_from_slice(&[7u8; 10]);
+///
+/// assert_de_in_place_reuses_capacity(
+///     initial,
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+///     &vec![1, 2],
+///     Vec::capacity,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_in_place_reuses_capacity<'test, 'de: 'test, T, F>(
+    initial: T,
+    tokens: impl IntoTokens<'test, 'de>,
+    expected: &T,
+    capacity: F,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+    F: Fn(&T) -> usize,
+{
+    let tokens = tokens.into_tokens();
+    if let Err(msg) = de_in_place_case(initial, &tokens, expected, Some(&capacity)) {
+        panic!("{}", msg);
+    }
+}
+
+/// Runs [`assert_de_tokens`] on every `(value, tokens)` case, then panics
+/// once with the failures of all of them, numbered from 0.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_table, Token};
+/// #
+/// assert_de_tokens_table([
+///     (1u32, vec![Token::U8(1)]),
+///     (1u32, vec![Token::U64(1)]),
+///     (1u32, vec![Token::I16(1)]),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_table<'test, 'de: 'test, T, K>(cases: impl IntoIterator<Item = (T, K)>)
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+    K: IntoTokens<'test, 'de>,
+{
+    let mut count = 0;
+    let mut failures = Vec::new();
+    for (index, (value, tokens)) in cases.into_iter().enumerate() {
+        count += 1;
+        if let Err(msg) = de_tokens_case(&value, &tokens.into_tokens()) {
+            failures.push((index, msg));
+        }
+    }
+    report_table(&failures, count);
+}
+
+/// Like [`assert_de_tokens`], but for types that own all their data.
+///
+/// Such a type copies whatever it is given, so besides the usual passes the
+/// tokens are deserialized once more with every string and bytes token only
+/// lent for the visit, as [`Token::Str`] and [`Token::Bytes`]. A visitor
+/// that only takes ownership, through `visit_string` or `visit_byte_buf`,
+/// fails that pass.
+///
+/// ```should_panic
+/// # use serde::de::{Deserialize, Deserializer, Visitor};
+/// # use serde_test::{assert_de_tokens_owned, Token};
+/// # use std::fmt;
+/// #
+/// #[derive(PartialEq, Debug)]
+/// struct Name(String);
+///
+/// impl<'de> Deserialize<'de> for Name {
+///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         struct NameVisitor;
+///
+///         impl<'de> Visitor<'de> for NameVisitor {
+///             type Value = Name;
+///
+///             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///                 f.write_str("a name")
+///             }
+///
+///             fn visit_string<E>(self, v: String) -> Result<Name, E> {
+///                 Ok(Name(v))
+///             }
+///         }
+///
+///         deserializer.deserialize_string(NameVisitor)
+///     }
+/// }
+///
+/// // deserialize with transient tokens: ... invalid type: string "sky", expected a name
+/// assert_de_tokens_owned(&Name("sky".to_owned()), &[Token::String("sky")]);
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_owned<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>)
+where
+    T: DeserializeOwned + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let transient: Vec<Token<'_, '_>> = tokens
+        .iter()
+        .map(|&token| match token {
+            Token::BorrowedStr(v) | Token::String(v) => Token::Str(v),
+            Token::BorrowedBytes(v) | Token::ByteBuf(v) => Token::Bytes(v),
+            token => token,
+        })
+        .collect();
+    let (place, de) = de_pass(value, tokens, None);
+    let de_in_place = place.map(|mut place| de_in_place_pass(value, tokens, &mut place, None));
+    let (_, de_transient) = de_pass(value, &transient, None);
+    report(&[
+        ("deserialize", Some(de)),
+        ("deserialize_in_place", de_in_place),
+        ("deserialize with transient tokens", Some(de_transient)),
+    ]);
+}
+
+/// Like [`assert_de_tokens`], but without requiring `T: Debug`.
+///
+/// Failures are reported in terms of token positions only, so values that
+/// must never be printed (secrets, FFI handles) can still be tested.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_tokens_opaque, Token};
+/// #
+/// #[derive(Deserialize, PartialEq)]
+/// struct Secret(String);
+///
+/// assert_de_tokens_opaque(
+///     &Secret("hunter2".to_owned()),
+///     &[Token::NewtypeStruct { name: "Secret" }, Token::Str("hunter2")],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_opaque<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de> + PartialEq,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    let mut deserialized_val = match T::deserialize(&mut de) {
+        Ok(v) => v,
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    };
+    if deserialized_val != *value {
+        panic!(
+            "value deserialized from tokens 0..{} does not equal the expected value",
+            de.position(),
+        );
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+
+    let mut de = Deserializer::new(tokens);
+    if let Err(e) = T::deserialize_in_place(&mut de, &mut deserialized_val) {
+        deserialize_failed(
+            "tokens failed to deserialize_in_place",
+            &e,
+            de.position(),
+            tokens,
+        );
+    }
+    if deserialized_val != *value {
+        panic!(
+            "value deserialized in place from tokens 0..{} does not equal the expected value",
+            de.position(),
+        );
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+}
+
+/// Runs both `assert_ser_tokens` and `assert_de_tokens_opaque`, without
+/// requiring `T: Debug`.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_tokens_opaque, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq)]
+/// struct Handle(u64);
+///
+/// assert_tokens_opaque(
+///     &Handle(7),
+///     &[Token::NewtypeStruct { name: "Handle" }, Token::U64(7)],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_opaque<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Serialize + Deserialize<'de> + PartialEq,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_ser_tokens(value, tokens);
+    assert_de_tokens_opaque(value, tokens);
+}
+
+/// Asserts that the given `tokens` deserialize into a value accepted by
+/// `check`.
+///
+/// This is [`assert_de_tokens`] for types without a usable `PartialEq`, such
+/// as types containing NaN or interior mutability. `check` is called with the
+/// result of `deserialize` and again with the result of
+/// `deserialize_in_place`, and should panic if the value is wrong.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_tokens_with, Token};
+/// # use std::cell::Cell;
+/// #
+/// #[derive(Deserialize)]
+/// struct Reading {
+///     value: f64,
+///     seen: Cell<bool>,
+/// }
+///
+/// assert_de_tokens_with(
+///     &[
+///         Token::Struct { name: "Reading", len: 2 },
+///         Token::Str("value"),
+///         Token::F64(f64::NAN),
+///         Token::Str("seen"),
+///         Token::Bool(false),
+///         Token::StructEnd,
+///     ],
+///     |r: Reading| {
+///         assert!(r.value.is_nan());
+///         assert!(!r.seen.get());
+///     },
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_with<'test, 'de: 'test, T, F>(
+    tokens: impl IntoTokens<'test, 'de>,
+    mut check: F,
+) where
+    T: Deserialize<'de>,
+    F: FnMut(T),
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(v) => check(v),
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+
+    // deserialize_in_place needs a place to deserialize into, and without
+    // Clone the only way to get one is to deserialize again.
+    let mut de = Deserializer::new(tokens);
+    let mut place = match T::deserialize(&mut de) {
+        Ok(v) => v,
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    };
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize_in_place(&mut de, &mut place) {
+        Ok(()) => check(place),
+        Err(e) => deserialize_failed(
+            "tokens failed to deserialize_in_place",
+            &e,
+            de.position(),
+            tokens,
+        ),
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+}
+
+/// Asserts that a `deserialize_with` function deserializes the given `tokens`
+/// into `expected`, without a wrapper type to call it through.
+///
+/// The function is given a [`Deserializer`](crate::de::Deserializer) by
+/// value, which checks that no tokens are left over.
+///
+/// ```
+/// # use serde::{Deserialize, Deserializer};
+/// # use serde_test::{assert_de_with, Token};
+/// #
+/// fn from_hex<'de, D>(deserializer: D) -> Result<u32, D::Error>
+/// where
+///     D: Deserializer<'de>,
+/// {
+///     use serde::de::Error;
+///     let s = <&str>::deserialize(deserializer)?;
+///     u32::from_str_radix(s, 16).map_err(D::Error::custom)
+/// }
+///
+/// assert_de_with(from_hex, &[Token::BorrowedStr("ff")], &255);
+/// ```
+#[track_caller]
+pub fn assert_de_with<'test, 'de: 'test, T, F>(
+    deserialize: F,
+    tokens: impl IntoTokens<'test, 'de>,
+    expected: &T,
+) where
+    T: PartialEq + Debug,
+    F: FnOnce(Deserializer<'test, 'de>) -> Result<T, Error>,
+{
+    let tokens = leak_tokens(tokens);
+    assert_valid(validate_tokens(tokens));
+
+    if let Err(err) = de_with_pass(deserialize, tokens, expected) {
+        panic!("{}", err);
+    }
+}
+
+/// A variant of an untagged enum `E` for [`assert_de_untagged`]: its name,
+/// and a function deserializing its contents into `E`.
+pub type UntaggedVariant<'a, 'test, 'de, E> = (
+    &'a str,
+    fn(&mut Deserializer<'test, 'de>) -> Result<E, Error>,
+);
+
+/// Asserts that the given `tokens` deserialize into the `expected_variant` of
+/// an untagged enum `E`, and explains which variants rejected them if not.
+///
+/// Serde tries the variants of an `#[serde(untagged)]` enum in order and
+/// reports only "data did not match any variant". Here each variant is
+/// replayed on its own: `variants` lists them in declaration order, each
+/// with a function deserializing its contents into `E`, and the panic
+/// message gives the token and error at which each one was rejected.
+///
+/// The test also fails if `variants` disagrees with `E` on the variant the
+/// tokens deserialize into.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_untagged, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// #[serde(untagged)]
+/// enum E {
+///     Int(u8),
+///     Pair(u8, bool),
+///     Text(String),
+/// }
+///
+/// assert_de_untagged::<E>(
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::U8(1),
+///         Token::Bool(true),
+///         Token::TupleEnd,
+///     ],
+///     "Pair",
+///     &[
+///         ("Int", |de| u8::deserialize(de).map(E::Int)),
+///         ("Pair", |de| <(u8, bool)>::deserialize(de).map(|(a, b)| E::Pair(a, b))),
+///         ("Text", |de| String::deserialize(de).map(E::Text)),
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_untagged<'test, 'de: 'test, E>(
+    tokens: impl IntoTokens<'test, 'de>,
+    expected_variant: &str,
+    variants: &[UntaggedVariant<'_, 'test, 'de, E>],
+) where
+    E: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = leak_tokens(tokens);
+    assert_valid(validate_tokens(tokens));
+    if !variants.iter().any(|&(name, _)| name == expected_variant) {
+        panic!(
+            "`{}` is not one of the variants given: {:?}",
+            expected_variant,
+            variants.iter().map(|&(name, _)| name).collect::<Vec<_>>(),
+        );
+    }
+
+    let mut de = Deserializer::new(tokens);
+    let deserialized = match E::deserialize(&mut de) {
+        Ok(_) if de.remaining() > 0 => Err(leftover(de.position(), de.remaining_tokens())),
+        Ok(v) => Ok(v),
+        Err(err) => Err(err.to_string()),
+    };
+
+    let attempts: Vec<Result<E, String>> = variants
+        .iter()
+        .map(|&(_, deserialize)| {
+            let mut de = Deserializer::new(tokens);
+            match deserialize(&mut de) {
+                Ok(_) if de.remaining() > 0 => Err(format!(
+                    "with {} tokens left over at token {}",
+                    de.remaining(),
+                    de.position(),
+                )),
+                Ok(v) => Ok(v),
+                Err(err) => Err(format!(
+                    "at index {} of {}: {}",
+                    failed_at(&err, de.position()),
+                    tokens.len(),
+                    err,
+                )),
+            }
+        })
+        .collect();
+    let matched = attempts.iter().position(Result::is_ok);
+
+    let failure = match (&deserialized, matched) {
+        (Err(err), _) => format!("they failed to deserialize: {}", err),
+        (Ok(v), None) => format!(
+            "none of the variants given matched, though they deserialized as {:?}",
+            v,
+        ),
+        (Ok(_), Some(i)) if variants[i].0 != expected_variant => {
+            format!("variant `{}` matched first", variants[i].0)
+        }
+        (Ok(v), Some(i)) => match &attempts[i] {
+            Ok(attempt) if attempt != v => format!(
+                "variant `{}` gave {:?} where the enum gave {:?}",
+                variants[i].0, attempt, v,
+            ),
+            _ => return,
+        },
+    };
+
+    let mut msg = format!(
+        "expected tokens to deserialize as variant `{}`, but {}\nvariants attempted, in order:",
+        expected_variant, failure,
+    );
+    for (i, (&(name, _), attempt)) in variants.iter().zip(&attempts).enumerate() {
+        let outcome = match attempt {
+            Err(err) => format!("rejected {}", err),
+            Ok(_) if matched == Some(i) => "matched".to_owned(),
+            Ok(_) => "would match, but is never reached".to_owned(),
+        };
+        msg.push_str(&format!("\n  {}: {}", name, outcome));
+    }
+    panic!("{}", msg);
+}
+
+/// Runs both [`assert_ser_with`] and [`assert_de_with`], which is how a
+/// `#[serde(with = "...")]` attribute uses the module or type it names.
+///
+/// This tests the shim type of a `#[serde(remote = "...")]` derive directly,
+/// with no local type to use it in. The tokens name the shim type, not the
+/// remote one.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_with, Token};
+/// #
+/// mod other_crate {
+///     #[derive(PartialEq, Debug)]
+///     pub struct Duration {
+///         pub secs: i64,
+///         pub nanos: i32,
+///     }
+/// }
+/// use other_crate::Duration;
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(remote = "Duration")]
+/// struct DurationDef {
+///     secs: i64,
+///     nanos: i32,
+/// }
+///
+/// assert_with(
+///     &Duration { secs: 1, nanos: 0 },
+///     DurationDef::serialize,
+///     DurationDef::deserialize,
+///     &[
+///         Token::Struct { name: "DurationDef", len: 2 },
+///         Token::Str("secs"),
+///         Token::I64(1),
+///         Token::Str("nanos"),
+///         Token::I32(0),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+///
+/// [`assert_remote_tokens!`](crate::assert_remote_tokens) does the same given
+/// just `DurationDef`.
+#[track_caller]
+pub fn assert_with<'test, 'de: 'test, T, S, D>(
+    value: &T,
+    serialize: S,
+    deserialize: D,
+    tokens: impl IntoTokens<'test, 'de>,
+) where
+    T: PartialEq + Debug,
+    S: FnOnce(&T, Serializer<'test>) -> Result<(), Error>,
+    D: FnOnce(Deserializer<'test, 'de>) -> Result<T, Error>,
+{
+    let tokens = leak_tokens(tokens);
+    assert_valid(validate_tokens(tokens));
+
+    let ser = ser_with_pass(value, serialize, tokens);
+    let de = de_with_pass(deserialize, tokens, value);
+    report(&[("serialize", Some(ser)), ("deserialize", Some(de))]);
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing.
+///
+/// `error` may also be a list of messages, any one of which is accepted. See
+/// [`ErrorMessages`].
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_tokens_error, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// assert_de_tokens_error::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("x"),
+///     ],
+///     "unknown field `x`, expected `a` or `b`",
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    error: impl ErrorMessages,
+) where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, rest) = de_error(PhantomData::<T>, tokens);
+    assert_message(&err, error.messages());
+    if let Some(rest) = rest {
+        panic!("{}", rest);
+    }
+}
+
+/// The messages [`assert_de_tokens_error`], [`assert_ser_tokens_error`] and
+/// the other error asserts accept: a single `&str`, or a slice or array of
+/// them when the wording differs between versions of serde or of another
+/// dependency.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_error, Token};
+/// #
+/// assert_de_tokens_error::<u8>(
+///     &[Token::Bool(true)],
+///     [
+///         "invalid type: boolean `true`, expected u8",
+///         "invalid type: bool `true`, expected u8",
+///     ],
+/// );
+/// ```
+pub trait ErrorMessages {
+    fn messages(&self) -> &[&str];
+}
+
+impl ErrorMessages for &str {
+    fn messages(&self) -> &[&str] {
+        slice::from_ref(self)
+    }
+}
+
+impl ErrorMessages for &[&str] {
+    fn messages(&self) -> &[&str] {
+        self
+    }
+}
+
+impl<const N: usize> ErrorMessages for [&str; N] {
+    fn messages(&self) -> &[&str] {
+        self
+    }
+}
+
+impl<const N: usize> ErrorMessages for &[&str; N] {
+    fn messages(&self) -> &[&str] {
+        *self
+    }
+}
+
+/// Panics unless the message of `err` is one of `expected`.
+#[track_caller]
+fn assert_message(err: &Error, expected: &[&str]) {
+    match expected {
+        [expected] => assert_eq!(err.msg(), *expected),
+        _ => {
+            if !expected.contains(&err.msg()) {
+                panic!("{}", unexpected_message(err, expected));
+            }
+        }
+    }
+}
+
+fn unexpected_message(err: &Error, expected: &[&str]) -> String {
+    match expected {
+        [expected] => format!(
+            "expected the error message {:?} but the error was {:?}",
+            expected,
+            err.msg(),
+        ),
+        _ => format!(
+            "expected one of the error messages {:?} but the error was {:?}",
+            expected,
+            err.msg(),
+        ),
+    }
+}
+
+/// Runs [`assert_de_tokens_error`] on every `(tokens, error)` case, then
+/// panics once with the failures of all of them, numbered from 0.
+///
+/// ```
+/// # use serde_test::{assert_de_errors_table, Token};
+/// #
+/// assert_de_errors_table::<u8, _>([
+///     (vec![Token::Bool(true)], "invalid type: boolean `true`, expected u8"),
+///     (vec![Token::I8(-1)], "invalid value: integer `-1`, expected u8"),
+///     (vec![Token::Unit], "invalid type: unit value, expected u8"),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_de_errors_table<'test, 'de: 'test, T, K>(
+    cases: impl IntoIterator<Item = (K, impl ErrorMessages)>,
+) where
+    T: Deserialize<'de>,
+    K: IntoTokens<'test, 'de>,
+{
+    let mut count = 0;
+    let mut failures = Vec::new();
+    for (index, (tokens, error)) in cases.into_iter().enumerate() {
+        count += 1;
+        if let Err(msg) = de_error_case(PhantomData::<T>, &tokens.into_tokens(), error.messages()) {
+            failures.push((index, msg));
+        }
+    }
+    report_table(&failures, count);
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing, leaving
+/// exactly the `remaining` tokens unconsumed.
+///
+/// [`assert_de_tokens_error`] wants the stream to end where the error was
+/// raised. This instead states what is left over, for errors that are
+/// raised partway through the stream.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_error_remaining, Token};
+/// #
+/// assert_de_tokens_error_remaining::<(u8,)>(
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::TupleEnd,
+///     ],
+///     "visit_seq returned at index 2 leaving 1 elements of the compound at index 0 unvisited: 2u8",
+///     &[Token::U8(2), Token::TupleEnd],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_remaining<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    error: impl ErrorMessages,
+    remaining: &[Token<'_, '_>],
+) where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_token_prefix(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(_) => panic!("tokens deserialized successfully"),
+        Err(err) => assert_message(&err, error.messages()),
+    }
+
+    if de.remaining_tokens() != remaining {
+        panic!(
+            "expected {} remaining tokens but found {}",
+            remaining.len(),
+            leftover(de.position(), de.remaining_tokens()),
+        );
+    }
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing, and that
+/// it is raised at the token at `index`.
+///
+/// The index is that of the token the deserializer was reading, the one
+/// failure messages mark. Pinning it catches errors that are still raised,
+/// but only after reading much further into the stream than intended.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_error_at, Token};
+/// #
+/// assert_de_tokens_error_at::<(u8, u8)>(
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::U8(1),
+///         Token::Bool(true),
+///         Token::TupleEnd,
+///     ],
+///     "invalid type: boolean `true`, expected u8",
+///     2,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_at<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    error: impl ErrorMessages,
+    index: usize,
+) where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_token_prefix(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    let err = match T::deserialize(&mut de) {
+        Ok(_) => panic!("tokens deserialized successfully"),
+        Err(err) => err,
+    };
+    assert_message(&err, error.messages());
+
+    let at = failed_at(&err, de.position());
+    if at != index {
+        panic!(
+            "expected the error at index {} but it was raised at index {}\n{}",
+            index,
+            at,
+            around(tokens, at),
+        );
+    }
+}
+
+/// Asserts that deserializing `T` over and over from `tokens` gives each
+/// of the `expected` outcomes in turn, with errors given by their message.
+///
+/// After each error, the deserializer is rewound to where the value began
+/// and skips past all of it, so the values after it are still read. This is
+/// how a streaming or iterator `Deserialize` wrapper is expected to recover
+/// from one bad element.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_errors_then_continues, Token};
+/// #
+/// assert_de_tokens_errors_then_continues::<u8>(
+///     &[
+///         Token::U8(1),
+///         Token::Seq { len: Some(1) },
+///         Token::U8(2),
+///         Token::SeqEnd,
+///         Token::U8(3),
+///     ],
+///     &[
+///         Ok(1),
+///         Err("invalid type: sequence, expected u8"),
+///         Ok(3),
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_errors_then_continues<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    expected: &[Result<T, &str>],
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let mut de = Deserializer::new(tokens);
+    for (index, expected_outcome) in expected.iter().enumerate() {
+        let checkpoint = de.checkpoint();
+        let outcome = T::deserialize(&mut de);
+        match (&outcome, expected_outcome) {
+            (Ok(found), Ok(value)) if found == value => {}
+            (Err(err), Err(msg)) if err.msg() == *msg => {
+                de.rewind(checkpoint);
+                if let Err(err) = de.skip_value() {
+                    panic!(
+                        "value {} of {} could not be skipped after its error: {}",
+                        index,
+                        expected.len(),
+                        err,
+                    );
+                }
+            }
+            _ => panic!(
+                "value {} of {} does not match\n     found: {:?}\n  expected: {:?}",
+                index,
+                expected.len(),
+                outcome.as_ref().map_err(Error::msg),
+                expected_outcome,
+            ),
+        }
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+}
+
+/// Asserts that the given `tokens` yield an error matched by `pattern` when
+/// deserializing.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::pattern::starts_with;
+/// # use serde_test::{assert_de_tokens_error_matches, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_error_matches::<S, _>(
+///     &[Token::Struct { name: "S", len: 1 }, Token::Str("x")],
+///     starts_with("unknown field `x`"),
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_matches<'test, 'de: 'test, T, P>(
+    tokens: impl IntoTokens<'test, 'de>,
+    pattern: P,
+) where
+    T: Deserialize<'de>,
+    P: ErrorPattern,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, rest) = de_error(PhantomData::<T>, tokens);
+    assert_matches(err.msg(), &pattern);
+    if let Some(rest) = rest {
+        panic!("{}", rest);
+    }
+}
+
+/// Asserts that the given `tokens` yield `error` of the given `kind` when
+/// deserializing.
+///
+/// This tells an error raised by the type's `Deserialize` impl apart from
+/// the same message coming from the token stream running out or not
+/// matching.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_tokens_error_kind, ErrorKind, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_error_kind::<S>(
+///     &[Token::Struct { name: "S", len: 0 }, Token::StructEnd],
+///     ErrorKind::Custom,
+///     "missing field `a`",
+/// );
+/// assert_de_tokens_error_kind::<S>(
+///     &[Token::Struct { name: "S", len: 1 }, Token::Str("a")],
+///     ErrorKind::EndOfTokens,
+///     "ran out of tokens to deserialize after all 2 tokens",
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_kind<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    kind: ErrorKind,
+    error: impl ErrorMessages,
+) where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, rest) = de_error(PhantomData::<T>, tokens);
+    assert_kind(&err, kind);
+    assert_message(&err, error.messages());
+    if let Some(rest) = rest {
+        panic!("{}", rest);
+    }
+}
+
+/// Asserts that the given `tokens` fail to deserialize, with any error.
+///
+/// Use this to pin down that a type must not silently accept some input,
+/// when the exact error message is not part of the contract.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_rejects, Token};
+/// #
+/// #[derive(Deserialize)]
+/// struct UserId(u64);
+///
+/// assert_de_rejects::<UserId>(&[
+///     Token::NewtypeStruct { name: "UserId" },
+///     Token::I8(-1),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_de_rejects<'test, 'de: 'test, T>(tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_token_prefix(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    if T::deserialize(&mut de).is_ok() {
+        panic!("tokens deserialized successfully");
+    }
+}
+
+#[track_caller]
+fn assert_rejects_each<T>(kind: &str, samples: &[Token<'static, 'static>])
+where
+    T: DeserializeOwned,
+{
+    for sample in samples {
+        let mut de = Deserializer::new(std::slice::from_ref(sample));
+        if T::deserialize(&mut de).is_ok() {
+            panic!("{} token Token::{} deserialized successfully", kind, sample);
+        }
+    }
+}
+
+/// Asserts that a lone float token never deserializes, whatever its value.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::assert_rejects_floats;
+/// #
+/// #[derive(Deserialize)]
+/// struct UserId(u64);
+///
+/// assert_rejects_floats::<UserId>();
+/// ```
+#[track_caller]
+pub fn assert_rejects_floats<T>()
+where
+    T: DeserializeOwned,
+{
+    assert_rejects_each::<T>(
+        "float",
+        &[
+            Token::F32(0.0),
+            Token::F32(1.0),
+            Token::F32(-1.5),
+            Token::F64(0.0),
+            Token::F64(1.0),
+            Token::F64(-1.5),
+            Token::F64(f64::NAN),
+            Token::F64(f64::INFINITY),
+        ],
+    );
+}
+
+/// Asserts that a lone string token never deserializes, whatever its value
+/// and however it is handed to the visitor.
+///
+/// ```
+/// # use serde_test::assert_rejects_strings;
+/// #
+/// assert_rejects_strings::<Option<u32>>();
+/// ```
+#[track_caller]
+pub fn assert_rejects_strings<T>()
+where
+    T: DeserializeOwned,
+{
+    assert_rejects_each::<T>(
+        "string",
+        &[
+            Token::Str(""),
+            Token::Str("0"),
+            Token::Str("true"),
+            Token::BorrowedStr("0"),
+            Token::String("0"),
+            Token::String("a"),
+        ],
+    );
+}
+
+/// Asserts that a lone bytes token never deserializes, whatever its value
+/// and however it is handed to the visitor.
+///
+/// ```
+/// # use serde_test::assert_rejects_bytes;
+/// #
+/// assert_rejects_bytes::<u32>();
+/// ```
+#[track_caller]
+pub fn assert_rejects_bytes<T>()
+where
+    T: DeserializeOwned,
+{
+    assert_rejects_each::<T>(
+        "bytes",
+        &[
+            Token::Bytes(b""),
+            Token::Bytes(b"0"),
+            Token::BorrowedBytes(b"0"),
+            Token::ByteBuf(b"0"),
+            Token::ByteBuf(b"\xff"),
+        ],
+    );
+}
+
+/// How many times [`assert_de_error_deterministic`] deserializes the tokens.
+const DETERMINISM_RUNS: usize = 16;
+
+/// Asserts that the given `tokens` fail to deserialize with the same error,
+/// at the same token, every time.
+///
+/// Error messages built from unordered collections, such as an "expected one
+/// of" list collected from a `HashMap`, can differ between runs and make
+/// [`assert_de_tokens_error`] tests flaky. This deserializes `tokens`
+/// repeatedly and panics if any run disagrees with the first.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_error_deterministic, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// assert_de_error_deterministic::<S>(&[
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("x"),
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_de_error_deterministic<'test, 'de: 'test, T>(tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_token_prefix(tokens));
+
+    let run = || {
+        let mut de = Deserializer::new(tokens);
+        match T::deserialize(&mut de) {
+            Ok(_) => panic!("tokens deserialized successfully"),
+            Err(e) => (e, de.position()),
+        }
+    };
+
+    let (first, first_position) = run();
+    for i in 1..DETERMINISM_RUNS {
+        let (error, position) = run();
+        if error.msg() != first.msg() || position != first_position {
+            panic!(
+                "run {} failed with `{}` at token {}, but run 0 failed with `{}` at token {}",
+                i, error, position, first, first_position,
+            );
+        }
+    }
+}
+
+/// Asserts that the given `tokens` still deserialize to the same value after
+/// renaming a field key to each of its aliases.
+///
+/// For every `(field, aliases)` pair, each map or struct key equal to `field`
+/// is rewritten to one alias at a time. This systematically covers
+/// `#[serde(alias = "...")]` attributes.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_aliases, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     #[serde(alias = "Name", alias = "NAME")]
+///     name: String,
+/// }
+///
+/// assert_de_aliases::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("name"),
+///         Token::Str("sky"),
+///         Token::StructEnd,
+///     ],
+///     &[("name", &["Name", "NAME"])],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_aliases<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    aliases: &[(&str, &[&'de str])],
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    let expected = match T::deserialize(&mut de) {
+        Ok(v) => v,
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    };
+
+    let entries = group::all_entries(tokens);
+    for &(field, field_aliases) in aliases {
+        let keys: Vec<usize> = entries
+            .iter()
+            .map(|entry| entry.key.start)
+            .filter(|&i| tokens[i].as_str() == Some(field))
+            .collect();
+        if keys.is_empty() {
+            panic!("field `{}` does not appear as a key in the tokens", field);
+        }
+
+        for &alias in field_aliases {
+            let mut renamed = tokens.to_vec();
+            for &i in &keys {
+                renamed[i] = Token::Str(alias);
+            }
+
+            let mut de = Deserializer::new(&renamed);
+            match T::deserialize(&mut de) {
+                Ok(v) => assert_eq!(v, expected, "with `{}` renamed to `{}`", field, alias),
+                Err(e) => panic!(
+                    "tokens with `{}` renamed to `{}` failed to deserialize: {}",
+                    field, alias, e,
+                ),
+            }
+            if de.remaining() > 0 {
+                panic!("{}", leftover(de.position(), de.remaining_tokens()));
+            }
+        }
+    }
+}
+
+/// Asserts that the given `tokens` still deserialize to `expected` with an
+/// unknown entry added to a map or struct.
+///
+/// `extra` is the entry, one key and one value. It is inserted at every
+/// position of every map and struct in `tokens` in turn, with the declared
+/// `len` adjusted, which covers the paths that skip unknown fields through
+/// `deserialize_ignored_any`.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_tokens_ignores_extra, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_ignores_extra(
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+///     &[
+///         Token::Str("unknown"),
+///         Token::Seq { len: None },
+///         Token::Bool(true),
+///         Token::SeqEnd,
+///     ],
+///     &S { a: 1 },
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_ignores_extra<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    extra: impl IntoTokens<'test, 'de>,
+    expected: &T,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+    let extra = extra.into_tokens();
+    let extra = &*extra;
+    let entry_end = group::value_end(extra, 0).and_then(|key_end| group::value_end(extra, key_end));
+    if entry_end != Some(extra.len()) {
+        panic!("extra tokens must be one key followed by one value");
+    }
+
+    let mut injected = false;
+    for header in 0..tokens.len() {
+        let entries = match group::entries(tokens, header) {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let with_len = resized(tokens[header], |len| len + 1);
+        let end = group::value_end(tokens, header).unwrap() - 1;
+        let positions = entries.iter().map(|entry| entry.key.start).chain(Some(end));
+        for position in positions {
+            injected = true;
+            let mut stream = tokens.to_vec();
+            stream[header] = with_len;
+            stream.splice(position..position, extra.iter().copied());
+
+            let mut de = Deserializer::new(&stream);
+            match T::deserialize(&mut de) {
+                Ok(v) => assert_eq!(
+                    v, *expected,
+                    "with the extra entry inserted at index {}",
+                    position,
+                ),
+                Err(e) => deserialize_failed(
+                    &format!(
+                        "tokens with the extra entry inserted at index {} failed to deserialize",
+                        position,
+                    ),
+                    &e,
+                    de.position(),
+                    &stream,
+                ),
+            }
+            if de.remaining() > 0 {
+                panic!("{}", leftover(de.position(), de.remaining_tokens()));
+            }
+        }
+    }
+    if !injected {
+        panic!("tokens contain no map or struct to add the extra entry to");
+    }
+}
+
+/// Asserts that the given `tokens` deserialize to `expected` whatever order
+/// the fields of each map and struct come in.
+///
+/// The entries of every map and struct in `tokens` are reordered in turn,
+/// into every permutation for up to 6 entries and otherwise into every
+/// rotation and the reverse of each. Formats such as JSON do not keep keys
+/// in declaration order, which `Deserialize` impls sometimes assume.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_tokens_any_field_order, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     b: bool,
+///     c: char,
+/// }
+///
+/// assert_de_tokens_any_field_order(
+///     &[
+///         Token::Struct { name: "S", len: 3 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::Str("b"),
+///         Token::Bool(true),
+///         Token::Str("c"),
+///         Token::Char('x'),
+///         Token::StructEnd,
+///     ],
+///     &S { a: 1, b: true, c: 'x' },
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_any_field_order<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    expected: &T,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut reordered = false;
+    for header in 0..tokens.len() {
+        let entries = match group::entries(tokens, header) {
+            Some(entries) if entries.len() >= 2 => entries,
+            _ => continue,
+        };
+        let end = group::value_end(tokens, header).unwrap() - 1;
+        // Markers between the entries stay after them.
+        let markers = (header + 1..end)
+            .filter(|&i| {
+                !entries
+                    .iter()
+                    .any(|entry| (entry.key.start..entry.value.end).contains(&i))
+            })
+            .map(|i| tokens[i]);
+        let markers: Vec<Token<'test, 'de>> = markers.collect();
+
+        for order in field_orders(entries.len()) {
+            reordered = true;
+            let mut stream = tokens[..=header].to_vec();
+            for &i in &order {
+                stream.extend_from_slice(&tokens[entries[i].key.start..entries[i].value.end]);
+            }
+            stream.extend_from_slice(&markers);
+            stream.extend_from_slice(&tokens[end..]);
+
+            let mut de = Deserializer::new(&stream);
+            match T::deserialize(&mut de) {
+                Ok(v) => assert_eq!(
+                    v, *expected,
+                    "with the entries at index {} in the order {:?}",
+                    header, order,
+                ),
+                Err(e) => deserialize_failed(
+                    &format!(
+                        "tokens with the entries at index {} in the order {:?} failed to deserialize",
+                        header, order,
+                    ),
+                    &e,
+                    de.position(),
+                    &stream,
+                ),
+            }
+            if de.remaining() > 0 {
+                panic!("{}", leftover(de.position(), de.remaining_tokens()));
+            }
+        }
+    }
+    if !reordered {
+        panic!("tokens contain no map or struct with entries to reorder");
+    }
+}
+
+/// The orders [`assert_de_tokens_any_field_order`] tries for `n` entries,
+/// other than the original one.
+fn field_orders(n: usize) -> Vec<Vec<usize>> {
+    let mut orders = Vec::new();
+    if n <= 6 {
+        // Every permutation, in lexicographic order.
+        let mut order: Vec<usize> = (0..n).collect();
+        while let Some(i) = (1..n).rev().find(|&i| order[i - 1] < order[i]) {
+            let j = (i..n).rev().find(|&j| order[i - 1] < order[j]).unwrap();
+            order.swap(i - 1, j);
+            order[i..].reverse();
+            orders.push(order.clone());
+        }
+    } else {
+        for shift in 0..n {
+            let rotated: Vec<usize> = (0..n).map(|i| (i + shift) % n).collect();
+            if shift > 0 {
+                orders.push(rotated.clone());
+            }
+            orders.push(rotated.into_iter().rev().collect());
+        }
+    }
+    orders
+}
+
+/// How a type handles a field given twice, for
+/// [`assert_de_duplicate_field`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum OnDuplicate<T> {
+    /// Fails with serde's "duplicate field" error, as derived impls do.
+    Reject,
+    /// Keeps the value given last, and deserializes to the value held.
+    LastWins(T),
+}
+
+/// Asserts that the given `tokens` are handled as `on_duplicate` says with
+/// `field` given a second time, holding `value`.
+///
+/// The duplicate entry is inserted at every position after the original in
+/// every map and struct that has `field`, with the declared `len` adjusted.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_duplicate_field, OnDuplicate, Token};
+/// # use std::collections::BTreeMap;
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// let tokens = [
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("a"),
+///     Token::U8(1),
+///     Token::Str("b"),
+///     Token::U8(2),
+///     Token::StructEnd,
+/// ];
+/// assert_de_duplicate_field::<S>(&tokens, "a", [Token::U8(3)], OnDuplicate::Reject);
+///
+/// let map = BTreeMap::from([("a".to_owned(), 3u8), ("b".to_owned(), 2)]);
+/// assert_de_duplicate_field(&tokens, "a", [Token::U8(3)], OnDuplicate::LastWins(map));
+/// ```
+#[track_caller]
+pub fn assert_de_duplicate_field<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    field: &str,
+    value: impl IntoTokens<'test, 'de>,
+    on_duplicate: OnDuplicate<T>,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+    let value = value.into_tokens();
+    assert_valid(validate_tokens(&value));
+
+    let mut duplicated = false;
+    for header in 0..tokens.len() {
+        let entries = match group::entries(tokens, header) {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let original = match entries
+            .iter()
+            .position(|entry| tokens[entry.key.start].as_str() == Some(field))
+        {
+            Some(original) => original,
+            None => continue,
+        };
+        let with_len = resized(tokens[header], |len| len + 1);
+        let mut duplicate = tokens[entries[original].key.clone()].to_vec();
+        duplicate.extend_from_slice(&value);
+        let end = group::value_end(tokens, header).unwrap() - 1;
+        let positions = entries[original + 1..]
+            .iter()
+            .map(|entry| entry.key.start)
+            .chain(Some(end));
+        for position in positions {
+            duplicated = true;
+            let mut stream = tokens.to_vec();
+            stream[header] = with_len;
+            stream.splice(position..position, duplicate.iter().copied());
+
+            let mut de = Deserializer::new(&stream);
+            let result = T::deserialize(&mut de);
+            match (result, &on_duplicate) {
+                (Err(e), OnDuplicate::Reject) => {
+                    let msg = format!("duplicate field `{}`", field);
+                    if e.msg() != msg {
+                        panic!(
+                            "with `{}` duplicated at index {}, expected error {:?} but got {:?}",
+                            field,
+                            position,
+                            msg,
+                            e.msg(),
+                        );
+                    }
+                }
+                (Ok(v), OnDuplicate::Reject) => panic!(
+                    "with `{}` duplicated at index {}, expected an error but deserialized {:?}",
+                    field, position, v,
+                ),
+                (Ok(v), OnDuplicate::LastWins(expected)) => {
+                    assert_eq!(
+                        v, *expected,
+                        "with `{}` duplicated at index {}",
+                        field, position,
+                    );
+                    if de.remaining() > 0 {
+                        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+                    }
+                }
+                (Err(e), OnDuplicate::LastWins(_)) => deserialize_failed(
+                    &format!(
+                        "tokens with `{}` duplicated at index {} failed to deserialize",
+                        field, position,
+                    ),
+                    &e,
+                    de.position(),
+                    &stream,
+                ),
+            }
+        }
+    }
+    if !duplicated {
+        panic!("tokens contain no map or struct with the field `{}`", field);
+    }
+}
+
+/// Asserts that the given `tokens` fail to deserialize with an unknown field
+/// added anywhere in the struct, as `#[serde(deny_unknown_fields)]` does.
+///
+/// An entry keyed `"__serde_test_unknown"` is inserted at every position of
+/// the first map or struct in `tokens`, the value itself unless it is
+/// wrapped in an `Option` or newtype. Nested structs are left alone, since
+/// each type decides for itself whether to deny unknown fields. Types that
+/// should ignore unknown fields are covered by
+/// [`assert_de_tokens_ignores_extra`].
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_rejects_unknown_fields, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// assert_de_rejects_unknown_fields::<S>(&[
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("a"),
+///     Token::U8(1),
+///     Token::Str("b"),
+///     Token::U8(2),
+///     Token::StructEnd,
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_de_rejects_unknown_fields<'test, 'de: 'test, T>(tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de> + Debug,
+{
+    const UNKNOWN: &str = "__serde_test_unknown";
+
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+    let (header, entries) = match (0..tokens.len())
+        .find_map(|header| group::entries(tokens, header).map(|entries| (header, entries)))
+    {
+        Some(found) => found,
+        None => panic!("tokens contain no map or struct to add an unknown field to"),
+    };
+
+    let with_len = resized(tokens[header], |len| len + 1);
+    let end = group::value_end(tokens, header).unwrap() - 1;
+    let positions = entries.iter().map(|entry| entry.key.start).chain(Some(end));
+    for position in positions {
+        let mut stream = tokens.to_vec();
+        stream[header] = with_len;
+        stream.splice(position..position, [Token::Str(UNKNOWN), Token::Unit]);
+
+        match T::deserialize(&mut Deserializer::new(&stream)) {
+            Ok(v) => panic!(
+                "with an unknown field inserted at index {}, expected an error but deserialized {:?}",
+                position, v,
+            ),
+            Err(e) => {
+                let prefix = format!("unknown field `{}`", UNKNOWN);
+                if !e.msg().starts_with(&prefix) {
+                    panic!(
+                        "with an unknown field inserted at index {}, expected an {} error but got {:?}",
+                        position,
+                        prefix,
+                        e.msg(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Asserts that the given `tokens` still deserialize with each field of the
+/// struct removed in turn only if the field is one of the `optional` ones,
+/// and fail with a "missing field" error otherwise.
+///
+/// Fields are removed from the first map or struct in `tokens`, the value
+/// itself unless it is wrapped in an `Option` or newtype. Fields marked
+/// `#[serde(default)]` and fields of type `Option` are optional.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_missing_fields, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     #[serde(default)]
+///     b: u8,
+///     c: Option<u8>,
+/// }
+///
+/// assert_de_missing_fields::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 3 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::Str("b"),
+///         Token::U8(2),
+///         Token::Str("c"),
+///         Token::None,
+///         Token::StructEnd,
+///     ],
+///     &["b", "c"],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_missing_fields<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    optional: &[&str],
+) where
+    T: Deserialize<'de> + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+    let (header, entries) = match (0..tokens.len())
+        .find_map(|header| group::entries(tokens, header).map(|entries| (header, entries)))
+    {
+        Some(found) => found,
+        None => panic!("tokens contain no map or struct to remove fields from"),
+    };
+
+    let with_len = resized(tokens[header], |len| len.saturating_sub(1));
+    for entry in &entries {
+        let field = match tokens[entry.key.start].as_str() {
+            Some(field) => field,
+            None => continue,
+        };
+        let mut stream = tokens.to_vec();
+        stream[header] = with_len;
+        stream.drain(entry.key.start..entry.value.end);
+
+        let mut de = Deserializer::new(&stream);
+        match T::deserialize(&mut de) {
+            Ok(v) if !optional.contains(&field) => panic!(
+                "without `{}`, expected a missing field error but deserialized {:?}",
+                field, v,
+            ),
+            Ok(_) => {
+                if de.remaining() > 0 {
+                    panic!("{}", leftover(de.position(), de.remaining_tokens()));
+                }
+            }
+            Err(e) if optional.contains(&field) => deserialize_failed(
+                &format!("tokens without `{}` failed to deserialize", field),
+                &e,
+                de.position(),
+                &stream,
+            ),
+            Err(e) => {
+                let msg = format!("missing field `{}`", field);
+                if e.msg() != msg {
+                    panic!(
+                        "without `{}`, expected error {:?} but got {:?}",
+                        field,
+                        msg,
+                        e.msg(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Asserts that deserializing `T` from the given `tokens` calls exactly the
+/// `hints`, the `deserialize_*` methods of the deserializer, in order.
+///
+/// This catches types that fall back on `deserialize_any`, which
+/// non-self-describing formats cannot support, or that ask for a
+/// `deserialize_str` where a `deserialize_identifier` is expected.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_hints, Token};
+/// #
+/// #[derive(Deserialize)]
+/// struct S {
+///     a: Option<u8>,
+/// }
+///
+/// assert_de_hints::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::None,
+///         Token::StructEnd,
+///     ],
+///     &["deserialize_struct", "deserialize_identifier", "deserialize_option"],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_hints<'test, 'de: 'test, T>(tokens: impl IntoTokens<'test, 'de>, hints: &[&str])
+where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens).record_hints();
+    if let Err(e) = T::deserialize(&mut de) {
+        deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens);
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+
+    if de.hints() != hints {
+        panic!(
+            "expected deserialization to call {:?}\n    but it called {:?}",
+            hints,
+            de.hints(),
+        );
+    }
+}
+
+/// Asserts that `value` and its `tokens` round-trip with `is_human_readable`
+/// answering `readable`, and that both serialization and deserialization
+/// ask it exactly `queries` times.
+///
+/// With `queries` of 0 this checks that a type meant to be format-agnostic
+/// never branches on readability; otherwise the tokens are those of the
+/// `readable` form.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_readable_queries, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Plain(u8);
+///
+/// assert_readable_queries(
+///     &Plain(1),
+///     &[Token::NewtypeStruct { name: "Plain" }, Token::U8(1)],
+///     true,
+///     0,
+/// );
+/// assert_readable_queries(&Ipv4Addr::LOCALHOST, &[Token::Str("127.0.0.1")], true, 1);
+/// ```
+#[track_caller]
+pub fn assert_readable_queries<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: impl IntoTokens<'test, 'de>,
+    readable: bool,
+    queries: usize,
+) where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut ser = Serializer::new(tokens).human_readable(readable);
+    if let Err(err) = value.serialize(&mut ser) {
+        let index = err.position().unwrap_or(ser.position());
+        panic!(
+            "{}\n{}",
+            failure("value failed to serialize", &err, index, tokens.len()),
+            around(tokens, index),
+        );
+    }
+    if let Err(err) = ser.check_ended() {
+        panic!("{}", err);
+    }
+    if ser.remaining() > 0 {
+        panic!("{}", leftover(ser.position(), ser.remaining_tokens()));
+    }
+
+    let mut de = Deserializer::new(tokens).human_readable(readable);
+    match T::deserialize(&mut de) {
+        Ok(v) => assert_eq!(v, *value),
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+
+    for (pass, actual) in [
+        ("serialization", ser.readable_queries()),
+        ("deserialization", de.readable_queries()),
+    ] {
+        if actual != queries {
+            panic!(
+                "expected {} to call is_human_readable {} times, but it called it {} times",
+                pass, queries, actual,
+            );
+        }
+    }
+}
+
+/// Asserts that the given `tokens` deserialize into a `T` whose `borrowed`
+/// strings and bytes point into the [`Token::BorrowedStr`] and
+/// [`Token::BorrowedBytes`] data of the tokens, rather than into a copy.
+///
+/// `borrowed` picks the slices to check out of the deserialized value.
+/// Empty slices are not checked, since they need not point anywhere.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::{assert_de_borrows, Token};
+/// # use std::borrow::Cow;
+/// #
+/// #[derive(Deserialize)]
+/// struct S<'a> {
+///     name: &'a str,
+///     #[serde(borrow)]
+///     note: Cow<'a, str>,
+/// }
+///
+/// assert_de_borrows::<S, _>(
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("name"),
+///         Token::BorrowedStr("sky"),
+///         Token::Str("note"),
+///         Token::BorrowedStr("zero-copy"),
+///         Token::StructEnd,
+///     ],
+///     |s| vec![s.name.as_bytes(), s.note.as_bytes()],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_borrows<'test, 'de: 'test, T, F>(tokens: impl IntoTokens<'test, 'de>, borrowed: F)
+where
+    T: Deserialize<'de>,
+    F: FnOnce(&T) -> Vec<&[u8]>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    let value = match T::deserialize(&mut de) {
+        Ok(v) => v,
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    };
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+
+    let sources: Vec<&[u8]> = tokens
+        .iter()
+        .filter_map(|token| match *token {
+            Token::BorrowedStr(v) => Some(v.as_bytes()),
+            Token::BorrowedBytes(v) => Some(v),
+            _ => None,
+        })
+        .collect();
+    for (i, slice) in borrowed(&value).into_iter().enumerate() {
+        if slice.is_empty() {
+            continue;
+        }
+        let start = slice.as_ptr() as usize;
+        let end = start + slice.len();
+        let borrows = sources.iter().any(|source| {
+            let source_start = source.as_ptr() as usize;
+            source_start <= start && end <= source_start + source.len()
+        });
+        if !borrows {
+            panic!(
+                "slice {} ({:?}) does not borrow from a Token::BorrowedStr or Token::BorrowedBytes",
+                i,
+                String::from_utf8_lossy(slice),
+            );
+        }
+    }
+}
+
+/// Asserts that the given `tokens` deserialize into `value` when driven by
+/// `seed`.
+///
+/// This is the [`DeserializeSeed`] counterpart of [`assert_de_tokens`], for
+/// stateful deserialization such as arena allocation or schema-driven
+/// decoding.
+///
+/// ```
+/// # use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+/// # use serde_test::{assert_de_seed_tokens, Token};
+/// #
+/// struct Scaled(u32);
+///
+/// impl<'de> DeserializeSeed<'de> for Scaled {
+///     type Value = u32;
+///
+///     fn deserialize<D>(self, deserializer: D) -> Result<u32, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         u32::deserialize(deserializer).map(|v| v * self.0)
+///     }
+/// }
+///
+/// assert_de_seed_tokens(Scaled(10), &50, &[Token::U32(5)]);
+/// ```
+#[track_caller]
+pub fn assert_de_seed_tokens<'test, 'de: 'test, S>(
+    seed: S,
+    value: &S::Value,
+    tokens: impl IntoTokens<'test, 'de>,
+) where
+    S: DeserializeSeed<'de>,
+    S::Value: PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    assert_valid(validate_tokens(tokens));
+
+    let mut de = Deserializer::new(tokens);
+    match seed.deserialize(&mut de) {
+        Ok(v) => assert_eq!(v, *value),
+        Err(e) => deserialize_failed("tokens failed to deserialize", &e, de.position(), tokens),
+    }
+    if de.remaining() > 0 {
+        panic!("{}", leftover(de.position(), de.remaining_tokens()));
+    }
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing with
+/// `seed`.
+///
+/// ```
+/// # use serde::de::{Deserialize, DeserializeSeed, Deserializer, Error};
+/// # use serde_test::{assert_de_seed_tokens_error, Token};
+/// #
+/// struct Bounded(u32);
+///
+/// impl<'de> DeserializeSeed<'de> for Bounded {
+///     type Value = u32;
+///
+///     fn deserialize<D>(self, deserializer: D) -> Result<u32, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         let v = u32::deserialize(deserializer)?;
+///         if v > self.0 {
+///             return Err(D::Error::custom("out of bounds"));
+///         }
+///         Ok(v)
+///     }
+/// }
+///
+/// assert_de_seed_tokens_error(Bounded(10), &[Token::U32(11)], "out of bounds");
+/// ```
+#[track_caller]
+pub fn assert_de_seed_tokens_error<'test, 'de: 'test, S>(
+    seed: S,
+    tokens: impl IntoTokens<'test, 'de>,
+    error: impl ErrorMessages,
+) where
+    S: DeserializeSeed<'de>,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let (err, rest) = de_error(seed, tokens);
+    assert_message(&err, error.messages());
+    if let Some(rest) = rest {
+        panic!("{}", rest);
+    }
+}
+
+/// Serializes `value` into the token prefix `tokens`, which must fail.
+/// Returns the error, and the number of tokens consumed before it.
+#[track_caller]
+fn ser_error<T>(value: &T, tokens: &[Token<'_, '_>]) -> (Error, usize)
+where
+    T: ?Sized + Serialize,
+{
+    assert_valid(validate_token_prefix(tokens));
+
+    let mut ser = Serializer::new(tokens);
+    match value.serialize(&mut ser) {
+        Ok(()) => panic!("value serialized successfully"),
+        Err(err) => (err, ser.position()),
+    }
+}
+
+/// Describes the tokens left over after the first `consumed`, if any.
+fn unconsumed(tokens: &[Token<'_, '_>], consumed: usize) -> Option<String> {
+    if consumed < tokens.len() {
+        Some(leftover(consumed, &tokens[consumed..]))
+    } else {
+        None
+    }
+}
+
+/// Deserializes `seed` from the token prefix `tokens`, which must fail.
+/// Returns the error, and a description of any tokens left over.
+#[track_caller]
+fn de_error<'de, S>(seed: S, tokens: &[Token<'_, 'de>]) -> (Error, Option<String>)
+where
+    S: DeserializeSeed<'de>,
+{
+    match try_de_error(seed, tokens) {
+        Ok(outcome) => outcome,
+        Err(msg) => panic!("{}", msg),
+    }
+}
+
+/// [`de_error`], describing what went wrong instead of panicking if the
+/// tokens are invalid or deserialize successfully.
+fn try_de_error<'de, S>(
+    seed: S,
+    tokens: &[Token<'_, 'de>],
+) -> Result<(Error, Option<String>), String>
+where
+    S: DeserializeSeed<'de>,
+{
+    if let Err(err) = validate_token_prefix(tokens) {
+        return Err(format!("invalid token stream: {}", err));
+    }
+
+    let mut de = Deserializer::new(tokens);
+    let err = match seed.deserialize(&mut de) {
+        Ok(_) => return Err("tokens deserialized successfully".to_owned()),
+        Err(err) => err,
+    };
+    // Tokens that were only peeked at still count, because a peek can be
+    // what caused the error.
+    let rest = if de.unexamined() > 0 {
+        let index = tokens.len() - de.unexamined();
+        Some(leftover(index, &tokens[index..]))
+    } else {
+        None
+    };
+    Ok((err, rest))
+}
+
+/// Checks that `tokens` yield `error` and nothing is left over, returning a
+/// description of the failure instead of panicking.
+fn de_error_case<'de, S>(seed: S, tokens: &[Token<'_, 'de>], error: &[&str]) -> Result<(), String>
+where
+    S: DeserializeSeed<'de>,
+{
+    let (err, rest) = try_de_error(seed, tokens)?;
+    if !error.contains(&err.msg()) {
+        return Err(unexpected_message(&err, error));
+    }
+    match rest {
+        Some(rest) => Err(rest),
+        None => Ok(()),
+    }
+}
+
+#[track_caller]
+fn assert_kind(err: &Error, kind: ErrorKind) {
+    if err.kind() != kind {
+        panic!(
+            "expected an error of kind {:?} but got {:?}: {}",
+            kind,
+            err.kind(),
+            err,
+        );
+    }
+}
+
+// Each pass of the round-trip asserts, returning a description of the
+// failure instead of panicking so that later passes still run. A `readable`
+// answer, if given, is what `is_human_readable` returns.
+
+fn ser_pass<T>(value: &T, tokens: &[Token<'_, '_>], readable: Option<bool>) -> Result<(), String>
+where
+    T: ?Sized + Serialize,
+{
+    let (ser, result) = ser::serialize_recorded(value, tokens, readable);
+    if let Err(err) = result {
+        let index = err.position().unwrap_or(ser.position());
+        let msg = failure("value failed to serialize", &err, index, tokens.len());
+        if !matches!(err.kind(), ErrorKind::Mismatch | ErrorKind::EndOfTokens) {
+            return Err(format!(
+                "{}\n{}{}",
+                msg,
+                around(tokens, index),
+                backtrace(&err),
+            ));
+        }
+        // Serialization went on past the mismatch, recording the rest of
+        // what the value serialized as.
+        let expected: Vec<Token<'_, '_>> = tokens
+            .iter()
+            .copied()
+            .filter(|token| !ser::is_marker(token))
+            .collect();
+        let emitted: Vec<Token<'_, '_>> = ser
+            .emitted()
+            .iter()
+            .map(OwnedToken::as_token)
+            .filter(|token| !ser::is_marker(token))
+            .collect();
+        return Err(format!(
+            "{}\n{}{}",
+            msg,
+            diff_tokens(&expected, &emitted),
+            backtrace(&err),
+        ));
+    }
+    if let Err(err) = ser.check_ended() {
+        return Err(err.to_string());
+    }
+    if ser.remaining() > 0 {
+        return Err(leftover(ser.position(), ser.remaining_tokens()));
+    }
+    Ok(())
+}
+
+/// Collects `tokens` for the asserts taking a `serialize_with` or
+/// `deserialize_with` function, or an [`UntaggedVariant`]. Those functions
+/// are generic over, or fixed to, a serializer or deserializer borrowing the
+/// tokens for the caller's `'test`, so they cannot be handed one borrowing a
+/// local `Vec`; the tokens are leaked to live that long instead.
+fn leak_tokens<'test, 'de: 'test>(
+    tokens: impl IntoTokens<'test, 'de>,
+) -> &'test [Token<'test, 'de>] {
+    Box::leak(tokens.into_tokens().into_boxed_slice())
+}
+
+fn ser_with_pass<'test, T, F>(
+    value: &T,
+    serialize: F,
+    tokens: &'test [Token<'test, 'test>],
+) -> Result<(), String>
+where
+    T: ?Sized,
+    F: FnOnce(&T, Serializer<'test>) -> Result<(), Error>,
+{
+    match serialize(value, Serializer::new(tokens)) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(match err.position() {
+            Some(index) => format!(
+                "{}\n{}",
+                failure("value failed to serialize", &err, index, tokens.len()),
+                around(tokens, index),
+            ),
+            None => format!("value failed to serialize ({}): {}", blame(&err), err),
+        }),
+    }
+}
+
+fn de_with_pass<'test, 'de: 'test, T, F>(
+    deserialize: F,
+    tokens: &'test [Token<'test, 'de>],
+    expected: &T,
+) -> Result<(), String>
+where
+    T: PartialEq + Debug,
+    F: FnOnce(Deserializer<'test, 'de>) -> Result<T, Error>,
+{
+    match deserialize(Deserializer::new(tokens)) {
+        Ok(value) if value == *expected => Ok(()),
+        Ok(value) => Err(mismatch(&value, expected)),
+        Err(err) => Err(match err.position() {
+            Some(index) => format!(
+                "{}\n{}",
+                failure("tokens failed to deserialize", &err, index, tokens.len()),
+                around(tokens, index),
+            ),
+            None => format!("tokens failed to deserialize ({}): {}", blame(&err), err),
+        }),
+    }
+}
+
+/// Panics if the tokens an assert was given failed `validated`.
+#[track_caller]
+pub(crate) fn assert_valid(validated: Result<(), TokenStreamError>) {
+    if let Err(err) = validated {
+        panic!("invalid token stream: {}", err);
+    }
+}
+
+/// Panics with `err`, which `what` raised when the deserializer was at
+/// `position` in `tokens`.
+#[track_caller]
+fn deserialize_failed(what: &str, err: &Error, position: usize, tokens: &[Token<'_, '_>]) -> ! {
+    let index = failed_at(err, position);
+    panic!(
+        "{}\n{}",
+        failure(what, err, index, tokens.len()),
+        around(tokens, index),
+    );
+}
+
+/// Also returns the deserialized value, if any, as the starting place for
+/// [`de_in_place_pass`].
+fn de_pass<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+    readable: Option<bool>,
+) -> (Option<T>, Result<(), String>)
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let mut de = Deserializer::new(tokens);
+    if let Some(readable) = readable {
+        de = de.human_readable(readable);
+    }
+    let deserialized = match T::deserialize(&mut de) {
+        Ok(v) => v,
+        Err(err) => {
+            let index = failed_at(&err, de.position());
+            let msg = format!(
+                "{}\n{}{}",
+                failure("tokens failed to deserialize", &err, index, tokens.len()),
+                around(tokens, index),
+                backtrace(&err),
+            );
+            return (None, Err(msg));
+        }
+    };
+    let result = if deserialized != *value {
+        Err(mismatch(&deserialized, value))
+    } else if de.remaining() > 0 {
+        Err(leftover(de.position(), de.remaining_tokens()))
+    } else {
+        Ok(())
+    };
+    (Some(deserialized), result)
+}
+
+// This isn't *great* because a no-op impl of deserialize_in_place can
+// technically succeed here. Still, this should catch a lot of junk.
+fn de_in_place_pass<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+    place: &mut T,
+    readable: Option<bool>,
+) -> Result<(), String>
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let mut de = Deserializer::new(tokens);
+    if let Some(readable) = readable {
+        de = de.human_readable(readable);
+    }
+    if let Err(err) = T::deserialize_in_place(&mut de, place) {
+        let index = failed_at(&err, de.position());
+        return Err(format!(
+            "{}\n{}{}",
+            failure(
+                "tokens failed to deserialize_in_place",
+                &err,
+                index,
+                tokens.len()
+            ),
+            around(tokens, index),
+            backtrace(&err),
+        ));
+    }
+    if *place != *value {
+        return Err(mismatch(place, value));
+    }
+    if de.remaining() > 0 {
+        return Err(leftover(de.position(), de.remaining_tokens()));
+    }
+    Ok(())
+}
+
+/// Says whose fault a failed pass is: the tokens written in the test, or
+/// the impl under test.
+/// Describes `err`, which failed a pass at the token at `index` of `len`,
+/// with `what` failed first. Mismatches already say where they happened;
+/// the position is added to other errors.
+fn failure(what: &str, err: &Error, index: usize, len: usize) -> String {
+    match err.kind() {
+        ErrorKind::Mismatch | ErrorKind::EndOfTokens => {
+            format!("{} ({}): {}", what, blame(err), err)
+        }
+        _ => format!(
+            "{} at index {} of {} ({}): {}",
+            what,
+            index,
+            len,
+            blame(err),
+            err
+        ),
+    }
+}
+
+fn blame(err: &Error) -> &'static str {
+    match err.kind() {
+        ErrorKind::Mismatch | ErrorKind::EndOfTokens => "token mismatch, fix the test",
+        ErrorKind::LimitExceeded => "limit exceeded",
+        ErrorKind::Custom => "the type returned its own error",
+        ErrorKind::Misuse => "the type misused the serializer",
+    }
+}
+
+/// The backtrace captured with `err`, to append to a failure message.
+#[cfg(feature = "backtrace")]
+#[clippy::msrv = "1.65"]
+fn backtrace(err: &Error) -> String {
+    match err.backtrace().status() {
+        BacktraceStatus::Captured => format!("\nbacktrace:\n{}", err.backtrace()),
+        _ => String::new(),
+    }
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn backtrace(_err: &Error) -> String {
+    String::new()
+}
+
+/// The header of a map or struct with its declared `len` changed by `f`.
+fn resized<'test, 'de>(header: Token<'test, 'de>, f: fn(usize) -> usize) -> Token<'test, 'de> {
+    match header {
+        Token::Struct { name, len } => Token::Struct { name, len: f(len) },
+        Token::StructVariant { name, variant, len } => Token::StructVariant {
+            name,
+            variant,
+            len: f(len),
+        },
+        Token::Map { len } => Token::Map { len: len.map(f) },
+        _ => unreachable!("entries are only found in maps and structs"),
+    }
+}
+
+/// The index of the token a deserialization error is about. Errors raised
+/// by a `Deserialize` impl are taken to be about the last token it consumed.
+fn failed_at(err: &Error, position: usize) -> usize {
+    err.position().unwrap_or(position.saturating_sub(1))
+}
+
+/// Describes how the names `declared` to a `deserialize_*` method when the
+/// deserializer was at `position` disagree with the tokens serialized there,
+/// returning the index of the disagreeing token.
+fn asymmetry(
+    tokens: &[Token<'_, '_>],
+    position: usize,
+    declared: &Declared,
+) -> Option<(usize, String)> {
+    let index = group::skip_markers(tokens, position);
+    let found = *tokens.get(index)?;
+    let (what, method, fields) = match (declared, found) {
+        (Declared::UnitStruct(name), Token::UnitStruct { name: n })
+        | (Declared::NewtypeStruct(name), Token::NewtypeStruct { name: n })
+        | (Declared::TupleStruct(name), Token::TupleStruct { name: n, .. })
+            if *name == n =>
+        {
+            return None;
+        }
+        (Declared::Struct { name, fields }, Token::Struct { name: n, .. }) if *name == n => {
+            (format!("struct {:?}", name), "deserialize_struct", *fields)
+        }
+        (Declared::StructVariant { fields }, Token::StructVariant { name, variant, .. }) => (
+            format!("variant {:?}::{:?}", name, variant),
+            "struct_variant",
+            *fields,
+        ),
+        (
+            Declared::Enum { name, variants },
+            Token::UnitVariant { name: n, variant }
+            | Token::NewtypeVariant { name: n, variant }
+            | Token::TupleVariant {
+                name: n, variant, ..
+            }
+            | Token::StructVariant {
+                name: n, variant, ..
+            },
+        ) if *name == n => {
+            if variants.contains(&variant) {
+                return None;
+            }
+            let msg = format!(
+                "enum {:?} serialized variant {:?} that deserialize_enum does not expect, \
+                 which are {:?}",
+                name, variant, variants,
+            );
+            return Some((index, msg));
+        }
+        _ => {
+            let expected = match declared {
+                Declared::UnitStruct(name) => format!("deserialize_unit_struct expects {:?}", name),
+                Declared::NewtypeStruct(name) => {
+                    format!("deserialize_newtype_struct expects {:?}", name)
+                }
+                Declared::TupleStruct(name) => {
+                    format!("deserialize_tuple_struct expects {:?}", name)
+                }
+                Declared::Struct { name, .. } => format!("deserialize_struct expects {:?}", name),
+                Declared::Enum { name, .. } => format!("deserialize_enum expects {:?}", name),
+                Declared::StructVariant { .. } => {
+                    "struct_variant expects a struct variant".to_owned()
+                }
+            };
+            let msg = format!(
+                "{} at index {}, but the value serialized Token::{}",
+                expected, index, found,
+            );
+            return Some((index, msg));
+        }
+    };
+    let unknown: Vec<&str> = group::entries(tokens, index)?
+        .into_iter()
+        .filter_map(|entry| match tokens[entry.key.start] {
+            Token::Str(key) if !fields.contains(&key) => Some(key),
+            _ => None,
+        })
+        .collect();
+    if unknown.is_empty() {
+        return None;
+    }
+    let msg = format!(
+        "{} serialized fields {:?} that {} does not expect, which are {:?}",
+        what, unknown, method, fields,
+    );
+    Some((index, msg))
+}
+
+/// Shows the tokens around `index`, where a pass failed.
+fn around<'a, 'test, 'de>(tokens: &'a [Token<'test, 'de>], index: usize) -> Pretty<'a, 'test, 'de> {
+    Pretty::new(tokens)
+        .numbered()
+        .mark(index)
+        .context(pretty::failure_context())
+}
+
+/// Describes the `remaining` tokens that were left over, starting at `index`.
+fn leftover(index: usize, remaining: &[Token<'_, '_>]) -> String {
+    format!(
+        "{} remaining tokens starting at index {}:\n{}",
+        remaining.len(),
+        index,
+        DisplayTokens::new(remaining),
+    )
+}
+
+fn mismatch<T>(found: &T, expected: &T) -> String
+where
+    T: Debug,
+{
+    format!(
+        "value does not match\n     found: {:?}\n  expected: {:?}",
+        found, expected,
+    )
+}
+
+/// Describes the outcome of every pass if any of them failed. A `None`
+/// outcome means the pass was skipped because an earlier one produced
+/// nothing to work with.
+fn outcome(passes: &[(&str, Option<Result<(), String>>)]) -> Result<(), String> {
+    let failed = passes
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Some(Err(_))))
+        .count();
+    if failed == 0 {
+        return Ok(());
+    }
+
+    let mut msg = format!("{} of {} passes failed", failed, passes.len());
+    for (name, outcome) in passes {
+        let outcome = match outcome {
+            Some(Ok(())) => "ok",
+            Some(Err(err)) => err,
+            None => "skipped",
+        };
+        msg.push_str(&format!("\n{}: {}", name, outcome));
+    }
+    Err(msg)
+}
+
+/// Panics with the [`outcome`] of every pass if any of them failed.
+#[track_caller]
+fn report(passes: &[(&str, Option<Result<(), String>>)]) {
+    if let Err(msg) = outcome(passes) {
+        panic!("{}", msg);
+    }
+}
+
+/// The checks of [`assert_tokens`].
+fn tokens_case<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+) -> Result<(), String>
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    if let Err(err) = validate_tokens(tokens) {
+        return Err(format!("invalid token stream: {}", err));
+    }
+
+    let ser = ser_pass(value, tokens, None);
+    let (place, de) = de_pass(value, tokens, None);
+    let de_in_place = place.map(|mut place| de_in_place_pass(value, tokens, &mut place, None));
+    outcome(&[
+        ("serialize", Some(ser)),
+        ("deserialize", Some(de)),
+        ("deserialize_in_place", de_in_place),
+    ])
+}
+
+/// The checks of [`assert_de_in_place_tokens`], and of the capacity being
+/// kept if a `capacity` is given.
+fn de_in_place_case<'test, 'de: 'test, T>(
+    mut place: T,
+    tokens: &'test [Token<'test, 'de>],
+    expected: &T,
+    capacity: Option<&dyn Fn(&T) -> usize>,
+) -> Result<(), String>
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    if let Err(err) = validate_tokens(tokens) {
+        return Err(format!("invalid token stream: {}", err));
+    }
+    let initial = format!("{:?}", place);
+    let before = capacity.map(|capacity| capacity(&place));
+    if let Err(msg) = de_in_place_pass(expected, tokens, &mut place, None) {
+        return Err(format!("{}\n  initial: {}", msg, initial));
+    }
+    match (capacity, before) {
+        (Some(capacity), Some(before)) if capacity(&place) != before => Err(format!(
+            "deserialize_in_place did not reuse the allocation: capacity went from {} to {}\n  initial: {}",
+            before,
+            capacity(&place),
+            initial,
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The checks of [`assert_ser_tokens`].
+fn ser_tokens_case<T>(value: &T, tokens: &[Token<'_, '_>]) -> Result<(), String>
+where
+    T: ?Sized + Serialize,
+{
+    if let Err(err) = validate_tokens(tokens) {
+        return Err(format!("invalid token stream: {}", err));
+    }
+    ser_pass(value, tokens, None)
+}
+
+/// The checks of [`assert_de_tokens`].
+fn de_tokens_case<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+) -> Result<(), String>
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    if let Err(err) = validate_tokens(tokens) {
+        return Err(format!("invalid token stream: {}", err));
+    }
+
+    let (place, de) = de_pass(value, tokens, None);
+    let de_in_place = place.map(|mut place| de_in_place_pass(value, tokens, &mut place, None));
+    outcome(&[
+        ("deserialize", Some(de)),
+        ("deserialize_in_place", de_in_place),
+    ])
+}
+
+/// Panics with the failures of a table of cases, if there were any.
+#[track_caller]
+fn report_table(failures: &[(usize, String)], cases: usize) {
+    if failures.is_empty() {
+        return;
+    }
+
+    let mut msg = format!("{} of {} cases failed", failures.len(), cases);
+    for (index, failure) in failures {
+        msg.push_str(&format!("\n\ncase {}: {}", index, failure));
     }
+    panic!("{}", msg);
 }
@@ -0,0 +1,199 @@
+use crate::assert::assert_valid;
+use crate::group;
+use crate::token::{EndToken, OwnedToken, Token};
+use crate::validate::validate_tokens;
+
+/// Assembles a token stream one call at a time, keeping track of which
+/// compounds are open.
+///
+/// Each compound method opens a compound that is closed by [`end`], which
+/// appends the matching end token. Closing a compound that is not open,
+/// pushing an end token by hand that does not match, or calling [`build`]
+/// while a compound is still open panics at the offending call.
+///
+/// [`end`]: TokenBuilder::end
+/// [`build`]: TokenBuilder::build
+///
+/// ```
+/// # use serde_test::{OwnedToken, Token, TokenBuilder};
+/// #
+/// let mut builder = TokenBuilder::new().seq(None);
+/// for i in 0..2u8 {
+///     builder = builder
+///         .struct_("S", 1)
+///         .field("a", Token::U8(i))
+///         .end();
+/// }
+/// let tokens = builder.end().build();
+///
+/// assert_eq!(tokens.len(), 10);
+/// assert_eq!(tokens[0], Token::Seq { len: None });
+/// assert_eq!(tokens[9], OwnedToken::SeqEnd);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TokenBuilder {
+    tokens: Vec<OwnedToken>,
+    open: Vec<EndToken>,
+}
+
+impl TokenBuilder {
+    pub fn new() -> Self {
+        TokenBuilder::default()
+    }
+
+    /// Appends a single token.
+    ///
+    /// Compound headers and end tokens are tracked the same way as the
+    /// compound methods and [`end`](Self::end).
+    #[track_caller]
+    pub fn token<T>(mut self, token: T) -> Self
+    where
+        T: Into<OwnedToken>,
+    {
+        let token = token.into();
+        match EndToken::from_token(token.as_token()) {
+            Some(end) => self.close(end),
+            None => {
+                if let Some(end) = group::compound_end(token.as_token()) {
+                    self.open.push(end);
+                }
+            }
+        }
+        self.tokens.push(token);
+        self
+    }
+
+    /// Appends a [`Token::Str`] map or struct key.
+    #[track_caller]
+    pub fn key(self, key: &str) -> Self {
+        self.token(Token::Str(key))
+    }
+
+    /// Appends a [`Token::Str`] key followed by a single-token value.
+    #[track_caller]
+    pub fn field<T>(self, key: &str, value: T) -> Self
+    where
+        T: Into<OwnedToken>,
+    {
+        self.key(key).token(value)
+    }
+
+    /// Opens a [`Token::Seq`].
+    pub fn seq(self, len: Option<usize>) -> Self {
+        self.token(Token::Seq { len })
+    }
+
+    /// Opens a [`Token::Tuple`].
+    pub fn tuple(self, len: usize) -> Self {
+        self.token(Token::Tuple { len })
+    }
+
+    /// Opens a [`Token::TupleStruct`].
+    pub fn tuple_struct(self, name: &'static str, len: usize) -> Self {
+        self.token(Token::TupleStruct { name, len })
+    }
+
+    /// Opens a [`Token::TupleVariant`].
+    pub fn tuple_variant(self, name: &'static str, variant: &'static str, len: usize) -> Self {
+        self.token(Token::TupleVariant { name, variant, len })
+    }
+
+    /// Opens a [`Token::Map`].
+    pub fn map(self, len: Option<usize>) -> Self {
+        self.token(Token::Map { len })
+    }
+
+    /// Opens a [`Token::Struct`].
+    pub fn struct_(self, name: &'static str, len: usize) -> Self {
+        self.token(Token::Struct { name, len })
+    }
+
+    /// Opens a [`Token::StructVariant`].
+    pub fn struct_variant(self, name: &'static str, variant: &'static str, len: usize) -> Self {
+        self.token(Token::StructVariant { name, variant, len })
+    }
+
+    /// Appends the tokens added by `f` only if `cond` holds, such as
+    /// `cfg!(feature = "...")` for a field that a feature skips.
+    ///
+    /// `f` may open and close compounds, which are tracked as usual.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::{assert_ser_tokens, Token, TokenBuilder};
+    /// #
+    /// #[derive(Serialize)]
+    /// struct S {
+    ///     a: u8,
+    ///     #[cfg_attr(not(feature = "std"), serde(skip))]
+    ///     b: u8,
+    /// }
+    ///
+    /// let b = cfg!(feature = "std");
+    /// let tokens = TokenBuilder::new()
+    ///     .struct_("S", if b { 2 } else { 1 })
+    ///     .field("a", Token::U8(0))
+    ///     .when(b, |builder| builder.field("b", Token::U8(1)))
+    ///     .end()
+    ///     .build();
+    ///
+    /// assert_ser_tokens(&S { a: 0, b: 1 }, &tokens);
+    /// ```
+    #[track_caller]
+    pub fn when<F>(self, cond: bool, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        if cond {
+            f(self)
+        } else {
+            self
+        }
+    }
+
+    /// Closes the innermost open compound.
+    #[track_caller]
+    pub fn end(self) -> Self {
+        match self.open.last() {
+            Some(&end) => self.token(end.token()),
+            None => panic!("end() called with no open compound"),
+        }
+    }
+
+    /// Returns the finished stream.
+    ///
+    /// Panics if a compound is still open, or if the stream is otherwise
+    /// rejected by [`validate_tokens`](crate::validate_tokens), such as a
+    /// declared `len` that does not match the contents.
+    #[track_caller]
+    pub fn build(self) -> Vec<OwnedToken> {
+        if let Some(end) = self.open.last() {
+            panic!(
+                "{} compounds still open, innermost expects Token::{}",
+                self.open.len(),
+                end,
+            );
+        }
+        let tokens: Vec<Token<'_, '_>> = self.tokens.iter().map(OwnedToken::as_token).collect();
+        assert_valid(validate_tokens(&tokens));
+        self.tokens
+    }
+
+    #[track_caller]
+    fn close(&mut self, end: EndToken) {
+        match self.open.pop() {
+            Some(expected) if expected == end => {}
+            Some(expected) => panic!(
+                "Token::{} at index {} closes a compound that expects Token::{}",
+                end,
+                self.tokens.len(),
+                expected,
+            ),
+            None => panic!(
+                "Token::{} at index {} closes no open compound",
+                end,
+                self.tokens.len(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,1030 @@
+use crate::configure;
+use crate::error::{Error, ErrorKind};
+use crate::pretty::Pretty;
+use crate::token::{EndToken, OwnedToken, Token};
+use crate::TestResult;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// Serializes `value` and returns the tokens it produced.
+///
+/// Strings and bytes are captured as [`Token::Str`] and [`Token::Bytes`], and
+/// enum variants in their [`Token::UnitVariant`]-style form. Skipped struct
+/// fields are not recorded.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::{capture_tokens, Token};
+/// #
+/// #[derive(Serialize)]
+/// struct S {
+///     a: u8,
+///     b: Option<&'static str>,
+/// }
+///
+/// let tokens = capture_tokens(&S { a: 1, b: Some("x") }).unwrap();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::Str("b"),
+///         Token::Some,
+///         Token::Str("x"),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+pub fn capture_tokens<T>(value: &T) -> TestResult<Vec<OwnedToken>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut capture = Capture {
+        tokens: Vec::new(),
+        forbidden: &[],
+    };
+    value.serialize(&mut capture)?;
+    Ok(capture.tokens)
+}
+
+/// Serializes `value` like [`capture_tokens`], failing as soon as one of the
+/// `forbidden` `Serializer` methods is called. Also returns the tokens
+/// captured up to then.
+pub(crate) fn capture_forbidding<T>(value: &T, forbidden: &[&str]) -> (Vec<OwnedToken>, TestResult)
+where
+    T: ?Sized + Serialize,
+{
+    let mut capture = Capture {
+        tokens: Vec::new(),
+        forbidden,
+    };
+    let result = value.serialize(&mut capture);
+    (capture.tokens, result)
+}
+
+/// Serializes `value` and returns Rust source for the token array it
+/// produced, to paste into a test as a starting point.
+///
+/// The array is laid out like [`DisplayTokens`](crate::DisplayTokens), one
+/// token per line with compounds indented.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::tokens_source_code;
+/// #
+/// #[derive(Serialize)]
+/// struct S {
+///     a: Vec<u8>,
+/// }
+///
+/// assert_eq!(
+///     tokens_source_code(&S { a: vec![1] }).unwrap(),
+///     r#"&[
+///     Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::Seq { len: Some(1) },
+///             Token::U8(1),
+///         Token::SeqEnd,
+///     Token::StructEnd,
+/// ]"#,
+/// );
+/// ```
+pub fn tokens_source_code<T>(value: &T) -> TestResult<String>
+where
+    T: ?Sized + Serialize,
+{
+    let captured = capture_tokens(value)?;
+    let tokens: Vec<Token<'_, '_>> = captured.iter().map(OwnedToken::as_token).collect();
+    let mut source = String::from("&[\n");
+    for line in Pretty::new(&tokens).rust().to_string().lines() {
+        source.push_str("    ");
+        source.push_str(line);
+        source.push('\n');
+    }
+    source.push(']');
+    Ok(source)
+}
+
+struct Capture<'f> {
+    tokens: Vec<OwnedToken>,
+    /// The `Serializer` methods that fail when called.
+    forbidden: &'f [&'f str],
+}
+
+impl Capture<'_> {
+    fn push(&mut self, token: Token<'_, '_>) -> TestResult {
+        self.tokens.push(token.into());
+        Ok(())
+    }
+
+    /// Records the `token` written by the `Serializer` method `method`.
+    fn call(&mut self, method: &str, token: Token<'_, '_>) -> TestResult {
+        let index = self.tokens.len();
+        self.push(token)?;
+        if self.forbidden.contains(&method) {
+            return Err(Error::with_kind(
+                ErrorKind::Misuse,
+                format_args!(
+                    "{} was called at index {}, but the test forbids it",
+                    method, index,
+                ),
+            )
+            .at(index)
+            .with_found(token));
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'f> ser::Serializer for &'a mut Capture<'f> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, 'f>;
+    type SerializeTuple = Compound<'a, 'f>;
+    type SerializeTupleStruct = Compound<'a, 'f>;
+    type SerializeTupleVariant = Compound<'a, 'f>;
+    type SerializeMap = Compound<'a, 'f>;
+    type SerializeStruct = Compound<'a, 'f>;
+    type SerializeStructVariant = Compound<'a, 'f>;
+
+    fn serialize_bool(self, v: bool) -> TestResult {
+        self.call("serialize_bool", Token::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> TestResult {
+        self.call("serialize_i8", Token::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> TestResult {
+        self.call("serialize_i16", Token::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> TestResult {
+        self.call("serialize_i32", Token::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> TestResult {
+        self.call("serialize_i64", Token::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> TestResult {
+        self.call("serialize_i128", Token::I128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> TestResult {
+        self.call("serialize_u8", Token::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> TestResult {
+        self.call("serialize_u16", Token::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> TestResult {
+        self.call("serialize_u32", Token::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> TestResult {
+        self.call("serialize_u64", Token::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> TestResult {
+        self.call("serialize_u128", Token::U128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> TestResult {
+        self.call("serialize_f32", Token::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> TestResult {
+        self.call("serialize_f64", Token::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> TestResult {
+        self.call("serialize_char", Token::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> TestResult {
+        self.call("serialize_str", Token::Str(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> TestResult {
+        self.call("serialize_bytes", Token::Bytes(v))
+    }
+
+    fn serialize_none(self) -> TestResult {
+        self.call("serialize_none", Token::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call("serialize_some", Token::Some)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> TestResult {
+        self.call("serialize_unit", Token::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> TestResult {
+        self.call("serialize_unit_struct", Token::UnitStruct { name })
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> TestResult {
+        self.call(
+            "serialize_unit_variant",
+            Token::UnitVariant { name, variant },
+        )
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call("serialize_newtype_struct", Token::NewtypeStruct { name })?;
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(
+            "serialize_newtype_variant",
+            Token::NewtypeVariant { name, variant },
+        )?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> TestResult<Compound<'a, 'f>> {
+        self.call("serialize_seq", Token::Seq { len })?;
+        Ok(Compound::new(self, Token::SeqEnd))
+    }
+
+    fn serialize_tuple(self, len: usize) -> TestResult<Compound<'a, 'f>> {
+        self.call("serialize_tuple", Token::Tuple { len })?;
+        Ok(Compound::new(self, Token::TupleEnd))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> TestResult<Compound<'a, 'f>> {
+        self.call("serialize_tuple_struct", Token::TupleStruct { name, len })?;
+        Ok(Compound::new(self, Token::TupleStructEnd))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> TestResult<Compound<'a, 'f>> {
+        self.call(
+            "serialize_tuple_variant",
+            Token::TupleVariant { name, variant, len },
+        )?;
+        Ok(Compound::new(self, Token::TupleVariantEnd))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> TestResult<Compound<'a, 'f>> {
+        self.call("serialize_map", Token::Map { len })?;
+        Ok(Compound::new(self, Token::MapEnd))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> TestResult<Compound<'a, 'f>> {
+        self.call("serialize_struct", Token::Struct { name, len })?;
+        Ok(Compound::new(self, Token::StructEnd))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> TestResult<Compound<'a, 'f>> {
+        self.call(
+            "serialize_struct_variant",
+            Token::StructVariant { name, variant, len },
+        )?;
+        Ok(Compound::new(self, Token::StructVariantEnd))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        match configure::default_readable() {
+            Some(readable) => readable,
+            None => panic!(
+                "Types which have different human-readable and compact representations \
+                 must explicitly mark their test cases with `serde_test::Configure`"
+            ),
+        }
+    }
+}
+
+struct Compound<'a, 'f> {
+    capture: &'a mut Capture<'f>,
+    end: Token<'static, 'static>,
+}
+
+impl<'a, 'f> Compound<'a, 'f> {
+    fn new(capture: &'a mut Capture<'f>, end: Token<'static, 'static>) -> Self {
+        Compound { capture, end }
+    }
+}
+
+macro_rules! impl_compound {
+    ($tr:ident: $($method:ident),*) => {
+        impl ser::$tr for Compound<'_, '_> {
+            type Ok = ();
+            type Error = Error;
+
+            $(
+            fn $method<T>(&mut self, value: &T) -> TestResult
+            where
+                T: ?Sized + Serialize,
+            {
+                value.serialize(&mut *self.capture)
+            }
+            )*
+
+            fn end(self) -> TestResult {
+                self.capture.push(self.end)
+            }
+        }
+    };
+
+    (struct $tr:ident) => {
+        impl ser::$tr for Compound<'_, '_> {
+            type Ok = ();
+            type Error = Error;
+
+            fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> TestResult
+            where
+                T: ?Sized + Serialize,
+            {
+                self.capture.push(Token::Str(key))?;
+                value.serialize(&mut *self.capture)
+            }
+
+            fn end(self) -> TestResult {
+                self.capture.push(self.end)
+            }
+        }
+    };
+}
+
+impl_compound!(SerializeSeq: serialize_element);
+impl_compound!(SerializeTuple: serialize_element);
+impl_compound!(SerializeTupleStruct: serialize_field);
+impl_compound!(SerializeTupleVariant: serialize_field);
+impl_compound!(SerializeMap: serialize_key, serialize_value);
+impl_compound!(struct SerializeStruct);
+impl_compound!(struct SerializeStructVariant);
+
+/// Deserializes a `T` from a real format's `deserializer` and returns it
+/// together with the tokens the format fed it.
+///
+/// The tokens replay what the format did, to be pasted into a token-based
+/// regression test. See [`CaptureDeserializer`] for how they are recorded.
+///
+/// ```
+/// # use serde::de::value::{Error, SeqDeserializer};
+/// # use serde::de::IntoDeserializer;
+/// # use serde_test::{assert_de_tokens, capture_de_tokens, Token};
+/// #
+/// let deserializer: SeqDeserializer<_, Error> = vec![1u8, 2].into_deserializer();
+/// let (value, tokens) = capture_de_tokens::<Vec<u8>, _>(deserializer).unwrap();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+/// );
+///
+/// assert_de_tokens(&value, &tokens);
+/// ```
+pub fn capture_de_tokens<'de, T, D>(deserializer: D) -> Result<(T, Vec<OwnedToken>), D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let mut tokens = Vec::new();
+    let value = T::deserialize(CaptureDeserializer::new(deserializer, &mut tokens))?;
+    Ok((value, tokens))
+}
+
+/// A `serde::Deserializer` that forwards to another one, such as a real
+/// format's, and records the values it hands to the visitor as tokens.
+///
+/// Each `visit_*` call becomes the matching token: `visit_borrowed_str`
+/// becomes [`Token::BorrowedStr`], `visit_string` becomes [`Token::String`]
+/// and so on. Compounds take their header from the `deserialize_*` method
+/// that was called, such as [`Token::Struct`] for `deserialize_struct`, with
+/// the number of elements actually visited as their `len`. Enum variants
+/// named by a string take their [`Token::UnitVariant`]-style form.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde::de::value::{Error, MapDeserializer};
+/// # use serde_test::{CaptureDeserializer, Token};
+/// #
+/// #[derive(Deserialize)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// let deserializer = MapDeserializer::<_, Error>::new(vec![("a", 1u8)].into_iter());
+/// let mut tokens = Vec::new();
+/// S::deserialize(CaptureDeserializer::new(deserializer, &mut tokens)).unwrap();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+pub struct CaptureDeserializer<'a, D> {
+    inner: D,
+    tokens: &'a mut Vec<OwnedToken>,
+}
+
+impl<'a, D> CaptureDeserializer<'a, D> {
+    /// Wraps `inner`, appending the tokens it produces to `tokens`.
+    pub fn new(inner: D, tokens: &'a mut Vec<OwnedToken>) -> Self {
+        CaptureDeserializer { inner, tokens }
+    }
+}
+
+macro_rules! forward_captured {
+    ($($method:ident($($arg:ident: $ty:ty),*) => $shape:expr,)*) => {
+        $(
+        fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let visitor = CaptureVisitor {
+                visitor,
+                tokens: self.tokens,
+                shape: $shape,
+            };
+            self.inner.$method($($arg,)* visitor)
+        }
+        )*
+    };
+}
+
+impl<'a, 'de, D> Deserializer<'de> for CaptureDeserializer<'a, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    forward_captured! {
+        deserialize_any() => Shape::Any,
+        deserialize_bool() => Shape::Any,
+        deserialize_i8() => Shape::Any,
+        deserialize_i16() => Shape::Any,
+        deserialize_i32() => Shape::Any,
+        deserialize_i64() => Shape::Any,
+        deserialize_i128() => Shape::Any,
+        deserialize_u8() => Shape::Any,
+        deserialize_u16() => Shape::Any,
+        deserialize_u32() => Shape::Any,
+        deserialize_u64() => Shape::Any,
+        deserialize_u128() => Shape::Any,
+        deserialize_f32() => Shape::Any,
+        deserialize_f64() => Shape::Any,
+        deserialize_char() => Shape::Any,
+        deserialize_str() => Shape::Any,
+        deserialize_string() => Shape::Any,
+        deserialize_bytes() => Shape::Any,
+        deserialize_byte_buf() => Shape::Any,
+        deserialize_option() => Shape::Any,
+        deserialize_unit() => Shape::Any,
+        deserialize_unit_struct(name: &'static str) => Shape::UnitStruct(name),
+        deserialize_newtype_struct(name: &'static str) => Shape::NewtypeStruct(name),
+        deserialize_seq() => Shape::Any,
+        deserialize_tuple(len: usize) => Shape::Tuple,
+        deserialize_tuple_struct(name: &'static str, len: usize) => Shape::TupleStruct(name),
+        deserialize_map() => Shape::Any,
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]) => {
+            Shape::Struct(name)
+        },
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]) => {
+            Shape::Enum(name)
+        },
+        deserialize_identifier() => Shape::Any,
+        deserialize_ignored_any() => Shape::Any,
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// What the `deserialize_*` method called says about the value, which
+/// decides the header and end tokens recorded for it.
+#[derive(Copy, Clone)]
+enum Shape {
+    Any,
+    UnitStruct(&'static str),
+    NewtypeStruct(&'static str),
+    Tuple,
+    TupleStruct(&'static str),
+    Struct(&'static str),
+    Enum(&'static str),
+    /// The contents of a tuple or struct variant, whose header has already
+    /// been recorded.
+    Variant(EndToken),
+}
+
+impl Shape {
+    fn name(self) -> &'static str {
+        match self {
+            Shape::UnitStruct(name)
+            | Shape::NewtypeStruct(name)
+            | Shape::TupleStruct(name)
+            | Shape::Struct(name)
+            | Shape::Enum(name) => name,
+            Shape::Any | Shape::Tuple | Shape::Variant(_) => "",
+        }
+    }
+}
+
+struct CaptureVisitor<'a, V> {
+    visitor: V,
+    tokens: &'a mut Vec<OwnedToken>,
+    shape: Shape,
+}
+
+impl<V> CaptureVisitor<'_, V> {
+    fn push(&mut self, token: Token<'_, '_>) {
+        self.tokens.push(token.into());
+    }
+}
+
+macro_rules! visit_captured {
+    ($($method:ident($ty:ty) => $token:ident,)*) => {
+        $(
+        fn $method<E>(mut self, v: $ty) -> Result<V::Value, E>
+        where
+            E: de::Error,
+        {
+            self.push(Token::$token(v));
+            self.visitor.$method(v)
+        }
+        )*
+    };
+}
+
+impl<'a, 'de, V> Visitor<'de> for CaptureVisitor<'a, V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    visit_captured! {
+        visit_bool(bool) => Bool,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_i64(i64) => I64,
+        visit_i128(i128) => I128,
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_u64(u64) => U64,
+        visit_u128(u128) => U128,
+        visit_f32(f32) => F32,
+        visit_f64(f64) => F64,
+        visit_char(char) => Char,
+        visit_str(&str) => Str,
+        visit_borrowed_str(&'de str) => BorrowedStr,
+        visit_bytes(&[u8]) => Bytes,
+        visit_borrowed_bytes(&'de [u8]) => BorrowedBytes,
+    }
+
+    fn visit_string<E>(mut self, v: String) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.push(Token::String(&v));
+        self.visitor.visit_string(v)
+    }
+
+    fn visit_byte_buf<E>(mut self, v: Vec<u8>) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.push(Token::ByteBuf(&v));
+        self.visitor.visit_byte_buf(v)
+    }
+
+    fn visit_none<E>(mut self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.push(Token::None);
+        self.visitor.visit_none()
+    }
+
+    fn visit_some<D>(mut self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.push(Token::Some);
+        self.visitor
+            .visit_some(CaptureDeserializer::new(deserializer, self.tokens))
+    }
+
+    fn visit_unit<E>(mut self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        match self.shape {
+            Shape::UnitStruct(name) => self.push(Token::UnitStruct { name }),
+            _ => self.push(Token::Unit),
+        }
+        self.visitor.visit_unit()
+    }
+
+    fn visit_newtype_struct<D>(mut self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = self.shape.name();
+        self.push(Token::NewtypeStruct { name });
+        self.visitor
+            .visit_newtype_struct(CaptureDeserializer::new(deserializer, self.tokens))
+    }
+
+    fn visit_seq<A>(mut self, seq: A) -> Result<V::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let start = self.tokens.len();
+        let len = seq.size_hint();
+        let end = match self.shape {
+            Shape::Tuple => {
+                self.push(Token::Tuple { len: 0 });
+                EndToken::Tuple
+            }
+            Shape::TupleStruct(name) => {
+                self.push(Token::TupleStruct { name, len: 0 });
+                EndToken::TupleStruct
+            }
+            Shape::Variant(end) => end,
+            _ => {
+                self.push(Token::Seq { len });
+                EndToken::Seq
+            }
+        };
+        let mut count = 0;
+        let value = self.visitor.visit_seq(CaptureSeq {
+            inner: seq,
+            tokens: &mut *self.tokens,
+            count: &mut count,
+        })?;
+        set_len(self.tokens, start, count);
+        self.tokens.push(end.token().into());
+        Ok(value)
+    }
+
+    fn visit_map<A>(mut self, map: A) -> Result<V::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let start = self.tokens.len();
+        let len = map.size_hint();
+        let end = match self.shape {
+            Shape::Struct(name) => {
+                self.push(Token::Struct { name, len: 0 });
+                EndToken::Struct
+            }
+            Shape::Variant(end) => end,
+            _ => {
+                self.push(Token::Map { len });
+                EndToken::Map
+            }
+        };
+        let mut count = 0;
+        let value = self.visitor.visit_map(CaptureMap {
+            inner: map,
+            tokens: &mut *self.tokens,
+            count: &mut count,
+        })?;
+        set_len(self.tokens, start, count);
+        self.tokens.push(end.token().into());
+        Ok(value)
+    }
+
+    fn visit_enum<A>(mut self, data: A) -> Result<V::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let start = self.tokens.len();
+        let name = self.shape.name();
+        self.push(Token::Enum { name });
+        self.visitor.visit_enum(CaptureEnum {
+            inner: data,
+            tokens: self.tokens,
+            name,
+            start,
+        })
+    }
+}
+
+/// Sets the `len` of the header recorded at `start` to the number of
+/// elements visited, unless the header has no `len` or is a variant's.
+fn set_len(tokens: &mut [OwnedToken], start: usize, count: usize) {
+    match tokens.get_mut(start) {
+        Some(OwnedToken::Seq { len: Some(len) })
+        | Some(OwnedToken::Map { len: Some(len) })
+        | Some(OwnedToken::Tuple { len })
+        | Some(OwnedToken::TupleStruct { len, .. })
+        | Some(OwnedToken::Struct { len, .. }) => *len = count,
+        _ => {}
+    }
+}
+
+struct CaptureSeed<'a, T> {
+    seed: T,
+    tokens: &'a mut Vec<OwnedToken>,
+}
+
+impl<'a, 'de, T> DeserializeSeed<'de> for CaptureSeed<'a, T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = T::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.seed
+            .deserialize(CaptureDeserializer::new(deserializer, self.tokens))
+    }
+}
+
+struct CaptureSeq<'a, A> {
+    inner: A,
+    tokens: &'a mut Vec<OwnedToken>,
+    count: &'a mut usize,
+}
+
+impl<'a, 'de, A> SeqAccess<'de> for CaptureSeq<'a, A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let seed = CaptureSeed {
+            seed,
+            tokens: &mut *self.tokens,
+        };
+        let element = self.inner.next_element_seed(seed)?;
+        if element.is_some() {
+            *self.count += 1;
+        }
+        Ok(element)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+struct CaptureMap<'a, A> {
+    inner: A,
+    tokens: &'a mut Vec<OwnedToken>,
+    count: &'a mut usize,
+}
+
+impl<'a, 'de, A> MapAccess<'de> for CaptureMap<'a, A>
+where
+    A: MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let seed = CaptureSeed {
+            seed,
+            tokens: &mut *self.tokens,
+        };
+        let key = self.inner.next_key_seed(seed)?;
+        if key.is_some() {
+            *self.count += 1;
+        }
+        Ok(key)
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.inner.next_value_seed(CaptureSeed {
+            seed,
+            tokens: &mut *self.tokens,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+struct CaptureEnum<'a, A> {
+    inner: A,
+    tokens: &'a mut Vec<OwnedToken>,
+    name: &'static str,
+    /// The index of the recorded [`Token::Enum`].
+    start: usize,
+}
+
+impl<'a, 'de, A> EnumAccess<'de> for CaptureEnum<'a, A>
+where
+    A: EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = CaptureEnum<'a, A::Variant>;
+
+    fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self::Variant), A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let seed = CaptureSeed {
+            seed,
+            tokens: &mut *self.tokens,
+        };
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        let variant = CaptureEnum {
+            inner: variant,
+            tokens: self.tokens,
+            name: self.name,
+            start: self.start,
+        };
+        Ok((value, variant))
+    }
+}
+
+impl<V> CaptureEnum<'_, V> {
+    /// Replaces the recorded [`Token::Enum`] and variant name with the
+    /// single token `header` makes of them, if the variant was named by one
+    /// string. Otherwise records `fallback` after them.
+    fn header(&mut self, header: impl FnOnce(&str) -> OwnedToken, fallback: Option<OwnedToken>) {
+        let variant = match &self.tokens[self.start + 1..] {
+            [OwnedToken::Str(v)] | [OwnedToken::BorrowedStr(v)] | [OwnedToken::String(v)] => {
+                Some(header(v))
+            }
+            _ => None,
+        };
+        match variant {
+            Some(token) => {
+                self.tokens.truncate(self.start);
+                self.tokens.push(token);
+            }
+            None => self.tokens.extend(fallback),
+        }
+    }
+
+    fn owned(&self) -> Cow<'static, str> {
+        Cow::Borrowed(self.name)
+    }
+}
+
+impl<'a, 'de, A> VariantAccess<'de> for CaptureEnum<'a, A>
+where
+    A: VariantAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn unit_variant(mut self) -> Result<(), A::Error> {
+        let name = self.owned();
+        self.header(
+            |variant| OwnedToken::UnitVariant {
+                name,
+                variant: variant.to_owned().into(),
+            },
+            Some(OwnedToken::Unit),
+        );
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let name = self.owned();
+        self.header(
+            |variant| OwnedToken::NewtypeVariant {
+                name,
+                variant: variant.to_owned().into(),
+            },
+            None,
+        );
+        self.inner.newtype_variant_seed(CaptureSeed {
+            seed,
+            tokens: self.tokens,
+        })
+    }
+
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value, A::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let name = self.owned();
+        let mut end = EndToken::Seq;
+        self.header(
+            |variant| {
+                end = EndToken::TupleVariant;
+                OwnedToken::TupleVariant {
+                    name,
+                    variant: variant.to_owned().into(),
+                    len,
+                }
+            },
+            Some(OwnedToken::Seq { len: Some(len) }),
+        );
+        self.inner.tuple_variant(
+            len,
+            CaptureVisitor {
+                visitor,
+                tokens: self.tokens,
+                shape: Shape::Variant(end),
+            },
+        )
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let name = self.owned();
+        let mut end = EndToken::Map;
+        self.header(
+            |variant| {
+                end = EndToken::StructVariant;
+                OwnedToken::StructVariant {
+                    name,
+                    variant: variant.to_owned().into(),
+                    len: fields.len(),
+                }
+            },
+            Some(OwnedToken::Map {
+                len: Some(fields.len()),
+            }),
+        );
+        self.inner.struct_variant(
+            fields,
+            CaptureVisitor {
+                visitor,
+                tokens: self.tokens,
+                shape: Shape::Variant(end),
+            },
+        )
+    }
+}
@@ -0,0 +1,363 @@
+use crate::de::Deserializer;
+use crate::diff::{changes, Change};
+use crate::error::Error;
+use crate::into_tokens::IntoTokens;
+use crate::ser;
+use crate::token::{OwnedToken, Token};
+use crate::validate::validate_tokens;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Display, Formatter};
+
+/// The pass of [`check_tokens`] that found a [`Mismatch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// The token stream itself is malformed, so no pass ran.
+    Validate,
+    Serialize,
+    Deserialize,
+    DeserializeInPlace,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Validate => "validate",
+            Phase::Serialize => "serialize",
+            Phase::Deserialize => "deserialize",
+            Phase::DeserializeInPlace => "deserialize_in_place",
+        })
+    }
+}
+
+/// One way in which a value and its tokens disagree.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    phase: Phase,
+    msg: String,
+    position: Option<usize>,
+    expected: Option<OwnedToken>,
+    found: Option<OwnedToken>,
+}
+
+impl Mismatch {
+    fn new(phase: Phase, msg: impl Display) -> Self {
+        Mismatch {
+            phase,
+            msg: msg.to_string(),
+            position: None,
+            expected: None,
+            found: None,
+        }
+    }
+
+    fn at(mut self, position: usize, tokens: &[Token<'_, '_>]) -> Self {
+        self.position = Some(position);
+        self.expected = tokens.get(position).map(|&token| token.into());
+        self
+    }
+
+    fn with_found(mut self, found: Token<'_, '_>) -> Self {
+        self.found = Some(found.into());
+        self
+    }
+
+    fn from_error(phase: Phase, err: &Error) -> Self {
+        Mismatch {
+            phase,
+            msg: err.msg().to_owned(),
+            position: err.position(),
+            expected: err.expected().map(Into::into),
+            found: err.found().map(Into::into),
+        }
+    }
+
+    /// The pass that found the mismatch.
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// A description of the mismatch.
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// The index in the tokens at which the mismatch was found.
+    ///
+    /// `None` if it is about the value as a whole, such as a deserialized
+    /// value that differs from the expected one.
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    /// The token the tokens have at [`position`](Self::position).
+    pub fn expected(&self) -> Option<Token<'_, '_>> {
+        self.expected.as_ref().map(OwnedToken::as_token)
+    }
+
+    /// The token the value serialized as instead, if any.
+    pub fn found(&self) -> Option<Token<'_, '_>> {
+        self.found.as_ref().map(OwnedToken::as_token)
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} at index {}: {}", self.phase, position, self.msg),
+            None => write!(f, "{}: {}", self.phase, self.msg),
+        }
+    }
+}
+
+/// Everything [`check_tokens`] found wrong, in the order of the passes.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    mismatches: Vec<Mismatch>,
+}
+
+impl Report {
+    /// Whether the value and its tokens agree in every pass.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return f.write_str("no mismatches");
+        }
+        write!(f, "{} mismatches", self.mismatches.len())?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n{}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the passes of [`assert_tokens`](crate::assert_tokens) without
+/// panicking, and reports every mismatch found.
+///
+/// Where serialization goes wrong, it carries on without checking the rest
+/// of the tokens, and every token in which the value differs from `tokens`
+/// is reported, rather than only the first. Deserialization stops at its
+/// first error.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{check_tokens, Phase, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// let report = check_tokens(
+///     &S { a: 1, b: 2 },
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::Str("b"),
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ],
+/// );
+///
+/// let mismatches = report.mismatches();
+/// assert_eq!(mismatches.len(), 4);
+///
+/// assert_eq!(mismatches[0].phase(), Phase::Serialize);
+/// assert_eq!(mismatches[0].position(), Some(2));
+/// assert_eq!(mismatches[0].expected(), Some(Token::U8(0)));
+/// assert_eq!(mismatches[0].found(), Some(Token::U8(1)));
+/// assert_eq!(mismatches[1].position(), Some(4));
+/// assert_eq!(mismatches[1].found(), Some(Token::U8(2)));
+///
+/// assert_eq!(mismatches[2].phase(), Phase::Deserialize);
+/// assert_eq!(mismatches[2].position(), None);
+/// assert_eq!(mismatches[3].phase(), Phase::DeserializeInPlace);
+///
+/// // Serialized only once, with the answer to `is_human_readable` the
+/// // tokens give.
+/// let report = check_tokens(
+///     &Ipv4Addr::LOCALHOST,
+///     &[Token::Readable(true), Token::Str("127.0.0.2")],
+/// );
+/// assert_eq!(report.mismatches()[0].found(), Some(Token::Str("127.0.0.1")));
+/// ```
+pub fn check_tokens<'test, 'de: 'test, T>(value: &T, tokens: impl IntoTokens<'test, 'de>) -> Report
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let tokens = tokens.into_tokens();
+    let tokens = &*tokens;
+    let mut report = Report::default();
+    if let Err(err) = validate_tokens(tokens) {
+        let mismatch = Mismatch::new(Phase::Validate, &err).at(err.index(), tokens);
+        report.mismatches.push(mismatch);
+        return report;
+    }
+
+    check_ser(value, tokens, &mut report.mismatches);
+    if let Some(mut place) = check_de(value, tokens, &mut report.mismatches) {
+        check_de_in_place(value, tokens, &mut place, &mut report.mismatches);
+    }
+    report
+}
+
+fn check_ser<T>(value: &T, tokens: &[Token<'_, '_>], out: &mut Vec<Mismatch>)
+where
+    T: ?Sized + Serialize,
+{
+    let (ser, result) = ser::serialize_recorded(value, tokens, None);
+    let err = match result {
+        Ok(()) => {
+            if let Err(err) = ser.check_ended() {
+                let start = err.position().unwrap();
+                out.push(Mismatch::new(Phase::Serialize, err).at(start, tokens));
+                return;
+            }
+            let position = ser.position();
+            for index in position..position + ser.remaining() {
+                out.push(
+                    Mismatch::new(Phase::Serialize, "token was not serialized").at(index, tokens),
+                );
+            }
+            return;
+        }
+        Err(err) => err,
+    };
+
+    // Serialization went on past the first mismatch, recording what the
+    // value serialized as.
+    let indexes: Vec<usize> = (0..tokens.len())
+        .filter(|&i| !ser::is_marker(&tokens[i]))
+        .collect();
+    let expected: Vec<Token<'_, '_>> = indexes.iter().map(|&i| tokens[i]).collect();
+    let emitted: Vec<Token<'_, '_>> = ser
+        .emitted()
+        .iter()
+        .map(OwnedToken::as_token)
+        .filter(|token| !ser::is_marker(token))
+        .collect();
+
+    let start = out.len();
+    for change in changes(&expected, &emitted) {
+        let mismatch = match change {
+            Change::Changed(i, j) => Mismatch::new(
+                Phase::Serialize,
+                format_args!("value serialized as Token::{}", emitted[j]),
+            )
+            .at(indexes[i], tokens)
+            .with_found(emitted[j]),
+            Change::Removed(i) => {
+                Mismatch::new(Phase::Serialize, "token was not serialized").at(indexes[i], tokens)
+            }
+            Change::Added(i, j) => {
+                let index = indexes.get(i).copied().unwrap_or(tokens.len());
+                Mismatch::new(
+                    Phase::Serialize,
+                    format_args!("value serialized an extra Token::{}", emitted[j]),
+                )
+                .at(index, tokens)
+                .with_found(emitted[j])
+            }
+        };
+        out.push(mismatch);
+    }
+    // The value failed for a reason other than a differing token, such as
+    // an error raised by its `Serialize` impl.
+    if out.len() == start {
+        out.push(Mismatch::from_error(Phase::Serialize, &err));
+    }
+}
+
+/// Also returns the deserialized value, if any, as the starting place for
+/// [`check_de_in_place`].
+fn check_de<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+    out: &mut Vec<Mismatch>,
+) -> Option<T>
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(deserialized) => {
+            check_de_result(Phase::Deserialize, &deserialized, value, &de, tokens, out);
+            Some(deserialized)
+        }
+        Err(err) => {
+            out.push(de_error(Phase::Deserialize, &err, de.position(), tokens));
+            None
+        }
+    }
+}
+
+fn check_de_in_place<'test, 'de: 'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'de>],
+    place: &mut T,
+    out: &mut Vec<Mismatch>,
+) where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize_in_place(&mut de, place) {
+        Ok(()) => check_de_result(Phase::DeserializeInPlace, place, value, &de, tokens, out),
+        Err(err) => out.push(de_error(
+            Phase::DeserializeInPlace,
+            &err,
+            de.position(),
+            tokens,
+        )),
+    }
+}
+
+fn check_de_result<T>(
+    phase: Phase,
+    deserialized: &T,
+    value: &T,
+    de: &Deserializer<'_, '_>,
+    tokens: &[Token<'_, '_>],
+    out: &mut Vec<Mismatch>,
+) where
+    T: PartialEq + Debug,
+{
+    if *deserialized != *value {
+        out.push(Mismatch::new(
+            phase,
+            format_args!(
+                "value does not match\n     found: {:?}\n  expected: {:?}",
+                deserialized, value,
+            ),
+        ));
+    } else if de.remaining() > 0 {
+        out.push(
+            Mismatch::new(
+                phase,
+                format_args!("{} tokens were not deserialized", de.remaining()),
+            )
+            .at(de.position(), tokens),
+        );
+    }
+}
+
+/// Errors raised by a `Deserialize` impl are taken to be about the last
+/// token it consumed.
+fn de_error(phase: Phase, err: &Error, position: usize, tokens: &[Token<'_, '_>]) -> Mismatch {
+    let mut mismatch = Mismatch::from_error(phase, err);
+    if mismatch.position.is_none() {
+        mismatch = mismatch.at(position.saturating_sub(1), tokens);
+    }
+    mismatch
+}
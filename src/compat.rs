@@ -0,0 +1,232 @@
+//! The API of `serde_test` 1.x, for moving an existing test suite over.
+//!
+//! Switching is a matter of changing the import, after which tests can adopt
+//! the rest of this crate one at a time:
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! use serde_test::compat::{assert_tokens, Token};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct S {
+//!     a: u8,
+//! }
+//!
+//! assert_tokens(
+//!     &S { a: 0 },
+//!     &[
+//!         Token::Struct { name: "S", len: 1 },
+//!         Token::Str("a"),
+//!         Token::U8(0),
+//!         Token::StructEnd,
+//!     ],
+//! );
+//! ```
+//!
+//! The asserts run on this crate's serializer and deserializer, so token
+//! streams are validated first, declared lengths are checked, and the
+//! messages of errors raised by the harness itself are worded as in the rest
+//! of this crate.
+
+use crate::token::Token as Token2;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Display};
+
+pub use crate::configure::{Compact, Configure, Readable};
+
+/// The token set of `serde_test` 1.x.
+///
+/// Each variant is the same as the [`Token`](crate::Token) variant of that
+/// name.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Token {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(&'static str),
+    BorrowedStr(&'static str),
+    String(&'static str),
+    Bytes(&'static [u8]),
+    BorrowedBytes(&'static [u8]),
+    ByteBuf(&'static [u8]),
+    None,
+    Some,
+    Unit,
+    UnitStruct {
+        name: &'static str,
+    },
+    UnitVariant {
+        name: &'static str,
+        variant: &'static str,
+    },
+    NewtypeStruct {
+        name: &'static str,
+    },
+    NewtypeVariant {
+        name: &'static str,
+        variant: &'static str,
+    },
+    Seq {
+        len: Option<usize>,
+    },
+    SeqEnd,
+    Tuple {
+        len: usize,
+    },
+    TupleEnd,
+    TupleStruct {
+        name: &'static str,
+        len: usize,
+    },
+    TupleStructEnd,
+    TupleVariant {
+        name: &'static str,
+        variant: &'static str,
+        len: usize,
+    },
+    TupleVariantEnd,
+    Map {
+        len: Option<usize>,
+    },
+    MapEnd,
+    Struct {
+        name: &'static str,
+        len: usize,
+    },
+    StructEnd,
+    StructVariant {
+        name: &'static str,
+        variant: &'static str,
+        len: usize,
+    },
+    StructVariantEnd,
+    Enum {
+        name: &'static str,
+    },
+}
+
+impl From<Token> for Token2<'static, 'static> {
+    fn from(token: Token) -> Self {
+        match token {
+            Token::Bool(v) => Token2::Bool(v),
+            Token::I8(v) => Token2::I8(v),
+            Token::I16(v) => Token2::I16(v),
+            Token::I32(v) => Token2::I32(v),
+            Token::I64(v) => Token2::I64(v),
+            Token::U8(v) => Token2::U8(v),
+            Token::U16(v) => Token2::U16(v),
+            Token::U32(v) => Token2::U32(v),
+            Token::U64(v) => Token2::U64(v),
+            Token::F32(v) => Token2::F32(v),
+            Token::F64(v) => Token2::F64(v),
+            Token::Char(v) => Token2::Char(v),
+            Token::Str(v) => Token2::Str(v),
+            Token::BorrowedStr(v) => Token2::BorrowedStr(v),
+            Token::String(v) => Token2::String(v),
+            Token::Bytes(v) => Token2::Bytes(v),
+            Token::BorrowedBytes(v) => Token2::BorrowedBytes(v),
+            Token::ByteBuf(v) => Token2::ByteBuf(v),
+            Token::None => Token2::None,
+            Token::Some => Token2::Some,
+            Token::Unit => Token2::Unit,
+            Token::UnitStruct { name } => Token2::UnitStruct { name },
+            Token::UnitVariant { name, variant } => Token2::UnitVariant { name, variant },
+            Token::NewtypeStruct { name } => Token2::NewtypeStruct { name },
+            Token::NewtypeVariant { name, variant } => Token2::NewtypeVariant { name, variant },
+            Token::Seq { len } => Token2::Seq { len },
+            Token::SeqEnd => Token2::SeqEnd,
+            Token::Tuple { len } => Token2::Tuple { len },
+            Token::TupleEnd => Token2::TupleEnd,
+            Token::TupleStruct { name, len } => Token2::TupleStruct { name, len },
+            Token::TupleStructEnd => Token2::TupleStructEnd,
+            Token::TupleVariant { name, variant, len } => {
+                Token2::TupleVariant { name, variant, len }
+            }
+            Token::TupleVariantEnd => Token2::TupleVariantEnd,
+            Token::Map { len } => Token2::Map { len },
+            Token::MapEnd => Token2::MapEnd,
+            Token::Struct { name, len } => Token2::Struct { name, len },
+            Token::StructEnd => Token2::StructEnd,
+            Token::StructVariant { name, variant, len } => {
+                Token2::StructVariant { name, variant, len }
+            }
+            Token::StructVariantEnd => Token2::StructVariantEnd,
+            Token::Enum { name } => Token2::Enum { name },
+        }
+    }
+}
+
+impl From<&Token> for Token2<'static, 'static> {
+    fn from(token: &Token) -> Self {
+        Token2::from(*token)
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&Token2::from(*self), f)
+    }
+}
+
+/// Converts `tokens`, for deserializers borrowing from them for `'de`.
+fn convert<'de>(tokens: &[Token]) -> Vec<Token2<'de, 'de>> {
+    tokens
+        .iter()
+        .map(|token| -> Token2<'de, 'de> { token.into() })
+        .collect()
+}
+
+/// Runs both `assert_ser_tokens` and `assert_de_tokens`.
+#[track_caller]
+pub fn assert_tokens<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    crate::assert_tokens(value, convert(tokens));
+}
+
+/// Asserts that `value` serializes to the given `tokens`.
+#[track_caller]
+pub fn assert_ser_tokens<T>(value: &T, tokens: &[Token])
+where
+    T: ?Sized + Serialize,
+{
+    crate::assert_ser_tokens(value, tokens);
+}
+
+/// Asserts that `value` serializes to the given `tokens`, and then yields
+/// `error`.
+#[track_caller]
+pub fn assert_ser_tokens_error<T>(value: &T, tokens: &[Token], error: &str)
+where
+    T: ?Sized + Serialize,
+{
+    crate::assert_ser_tokens_error(value, tokens, error);
+}
+
+/// Asserts that the given `tokens` deserialize into `value`.
+#[track_caller]
+pub fn assert_de_tokens<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    crate::assert_de_tokens(value, convert(tokens));
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing.
+#[track_caller]
+pub fn assert_de_tokens_error<'de, T>(tokens: &'de [Token], error: &str)
+where
+    T: Deserialize<'de>,
+{
+    crate::assert_de_tokens_error::<T>(convert(tokens), error);
+}
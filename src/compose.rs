@@ -0,0 +1,241 @@
+//! Helpers that assemble token streams out of smaller ones.
+//!
+//! Each returns an owned stream that can be fed to another, so generated
+//! streams never need their end tokens rebuilt by hand.
+
+use crate::assert::assert_valid;
+use crate::display::DisplayTokens;
+use crate::group;
+use crate::into_tokens::IntoTokens;
+use crate::token::{OwnedToken, Token};
+use crate::validate::validate_tokens;
+
+/// Merges the tokens of a struct and of the values flattened into it into
+/// the single map that `#[serde(flatten)]` serializes as.
+///
+/// A struct with a flattened field is written as a `Map { len: None }`
+/// holding its own fields and the fields of every flattened value, rather
+/// than as a `Struct`. `outer` and each of `inner` are written as they would
+/// be on their own: a `Struct` or `Map`, or a `Unit`, `None` or `Some` of
+/// one of those. Their entries are added in order, so fields declared after
+/// a flattened one go in a later component.
+///
+/// Panics if a component is not a single value of one of those kinds.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::compose::flatten_tokens;
+/// # use serde_test::{assert_tokens, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Outer {
+///     a: u8,
+///     #[serde(flatten)]
+///     inner: Inner,
+/// }
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Inner {
+///     b: u8,
+/// }
+///
+/// let tokens = flatten_tokens(
+///     [
+///         Token::Struct { name: "Outer", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+///     [[
+///         Token::Struct { name: "Inner", len: 1 },
+///         Token::Str("b"),
+///         Token::U8(2),
+///         Token::StructEnd,
+///     ]],
+/// );
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Map { len: None },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::Str("b"),
+///         Token::U8(2),
+///         Token::MapEnd,
+///     ],
+/// );
+/// assert_tokens(&Outer { a: 1, inner: Inner { b: 2 } }, &tokens);
+/// ```
+#[track_caller]
+pub fn flatten_tokens<'test, 'de: 'test, I>(
+    outer: impl IntoTokens<'test, 'de>,
+    inner: I,
+) -> Vec<OwnedToken>
+where
+    I: IntoIterator,
+    I::Item: IntoTokens<'test, 'de>,
+{
+    let mut tokens = vec![OwnedToken::Map { len: None }];
+    push_entries(&mut tokens, &outer.into_tokens());
+    for component in inner {
+        push_entries(&mut tokens, &component.into_tokens());
+    }
+    tokens.push(OwnedToken::MapEnd);
+    tokens
+}
+
+/// Joins token streams end to end.
+///
+/// ```
+/// # use serde_test::compose::concat;
+/// # use serde_test::Token;
+/// #
+/// assert_eq!(
+///     concat([vec![Token::Some], vec![Token::U8(1)]]),
+///     [Token::Some, Token::U8(1)],
+/// );
+/// ```
+pub fn concat<'test, 'de: 'test, I>(streams: I) -> Vec<OwnedToken>
+where
+    I: IntoIterator,
+    I::Item: IntoTokens<'test, 'de>,
+{
+    streams.into_iter().flat_map(owned_tokens).collect()
+}
+
+/// Repeats a token stream `times` times.
+///
+/// ```
+/// # use serde_test::compose::{repeat, surround};
+/// # use serde_test::{assert_tokens, Token};
+/// #
+/// let tokens = surround(
+///     &[Token::Seq { len: Some(10_000) }],
+///     &[Token::SeqEnd],
+///     &repeat([Token::U8(7)], 10_000),
+/// );
+/// assert_tokens(&vec![7u8; 10_000], &tokens);
+/// ```
+pub fn repeat<'test, 'de: 'test>(
+    tokens: impl IntoTokens<'test, 'de>,
+    times: usize,
+) -> Vec<OwnedToken> {
+    let tokens = owned_tokens(tokens);
+    let mut repeated = Vec::with_capacity(tokens.len() * times);
+    for _ in 0..times {
+        repeated.extend(tokens.iter().cloned());
+    }
+    repeated
+}
+
+/// Puts `start` before and `end` after a token stream.
+///
+/// Folding over it nests a value any number of levels deep.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::compose::surround;
+/// # use serde_test::{assert_tokens, OwnedToken, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Wrap(Option<Box<Wrap>>);
+///
+/// let value = (0..3).fold(Wrap(None), |inner, _| Wrap(Some(Box::new(inner))));
+/// let tokens = (0..3).fold(
+///     vec![OwnedToken::NewtypeStruct { name: "Wrap".into() }, OwnedToken::None],
+///     |inner, _| {
+///         surround(&[Token::NewtypeStruct { name: "Wrap" }, Token::Some], &[], &inner)
+///     },
+/// );
+/// assert_tokens(&value, &tokens);
+/// ```
+pub fn surround<'test, 'de: 'test>(
+    start: &[Token<'_, '_>],
+    end: &[Token<'_, '_>],
+    tokens: impl IntoTokens<'test, 'de>,
+) -> Vec<OwnedToken> {
+    let mut surrounded = owned_tokens(start);
+    surrounded.extend(owned_tokens(tokens));
+    surrounded.extend(owned_tokens(end));
+    surrounded
+}
+
+/// Replaces the whole value starting at `index`, compounds included, with
+/// `replacement`.
+///
+/// Panics if no complete value starts at `index`.
+///
+/// ```
+/// # use serde_test::compose::replace_at;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Tuple { len: 2 },
+///     Token::Seq { len: Some(1) },
+///     Token::U8(0),
+///     Token::SeqEnd,
+///     Token::Bool(true),
+///     Token::TupleEnd,
+/// ];
+/// assert_eq!(
+///     replace_at(tokens, 1, [Token::Unit]),
+///     [
+///         Token::Tuple { len: 2 },
+///         Token::Unit,
+///         Token::Bool(true),
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn replace_at<'test, 'de: 'test>(
+    tokens: impl IntoTokens<'test, 'de>,
+    index: usize,
+    replacement: impl IntoTokens<'test, 'de>,
+) -> Vec<OwnedToken> {
+    let tokens = tokens.into_tokens();
+    let end = match group::value_end(&tokens, index) {
+        Some(end) => end,
+        None => panic!("no complete value starts at index {}", index),
+    };
+    let mut replaced = owned_tokens(&tokens[..index]);
+    replaced.extend(owned_tokens(replacement));
+    replaced.extend(owned_tokens(&tokens[end..]));
+    replaced
+}
+
+fn owned_tokens<'test, 'de: 'test>(tokens: impl IntoTokens<'test, 'de>) -> Vec<OwnedToken> {
+    tokens
+        .into_tokens()
+        .into_iter()
+        .map(OwnedToken::from)
+        .collect()
+}
+
+/// Appends the entries of the flattenable value `tokens` to `out`.
+#[track_caller]
+fn push_entries(out: &mut Vec<OwnedToken>, tokens: &[Token<'_, '_>]) {
+    assert_valid(validate_tokens(tokens));
+    let entries = match tokens.first() {
+        Some(Token::Unit | Token::None) if tokens.len() == 1 => return,
+        Some(Token::Some) => return push_entries(out, &tokens[1..]),
+        Some(Token::Struct { .. } | Token::Map { .. })
+            if group::value_end(tokens, 0) == Some(tokens.len()) =>
+        {
+            group::entries(tokens, 0)
+        }
+        _ => None,
+    };
+    match entries {
+        Some(entries) => {
+            for entry in entries {
+                let entry = &tokens[entry.key.start..entry.value.end];
+                out.extend(entry.iter().map(|&token| OwnedToken::from(token)));
+            }
+        }
+        None => panic!(
+            "expected a Struct, Map, Unit or Option of one to flatten, found:\n{}",
+            DisplayTokens::new(tokens),
+        ),
+    }
+}
@@ -6,6 +6,7 @@ use serde::ser::{
     Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
     SerializeTupleStruct, SerializeTupleVariant, Serializer,
 };
+use std::cell::Cell;
 use std::fmt;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -88,6 +89,55 @@ pub trait Configure {
 
 impl<T: ?Sized> Configure for T {}
 
+thread_local! {
+    static DEFAULT_READABLE: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `is_human_readable` answered by `readable` wherever the
+/// test has not chosen an answer itself.
+///
+/// This covers types nested deep inside the value under test that cannot be
+/// wrapped in [`Readable`] or [`Compact`]. The [`Configure`] wrappers, the
+/// [`Token::Readable`](crate::Token::Readable) marker and the
+/// `human_readable` option of the serializer and deserializer all take
+/// precedence. Without any of them, `is_human_readable` panics as before.
+///
+/// The default applies to the current thread until `f` returns.
+///
+/// ```
+/// # use serde_test::{assert_tokens, with_human_readable, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// let hosts = vec![Ipv4Addr::LOCALHOST];
+/// with_human_readable(true, || {
+///     assert_tokens(
+///         &hosts,
+///         &[
+///             Token::Seq { len: Some(1) },
+///             Token::Str("127.0.0.1"),
+///             Token::SeqEnd,
+///         ],
+///     );
+/// });
+/// ```
+pub fn with_human_readable<R>(readable: bool, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<bool>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEFAULT_READABLE.with(|default| default.set(self.0));
+        }
+    }
+
+    let _restore = Restore(DEFAULT_READABLE.with(|default| default.replace(Some(readable))));
+    f()
+}
+
+/// The answer chosen by [`with_human_readable`] for this thread, if any.
+pub(crate) fn default_readable() -> Option<bool> {
+    DEFAULT_READABLE.with(Cell::get)
+}
+
 impl<T: ?Sized> Serialize for Readable<T>
 where
     T: Serialize,
@@ -122,6 +172,13 @@ where
     {
         T::deserialize(Readable(deserializer)).map(Readable)
     }
+
+    fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize_in_place(Readable(deserializer), &mut place.0)
+    }
 }
 impl<'de, T> Deserialize<'de> for Compact<T>
 where
@@ -133,6 +190,13 @@ where
     {
         T::deserialize(Compact(deserializer)).map(Compact)
     }
+
+    fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize_in_place(Compact(deserializer), &mut place.0)
+    }
 }
 
 impl<'de, T> DeserializeSeed<'de> for Readable<T>
@@ -222,6 +286,13 @@ macro_rules! impl_serializer {
                 self.0.serialize_unit()
             }
 
+            fn collect_str<T>(self, value: &T) -> Result<S::Ok, S::Error>
+            where
+                T: ?Sized + fmt::Display,
+            {
+                self.0.collect_str(value)
+            }
+
             fn serialize_unit_variant(
                 self,
                 name: &'static str,
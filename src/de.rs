@@ -1,77 +1,1594 @@
-use crate::error::Error;
-use crate::token::{EndToken, Token};
+use crate::configure;
+use crate::display::DisplayTokens;
+use crate::error::{Error, ErrorKind};
+use crate::group::{self, Walker};
+use crate::token::{EndToken, OwnedToken, Token, TokenKind};
+use crate::trace;
 use crate::TestResult;
-use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::value::{
+    BorrowedBytesDeserializer, BorrowedStrDeserializer, MapAccessDeserializer,
+    SeqAccessDeserializer, SeqDeserializer,
+};
 use serde::de::{
     self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
     VariantAccess, Visitor,
 };
 use serde::forward_to_deserialize_any;
-use std::{iter, slice};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::iter::Map;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::slice;
 
 #[derive(Debug)]
-pub struct Deserializer<'test, 'de: 'test> {
-    tokens: iter::Copied<slice::Iter<'test, Token<'test, 'de>>>,
+pub struct Deserializer<'test, 'de: 'test, S = &'test [Token<'test, 'de>]> {
+    source: S,
+    /// The total number of tokens, if the source knew it up front.
+    len: Option<usize>,
+    position: usize,
+    /// The number of leading tokens that have been consumed or peeked at.
+    examined: usize,
+    /// The number of markers at the front of the source that a peek has
+    /// already looked past, so that the next peek starts after them.
+    skipped: usize,
+    stats: BorrowStats,
+    limits: Limits,
+    /// How many values are being deserialized inside one another.
+    depth: usize,
+    /// The `Visitor` methods called so far, if they are being recorded.
+    visits: Option<Rc<RefCell<Vec<&'static str>>>>,
+    /// The `deserialize_*` methods called so far, if they are being recorded.
+    hints: Option<Vec<&'static str>>,
+    /// The names passed to the `deserialize_*` methods of named types, and
+    /// the position each was called at, if they are being recorded.
+    declared: Option<Vec<(usize, Declared)>>,
+    /// The answer to `is_human_readable`, which panics if there is none.
+    human_readable: Option<bool>,
+    /// The answer set by the last [`Token::Readable`] marker, which takes
+    /// precedence over `human_readable`.
+    marked_readable: Option<bool>,
+    readable_queries: Cell<usize>,
+    /// Whether bytes tokens are visited as a sequence of `u8`.
+    bytes_as_seq: bool,
+    /// Whether `deserialize_bytes` and `deserialize_byte_buf` collect a
+    /// sequence of `u8` into bytes.
+    seq_as_bytes: bool,
+    /// Whether `deserialize_char` visits a one-character string as a `char`.
+    char_as_str: bool,
+    /// Whether [`Token::String`] and [`Token::ByteBuf`] are visited without
+    /// handing over an allocation.
+    owned_as_transient: bool,
+    /// The integer width to visit [`Token::Number`]s with, if not chosen by
+    /// their sign and size.
+    number_kind: Option<TokenKind>,
+    size_hint: SizeHint,
+    /// Whether elements a visitor returns without visiting are skipped.
+    drain_unvisited: bool,
+    /// The fields, elements and variants being deserialized, if they are
+    /// being tracked.
+    path: Option<Vec<Segment>>,
+    marker: PhantomData<Token<'test, 'de>>,
+}
+
+/// The names a type declared to a `deserialize_*` method, recorded by
+/// [`Deserializer::record_declared`].
+#[derive(Clone, Debug)]
+pub(crate) enum Declared {
+    UnitStruct(&'static str),
+    NewtypeStruct(&'static str),
+    TupleStruct(&'static str),
+    Struct {
+        name: &'static str,
+        fields: &'static [&'static str],
+    },
+    Enum {
+        name: &'static str,
+        variants: &'static [&'static str],
+    },
+    StructVariant {
+        fields: &'static [&'static str],
+    },
+}
+
+/// What the `SeqAccess` and `MapAccess` of a [`Deserializer`] report as
+/// their `size_hint`, set with [`Deserializer::size_hint`].
+///
+/// Formats are free to give no hint or a wrong one, so a `Deserialize` impl
+/// that preallocates or trusts the hint should be tested against each of
+/// these.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SizeHint {
+    /// The number of elements left according to the `len` of the tokens,
+    /// the default.
+    Exact,
+    /// No hint at all.
+    None,
+    /// A hint of zero elements, however many are left.
+    Zero,
+    /// This many more elements than are left, or this many if the tokens
+    /// give no `len`.
+    Inflated(usize),
+}
+
+impl SizeHint {
+    fn hint(self, len: Option<usize>) -> Option<usize> {
+        match self {
+            SizeHint::Exact => len,
+            SizeHint::None => None,
+            SizeHint::Zero => Some(0),
+            SizeHint::Inflated(extra) => Some(len.unwrap_or(0).saturating_add(extra)),
+        }
+    }
+}
+
+/// Where a [`Deserializer`] gets its tokens from.
+///
+/// Implemented for token slices, which is what [`Deserializer::new`] takes,
+/// for slices of [`OwnedToken`]s, and by [`IterSource`], which [`Deserializer::from_iter`] uses to produce
+/// tokens lazily.
+pub trait TokenSource<'test, 'de: 'test> {
+    /// Consumes and returns the next token.
+    fn next_token(&mut self) -> Option<Token<'test, 'de>>;
+
+    /// Returns the token `n` places after the next one, without consuming
+    /// anything.
+    fn peek_nth(&mut self, n: usize) -> Option<Token<'test, 'de>>;
+
+    /// Returns the number of tokens left, if it is known exactly.
+    fn remaining(&self) -> Option<usize>;
 }
 
-fn assert_next_token<'test, 'de>(
-    de: &mut Deserializer<'test, 'de>,
+impl<'test, 'de> TokenSource<'test, 'de> for &'test [Token<'test, 'de>] {
+    fn next_token(&mut self) -> Option<Token<'test, 'de>> {
+        let (&first, rest) = self.split_first()?;
+        *self = rest;
+        Some(first)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<Token<'test, 'de>> {
+        self.get(n).copied()
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Lets a stream generated at runtime be deserialized from without first
+/// collecting borrowed `Token`s out of it.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::de::Deserializer;
+/// # use serde_test::OwnedToken;
+/// #
+/// let generated = "x".repeat(3);
+/// let tokens = [OwnedToken::String(generated)];
+/// let mut de = Deserializer::from_source(&tokens[..]);
+/// assert_eq!(String::deserialize(&mut de).unwrap(), "xxx");
+/// ```
+impl<'test> TokenSource<'test, 'test> for &'test [OwnedToken] {
+    fn next_token(&mut self) -> Option<Token<'test, 'test>> {
+        let (first, rest) = self.split_first()?;
+        *self = rest;
+        Some(first.as_token())
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<Token<'test, 'test>> {
+        self.get(n).map(OwnedToken::as_token)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// A [`TokenSource`] that pulls tokens out of an iterator as deserialization
+/// asks for them.
+///
+/// Only tokens that have been peeked at but not consumed yet are buffered.
+#[derive(Clone, Debug)]
+pub struct IterSource<'test, 'de, I> {
+    iter: I,
+    peeked: VecDeque<Token<'test, 'de>>,
+}
+
+impl<'test, 'de, I> IterSource<'test, 'de, I>
+where
+    I: Iterator<Item = Token<'test, 'de>>,
+{
+    pub fn new(iter: I) -> Self {
+        IterSource {
+            iter,
+            peeked: VecDeque::new(),
+        }
+    }
+}
+
+impl<'test, 'de, I> TokenSource<'test, 'de> for IterSource<'test, 'de, I>
+where
+    I: Iterator<Item = Token<'test, 'de>>,
+{
+    fn next_token(&mut self) -> Option<Token<'test, 'de>> {
+        self.peeked.pop_front().or_else(|| self.iter.next())
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<Token<'test, 'de>> {
+        while self.peeked.len() <= n {
+            self.peeked.push_back(self.iter.next()?);
+        }
+        Some(self.peeked[n])
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(self.peeked.len() + lower),
+            _ => None,
+        }
+    }
+}
+
+/// Formats a token index, together with the length of the stream if known.
+struct At {
+    index: usize,
+    len: Option<usize>,
+}
+
+impl Display for At {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.len {
+            Some(len) => write!(f, "index {} of {}", self.index, len),
+            None => write!(f, "index {}", self.index),
+        }
+    }
+}
+
+/// One step of the path to the value being deserialized.
+#[derive(Clone, Debug)]
+enum Segment {
+    /// A struct field, string map key or enum variant.
+    Field(String),
+    /// Any other map key or variant, as a token.
+    Key(String),
+    /// A sequence or tuple element.
+    Index(usize),
+}
+
+impl Segment {
+    fn key(token: Token<'_, '_>) -> Self {
+        match token {
+            Token::Str(key)
+            | Token::BorrowedStr(key)
+            | Token::String(key)
+            | Token::CollectStr(key) => Segment::Field(key.to_owned()),
+            token => Segment::Key(format!("{:#}", DisplayTokens::new(&[token]))),
+        }
+    }
+}
+
+/// Formats a path of segments as `value.inner.items[3]`.
+struct Path<'a>(&'a [Segment]);
+
+impl Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("value")?;
+        for segment in self.0 {
+            match segment {
+                Segment::Field(name) => write!(f, ".{}", name)?,
+                Segment::Key(key) => write!(f, "[{}]", key)?,
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size limits imitating a production deserializer that rejects oversized
+/// payloads.
+#[derive(Copy, Clone, Debug, Default)]
+struct Limits {
+    str_len: Option<usize>,
+    bytes_len: Option<usize>,
+    seq_len: Option<usize>,
+    depth: Option<usize>,
+}
+
+/// Tallies of the string and bytes visits made by a [`Deserializer`].
+///
+/// `borrowed_*` counts `visit_borrowed_str`/`visit_borrowed_bytes`,
+/// `transient_*` counts `visit_str`/`visit_bytes`, and `owned_*` counts
+/// `visit_string`/`visit_byte_buf`, each of which hands the visitor a fresh
+/// allocation.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::de::Deserializer;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Tuple { len: 2 },
+///     Token::BorrowedStr("zero"),
+///     Token::String("copy"),
+///     Token::TupleEnd,
+/// ];
+/// let mut de = Deserializer::new(&tokens);
+/// let _: (&str, String) = Deserialize::deserialize(&mut de).unwrap();
+///
+/// let stats = de.borrow_stats();
+/// assert_eq!(stats.borrowed_str, 1);
+/// assert_eq!(stats.owned_str, 1);
+/// assert!(stats.owned() <= 1);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BorrowStats {
+    pub borrowed_str: usize,
+    pub transient_str: usize,
+    pub owned_str: usize,
+    pub borrowed_bytes: usize,
+    pub transient_bytes: usize,
+    pub owned_bytes: usize,
+}
+
+impl BorrowStats {
+    /// The total number of borrowed string and bytes visits.
+    pub fn borrowed(&self) -> usize {
+        self.borrowed_str + self.borrowed_bytes
+    }
+
+    /// The total number of transient string and bytes visits.
+    pub fn transient(&self) -> usize {
+        self.transient_str + self.transient_bytes
+    }
+
+    /// The total number of owned string and bytes visits.
+    pub fn owned(&self) -> usize {
+        self.owned_str + self.owned_bytes
+    }
+}
+
+/// The token the deserializer presents in place of `token`, which is the
+/// first alternative of a [`Token::OneOf`].
+fn first_alternative<'test, 'de>(token: Token<'test, 'de>) -> Token<'test, 'de> {
+    match token {
+        Token::OneOf(alternatives) => alternatives.first().copied().unwrap_or(token),
+        token => token,
+    }
+}
+
+fn assert_next_token<'test, 'de, S>(
+    de: &mut Deserializer<'test, 'de, S>,
     expected: Token<'test, 'de>,
-) -> Result<(), Error> {
+) -> Result<(), Error>
+where
+    S: TokenSource<'test, 'de>,
+{
     match de.next_token_opt() {
         Some(token) if token == expected => Ok(()),
-        Some(other) => Err(Error::new(format_args!(
-            "expected Token::{} but deserialization wants Token::{}",
-            other, expected,
-        ))),
-        None => Err(Error::new(format_args!(
-            "end of tokens but deserialization wants Token::{}",
-            expected,
-        ))),
+        Some(other) => Err(Error::with_kind(
+            ErrorKind::Mismatch,
+            format_args!(
+                "expected Token::{} at {} but deserialization wants Token::{}",
+                other,
+                de.at(de.position() - 1),
+                expected,
+            ),
+        )
+        .at(de.position() - 1)
+        .with_expected(other)
+        .with_found(expected)),
+        None => Err(Error::with_kind(
+            ErrorKind::EndOfTokens,
+            format_args!(
+                "end of tokens after all {} tokens but deserialization wants Token::{}",
+                de.position(),
+                expected,
+            ),
+        )
+        .at(de.position())
+        .with_found(expected)),
     }
 }
 
-fn unexpected(token: Token<'_, '_>) -> Error {
-    Error::new(format_args!(
-        "deserialization did not expect this token: {}",
-        token,
-    ))
+impl<'test, 'de> Deserializer<'test, 'de> {
+    pub fn new(tokens: &'test [Token<'test, 'de>]) -> Self {
+        Deserializer::from_source(tokens)
+    }
+
+    /// Returns the number of tokens that have not been consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.source.len()
+    }
+
+    /// Returns the number of tokens that have been neither consumed nor peeked
+    /// at.
+    ///
+    /// After a failed deserialization this may be less than
+    /// [`remaining`](Self::remaining), because the error was caused by a token
+    /// that was peeked at but not consumed, such as the `SeqEnd` that
+    /// prompted an "invalid length" error.
+    pub fn unexamined(&self) -> usize {
+        self.position + self.source.len() - self.examined
+    }
+
+    /// Returns the tokens that have not been consumed yet.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::U8(1), Token::Bool(true)];
+    /// let mut de = Deserializer::new(&tokens);
+    /// u8::deserialize(&mut de).unwrap();
+    /// assert_eq!(de.remaining_tokens(), [Token::Bool(true)]);
+    /// ```
+    pub fn remaining_tokens(&self) -> &'test [Token<'test, 'de>] {
+        self.source
+    }
 }
 
-fn end_of_tokens() -> Error {
-    Error::new("ran out of tokens to deserialize")
+impl<'test, 'de, I> Deserializer<'test, 'de, IterSource<'test, 'de, I>>
+where
+    I: Iterator<Item = Token<'test, 'de>>,
+{
+    /// Creates a deserializer that produces its tokens lazily, so that very
+    /// large streams never need to be held in memory.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::iter;
+    /// #
+    /// const N: u32 = 1_000_000;
+    ///
+    /// let tokens = iter::once(Token::Seq { len: None })
+    ///     .chain((0..N).map(Token::U32))
+    ///     .chain(iter::once(Token::SeqEnd));
+    /// let mut de = Deserializer::from_iter(tokens);
+    /// let v = Vec::<u32>::deserialize(&mut de).unwrap();
+    /// assert_eq!(v.len(), N as usize);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<T>(tokens: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Deserializer::from_source(IterSource::new(tokens.into_iter()))
+    }
 }
 
-impl<'test, 'de> Deserializer<'test, 'de> {
-    pub fn new(tokens: &'test [Token<'test, 'de>]) -> Self {
-        Deserializer {
-            tokens: tokens.iter().copied(),
+impl<'test, 'de, S> Deserializer<'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
+    /// Creates a deserializer that reads its tokens from any [`TokenSource`].
+    pub fn from_source(source: S) -> Self {
+        let mut de = Deserializer {
+            len: source.remaining(),
+            source,
+            position: 0,
+            examined: 0,
+            skipped: 0,
+            stats: BorrowStats::default(),
+            limits: Limits::default(),
+            depth: 0,
+            visits: None,
+            hints: None,
+            declared: None,
+            human_readable: None,
+            marked_readable: None,
+            readable_queries: Cell::new(0),
+            bytes_as_seq: false,
+            seq_as_bytes: false,
+            char_as_str: false,
+            owned_as_transient: false,
+            number_kind: None,
+            size_hint: SizeHint::Exact,
+            drain_unvisited: false,
+            path: None,
+            marker: PhantomData,
+        };
+        de.take_readable_markers();
+        de
+    }
+
+    /// Rejects strings longer than `max` bytes with an error, like a
+    /// size-limited deserializer would.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Str("too long")];
+    /// let mut de = Deserializer::new(&tokens).max_str_len(4);
+    /// let err = String::deserialize(&mut de).unwrap_err();
+    /// assert_eq!(err, "string of 8 bytes at index 0 exceeds the limit of 4");
+    /// ```
+    pub fn max_str_len(mut self, max: usize) -> Self {
+        self.limits.str_len = Some(max);
+        self
+    }
+
+    /// Rejects byte arrays longer than `max` bytes with an error, like a
+    /// size-limited deserializer would.
+    pub fn max_bytes_len(mut self, max: usize) -> Self {
+        self.limits.bytes_len = Some(max);
+        self
+    }
+
+    /// Rejects sequences, tuples and maps with more than `max` elements (or
+    /// entries) with an error, like a size-limited deserializer would.
+    ///
+    /// Declared lengths are checked up front, and undeclared ones as the
+    /// elements are visited.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: None },
+    ///     Token::U8(1),
+    ///     Token::U8(2),
+    ///     Token::SeqEnd,
+    /// ];
+    /// let mut de = Deserializer::new(&tokens).max_seq_len(1);
+    /// let err = Vec::<u8>::deserialize(&mut de).unwrap_err();
+    /// assert_eq!(err, "compound at index 0 exceeds the limit of 1 elements");
+    /// ```
+    pub fn max_seq_len(mut self, max: usize) -> Self {
+        self.limits.seq_len = Some(max);
+        self
+    }
+
+    /// Rejects values nested more than `max` deep with an error, like a
+    /// recursion-limited deserializer would.
+    ///
+    /// Each compound, [`Token::Some`], newtype and enum is one level. This
+    /// keeps a recursive `Deserialize` impl from overflowing the stack on a
+    /// deeply nested stream, so that the test fails instead of aborting.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: Some(1) },
+    ///     Token::Seq { len: Some(1) },
+    ///     Token::U8(1),
+    ///     Token::SeqEnd,
+    ///     Token::SeqEnd,
+    /// ];
+    /// let mut de = Deserializer::new(&tokens).max_depth(1);
+    /// let err = Vec::<Vec<u8>>::deserialize(&mut de).unwrap_err();
+    /// assert_eq!(err, "value at index 1 exceeds the depth limit of 1");
+    /// ```
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.limits.depth = Some(max);
+        self
+    }
+
+    /// Makes sequences and maps report a `size_hint` that does not follow
+    /// the `len` of their tokens, like a misbehaving format would.
+    ///
+    /// ```
+    /// # use serde::de::{Deserialize, Deserializer as _, IgnoredAny, SeqAccess, Visitor};
+    /// # use serde_test::de::{Deserializer, SizeHint};
+    /// # use serde_test::Token;
+    /// # use std::fmt;
+    /// #
+    /// struct Hint;
+    ///
+    /// impl<'de> Visitor<'de> for Hint {
+    ///     type Value = Option<usize>;
+    ///
+    ///     fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         f.write_str("a sequence")
+    ///     }
+    ///
+    ///     fn visit_seq<A>(self, mut seq: A) -> Result<Option<usize>, A::Error>
+    ///     where
+    ///         A: SeqAccess<'de>,
+    ///     {
+    ///         let hint = seq.size_hint();
+    ///         while seq.next_element::<IgnoredAny>()?.is_some() {}
+    ///         Ok(hint)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [Token::Seq { len: Some(1) }, Token::U8(0), Token::SeqEnd];
+    /// for (size_hint, expected) in [
+    ///     (SizeHint::Exact, Some(1)),
+    ///     (SizeHint::None, None),
+    ///     (SizeHint::Zero, Some(0)),
+    ///     (SizeHint::Inflated(1000), Some(1001)),
+    /// ] {
+    ///     let mut de = Deserializer::new(&tokens).size_hint(size_hint);
+    ///     assert_eq!(de.deserialize_seq(Hint).unwrap(), expected);
+    /// }
+    ///
+    /// let mut de = Deserializer::new(&tokens).size_hint(SizeHint::Zero);
+    /// assert_eq!(Vec::<u8>::deserialize(&mut de).unwrap(), [0]);
+    /// ```
+    pub fn size_hint(mut self, size_hint: SizeHint) -> Self {
+        self.size_hint = size_hint;
+        self
+    }
+
+    /// Skips the elements of a sequence or map that its visitor returns
+    /// without visiting, as many formats do, instead of failing.
+    ///
+    /// By default a visitor that stops early fails the deserialization,
+    /// naming the elements it left unvisited.
+    ///
+    /// ```
+    /// # use serde::de::{Deserializer as _, SeqAccess, Visitor};
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::fmt;
+    /// #
+    /// struct First;
+    ///
+    /// impl<'de> Visitor<'de> for First {
+    ///     type Value = Option<u8>;
+    ///
+    ///     fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         f.write_str("a sequence")
+    ///     }
+    ///
+    ///     fn visit_seq<A>(self, mut seq: A) -> Result<Option<u8>, A::Error>
+    ///     where
+    ///         A: SeqAccess<'de>,
+    ///     {
+    ///         seq.next_element()
+    ///     }
+    /// }
+    ///
+    /// let tokens = [
+    ///     Token::Seq { len: Some(3) },
+    ///     Token::U8(1),
+    ///     Token::U8(2),
+    ///     Token::Some,
+    ///     Token::U8(3),
+    ///     Token::SeqEnd,
+    /// ];
+    ///
+    /// let mut de = Deserializer::new(&tokens);
+    /// assert_eq!(
+    ///     de.deserialize_seq(First).unwrap_err(),
+    ///     "visit_seq returned at index 2 leaving 2 elements of the compound at index 0 unvisited: 2u8 some 3u8",
+    /// );
+    ///
+    /// let mut de = Deserializer::new(&tokens).drain_unvisited(true);
+    /// assert_eq!((&mut de).deserialize_seq(First).unwrap(), Some(1));
+    /// assert_eq!(de.remaining(), 0);
+    /// ```
+    pub fn drain_unvisited(mut self, drain: bool) -> Self {
+        self.drain_unvisited = drain;
+        self
+    }
+
+    /// Tracks the struct fields, map keys, sequence elements and enum
+    /// variants being deserialized, and prefixes errors with the path to the
+    /// innermost one, like `serde_path_to_error` does.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// #[derive(Deserialize, Debug)]
+    /// struct Outer {
+    ///     inner: Inner,
+    /// }
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct Inner {
+    ///     items: Vec<u8>,
+    /// }
+    ///
+    /// let tokens = [
+    ///     Token::Struct { name: "Outer", len: 1 },
+    ///     Token::Str("inner"),
+    ///     Token::Struct { name: "Inner", len: 1 },
+    ///     Token::Str("items"),
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::U8(1),
+    ///     Token::Str("2"),
+    ///     Token::SeqEnd,
+    ///     Token::StructEnd,
+    ///     Token::StructEnd,
+    /// ];
+    ///
+    /// let mut de = Deserializer::new(&tokens).track_path(true);
+    /// let err = Outer::deserialize(&mut de).unwrap_err();
+    ///
+    /// assert_eq!(err.path(), Some("value.inner.items[1]"));
+    /// assert_eq!(
+    ///     err,
+    ///     "at value.inner.items[1]: invalid type: string \"2\", expected u8",
+    /// );
+    /// ```
+    pub fn track_path(mut self, track: bool) -> Self {
+        self.path = if track { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Returns the string and bytes visits made so far.
+    pub fn borrow_stats(&self) -> BorrowStats {
+        self.stats
+    }
+
+    /// Records the name of every `Visitor` method the deserializer calls, in
+    /// order, to be read back with [`visits`](Self::visits).
+    ///
+    /// Variant names, and the variant keys of enums deserialized as maps,
+    /// are handed to the visitor by serde's own value deserializers and are
+    /// not recorded.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Tuple { len: 2 },
+    ///     Token::U8(1),
+    ///     Token::BorrowedStr("zero"),
+    ///     Token::TupleEnd,
+    /// ];
+    /// let mut de = Deserializer::new(&tokens).record_visits();
+    /// let _: (u8, &str) = Deserialize::deserialize(&mut de).unwrap();
+    ///
+    /// assert_eq!(de.visits(), ["visit_seq", "visit_u8", "visit_borrowed_str"]);
+    /// ```
+    pub fn record_visits(mut self) -> Self {
+        self.visits = Some(Rc::default());
+        self
+    }
+
+    /// Returns the `Visitor` methods called so far, or nothing if they are
+    /// not being recorded.
+    pub fn visits(&self) -> Vec<&'static str> {
+        match &self.visits {
+            Some(visits) => visits.borrow().clone(),
+            None => Vec::new(),
         }
     }
 
-    fn peek_token_opt(&self) -> Option<Token<'test, 'de>> {
-        self.tokens
-            .clone()
-            // ignore skip field tokens while deserializing
-            .find(|t| !matches!(t, Token::SkipStructField { .. }))
+    /// Records the name of every `deserialize_*` method called on the
+    /// deserializer, in order, to be read back with [`hints`](Self::hints).
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Some, Token::Str("a")];
+    /// let mut de = Deserializer::new(&tokens).record_hints();
+    /// Option::<String>::deserialize(&mut de).unwrap();
+    ///
+    /// assert_eq!(de.hints(), ["deserialize_option", "deserialize_string"]);
+    /// ```
+    pub fn record_hints(mut self) -> Self {
+        self.hints = Some(Vec::new());
+        self
     }
 
-    fn peek_token(&self) -> TestResult<Token<'test, 'de>> {
-        self.peek_token_opt().ok_or_else(end_of_tokens)
+    /// Returns the `deserialize_*` methods called so far, or nothing if they
+    /// are not being recorded.
+    pub fn hints(&self) -> &[&'static str] {
+        self.hints.as_deref().unwrap_or_default()
+    }
+
+    /// Answers `is_human_readable` with `readable` instead of panicking, for
+    /// types that are meant to ask. Every query is counted either way; see
+    /// [`readable_queries`](Self::readable_queries).
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// let tokens = [Token::Str("127.0.0.1")];
+    /// let mut de = Deserializer::new(&tokens).human_readable(true);
+    /// Ipv4Addr::deserialize(&mut de).unwrap();
+    /// assert_eq!(de.readable_queries(), 1);
+    /// ```
+    pub fn human_readable(mut self, readable: bool) -> Self {
+        self.human_readable = Some(readable);
+        self
+    }
+
+    /// Returns how many times `is_human_readable` has been called.
+    pub fn readable_queries(&self) -> usize {
+        self.readable_queries.get()
+    }
+
+    /// Visits [`Token::Bytes`], [`Token::BorrowedBytes`] and
+    /// [`Token::ByteBuf`] as a sequence of `u8`, the way formats without a
+    /// bytes type hand them out.
+    ///
+    /// Together with [`Serializer::bytes_as_seq`](crate::ser::Serializer::bytes_as_seq),
+    /// this tests `serde_bytes`-style wrappers against such formats.
+    ///
+    /// ```
+    /// # use serde::de::{Deserialize, SeqAccess, Visitor};
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::fmt;
+    /// #
+    /// struct Buf(Vec<u8>);
+    ///
+    /// impl<'de> Deserialize<'de> for Buf {
+    ///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    ///     where
+    ///         D: serde::Deserializer<'de>,
+    ///     {
+    ///         struct BufVisitor;
+    ///
+    ///         impl<'de> Visitor<'de> for BufVisitor {
+    ///             type Value = Buf;
+    ///
+    ///             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///                 f.write_str("bytes")
+    ///             }
+    ///
+    ///             fn visit_bytes<E>(self, v: &[u8]) -> Result<Buf, E> {
+    ///                 Ok(Buf(v.to_vec()))
+    ///             }
+    ///
+    ///             fn visit_seq<A>(self, mut seq: A) -> Result<Buf, A::Error>
+    ///             where
+    ///                 A: SeqAccess<'de>,
+    ///             {
+    ///                 let mut buf = Vec::new();
+    ///                 while let Some(byte) = seq.next_element()? {
+    ///                     buf.push(byte);
+    ///                 }
+    ///                 Ok(Buf(buf))
+    ///             }
+    ///         }
+    ///
+    ///         deserializer.deserialize_bytes(BufVisitor)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [Token::Bytes(b"ab")];
+    /// let mut de = Deserializer::new(&tokens).bytes_as_seq(true).record_visits();
+    /// assert_eq!(Buf::deserialize(&mut de).unwrap().0, b"ab");
+    /// assert_eq!(de.visits(), ["visit_seq"]);
+    /// ```
+    pub fn bytes_as_seq(mut self, as_seq: bool) -> Self {
+        self.bytes_as_seq = as_seq;
+        self
+    }
+
+    /// Makes `deserialize_bytes` and `deserialize_byte_buf` accept a
+    /// [`Token::Seq`] of [`Token::U8`]s, collected into a buffer and visited
+    /// as bytes, the way formats without a bytes type can hand them out.
+    /// Byte tokens are visited as usual.
+    ///
+    /// Together with [`bytes_as_seq`](Self::bytes_as_seq), this tests both
+    /// of the paths a bytes visitor may be given.
+    ///
+    /// ```
+    /// # use serde::de::{Deserializer as _, Visitor};
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::fmt;
+    /// #
+    /// struct BufVisitor;
+    ///
+    /// impl<'de> Visitor<'de> for BufVisitor {
+    ///     type Value = Vec<u8>;
+    ///
+    ///     fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         f.write_str("bytes")
+    ///     }
+    ///
+    ///     fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+    ///         Ok(v)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::U8(b'a'),
+    ///     Token::U8(b'b'),
+    ///     Token::SeqEnd,
+    /// ];
+    /// let mut de = Deserializer::new(&tokens).seq_as_bytes(true);
+    /// assert_eq!((&mut de).deserialize_byte_buf(BufVisitor).unwrap(), b"ab");
+    ///
+    /// let tokens = [Token::ByteBuf(b"ab")];
+    /// let mut de = Deserializer::new(&tokens).seq_as_bytes(true);
+    /// assert_eq!((&mut de).deserialize_byte_buf(BufVisitor).unwrap(), b"ab");
+    /// ```
+    pub fn seq_as_bytes(mut self, as_bytes: bool) -> Self {
+        self.seq_as_bytes = as_bytes;
+        self
+    }
+
+    /// Makes `deserialize_char` visit a one-character [`Token::Str`],
+    /// [`Token::BorrowedStr`] or [`Token::String`] with `visit_char`, the
+    /// way formats that write a `char` as a string hand it out.
+    ///
+    /// Together with [`Serializer::char_as_str`](crate::ser::Serializer::char_as_str),
+    /// this tests custom `char` handling against such formats.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Str("x")];
+    /// let mut de = Deserializer::new(&tokens).char_as_str(true).record_visits();
+    /// assert_eq!(char::deserialize(&mut de).unwrap(), 'x');
+    /// assert_eq!(de.visits(), ["visit_char"]);
+    /// ```
+    pub fn char_as_str(mut self, as_str: bool) -> Self {
+        self.char_as_str = as_str;
+        self
+    }
+
+    /// Visits [`Token::String`] and [`Token::ByteBuf`] with `visit_str` and
+    /// `visit_bytes`, like [`Token::Str`] and [`Token::Bytes`], instead of
+    /// allocating a `String` or `Vec<u8>` to hand to `visit_string` and
+    /// `visit_byte_buf`.
+    ///
+    /// A visitor can only be given one of these, so the harness cannot try
+    /// the transient visit and fall back to the owned one. This keeps tight
+    /// property-test loops over owned tokens free of allocations wherever
+    /// the visitor does not need the ownership, and the visits are counted
+    /// as transient in the [`borrow_stats`](Self::borrow_stats).
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::String("copy")];
+    /// let mut de = Deserializer::new(&tokens)
+    ///     .owned_as_transient(true)
+    ///     .record_visits();
+    /// assert_eq!(String::deserialize(&mut de).unwrap(), "copy");
+    /// assert_eq!(de.visits(), ["visit_str"]);
+    /// assert_eq!(de.borrow_stats().owned(), 0);
+    /// ```
+    pub fn owned_as_transient(mut self, transient: bool) -> Self {
+        self.owned_as_transient = transient;
+        self
+    }
+
+    /// Visits [`Token::Number`]s with the integer width of `kind`, like
+    /// `visit_u8` for [`TokenKind::U8`], instead of as a `u64` or `i64`.
+    ///
+    /// A number that does not fit in that width fails with an error.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::{Token, TokenKind};
+    /// #
+    /// let tokens = [Token::Number(7)];
+    /// let mut de = Deserializer::new(&tokens)
+    ///     .number_kind(TokenKind::U8)
+    ///     .record_visits();
+    /// assert_eq!(u32::deserialize(&mut de).unwrap(), 7);
+    /// assert_eq!(de.visits(), ["visit_u8"]);
+    ///
+    /// let tokens = [Token::Number(-7)];
+    /// let mut de = Deserializer::new(&tokens).number_kind(TokenKind::U8);
+    /// let err = i32::deserialize(&mut de).unwrap_err();
+    /// assert_eq!(err, "Token::Number(-7) at index 0 does not fit in U8");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `kind` is not one of the integer kinds.
+    #[track_caller]
+    pub fn number_kind(mut self, kind: TokenKind) -> Self {
+        match kind {
+            TokenKind::I8
+            | TokenKind::I16
+            | TokenKind::I32
+            | TokenKind::I64
+            | TokenKind::I128
+            | TokenKind::Isize
+            | TokenKind::U8
+            | TokenKind::U16
+            | TokenKind::U32
+            | TokenKind::U64
+            | TokenKind::U128
+            | TokenKind::Usize => self.number_kind = Some(kind),
+            _ => panic!("Token::Number cannot be visited as {}", kind),
+        }
+        self
+    }
+
+    fn query_readable(&self) -> bool {
+        self.readable_queries.set(self.readable_queries.get() + 1);
+        match self
+            .marked_readable
+            .or(self.human_readable)
+            .or_else(configure::default_readable)
+        {
+            Some(readable) => readable,
+            None => panic!(
+                "Types which have different human-readable and compact representations \
+                 must explicitly mark their test cases with `serde_test::Configure`"
+            ),
+        }
+    }
+
+    fn hint(&mut self, method: &'static str) {
+        if let Some(hints) = &mut self.hints {
+            hints.push(method);
+        }
+    }
+
+    /// Records the names a `deserialize_*` method was called with, so that
+    /// they can be checked against the ones the value serialized with.
+    pub(crate) fn record_declared(mut self) -> Self {
+        self.declared = Some(Vec::new());
+        self
+    }
+
+    /// The names recorded since [`record_declared`](Self::record_declared),
+    /// with the position of the next token when each was declared.
+    pub(crate) fn declared(&self) -> &[(usize, Declared)] {
+        self.declared.as_deref().unwrap_or_default()
+    }
+
+    fn declare(&mut self, declared: Declared) {
+        let position = self.position;
+        if let Some(all) = &mut self.declared {
+            all.push((position, declared));
+        }
+    }
+
+    /// Wraps `visitor` to record the methods called on it.
+    fn recorded<V>(&self, visitor: V) -> Recorded<V> {
+        Recorded {
+            visitor,
+            visits: self.visits.clone(),
+        }
+    }
+
+    fn peek_token_opt(&mut self) -> Option<Token<'test, 'de>> {
+        let mut n = self.skipped;
+        loop {
+            match self.source.peek_nth(n) {
+                // ignore skip field tokens while deserializing
+                Some(Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry) => {
+                    n += 1
+                }
+                Some(token) => {
+                    self.skipped = n;
+                    self.examined = self.examined.max(self.position + n + 1);
+                    return Some(first_alternative(token));
+                }
+                None => {
+                    self.skipped = n;
+                    self.examined = self.examined.max(self.position + n);
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Returns the next token without consuming it, skipping any
+    /// [`Token::SkipStructField`], [`Token::Readable`] and [`Token::MapEntry`]
+    /// markers.
+    ///
+    /// A peeked token no longer counts as [`unexamined`](Self::unexamined).
+    ///
+    /// ```
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::SkipStructField { name: "a" }, Token::U8(1)];
+    /// let mut de = Deserializer::new(&tokens);
+    /// assert_eq!(de.peek_token().unwrap(), Token::U8(1));
+    /// assert_eq!(de.position(), 0);
+    /// assert_eq!(de.unexamined(), 0);
+    /// ```
+    pub fn peek_token(&mut self) -> TestResult<Token<'test, 'de>> {
+        match self.peek_token_opt() {
+            Some(token) => Ok(token),
+            None => Err(self.end_of_tokens()),
+        }
     }
 
     pub fn next_token_opt(&mut self) -> Option<Token<'test, 'de>> {
-        self.tokens
-            // ignore skip field tokens while deserializing
-            .find(|t| !matches!(t, Token::SkipStructField { .. }))
+        while let Some(token) = self.source.next_token() {
+            self.position += 1;
+            self.skipped = self.skipped.saturating_sub(1);
+            self.examined = self.examined.max(self.position);
+            match token {
+                // ignore skip field tokens while deserializing
+                Token::SkipStructField { .. } | Token::MapEntry => {}
+                Token::Readable(readable) => self.marked_readable = Some(readable),
+                token => {
+                    trace::token("deserialize", self.position - 1, token);
+                    self.take_readable_markers();
+                    return Some(first_alternative(token));
+                }
+            }
+        }
+        None
+    }
+
+    /// Consumes any [`Token::Readable`] markers at the front of the stream,
+    /// so that the value after them sees their answer before it
+    /// deserializes anything.
+    fn take_readable_markers(&mut self) {
+        while let Some(Token::Readable(readable)) = self.source.peek_nth(0) {
+            self.source.next_token();
+            self.position += 1;
+            self.skipped = self.skipped.saturating_sub(1);
+            self.examined = self.examined.max(self.position);
+            self.marked_readable = Some(readable);
+        }
     }
 
     fn next_token(&mut self) -> TestResult<Token<'test, 'de>> {
-        self.next_token_opt().ok_or_else(end_of_tokens)
+        match self.next_token_opt() {
+            Some(token) => {
+                self.check_limits(token)?;
+                Ok(token)
+            }
+            None => Err(self.end_of_tokens()),
+        }
     }
 
-    pub fn remaining(&self) -> usize {
-        self.tokens.len()
+    /// Checks the most recently consumed token against the size limits.
+    fn check_limits(&self, token: Token<'_, '_>) -> TestResult {
+        let index = self.position() - 1;
+        let (kind, len, max) = match token {
+            Token::Str(v) | Token::BorrowedStr(v) | Token::String(v) | Token::CollectStr(v) => {
+                ("string", v.len(), self.limits.str_len)
+            }
+            Token::Bytes(v) | Token::BorrowedBytes(v) | Token::ByteBuf(v) => {
+                ("bytes", v.len(), self.limits.bytes_len)
+            }
+            Token::Seq { len: Some(len) }
+            | Token::Map { len: Some(len) }
+            | Token::Tuple { len }
+            | Token::TupleStruct { len, .. }
+            | Token::TupleVariant { len, .. } => {
+                return self.check_seq_len(index, len);
+            }
+            _ => return Ok(()),
+        };
+        match max {
+            Some(max) if len > max => Err(Error::with_kind(
+                ErrorKind::LimitExceeded,
+                format_args!(
+                    "{} of {} bytes at index {} exceeds the limit of {}",
+                    kind, len, index, max,
+                ),
+            )
+            .at(index)
+            .with_expected(token)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consumes a [`Token::Seq`] of [`Token::U8`]s and returns the bytes, if
+    /// [`seq_as_bytes`](Self::seq_as_bytes) is set and one is next.
+    fn seq_bytes(&mut self) -> TestResult<Option<Vec<u8>>> {
+        if !self.seq_as_bytes || !matches!(self.peek_token()?, Token::Seq { .. }) {
+            return Ok(None);
+        }
+        self.next_token()?;
+        let start = self.position() - 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next_token()? {
+                Token::U8(byte) => {
+                    bytes.push(byte);
+                    self.check_seq_len(start, bytes.len())?;
+                }
+                Token::SeqEnd => return Ok(Some(bytes)),
+                token => return Err(self.unexpected(token)),
+            }
+        }
+    }
+
+    /// Checks that the compound starting at `index` has at most `len`
+    /// elements.
+    fn check_seq_len(&self, index: usize, len: usize) -> TestResult {
+        match self.limits.seq_len {
+            Some(max) if len > max => Err(Error::with_kind(
+                ErrorKind::LimitExceeded,
+                format_args!(
+                    "compound at index {} exceeds the limit of {} elements",
+                    index, max,
+                ),
+            )
+            .at(index)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports `token`, the most recently consumed token, as unexpected.
+    fn unexpected(&self, token: Token<'_, '_>) -> Error {
+        Error::with_kind(
+            ErrorKind::Mismatch,
+            format_args!(
+                "deserialization did not expect this token at {}: {}",
+                self.at(self.position() - 1),
+                token,
+            ),
+        )
+        .at(self.position() - 1)
+        .with_expected(token)
+    }
+
+    fn end_of_tokens(&self) -> Error {
+        Error::with_kind(
+            ErrorKind::EndOfTokens,
+            format_args!(
+                "ran out of tokens to deserialize after all {} tokens",
+                self.position(),
+            ),
+        )
+        .at(self.position())
+    }
+
+    /// Checks that no tokens are left over once a deserializer used by value
+    /// is done.
+    fn finish(&mut self) -> TestResult {
+        match self.peek_token_opt() {
+            Some(token) => {
+                let index = self.position();
+                Err(Error::with_kind(
+                    ErrorKind::EndOfTokens,
+                    format_args!(
+                        "deserialization ended at index {} but Token::{} remains",
+                        index, token,
+                    ),
+                )
+                .at(index)
+                .with_expected(token))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes with the deserializer, failing with every token left over if
+    /// it has not consumed them all.
+    ///
+    /// This is the check the asserts make after deserializing, for harnesses
+    /// that drive the deserializer themselves. Trailing markers such as
+    /// [`Token::SkipStructField`] do not count as left over.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::U8(1), Token::Bool(true), Token::Unit];
+    /// let mut de = Deserializer::new(&tokens);
+    /// u8::deserialize(&mut de).unwrap();
+    ///
+    /// assert_eq!(
+    ///     de.end().unwrap_err(),
+    ///     "deserialization ended at index 1 but 2 tokens remain:\nBool(true),\nUnit,\n",
+    /// );
+    /// ```
+    pub fn end(mut self) -> TestResult {
+        let first = match self.peek_token_opt() {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        let index = self.position();
+        let mut remaining = Vec::new();
+        while let Some(token) = self.source.next_token() {
+            remaining.push(token);
+        }
+        Err(Error::with_kind(
+            ErrorKind::EndOfTokens,
+            format_args!(
+                "deserialization ended at index {} but {} tokens remain:\n{}",
+                index,
+                remaining.len(),
+                DisplayTokens::new(&remaining),
+            ),
+        )
+        .at(index)
+        .with_expected(first))
+    }
+
+    fn at(&self, index: usize) -> At {
+        At {
+            index,
+            len: self.len,
+        }
+    }
+
+    /// Returns the index of the next token to be consumed, counting from the
+    /// start of the tokens the deserializer was created with.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Some, Token::U8(1), Token::Bool(true)];
+    /// let mut de = Deserializer::new(&tokens);
+    /// Option::<u8>::deserialize(&mut de).unwrap();
+    /// assert_eq!(de.position(), 2);
+    /// assert_eq!(de.remaining_tokens(), [Token::Bool(true)]);
+    /// ```
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Consumes the next whole value, including everything inside it if it
+    /// is a compound, without visiting any of it.
+    ///
+    /// After a `Deserialize` impl fails, the deserializer is left wherever
+    /// the error was raised. [`rewind`](Self::rewind) to a checkpoint taken
+    /// before the value followed by `skip_value` moves past it, so that the
+    /// values after it can still be deserialized.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: Some(1) },
+    ///     Token::Str("x"),
+    ///     Token::SeqEnd,
+    ///     Token::U8(1),
+    /// ];
+    /// let mut de = Deserializer::new(&tokens);
+    ///
+    /// let checkpoint = de.checkpoint();
+    /// assert!(Vec::<u8>::deserialize(&mut de).is_err());
+    /// de.rewind(checkpoint);
+    /// de.skip_value().unwrap();
+    /// assert_eq!(u8::deserialize(&mut de).unwrap(), 1);
+    /// ```
+    pub fn skip_value(&mut self) -> TestResult {
+        let mut walker = Walker::new();
+        while !walker.is_done() {
+            let token = match self.next_token_opt() {
+                Some(token) => token,
+                None => return Err(self.end_of_tokens()),
+            };
+            if walker.step(self.position() - 1, token).is_err() {
+                return Err(self.unexpected(token));
+            }
+        }
+        Ok(())
+    }
+
+    /// Feeds the tokens straight into `visitor`, calling the `visit_*`
+    /// method that the next token calls for, as `deserialize_any` does, and
+    /// checks that no tokens are left over.
+    ///
+    /// This tests a hand-written `Visitor` on its own, without a
+    /// `Deserialize` impl around it.
+    ///
+    /// ```
+    /// # use serde::de::{SeqAccess, Visitor};
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::fmt;
+    /// #
+    /// struct SumVisitor;
+    ///
+    /// impl<'de> Visitor<'de> for SumVisitor {
+    ///     type Value = u64;
+    ///
+    ///     fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         f.write_str("a number or a sequence of numbers")
+    ///     }
+    ///
+    ///     fn visit_u64<E>(self, v: u64) -> Result<u64, E> {
+    ///         Ok(v)
+    ///     }
+    ///
+    ///     fn visit_seq<A>(self, mut seq: A) -> Result<u64, A::Error>
+    ///     where
+    ///         A: SeqAccess<'de>,
+    ///     {
+    ///         let mut sum = 0;
+    ///         while let Some(v) = seq.next_element::<u64>()? {
+    ///             sum += v;
+    ///         }
+    ///         Ok(sum)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [
+    ///     Token::Seq { len: None },
+    ///     Token::U8(1),
+    ///     Token::U32(2),
+    ///     Token::SeqEnd,
+    /// ];
+    /// assert_eq!(Deserializer::new(&tokens).visit(SumVisitor).unwrap(), 3);
+    ///
+    /// let err = Deserializer::new(&[Token::Str("x")]).visit(SumVisitor).unwrap_err();
+    /// assert_eq!(err, "invalid type: string \"x\", expected a number or a sequence of numbers");
+    /// ```
+    pub fn visit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    /// Consumes the end token of the compound whose header is at `start`
+    /// once its visitor has returned, first draining or failing on the
+    /// elements the visitor left unvisited.
+    fn end_compound(&mut self, start: usize, end: EndToken, map: bool) -> Result<(), Error> {
+        if let Some(unvisited) = self.unvisited(end) {
+            let mut values = 0;
+            let mut i = 0;
+            while i < unvisited.len() {
+                i = match group::value_end(&unvisited, i) {
+                    Some(i) => i,
+                    None => return assert_next_token(self, end.token()),
+                };
+                values += 1;
+            }
+            if self.drain_unvisited {
+                for _ in 0..values {
+                    self.skip_value()?;
+                }
+            } else {
+                let (method, count, what) = if map {
+                    ("visit_map", values / 2, "entries")
+                } else {
+                    ("visit_seq", values, "elements")
+                };
+                let index = self.position();
+                return Err(Error::with_kind(
+                    ErrorKind::Mismatch,
+                    format_args!(
+                        "{} returned at index {} leaving {} {} of the compound at index {} unvisited: {:#}",
+                        method,
+                        index,
+                        count,
+                        what,
+                        start,
+                        DisplayTokens::new(&unvisited),
+                    ),
+                )
+                .at(index)
+                .with_expected(unvisited[0]));
+            }
+        }
+        assert_next_token(self, end.token())
+    }
+
+    /// Returns the tokens, without markers, between the next one and the
+    /// `end` token closing the current compound, or `None` if the next
+    /// token is an end token or the compound is not closed by `end`.
+    fn unvisited(&mut self, end: EndToken) -> Option<Vec<Token<'test, 'de>>> {
+        if EndToken::from_token(self.peek_token_opt()?).is_some() {
+            return None;
+        }
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        let mut n = 0;
+        loop {
+            let token = self.source.peek_nth(n)?;
+            n += 1;
+            match EndToken::from_token(token) {
+                Some(found) if depth == 0 => return Some(tokens).filter(|_| found == end),
+                Some(_) => depth -= 1,
+                None if EndToken::for_header(token).is_some() => depth += 1,
+                None => {}
+            }
+            if !matches!(
+                token,
+                Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry
+            ) {
+                tokens.push(token);
+            }
+        }
+    }
+
+    /// Runs `f` with `segment` added to the tracked path, recording the path
+    /// in any error it returns. Runs `f` as is if paths are not tracked.
+    fn in_path<T>(
+        &mut self,
+        segment: impl FnOnce() -> Segment,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        match &mut self.path {
+            Some(path) => path.push(segment()),
+            None => return f(self),
+        }
+        let result = f(self);
+        let path = self.path.as_mut().unwrap();
+        let result = result.map_err(|err| err.with_path(Path(path)));
+        path.pop();
+        result
+    }
+
+    /// Runs `f` one level deeper, for the value starting at `index`, failing
+    /// instead if that exceeds the depth limit.
+    fn nested<T>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        match self.limits.depth {
+            Some(max) if self.depth >= max => {
+                return Err(Error::with_kind(
+                    ErrorKind::LimitExceeded,
+                    format_args!(
+                        "value at index {} exceeds the depth limit of {}",
+                        index, max
+                    ),
+                )
+                .at(index));
+            }
+            _ => {}
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Visits the [`Token::Number`] `v` at `index` with the integer width
+    /// chosen by [`number_kind`](Self::number_kind), or by its sign and size.
+    fn visit_number<V>(&self, index: usize, v: i128, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let kind = match self.number_kind {
+            Some(kind) => kind,
+            None if v < 0 && i64::try_from(v).is_ok() => TokenKind::I64,
+            None if v < 0 => TokenKind::I128,
+            None if u64::try_from(v).is_ok() => TokenKind::U64,
+            None => TokenKind::U128,
+        };
+        let visited = match kind {
+            TokenKind::I8 => i8::try_from(v).ok().map(|v| visitor.visit_i8(v)),
+            TokenKind::I16 => i16::try_from(v).ok().map(|v| visitor.visit_i16(v)),
+            TokenKind::I32 => i32::try_from(v).ok().map(|v| visitor.visit_i32(v)),
+            TokenKind::I64 => i64::try_from(v).ok().map(|v| visitor.visit_i64(v)),
+            TokenKind::Isize => isize::try_from(v).ok().map(|v| visitor.visit_i64(v as i64)),
+            TokenKind::U8 => u8::try_from(v).ok().map(|v| visitor.visit_u8(v)),
+            TokenKind::U16 => u16::try_from(v).ok().map(|v| visitor.visit_u16(v)),
+            TokenKind::U32 => u32::try_from(v).ok().map(|v| visitor.visit_u32(v)),
+            TokenKind::U64 => u64::try_from(v).ok().map(|v| visitor.visit_u64(v)),
+            TokenKind::U128 => u128::try_from(v).ok().map(|v| visitor.visit_u128(v)),
+            TokenKind::Usize => usize::try_from(v).ok().map(|v| visitor.visit_u64(v as u64)),
+            // Only `TokenKind::I128` is left.
+            _ => Some(visitor.visit_i128(v)),
+        };
+        match visited {
+            Some(result) => result,
+            None => Err(Error::with_kind(
+                ErrorKind::Mismatch,
+                format_args!(
+                    "Token::Number({}) at index {} does not fit in {}",
+                    v, index, kind,
+                ),
+            )
+            .at(index)
+            .with_found(Token::Number(v))),
+        }
     }
 
     fn visit_seq<V>(
@@ -83,8 +1600,18 @@ impl<'test, 'de> Deserializer<'test, 'de> {
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_seq(DeserializerSeqVisitor { de: self, len, end })?;
-        assert_next_token(self, end.token())?;
+        let start = self.position() - 1;
+        let _span = trace::compound("deserialize", start, end);
+        let value = self.nested(start, |de| {
+            visitor.visit_seq(DeserializerSeqVisitor {
+                start,
+                count: 0,
+                de,
+                len,
+                end,
+            })
+        })?;
+        self.end_compound(start, end, false)?;
         Ok(value)
     }
 
@@ -97,25 +1624,30 @@ impl<'test, 'de> Deserializer<'test, 'de> {
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_map(DeserializerMapVisitor { de: self, len, end })?;
-        assert_next_token(self, end.token())?;
+        let start = self.position() - 1;
+        let _span = trace::compound("deserialize", start, end);
+        let value = self.nested(start, |de| {
+            visitor.visit_map(DeserializerMapVisitor {
+                start,
+                count: 0,
+                de,
+                len,
+                end,
+                key: None,
+            })
+        })?;
+        self.end_compound(start, end, true)?;
         Ok(value)
     }
-}
-
-impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de> {
-    type Error = Error;
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit seq map identifier ignored_any
-    }
-
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    /// Deserializes the next value as self-describing, passing it to the
+    /// already recorded `visitor`.
+    fn any<V>(&mut self, visitor: Recorded<V>) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
         let token = self.next_token()?;
+        let index = self.position() - 1;
         match token {
             Token::Bool(v) => visitor.visit_bool(v),
             Token::I8(v) => visitor.visit_i8(v),
@@ -130,17 +1662,53 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
             Token::U128(v) => visitor.visit_u128(v),
             Token::F32(v) => visitor.visit_f32(v),
             Token::F64(v) => visitor.visit_f64(v),
+            Token::F32Bits(v) => visitor.visit_f32(f32::from_bits(v)),
+            Token::F64Bits(v) => visitor.visit_f64(f64::from_bits(v)),
+            Token::Isize(v) => visitor.visit_i64(v as i64),
+            Token::Usize(v) => visitor.visit_u64(v as u64),
+            Token::Number(v) => self.visit_number(index, v, visitor),
             Token::Char(v) => visitor.visit_char(v),
-            Token::Str(v) => visitor.visit_str(v),
-            Token::BorrowedStr(v) => visitor.visit_borrowed_str(v),
-            Token::String(v) => visitor.visit_string(v.to_owned()),
-            Token::Bytes(v) => visitor.visit_bytes(v),
-            Token::BorrowedBytes(v) => visitor.visit_borrowed_bytes(v),
-            Token::ByteBuf(v) => visitor.visit_byte_buf(v.to_owned()),
+            Token::Str(v) | Token::CollectStr(v) => {
+                self.stats.transient_str += 1;
+                visitor.visit_str(v)
+            }
+            Token::BorrowedStr(v) => {
+                self.stats.borrowed_str += 1;
+                visitor.visit_borrowed_str(v)
+            }
+            Token::String(v) if self.owned_as_transient => {
+                self.stats.transient_str += 1;
+                visitor.visit_str(v)
+            }
+            Token::String(v) => {
+                self.stats.owned_str += 1;
+                visitor.visit_string(v.to_owned())
+            }
+            Token::Bytes(v) | Token::BorrowedBytes(v) | Token::ByteBuf(v) if self.bytes_as_seq => {
+                visitor.visit_seq(SeqDeserializer::new(v.iter().copied()))
+            }
+            Token::Bytes(v) => {
+                self.stats.transient_bytes += 1;
+                visitor.visit_bytes(v)
+            }
+            Token::BorrowedBytes(v) => {
+                self.stats.borrowed_bytes += 1;
+                visitor.visit_borrowed_bytes(v)
+            }
+            Token::ByteBuf(v) if self.owned_as_transient => {
+                self.stats.transient_bytes += 1;
+                visitor.visit_bytes(v)
+            }
+            Token::ByteBuf(v) => {
+                self.stats.owned_bytes += 1;
+                visitor.visit_byte_buf(v.to_owned())
+            }
             Token::None => visitor.visit_none(),
-            Token::Some => visitor.visit_some(self),
+            Token::Some => self.nested(index, |de| visitor.visit_some(de)),
             Token::Unit | Token::UnitStruct { .. } => visitor.visit_unit(),
-            Token::NewtypeStruct { .. } => visitor.visit_newtype_struct(self),
+            Token::NewtypeStruct { .. } => {
+                self.nested(index, |de| visitor.visit_newtype_struct(de))
+            }
             Token::Seq { len } => self.visit_seq(len, EndToken::Seq, visitor),
             Token::Tuple { len } => self.visit_seq(Some(len), EndToken::Tuple, visitor),
             Token::TupleStruct { len, .. } => {
@@ -152,30 +1720,66 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                 let variant = self.next_token()?;
                 let next = self.peek_token()?;
                 match (variant, next) {
-                    (Token::Str(variant), Token::Unit) => {
+                    (Token::Str(variant) | Token::CollectStr(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.transient_str += 1;
                         visitor.visit_str(variant)
                     }
                     (Token::BorrowedStr(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.borrowed_str += 1;
                         visitor.visit_borrowed_str(variant)
                     }
+                    (Token::String(variant), Token::Unit) if self.owned_as_transient => {
+                        self.next_token()?;
+                        self.stats.transient_str += 1;
+                        visitor.visit_str(variant)
+                    }
                     (Token::String(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.owned_str += 1;
                         visitor.visit_string(variant.to_string())
                     }
                     (Token::Bytes(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.transient_bytes += 1;
                         visitor.visit_bytes(variant)
                     }
                     (Token::BorrowedBytes(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.borrowed_bytes += 1;
                         visitor.visit_borrowed_bytes(variant)
                     }
+                    (Token::ByteBuf(variant), Token::Unit) if self.owned_as_transient => {
+                        self.next_token()?;
+                        self.stats.transient_bytes += 1;
+                        visitor.visit_bytes(variant)
+                    }
                     (Token::ByteBuf(variant), Token::Unit) => {
                         self.next_token()?;
+                        self.stats.owned_bytes += 1;
                         visitor.visit_byte_buf(variant.to_vec())
                     }
+                    (Token::I8(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_i8(variant)
+                    }
+                    (Token::I16(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_i16(variant)
+                    }
+                    (Token::I32(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_i32(variant)
+                    }
+                    (Token::I64(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_i64(variant)
+                    }
+                    (Token::I128(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_i128(variant)
+                    }
                     (Token::U8(variant), Token::Unit) => {
                         self.next_token()?;
                         visitor.visit_u8(variant)
@@ -184,7 +1788,8 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                         self.next_token()?;
                         visitor.visit_u16(variant)
                     }
-                    (Token::U32(variant), Token::Unit) => {
+                    (Token::U32(variant), Token::Unit)
+                    | (Token::VariantIndex(variant), Token::Unit) => {
                         self.next_token()?;
                         visitor.visit_u32(variant)
                     }
@@ -192,43 +1797,260 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                         self.next_token()?;
                         visitor.visit_u64(variant)
                     }
-                    (variant, Token::Unit) => Err(unexpected(variant)),
-                    (variant, _) => {
-                        visitor.visit_map(EnumMapVisitor::new(self, variant, EnumFormat::Any))
+                    (Token::U128(variant), Token::Unit) => {
+                        self.next_token()?;
+                        visitor.visit_u128(variant)
                     }
+                    (variant, Token::Unit) => Err(self.unexpected(variant)),
+                    (variant, _) => self.nested(index, |de| {
+                        visitor.visit_map(EnumMapVisitor::new(de, variant, EnumFormat::Any))
+                    }),
                 }
             }
             Token::UnitVariant { variant, .. } => visitor.visit_str(variant),
-            Token::NewtypeVariant { variant, .. } => visitor.visit_map(EnumMapVisitor::new(
-                self,
-                Token::Str(variant),
-                EnumFormat::Any,
-            )),
-            Token::TupleVariant { variant, .. } => visitor.visit_map(EnumMapVisitor::new(
-                self,
-                Token::Str(variant),
-                EnumFormat::Seq,
-            )),
-            Token::StructVariant { variant, .. } => visitor.visit_map(EnumMapVisitor::new(
-                self,
-                Token::Str(variant),
-                EnumFormat::Map,
-            )),
+            Token::NewtypeVariant { variant, .. } => self.nested(index, |de| {
+                visitor.visit_map(EnumMapVisitor::new(
+                    de,
+                    Token::Str(variant),
+                    EnumFormat::Any,
+                ))
+            }),
+            Token::TupleVariant { variant, .. } => self.nested(index, |de| {
+                visitor.visit_map(EnumMapVisitor::new(
+                    de,
+                    Token::Str(variant),
+                    EnumFormat::Seq,
+                ))
+            }),
+            Token::StructVariant { variant, .. } => self.nested(index, |de| {
+                visitor.visit_map(EnumMapVisitor::new(
+                    de,
+                    Token::Str(variant),
+                    EnumFormat::Map,
+                ))
+            }),
             Token::SeqEnd
             | Token::TupleEnd
             | Token::TupleStructEnd
             | Token::MapEnd
             | Token::StructEnd
             | Token::TupleVariantEnd
-            | Token::StructVariantEnd => Err(unexpected(token)),
-            Token::SkipStructField { .. } => unreachable!("always ignored by next_token"),
+            | Token::StructVariantEnd => Err(self.unexpected(token)),
+            // Only an empty `OneOf` is left unresolved.
+            Token::OneOf(_) | Token::Matches(_) => Err(self.unexpected(token)),
+            Token::VariantIndex(_) => self.any(visitor),
+            Token::FieldIndex(v) => visitor.visit_u64(v),
+            Token::Identifier(_) | Token::IdentifierIndex(_) => Err(Error::with_kind(
+                ErrorKind::Mismatch,
+                format_args!(
+                    "Token::{:?} at {} is only accepted by deserialize_identifier",
+                    token,
+                    self.at(self.position() - 1),
+                ),
+            )
+            .at(self.position() - 1)
+            .with_expected(token)),
+            Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry => {
+                unreachable!("always ignored by next_token")
+            }
         }
     }
+}
+
+/// A clone picks up where the original is, with copies of its recorded
+/// visits, hints and counters that then evolve separately.
+impl<'test, 'de, S> Clone for Deserializer<'test, 'de, S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Deserializer {
+            source: self.source.clone(),
+            len: self.len,
+            position: self.position,
+            examined: self.examined,
+            skipped: self.skipped,
+            stats: self.stats,
+            limits: self.limits,
+            depth: self.depth,
+            visits: self
+                .visits
+                .as_ref()
+                .map(|visits| Rc::new(RefCell::new(visits.borrow().clone()))),
+            hints: self.hints.clone(),
+            declared: self.declared.clone(),
+            human_readable: self.human_readable,
+            marked_readable: self.marked_readable,
+            readable_queries: self.readable_queries.clone(),
+            bytes_as_seq: self.bytes_as_seq,
+            seq_as_bytes: self.seq_as_bytes,
+            char_as_str: self.char_as_str,
+            owned_as_transient: self.owned_as_transient,
+            number_kind: self.number_kind,
+            size_hint: self.size_hint,
+            drain_unvisited: self.drain_unvisited,
+            path: self.path.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'test, 'de, S> Deserializer<'test, 'de, S>
+where
+    S: Clone,
+{
+    /// Saves the current state, to return to with [`rewind`](Self::rewind)
+    /// after an attempt to deserialize fails or needs retrying.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Str("7")];
+    /// let mut de = Deserializer::new(&tokens);
+    ///
+    /// let checkpoint = de.checkpoint();
+    /// assert!(u8::deserialize(&mut de).is_err());
+    /// de.rewind(checkpoint);
+    /// assert_eq!(String::deserialize(&mut de).unwrap(), "7");
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint<'test, 'de, S> {
+        Checkpoint(self.clone())
+    }
+
+    /// Restores the state saved by [`checkpoint`](Self::checkpoint),
+    /// including the position, borrow stats and recorded visits and hints.
+    pub fn rewind(&mut self, checkpoint: Checkpoint<'test, 'de, S>) {
+        *self = checkpoint.0;
+    }
+}
+
+/// A saved [`Deserializer`] state, returned by
+/// [`Deserializer::checkpoint`].
+#[derive(Clone, Debug)]
+pub struct Checkpoint<'test, 'de: 'test, S = &'test [Token<'test, 'de>]>(
+    Deserializer<'test, 'de, S>,
+);
+
+macro_rules! forward_by_value {
+    ($($method:ident)*) => {
+        $(
+        fn $method<V>(mut self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            let value = (&mut self).$method(visitor)?;
+            self.finish()?;
+            Ok(value)
+        }
+        )*
+    };
+}
+
+macro_rules! forward_to_any {
+    ($($method:ident)*) => {
+        $(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            self.hint(stringify!($method));
+            let visitor = self.recorded(visitor);
+            self.any(visitor)
+        }
+        )*
+    };
+}
+
+impl<'test, 'de, S> de::Deserializer<'de> for &mut Deserializer<'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
+    type Error = Error;
+
+    forward_to_any! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_str
+        deserialize_string deserialize_unit deserialize_seq deserialize_map
+        deserialize_ignored_any
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.hint("deserialize_identifier");
+        let visitor = self.recorded(visitor);
+        match self.peek_token()? {
+            Token::Identifier(v) => {
+                self.next_token()?;
+                self.stats.transient_str += 1;
+                visitor.visit_str(v)
+            }
+            Token::IdentifierIndex(v) => {
+                self.next_token()?;
+                visitor.visit_u64(v)
+            }
+            _ => self.any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.hint("deserialize_bytes");
+        let visitor = self.recorded(visitor);
+        match self.seq_bytes()? {
+            Some(bytes) => {
+                self.stats.transient_bytes += 1;
+                visitor.visit_bytes(&bytes)
+            }
+            None => self.any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.hint("deserialize_byte_buf");
+        let visitor = self.recorded(visitor);
+        match self.seq_bytes()? {
+            Some(bytes) => {
+                self.stats.owned_bytes += 1;
+                visitor.visit_byte_buf(bytes)
+            }
+            None => self.any(visitor),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.hint("deserialize_char");
+        let visitor = self.recorded(visitor);
+        if self.char_as_str {
+            if let Token::Str(v) | Token::BorrowedStr(v) | Token::String(v) = self.peek_token()? {
+                let mut chars = v.chars();
+                if let (Some(c), None) = (chars.next(), chars.next()) {
+                    self.next_token()?;
+                    return visitor.visit_char(c);
+                }
+            }
+        }
+        self.any(visitor)
+    }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_option");
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::Unit | Token::None => {
                 self.next_token()?;
@@ -236,9 +2058,10 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
             }
             Token::Some => {
                 self.next_token()?;
-                visitor.visit_some(self)
+                let index = self.position() - 1;
+                self.nested(index, |de| visitor.visit_some(de))
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
@@ -246,12 +2069,15 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_unit_struct");
+        self.declare(Declared::UnitStruct(name));
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::UnitStruct { .. } => {
                 assert_next_token(self, Token::UnitStruct { name })?;
                 visitor.visit_unit()
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
@@ -263,12 +2089,16 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_newtype_struct");
+        self.declare(Declared::NewtypeStruct(name));
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::NewtypeStruct { .. } => {
                 assert_next_token(self, Token::NewtypeStruct { name })?;
-                visitor.visit_newtype_struct(self)
+                let index = self.position() - 1;
+                self.nested(index, |de| visitor.visit_newtype_struct(de))
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
@@ -276,6 +2106,8 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_tuple");
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::Unit | Token::UnitStruct { .. } => {
                 self.next_token()?;
@@ -293,7 +2125,7 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                 self.next_token()?;
                 self.visit_seq(Some(len), EndToken::TupleStruct, visitor)
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
@@ -306,6 +2138,9 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_tuple_struct");
+        self.declare(Declared::TupleStruct(name));
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::Unit => {
                 self.next_token()?;
@@ -327,7 +2162,7 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                 assert_next_token(self, Token::TupleStruct { name, len: n })?;
                 self.visit_seq(Some(len), EndToken::TupleStruct, visitor)
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
@@ -340,6 +2175,9 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_struct");
+        self.declare(Declared::Struct { name, fields });
+        let visitor = self.recorded(visitor);
         match self.peek_token()? {
             Token::Struct { len: n, .. } => {
                 assert_next_token(self, Token::Struct { name, len: n })?;
@@ -349,24 +2187,32 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
                 self.next_token()?;
                 self.visit_map(Some(fields.len()), EndToken::Map, visitor)
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
     fn deserialize_enum<V>(
         self,
         name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
+        self.hint("deserialize_enum");
+        self.declare(Declared::Enum { name, variants });
+        let visitor = self.recorded(visitor);
+        while let Token::VariantIndex(_) = self.peek_token()? {
+            self.next_token()?;
+        }
         match self.peek_token()? {
             Token::Enum { name: n } if name == n => {
                 self.next_token()?;
-
-                visitor.visit_enum(DeserializerEnumVisitor { de: self })
+                let index = self.position() - 1;
+                self.nested(index, |de| {
+                    visitor.visit_enum(DeserializerEnumVisitor { de, variant: None })
+                })
             }
             Token::UnitVariant { name: n, .. }
             | Token::NewtypeVariant { name: n, .. }
@@ -374,29 +2220,189 @@ impl<'a, 'test, 'de> de::Deserializer<'de> for &'a mut Deserializer<'test, 'de>
             | Token::StructVariant { name: n, .. }
                 if name == n =>
             {
-                visitor.visit_enum(DeserializerEnumVisitor { de: self })
+                let index = self.position();
+                self.nested(index, |de| {
+                    visitor.visit_enum(DeserializerEnumVisitor { de, variant: None })
+                })
             }
-            _ => self.deserialize_any(visitor),
+            _ => self.any(visitor),
         }
     }
 
     fn is_human_readable(&self) -> bool {
-        panic!(
-            "Types which have different human-readable and compact representations \
-             must explicitly mark their test cases with `serde_test::Configure`"
-        );
+        self.query_readable()
+    }
+}
+
+/// A deserializer can also be used by value, for code that consumes it, such
+/// as `deserialize_with` helpers. It then checks that no tokens are left
+/// over.
+impl<'test, 'de, S> de::Deserializer<'de> for Deserializer<'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
+    type Error = Error;
+
+    forward_by_value! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32
+        deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_seq deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_unit_struct(name, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_newtype_struct(name, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_tuple(len, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_tuple_struct(name, len, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn deserialize_struct<V>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_struct(name, fields, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = (&mut self).deserialize_enum(name, variants, visitor)?;
+        self.finish()?;
+        Ok(value)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.query_readable()
+    }
+}
+
+/// Token slices turn into a [`Deserializer`] over them.
+///
+/// ```
+/// # use serde::de::{Deserialize, Deserializer, Error, IntoDeserializer};
+/// # use serde_test::Token;
+/// #
+/// fn bool_from_int<'de, D>(deserializer: D) -> Result<bool, D::Error>
+/// where
+///     D: Deserializer<'de>,
+/// {
+///     match u8::deserialize(deserializer)? {
+///         0 => Ok(false),
+///         1 => Ok(true),
+///         n => Err(D::Error::custom(format_args!("not a bool: {}", n))),
+///     }
+/// }
+///
+/// let tokens = [Token::U8(1)];
+/// assert!(bool_from_int(tokens[..].into_deserializer()).unwrap());
+///
+/// let tokens = [Token::U8(2)];
+/// let err = bool_from_int(tokens[..].into_deserializer()).unwrap_err();
+/// assert_eq!(err, "not a bool: 2");
+/// ```
+impl<'test, 'de> IntoDeserializer<'de, Error> for &'test [Token<'test, 'de>] {
+    type Deserializer = Deserializer<'test, 'de>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        Deserializer::new(self)
+    }
+}
+
+/// The tokens produced from a slice of [`OwnedToken`]s.
+type OwnedTokens<'a> = Map<slice::Iter<'a, OwnedToken>, fn(&'a OwnedToken) -> Token<'a, 'a>>;
+
+/// Slices of [`OwnedToken`]s turn into a [`Deserializer`] over them.
+impl<'a> IntoDeserializer<'a, Error> for &'a [OwnedToken] {
+    type Deserializer = Deserializer<'a, 'a, IterSource<'a, 'a, OwnedTokens<'a>>>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        let as_token: fn(&'a OwnedToken) -> Token<'a, 'a> = OwnedToken::as_token;
+        Deserializer::from_iter(self.iter().map(as_token))
+    }
+}
+
+impl<'a> IntoDeserializer<'a, Error> for &'a Vec<OwnedToken> {
+    type Deserializer = Deserializer<'a, 'a, IterSource<'a, 'a, OwnedTokens<'a>>>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self.as_slice().into_deserializer()
     }
 }
 
 //////////////////////////////////////////////////////////////////////////
 
-struct DeserializerSeqVisitor<'a, 'test, 'de> {
-    de: &'a mut Deserializer<'test, 'de>,
+struct DeserializerSeqVisitor<'a, 'test, 'de, S> {
+    de: &'a mut Deserializer<'test, 'de, S>,
     len: Option<usize>,
     end: EndToken,
+    /// The index of the compound's header token.
+    start: usize,
+    /// The number of elements visited so far.
+    count: usize,
 }
 
-impl<'a, 'test, 'de> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de, S> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
@@ -407,33 +2413,50 @@ impl<'a, 'test, 'de> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'test, 'de> {
             return Ok(None);
         }
         self.len = self.len.map(|len| len.saturating_sub(1));
-        seed.deserialize(&mut *self.de).map(Some)
+        self.count += 1;
+        self.de.check_seq_len(self.start, self.count)?;
+        let index = self.count - 1;
+        self.de
+            .in_path(|| Segment::Index(index), |de| seed.deserialize(de))
+            .map(Some)
     }
 
     fn size_hint(&self) -> Option<usize> {
-        self.len
+        self.de.size_hint.hint(self.len)
     }
 }
 
 //////////////////////////////////////////////////////////////////////////
 
-struct DeserializerMapVisitor<'a, 'test, 'de> {
-    de: &'a mut Deserializer<'test, 'de>,
+struct DeserializerMapVisitor<'a, 'test, 'de, S> {
+    de: &'a mut Deserializer<'test, 'de, S>,
     len: Option<usize>,
     end: EndToken,
+    /// The index of the compound's header token.
+    start: usize,
+    /// The number of elements visited so far.
+    count: usize,
+    /// The first token of the last key, for the tracked path.
+    key: Option<Token<'test, 'de>>,
 }
 
-impl<'a, 'test, 'de> MapAccess<'de> for DeserializerMapVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de, S> MapAccess<'de> for DeserializerMapVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
     where
         K: DeserializeSeed<'de>,
     {
-        if self.de.peek_token_opt() == Some(self.end.token()) {
+        self.key = self.de.peek_token_opt();
+        if self.key == Some(self.end.token()) {
             return Ok(None);
         }
         self.len = self.len.map(|len| len.saturating_sub(1));
+        self.count += 1;
+        self.de.check_seq_len(self.start, self.count)?;
         seed.deserialize(&mut *self.de).map(Some)
     }
 
@@ -441,38 +2464,72 @@ impl<'a, 'test, 'de> MapAccess<'de> for DeserializerMapVisitor<'a, 'test, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let key = self.key.take();
+        self.de.in_path(
+            || key.map_or(Segment::Key("?".to_owned()), Segment::key),
+            |de| seed.deserialize(de),
+        )
     }
 
     fn size_hint(&self) -> Option<usize> {
-        self.len
+        self.de.size_hint.hint(self.len)
     }
 }
 
 //////////////////////////////////////////////////////////////////////////
 
-struct DeserializerEnumVisitor<'a, 'test, 'de> {
-    de: &'a mut Deserializer<'test, 'de>,
+struct DeserializerEnumVisitor<'a, 'test, 'de, S> {
+    de: &'a mut Deserializer<'test, 'de, S>,
+    /// The token naming the variant, for the tracked path.
+    variant: Option<Token<'test, 'de>>,
 }
 
-impl<'a, 'test, 'de> EnumAccess<'de> for DeserializerEnumVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de, S> DeserializerEnumVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
+    /// Deserializes the contents of the variant with `f`.
+    fn contents<T>(
+        self,
+        f: impl FnOnce(&mut Deserializer<'test, 'de, S>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let variant = self.variant;
+        self.de.in_path(
+            || variant.map_or(Segment::Key("?".to_owned()), Segment::key),
+            f,
+        )
+    }
+}
+
+impl<'a, 'test, 'de, S> EnumAccess<'de> for DeserializerEnumVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     type Error = Error;
     type Variant = Self;
 
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), Error>
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self), Error>
     where
         V: DeserializeSeed<'de>,
     {
         match self.de.peek_token()? {
+            Token::VariantIndex(index) => {
+                self.de.next_token()?;
+                self.variant = Some(Token::VariantIndex(index));
+                let value = seed.deserialize(index.into_deserializer())?;
+                Ok((value, self))
+            }
             Token::UnitVariant { variant: v, .. }
             | Token::NewtypeVariant { variant: v, .. }
             | Token::TupleVariant { variant: v, .. }
             | Token::StructVariant { variant: v, .. } => {
+                self.variant = Some(Token::Str(v));
                 let de = v.into_deserializer();
                 let value = seed.deserialize(de)?;
                 Ok((value, self))
             }
-            _ => {
+            token => {
+                self.variant = Some(token);
                 let value = seed.deserialize(&mut *self.de)?;
                 Ok((value, self))
             }
@@ -480,60 +2537,65 @@ impl<'a, 'test, 'de> EnumAccess<'de> for DeserializerEnumVisitor<'a, 'test, 'de>
     }
 }
 
-impl<'a, 'test, 'de> VariantAccess<'de> for DeserializerEnumVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de, S> VariantAccess<'de> for DeserializerEnumVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Error> {
-        match self.de.peek_token()? {
+        self.contents(|de| match de.peek_token()? {
             Token::UnitVariant { .. } => {
-                self.de.next_token()?;
+                de.next_token()?;
                 Ok(())
             }
-            _ => Deserialize::deserialize(self.de),
-        }
+            _ => Deserialize::deserialize(de),
+        })
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
     where
         T: DeserializeSeed<'de>,
     {
-        match self.de.peek_token()? {
+        self.contents(|de| match de.peek_token()? {
             Token::NewtypeVariant { .. } => {
-                self.de.next_token()?;
-                seed.deserialize(self.de)
+                de.next_token()?;
+                seed.deserialize(de)
             }
-            _ => seed.deserialize(self.de),
-        }
+            _ => seed.deserialize(de),
+        })
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        match self.de.peek_token()? {
-            Token::TupleVariant { len: enum_len, .. } => {
-                let token = self.de.next_token()?;
+        self.contents(|de| {
+            let visitor = de.recorded(visitor);
+            match de.peek_token()? {
+                Token::TupleVariant { len: enum_len, .. } => {
+                    let token = de.next_token()?;
 
-                if len == enum_len {
-                    self.de
-                        .visit_seq(Some(len), EndToken::TupleVariant, visitor)
-                } else {
-                    Err(unexpected(token))
+                    if len == enum_len {
+                        de.visit_seq(Some(len), EndToken::TupleVariant, visitor)
+                    } else {
+                        Err(de.unexpected(token))
+                    }
                 }
-            }
-            Token::Seq {
-                len: Some(enum_len),
-            } => {
-                let token = self.de.next_token()?;
+                Token::Seq {
+                    len: Some(enum_len),
+                } => {
+                    let token = de.next_token()?;
 
-                if len == enum_len {
-                    self.de.visit_seq(Some(len), EndToken::Seq, visitor)
-                } else {
-                    Err(unexpected(token))
+                    if len == enum_len {
+                        de.visit_seq(Some(len), EndToken::Seq, visitor)
+                    } else {
+                        Err(de.unexpected(token))
+                    }
                 }
+                _ => de.any(visitor),
             }
-            _ => de::Deserializer::deserialize_any(self.de, visitor),
-        }
+        })
     }
 
     fn struct_variant<V>(
@@ -544,39 +2606,43 @@ impl<'a, 'test, 'de> VariantAccess<'de> for DeserializerEnumVisitor<'a, 'test, '
     where
         V: Visitor<'de>,
     {
-        match self.de.peek_token()? {
-            Token::StructVariant { len: enum_len, .. } => {
-                let token = self.de.next_token()?;
+        self.contents(|de| {
+            de.declare(Declared::StructVariant { fields });
+            let visitor = de.recorded(visitor);
+            match de.peek_token()? {
+                Token::StructVariant { len: enum_len, .. } => {
+                    let token = de.next_token()?;
 
-                if fields.len() == enum_len {
-                    self.de
-                        .visit_map(Some(fields.len()), EndToken::StructVariant, visitor)
-                } else {
-                    Err(unexpected(token))
+                    if fields.len() == enum_len {
+                        de.visit_map(Some(fields.len()), EndToken::StructVariant, visitor)
+                    } else {
+                        Err(de.unexpected(token))
+                    }
                 }
-            }
-            Token::Map {
-                len: Some(enum_len),
-            } => {
-                let token = self.de.next_token()?;
+                Token::Map {
+                    len: Some(enum_len),
+                } => {
+                    let token = de.next_token()?;
 
-                if fields.len() == enum_len {
-                    self.de
-                        .visit_map(Some(fields.len()), EndToken::Map, visitor)
-                } else {
-                    Err(unexpected(token))
+                    if fields.len() == enum_len {
+                        de.visit_map(Some(fields.len()), EndToken::Map, visitor)
+                    } else {
+                        Err(de.unexpected(token))
+                    }
                 }
+                _ => de.any(visitor),
             }
-            _ => de::Deserializer::deserialize_any(self.de, visitor),
-        }
+        })
     }
 }
 
 //////////////////////////////////////////////////////////////////////////
 
-struct EnumMapVisitor<'a, 'test, 'de: 'test> {
-    de: &'a mut Deserializer<'test, 'de>,
+struct EnumMapVisitor<'a, 'test, 'de: 'test, S> {
+    de: &'a mut Deserializer<'test, 'de, S>,
     variant: Option<Token<'test, 'de>>,
+    /// The variant once taken as the key, for the tracked path.
+    key: Option<Token<'test, 'de>>,
     format: EnumFormat,
 }
 
@@ -586,34 +2652,64 @@ enum EnumFormat {
     Any,
 }
 
-impl<'a, 'test, 'de> EnumMapVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de, S> EnumMapVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     fn new(
-        de: &'a mut Deserializer<'test, 'de>,
+        de: &'a mut Deserializer<'test, 'de, S>,
         variant: Token<'test, 'de>,
         format: EnumFormat,
     ) -> Self {
         EnumMapVisitor {
             de,
             variant: Some(variant),
+            key: None,
             format,
         }
     }
 }
 
-impl<'a, 'test, 'de: 'test> MapAccess<'de> for EnumMapVisitor<'a, 'test, 'de> {
+impl<'a, 'test, 'de: 'test, S> MapAccess<'de> for EnumMapVisitor<'a, 'test, 'de, S>
+where
+    S: TokenSource<'test, 'de>,
+{
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
     where
         K: DeserializeSeed<'de>,
     {
+        self.key = self.variant;
         match self.variant.take() {
-            Some(Token::Str(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
-            Some(Token::Bytes(variant)) => seed
+            Some(Token::Str(variant) | Token::CollectStr(variant)) => {
+                seed.deserialize(variant.into_deserializer()).map(Some)
+            }
+            Some(Token::BorrowedStr(variant)) => seed
+                .deserialize(BorrowedStrDeserializer::new(variant))
+                .map(Some),
+            Some(Token::String(variant)) => seed
+                .deserialize(variant.to_owned().into_deserializer())
+                .map(Some),
+            Some(Token::Bytes(variant)) | Some(Token::ByteBuf(variant)) => seed
                 .deserialize(BytesDeserializer { value: variant })
                 .map(Some),
-            Some(Token::U32(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
-            Some(other) => Err(unexpected(other)),
+            Some(Token::BorrowedBytes(variant)) => seed
+                .deserialize(BorrowedBytesDeserializer::new(variant))
+                .map(Some),
+            Some(Token::I8(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::I16(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::I32(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::I64(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::I128(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::U8(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::U16(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::U32(variant)) | Some(Token::VariantIndex(variant)) => {
+                seed.deserialize(variant.into_deserializer()).map(Some)
+            }
+            Some(Token::U64(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(Token::U128(variant)) => seed.deserialize(variant.into_deserializer()).map(Some),
+            Some(other) => Err(self.de.unexpected(other)),
             None => Ok(None),
         }
     }
@@ -622,36 +2718,136 @@ impl<'a, 'test, 'de: 'test> MapAccess<'de> for EnumMapVisitor<'a, 'test, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        match self.format {
-            EnumFormat::Seq => {
-                let value = {
-                    let visitor = DeserializerSeqVisitor {
-                        de: self.de,
-                        len: None,
-                        end: EndToken::TupleVariant,
+        let key = self.key.take();
+        let format = &self.format;
+        self.de.in_path(
+            || key.map_or(Segment::Key("?".to_owned()), Segment::key),
+            |de| match format {
+                EnumFormat::Seq => {
+                    let start = de.position() - 1;
+                    let value = {
+                        let visitor = DeserializerSeqVisitor {
+                            start,
+                            count: 0,
+                            de: &mut *de,
+                            len: None,
+                            end: EndToken::TupleVariant,
+                        };
+                        seed.deserialize(SeqAccessDeserializer::new(visitor))?
                     };
-                    seed.deserialize(SeqAccessDeserializer::new(visitor))?
-                };
-                assert_next_token(self.de, Token::TupleVariantEnd)?;
-                Ok(value)
-            }
-            EnumFormat::Map => {
-                let value = {
-                    let visitor = DeserializerMapVisitor {
-                        de: self.de,
-                        len: None,
-                        end: EndToken::StructVariant,
+                    de.end_compound(start, EndToken::TupleVariant, false)?;
+                    Ok(value)
+                }
+                EnumFormat::Map => {
+                    let start = de.position() - 1;
+                    let value = {
+                        let visitor = DeserializerMapVisitor {
+                            start,
+                            count: 0,
+                            de: &mut *de,
+                            len: None,
+                            end: EndToken::StructVariant,
+                            key: None,
+                        };
+                        seed.deserialize(MapAccessDeserializer::new(visitor))?
                     };
-                    seed.deserialize(MapAccessDeserializer::new(visitor))?
-                };
-                assert_next_token(self.de, Token::StructVariantEnd)?;
-                Ok(value)
-            }
-            EnumFormat::Any => seed.deserialize(&mut *self.de),
+                    de.end_compound(start, EndToken::StructVariant, true)?;
+                    Ok(value)
+                }
+                EnumFormat::Any => seed.deserialize(de),
+            },
+        )
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////
+
+/// A visitor that records the methods called on it before forwarding them.
+struct Recorded<V> {
+    visitor: V,
+    visits: Option<Rc<RefCell<Vec<&'static str>>>>,
+}
+
+impl<V> Recorded<V> {
+    fn record(&self, method: &'static str) {
+        if let Some(visits) = &self.visits {
+            visits.borrow_mut().push(method);
         }
     }
 }
 
+macro_rules! forward_recorded {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+        fn $method<E>(self, $($arg: $ty),*) -> Result<V::Value, E>
+        where
+            E: de::Error,
+        {
+            self.record(stringify!($method));
+            self.visitor.$method($($arg),*)
+        }
+        )*
+    };
+}
+
+macro_rules! forward_recorded_access {
+    ($($method:ident($($tr:ident)::+);)*) => {
+        $(
+        fn $method<A>(self, access: A) -> Result<V::Value, A::Error>
+        where
+            A: $($tr)::+<'de>,
+        {
+            self.record(stringify!($method));
+            self.visitor.$method(access)
+        }
+        )*
+    };
+}
+
+impl<'de, V> Visitor<'de> for Recorded<V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    forward_recorded! {
+        visit_bool(v: bool);
+        visit_i8(v: i8);
+        visit_i16(v: i16);
+        visit_i32(v: i32);
+        visit_i64(v: i64);
+        visit_i128(v: i128);
+        visit_u8(v: u8);
+        visit_u16(v: u16);
+        visit_u32(v: u32);
+        visit_u64(v: u64);
+        visit_u128(v: u128);
+        visit_f32(v: f32);
+        visit_f64(v: f64);
+        visit_char(v: char);
+        visit_str(v: &str);
+        visit_borrowed_str(v: &'de str);
+        visit_string(v: String);
+        visit_bytes(v: &[u8]);
+        visit_borrowed_bytes(v: &'de [u8]);
+        visit_byte_buf(v: Vec<u8>);
+        visit_none();
+        visit_unit();
+    }
+
+    forward_recorded_access! {
+        visit_some(de::Deserializer);
+        visit_newtype_struct(de::Deserializer);
+        visit_seq(SeqAccess);
+        visit_map(MapAccess);
+        visit_enum(EnumAccess);
+    }
+}
+
 struct BytesDeserializer<'test> {
     value: &'test [u8],
 }
@@ -0,0 +1,254 @@
+use crate::group;
+use crate::pretty;
+use crate::ser::token_matches;
+use crate::token::{EndToken, Token};
+
+/// The largest table the alignment of two streams may fill. Past this, the
+/// differing middles of the streams are shown as removed and added whole.
+const MAX_CELLS: usize = 1 << 20;
+
+#[derive(Copy, Clone)]
+enum Op {
+    /// Indexes the expected stream.
+    Same(usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Aligns the two streams, keeping as many tokens in common as possible.
+fn align(expected: &[Token<'_, '_>], actual: &[Token<'_, '_>]) -> Vec<Op> {
+    let prefix = expected
+        .iter()
+        .zip(actual)
+        .take_while(|(e, a)| token_matches(**e, **a))
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| token_matches(**e, **a))
+        .count();
+    let (n, m) = (
+        expected.len() - prefix - suffix,
+        actual.len() - prefix - suffix,
+    );
+
+    let mut ops: Vec<Op> = (0..prefix).map(Op::Same).collect();
+    if n.saturating_mul(m) > MAX_CELLS {
+        ops.extend((prefix..prefix + n).map(Op::Removed));
+        ops.extend((prefix..prefix + m).map(Op::Added));
+    } else {
+        // lcs[i][j] is the longest common subsequence of the middles of
+        // expected[i..] and actual[j..].
+        let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if token_matches(expected[prefix + i], actual[prefix + j]) {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && token_matches(expected[prefix + i], actual[prefix + j]) {
+                ops.push(Op::Same(prefix + i));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(Op::Removed(prefix + i));
+                i += 1;
+            } else {
+                ops.push(Op::Added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    ops.extend((expected.len() - suffix..expected.len()).map(Op::Same));
+    ops
+}
+
+/// A difference between two streams, as indexes into them.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Change {
+    /// `expected[i]` is replaced by `actual[j]`.
+    Changed(usize, usize),
+    /// `expected[i]` is missing from `actual`.
+    Removed(usize),
+    /// `actual[j]` is inserted before `expected[i]`.
+    Added(usize, usize),
+}
+
+/// Lists the differences between two streams. Within a run of differing
+/// tokens, removed and added tokens are paired up in order as changes.
+pub(crate) fn changes(expected: &[Token<'_, '_>], actual: &[Token<'_, '_>]) -> Vec<Change> {
+    let ops = align(expected, actual);
+    let mut changes = Vec::new();
+    let mut next = 0;
+    let mut k = 0;
+    while k < ops.len() {
+        if let Op::Same(i) = ops[k] {
+            next = i + 1;
+            k += 1;
+            continue;
+        }
+        let removed: Vec<usize> = ops[k..]
+            .iter()
+            .map_while(|op| match *op {
+                Op::Removed(i) => Some(i),
+                _ => None,
+            })
+            .collect();
+        let added: Vec<usize> = ops[k + removed.len()..]
+            .iter()
+            .map_while(|op| match *op {
+                Op::Added(j) => Some(j),
+                _ => None,
+            })
+            .collect();
+        k += removed.len() + added.len();
+
+        let paired = removed.len().min(added.len());
+        for (&i, &j) in removed.iter().zip(&added) {
+            changes.push(Change::Changed(i, j));
+        }
+        for &i in &removed[paired..] {
+            changes.push(Change::Removed(i));
+        }
+        if let Some(&last) = removed.last() {
+            next = last + 1;
+        }
+        for &j in &added[paired..] {
+            changes.push(Change::Added(next, j));
+        }
+    }
+    changes
+}
+
+/// The nesting depth of each token, for indentation.
+fn depths(tokens: &[Token<'_, '_>]) -> Vec<usize> {
+    let mut depth = 0usize;
+    tokens
+        .iter()
+        .map(|token| {
+            if EndToken::from_token(*token).is_some() {
+                depth = depth.saturating_sub(1);
+            }
+            let this = depth;
+            if group::compound_end(*token).is_some() {
+                depth += 1;
+            }
+            this
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone)]
+enum Style {
+    Context,
+    Removed,
+    Added,
+    /// One of a run of removed tokens replaced by as many added ones.
+    ChangedRemoved,
+    ChangedAdded,
+}
+
+#[cfg(feature = "color")]
+fn paint(style: Style, line: &str, out: &mut String) {
+    use std::env;
+    use std::fmt::Write;
+
+    if env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty()) {
+        out.push_str(line);
+        return;
+    }
+    let code = match style {
+        Style::Context => "2",
+        Style::Removed => "31",
+        Style::Added => "32",
+        Style::ChangedRemoved => "1;31",
+        Style::ChangedAdded => "1;32",
+    };
+    let _ = write!(out, "\x1b[{}m{}\x1b[0m", code, line);
+}
+
+#[cfg(not(feature = "color"))]
+fn paint(_style: Style, line: &str, out: &mut String) {
+    out.push_str(line);
+}
+
+/// Renders the difference between two streams as a unified diff, showing
+/// each change with a few unchanged tokens around it.
+///
+/// With the `color` feature, removed tokens are red, added tokens green, and
+/// tokens changed in place bold. Setting `NO_COLOR` turns colors off.
+pub(crate) fn diff_tokens(expected: &[Token<'_, '_>], actual: &[Token<'_, '_>]) -> String {
+    let ops = align(expected, actual);
+    let (expected_depths, actual_depths) = (depths(expected), depths(actual));
+
+    // A run of n removals followed directly by n additions is a change.
+    let mut styles = Vec::with_capacity(ops.len());
+    let mut k = 0;
+    while k < ops.len() {
+        let removed = ops[k..]
+            .iter()
+            .take_while(|op| matches!(op, Op::Removed(_)))
+            .count();
+        let added = ops[k + removed..]
+            .iter()
+            .take_while(|op| matches!(op, Op::Added(_)))
+            .count();
+        if removed == 0 && added == 0 {
+            styles.push(Style::Context);
+            k += 1;
+        } else {
+            let changed = removed == added;
+            for _ in 0..removed {
+                styles.push(if changed {
+                    Style::ChangedRemoved
+                } else {
+                    Style::Removed
+                });
+            }
+            for _ in 0..added {
+                styles.push(if changed {
+                    Style::ChangedAdded
+                } else {
+                    Style::Added
+                });
+            }
+            k += removed + added;
+        }
+    }
+
+    // How many unchanged tokens are shown on either side of a change.
+    let context = pretty::failure_context();
+    let near_change = |k: usize| {
+        let lo = k.saturating_sub(context);
+        let hi = k.saturating_add(context).saturating_add(1).min(ops.len());
+        ops[lo..hi].iter().any(|op| !matches!(op, Op::Same(_)))
+    };
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let mut elided = false;
+    for (k, (op, style)) in ops.iter().zip(styles).enumerate() {
+        if !near_change(k) {
+            if !elided {
+                out.push_str("  ...\n");
+                elided = true;
+            }
+            continue;
+        }
+        elided = false;
+        let (sign, depth, token) = match *op {
+            Op::Same(i) => (' ', expected_depths[i], expected[i]),
+            Op::Removed(i) => ('-', expected_depths[i], expected[i]),
+            Op::Added(j) => ('+', actual_depths[j], actual[j]),
+        };
+        let line = format!("{} {:indent$}{:?},", sign, "", token, indent = depth * 4);
+        paint(style, &line, &mut out);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,230 @@
+use crate::pretty::Pretty;
+use crate::token::Token;
+use std::fmt::{self, Debug, Display, Formatter, Write};
+
+/// Longest string payload shown in full by the compact rendering.
+const MAX_STR_CHARS: usize = 32;
+
+/// Longest bytes payload shown in full by the compact rendering.
+const MAX_BYTES: usize = 16;
+
+/// Renders a token stream for snapshots, logs and failure messages.
+///
+/// `{}` renders the canonical form: one token per line in `Debug` syntax,
+/// each followed by a comma, with the contents of every compound indented
+/// by four spaces. This form is stable, so it can be stored by snapshot
+/// tools, and it can be read back by [`parse_tokens`](crate::parse_tokens).
+///
+/// `{:#}` renders the stream on one line in the same compact form as `{:#}`
+/// on a single [`Token`]: short lowercase names, hex bytes, and long payloads
+/// abbreviated.
+///
+/// ```
+/// # use serde_test::{DisplayTokens, Token};
+/// #
+/// let tokens = [
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("a"),
+///     Token::U8(1),
+///     Token::Str("b"),
+///     Token::Bytes(b"\x01\xff"),
+///     Token::StructEnd,
+/// ];
+///
+/// assert_eq!(
+///     DisplayTokens::new(&tokens).to_string(),
+///     r#"Struct { name: "S", len: 2 },
+///     Str("a"),
+///     U8(1),
+///     Str("b"),
+///     Bytes([1, 255]),
+/// StructEnd,
+/// "#,
+/// );
+/// assert_eq!(
+///     format!("{:#}", DisplayTokens::new(&tokens)),
+///     r#"struct S(2) "a" 1u8 "b" bytes:01ff /struct"#,
+/// );
+/// ```
+#[derive(Copy, Clone)]
+pub struct DisplayTokens<'a, 'test, 'de> {
+    tokens: &'a [Token<'test, 'de>],
+}
+
+impl<'a, 'test, 'de> DisplayTokens<'a, 'test, 'de> {
+    pub const fn new(tokens: &'a [Token<'test, 'de>]) -> Self {
+        DisplayTokens { tokens }
+    }
+}
+
+impl Display for DisplayTokens<'_, '_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            for (i, token) in self.tokens.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(' ')?;
+                }
+                write_compact(*token, f)?;
+            }
+            Ok(())
+        } else {
+            Display::fmt(&Pretty::new(self.tokens), f)
+        }
+    }
+}
+
+impl Debug for DisplayTokens<'_, '_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.tokens, f)
+    }
+}
+
+fn write_str(prefix: &str, v: &str, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str(prefix)?;
+    match v.char_indices().nth(MAX_STR_CHARS) {
+        Some((cut, _)) => write!(
+            f,
+            "{:?}..(+{})",
+            &v[..cut],
+            v.chars().count() - MAX_STR_CHARS,
+        ),
+        None => write!(f, "{:?}", v),
+    }
+}
+
+fn write_bytes(prefix: &str, v: &[u8], f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str(prefix)?;
+    for byte in v.iter().take(MAX_BYTES) {
+        write!(f, "{:02x}", byte)?;
+    }
+    if v.len() > MAX_BYTES {
+        write!(f, "..(+{})", v.len() - MAX_BYTES)?;
+    }
+    Ok(())
+}
+
+fn write_len(len: Option<usize>, f: &mut Formatter<'_>) -> fmt::Result {
+    match len {
+        Some(len) => write!(f, "({})", len),
+        None => f.write_str("(?)"),
+    }
+}
+
+/// Writes the one-line compact form of `token`.
+pub(crate) fn write_compact(token: Token<'_, '_>, f: &mut Formatter<'_>) -> fmt::Result {
+    match token {
+        Token::Bool(v) => write!(f, "{}", v),
+        Token::I8(v) => write!(f, "{}i8", v),
+        Token::I16(v) => write!(f, "{}i16", v),
+        Token::I32(v) => write!(f, "{}i32", v),
+        Token::I64(v) => write!(f, "{}i64", v),
+        Token::I128(v) => write!(f, "{}i128", v),
+        Token::U8(v) => write!(f, "{}u8", v),
+        Token::U16(v) => write!(f, "{}u16", v),
+        Token::U32(v) => write!(f, "{}u32", v),
+        Token::U64(v) => write!(f, "{}u64", v),
+        Token::U128(v) => write!(f, "{}u128", v),
+        Token::F32(v) => write!(f, "{:?}f32", v),
+        Token::F64(v) => write!(f, "{:?}f64", v),
+        Token::F32Bits(v) => write!(f, "f32 bits {:#010x}", v),
+        Token::F64Bits(v) => write!(f, "f64 bits {:#018x}", v),
+        Token::Isize(v) => write!(f, "{}isize", v),
+        Token::Usize(v) => write!(f, "{}usize", v),
+        Token::Number(v) => write!(f, "{}", v),
+        Token::Char(v) => write!(f, "{:?}", v),
+        Token::Str(v) => write_str("", v, f),
+        Token::BorrowedStr(v) => write_str("&", v, f),
+        Token::String(v) => write_str("string:", v, f),
+        Token::CollectStr(v) => write_str("collect:", v, f),
+        Token::Bytes(v) => write_bytes("bytes:", v, f),
+        Token::BorrowedBytes(v) => write_bytes("&bytes:", v, f),
+        Token::ByteBuf(v) => write_bytes("bytebuf:", v, f),
+        Token::None => f.write_str("none"),
+        Token::Some => f.write_str("some"),
+        Token::Unit => f.write_str("()"),
+        Token::UnitStruct { name } => write!(f, "unit {}", name),
+        Token::UnitVariant { name, variant } => write!(f, "unit {}::{}", name, variant),
+        Token::NewtypeStruct { name } => write!(f, "newtype {}", name),
+        Token::NewtypeVariant { name, variant } => write!(f, "newtype {}::{}", name, variant),
+        Token::Seq { len } => {
+            f.write_str("seq")?;
+            write_len(len, f)
+        }
+        Token::SeqEnd => f.write_str("/seq"),
+        Token::Tuple { len } => write!(f, "tuple({})", len),
+        Token::TupleEnd => f.write_str("/tuple"),
+        Token::TupleStruct { name, len } => write!(f, "tuple {}({})", name, len),
+        Token::TupleStructEnd => f.write_str("/tuple_struct"),
+        Token::TupleVariant { name, variant, len } => {
+            write!(f, "tuple {}::{}({})", name, variant, len)
+        }
+        Token::TupleVariantEnd => f.write_str("/tuple_variant"),
+        Token::Map { len } => {
+            f.write_str("map")?;
+            write_len(len, f)
+        }
+        Token::MapEnd => f.write_str("/map"),
+        Token::MapEntry => f.write_str("entry"),
+        Token::Struct { name, len } => write!(f, "struct {}({})", name, len),
+        Token::StructEnd => f.write_str("/struct"),
+        Token::StructVariant { name, variant, len } => {
+            write!(f, "struct {}::{}({})", name, variant, len)
+        }
+        Token::StructVariantEnd => f.write_str("/struct_variant"),
+        Token::SkipStructField { name } => write!(f, "skip {}", name),
+        Token::Enum { name } => write!(f, "enum {}", name),
+        Token::VariantIndex(v) => write!(f, "index {}", v),
+        Token::FieldIndex(v) => write!(f, "field {}", v),
+        Token::Identifier(v) => write_str("ident:", v, f),
+        Token::IdentifierIndex(v) => write!(f, "ident {}", v),
+        Token::Matches(predicate) => write!(f, "matching {}", predicate.name()),
+        Token::Readable(v) => write!(f, "readable {}", v),
+        Token::OneOf(alternatives) => {
+            for (i, &alternative) in alternatives.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" | ")?;
+                }
+                write_compact(alternative, f)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Writes `token` as a Rust expression that constructs it.
+///
+/// `Debug` already is one, apart from byte slices, non-finite floats and the
+/// slice inside [`Token::OneOf`].
+pub(crate) fn write_rust(token: Token<'_, '_>, f: &mut Formatter<'_>) -> fmt::Result {
+    match token {
+        Token::F32(v) if !v.is_finite() => {
+            write!(f, "Token::F32({})", float_const("f32", v.into()))
+        }
+        Token::F64(v) if !v.is_finite() => write!(f, "Token::F64({})", float_const("f64", v)),
+        Token::Bytes(v) => write!(f, "Token::Bytes(&{:?})", v),
+        Token::BorrowedBytes(v) => write!(f, "Token::BorrowedBytes(&{:?})", v),
+        Token::ByteBuf(v) => write!(f, "Token::ByteBuf(&{:?})", v),
+        Token::OneOf(alternatives) => {
+            f.write_str("Token::OneOf(&[")?;
+            for (i, &alternative) in alternatives.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write_rust(alternative, f)?;
+            }
+            f.write_str("])")
+        }
+        token => write!(f, "Token::{:?}", token),
+    }
+}
+
+fn float_const(ty: &str, v: f64) -> String {
+    let name = if v.is_nan() {
+        "NAN"
+    } else if v > 0.0 {
+        "INFINITY"
+    } else {
+        "NEG_INFINITY"
+    };
+    format!("{}::{}", ty, name)
+}
@@ -0,0 +1,97 @@
+//! Asserts for values serialized and deserialized through [`erased_serde`]
+//! trait objects.
+//!
+//! Requires the `erased-serde` feature.
+//!
+//! Plugin-style code that stores `Box<dyn Trait>` serializes through
+//! `&dyn erased_serde::Serialize` and deserializes through a
+//! `&mut dyn erased_serde::Deserializer` handed to a registered function.
+//! [`assert_ser_tokens_dyn`] and [`assert_de_tokens_dyn`] drive those paths
+//! with the token harness.
+
+use crate::assert::{assert_de_with, assert_ser_tokens};
+use crate::error::Error;
+use crate::token::Token;
+use std::fmt::Debug;
+
+/// Asserts that a type-erased `value` serializes to the given `tokens`.
+///
+/// ```
+/// # use serde_test::erased::assert_ser_tokens_dyn;
+/// # use serde_test::Token;
+/// #
+/// let values: Vec<Box<dyn erased_serde::Serialize>> = vec![Box::new(1u8), Box::new("a")];
+///
+/// assert_ser_tokens_dyn(&*values[0], &[Token::U8(1)]);
+/// assert_ser_tokens_dyn(&*values[1], &[Token::Str("a")]);
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_dyn<'test>(
+    value: &dyn erased_serde::Serialize,
+    tokens: &'test [Token<'test, 'test>],
+) {
+    assert_ser_tokens(value, tokens);
+}
+
+/// Asserts that `deserialize`, given the `tokens` as a
+/// `&mut dyn erased_serde::Deserializer`, returns `expected`.
+///
+/// No tokens may be left over. An error from `deserialize` fails the assert
+/// with its message.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::erased::assert_de_tokens_dyn;
+/// # use serde_test::Token;
+/// # use std::fmt::Debug;
+/// #
+/// trait Shape: Debug {
+///     fn area(&self) -> u32;
+/// }
+///
+/// impl PartialEq for Box<dyn Shape> {
+///     fn eq(&self, other: &Self) -> bool {
+///         self.area() == other.area()
+///     }
+/// }
+///
+/// #[derive(Deserialize, Debug)]
+/// struct Square(u32);
+///
+/// impl Shape for Square {
+///     fn area(&self) -> u32 {
+///         self.0 * self.0
+///     }
+/// }
+///
+/// // What a plugin registers to load its shape.
+/// fn load_square(
+///     de: &mut dyn erased_serde::Deserializer,
+/// ) -> Result<Box<dyn Shape>, erased_serde::Error> {
+///     Ok(Box::new(erased_serde::deserialize::<Square>(de)?))
+/// }
+///
+/// assert_de_tokens_dyn(
+///     &(Box::new(Square(2)) as Box<dyn Shape>),
+///     &[Token::NewtypeStruct { name: "Square" }, Token::U32(2)],
+///     load_square,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_dyn<'test, 'de: 'test, T, F>(
+    expected: &T,
+    tokens: &'test [Token<'test, 'de>],
+    deserialize: F,
+) where
+    T: PartialEq + Debug,
+    F: FnOnce(&mut dyn erased_serde::Deserializer<'de>) -> Result<T, erased_serde::Error>,
+{
+    assert_de_with(
+        |de| {
+            let mut de = <dyn erased_serde::Deserializer<'_>>::erase(de);
+            deserialize(&mut de).map_err(Error::new)
+        },
+        tokens,
+        expected,
+    );
+}
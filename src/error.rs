@@ -1,22 +1,196 @@
+use crate::token::{OwnedToken, Token};
 use serde::{de, ser};
+#[cfg(feature = "backtrace")]
+use std::backtrace::Backtrace;
 use std::fmt::{self, Display, Formatter};
+#[cfg(feature = "backtrace")]
+use std::sync::Arc;
 
 /// A de/serialization error.
+///
+/// Errors raised by the harness also record where in the token stream they
+/// happened, and which tokens were involved.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::de::Deserializer;
+/// # use serde_test::{ErrorKind, Token};
+/// #
+/// let tokens = [Token::Tuple { len: 1 }, Token::U8(1), Token::U8(2)];
+/// let mut de = Deserializer::new(&tokens);
+/// let err = <(u8,)>::deserialize(&mut de).unwrap_err();
+///
+/// assert_eq!(err.kind(), ErrorKind::Mismatch);
+/// assert_eq!(err.position(), Some(2));
+/// assert_eq!(err.expected(), Some(Token::U8(2)));
+/// assert_eq!(err.found(), Some(Token::TupleEnd));
+/// ```
 #[derive(Clone, Debug)]
 pub struct Error {
+    kind: ErrorKind,
     msg: String,
+    context: Option<Box<Context>>,
+    path: Option<String>,
+    // `std::backtrace` is newer than the crate's rust-version, so the
+    // `backtrace` feature needs Rust 1.65.
+    #[cfg(feature = "backtrace")]
+    #[clippy::msrv = "1.65"]
+    backtrace: Arc<Backtrace>,
+}
+
+/// Where in the token stream the harness raised an [`Error`].
+#[derive(Clone, Debug)]
+struct Context {
+    position: usize,
+    expected: Option<OwnedToken>,
+    found: Option<OwnedToken>,
+}
+
+/// What raised an [`Error`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The value did not match the expected tokens.
+    Mismatch,
+    /// The tokens ran out before the value did, or the value ran out before
+    /// the tokens.
+    EndOfTokens,
+    /// A limit set on the [`Deserializer`](crate::de::Deserializer) or
+    /// [`Serializer`](crate::ser::Serializer) was exceeded.
+    LimitExceeded,
+    /// The `Serialize` or `Deserialize` impl under test, or serde on its
+    /// behalf, raised the error through `ser::Error` or `de::Error`.
+    Custom,
+    /// The `Serialize` impl under test called the serializer's methods in an
+    /// order serde does not allow, such as a map value without a key.
+    Misuse,
 }
 
 impl Error {
+    /// Creates an error of kind [`ErrorKind::Custom`].
     pub fn new(msg: impl Display) -> Self {
+        Error::with_kind(ErrorKind::Custom, msg)
+    }
+
+    #[cfg_attr(feature = "backtrace", clippy::msrv = "1.65")]
+    pub(crate) fn with_kind(kind: ErrorKind, msg: impl Display) -> Self {
         Error {
+            kind,
             msg: msg.to_string(),
+            context: None,
+            path: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Arc::new(Backtrace::capture()),
+        }
+    }
+
+    /// Records the index of the token at which the error was raised. Must be
+    /// called before `with_expected` and `with_found`.
+    pub(crate) fn at(mut self, position: usize) -> Self {
+        self.context = Some(Box::new(Context {
+            position,
+            expected: None,
+            found: None,
+        }));
+        self
+    }
+
+    pub(crate) fn with_expected(mut self, token: Token<'_, '_>) -> Self {
+        if let Some(context) = &mut self.context {
+            context.expected = Some(token.into());
+        }
+        self
+    }
+
+    pub(crate) fn with_found(mut self, token: Token<'_, '_>) -> Self {
+        if let Some(context) = &mut self.context {
+            context.found = Some(token.into());
         }
+        self
+    }
+
+    /// Records the path of the value being deserialized when the error was
+    /// raised, prefixing it to the message. Errors that already have a path,
+    /// because a nested value raised them, keep it.
+    pub(crate) fn with_path(mut self, path: impl Display) -> Self {
+        if self.path.is_none() {
+            let path = path.to_string();
+            self.msg = format!("at {}: {}", path, self.msg);
+            self.path = Some(path);
+        }
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
     }
 
     pub fn msg(&self) -> &str {
         &self.msg
     }
+
+    /// The index of the token at which the harness raised the error.
+    ///
+    /// `None` for errors raised by the type under test.
+    pub fn position(&self) -> Option<usize> {
+        self.context.as_ref().map(|context| context.position)
+    }
+
+    /// The path of the field, element or variant that was being deserialized,
+    /// such as `value.inner.items[3]`, when the error was raised.
+    ///
+    /// `None` unless the [`Deserializer`](crate::de::Deserializer) tracks
+    /// paths, or if the error was raised outside of any field, element or
+    /// variant.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// The backtrace of where the error was created, such as deep inside the
+    /// `Deserialize` impl of a nested type.
+    ///
+    /// Requires the `backtrace` feature, and so Rust 1.65. As with [`Backtrace::capture`], it
+    /// is only captured if the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`
+    /// environment variable asks for one. The assert functions then include
+    /// it in their panic message.
+    ///
+    /// ```
+    /// # #[cfg(feature = "backtrace")]
+    /// # fn main() {
+    /// # use serde::Deserialize;
+    /// # use serde_test::de::Deserializer;
+    /// # use serde_test::Token;
+    /// # use std::backtrace::BacktraceStatus;
+    /// #
+    /// std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    ///
+    /// let tokens = [Token::Str("not a number")];
+    /// let err = u8::deserialize(&mut Deserializer::new(&tokens)).unwrap_err();
+    /// assert_eq!(err.backtrace().status(), BacktraceStatus::Captured);
+    /// # }
+    /// # #[cfg(not(feature = "backtrace"))]
+    /// # fn main() {}
+    /// ```
+    #[cfg(feature = "backtrace")]
+    #[clippy::msrv = "1.65"]
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    /// The token the test's stream has at [`position`](Self::position).
+    ///
+    /// `None` if the stream had already run out, or if no token was involved.
+    pub fn expected(&self) -> Option<Token<'_, '_>> {
+        let context = self.context.as_ref()?;
+        context.expected.as_ref().map(OwnedToken::as_token)
+    }
+
+    /// The token the value serialized as, or that deserialization asked for,
+    /// when it is known.
+    pub fn found(&self) -> Option<Token<'_, '_>> {
+        let context = self.context.as_ref()?;
+        context.found.as_ref().map(OwnedToken::as_token)
+    }
 }
 
 pub type TestResult<T = ()> = Result<T, Error>;
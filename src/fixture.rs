@@ -0,0 +1,96 @@
+use crate::token::{OwnedToken, Token};
+use std::collections::btree_set::{self, BTreeSet};
+use std::iter::FromIterator;
+
+/// An ordered, deduplicated collection of token streams.
+///
+/// Streams are stored as `Vec<OwnedToken>`, which is also usable directly as
+/// a key of a `BTreeMap` or `HashMap`.
+///
+/// ```
+/// # use serde_test::{FixtureSet, Token};
+/// #
+/// let mut fixtures = FixtureSet::new();
+/// assert!(fixtures.insert(&[Token::Some, Token::U8(1)]));
+/// assert!(fixtures.insert(&[Token::None]));
+/// assert!(!fixtures.insert(&[Token::Some, Token::U8(1)]));
+///
+/// assert_eq!(fixtures.len(), 2);
+/// assert!(fixtures.contains(&[Token::None]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixtureSet {
+    streams: BTreeSet<Vec<OwnedToken>>,
+}
+
+fn to_owned(tokens: &[Token<'_, '_>]) -> Vec<OwnedToken> {
+    tokens.iter().copied().map(OwnedToken::from).collect()
+}
+
+impl FixtureSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        FixtureSet::default()
+    }
+
+    /// Adds a stream to the set, returning whether it was not already
+    /// present.
+    pub fn insert(&mut self, tokens: &[Token<'_, '_>]) -> bool {
+        self.streams.insert(to_owned(tokens))
+    }
+
+    /// Returns whether the set contains the stream.
+    pub fn contains(&self, tokens: &[Token<'_, '_>]) -> bool {
+        self.streams.contains(&to_owned(tokens))
+    }
+
+    /// Removes a stream from the set, returning whether it was present.
+    pub fn remove(&mut self, tokens: &[Token<'_, '_>]) -> bool {
+        self.streams.remove(&to_owned(tokens))
+    }
+
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Iterates over the streams in order.
+    pub fn iter(&self) -> btree_set::Iter<'_, Vec<OwnedToken>> {
+        self.streams.iter()
+    }
+}
+
+impl FromIterator<Vec<OwnedToken>> for FixtureSet {
+    fn from_iter<I: IntoIterator<Item = Vec<OwnedToken>>>(iter: I) -> Self {
+        FixtureSet {
+            streams: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Vec<OwnedToken>> for FixtureSet {
+    fn extend<I: IntoIterator<Item = Vec<OwnedToken>>>(&mut self, iter: I) {
+        self.streams.extend(iter);
+    }
+}
+
+impl IntoIterator for FixtureSet {
+    type Item = Vec<OwnedToken>;
+    type IntoIter = btree_set::IntoIter<Vec<OwnedToken>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.streams.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a FixtureSet {
+    type Item = &'a Vec<OwnedToken>;
+    type IntoIter = btree_set::Iter<'a, Vec<OwnedToken>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.streams.iter()
+    }
+}
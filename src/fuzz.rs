@@ -0,0 +1,153 @@
+//! Fuzzing support built on [`arbitrary`].
+//!
+//! Requires the `arbitrary` feature.
+
+use crate::de::Deserializer;
+use crate::pathological::NESTING_DEPTH;
+use crate::token::{OwnedToken, Token};
+use arbitrary::{Arbitrary, Unstructured};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+
+/// The names and variants that arbitrary tokens are drawn from.
+const NAMES: &[&str] = &["A", "B", "S", "E", "a", "b", "value", ""];
+
+impl<'a> Arbitrary<'a> for OwnedToken {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let name = |u: &mut Unstructured<'a>| u.choose(NAMES).map(|&name| Cow::Borrowed(name));
+        let token = match u.int_in_range(0..=54u8)? {
+            0 => OwnedToken::Bool(u.arbitrary()?),
+            1 => OwnedToken::I8(u.arbitrary()?),
+            2 => OwnedToken::I16(u.arbitrary()?),
+            3 => OwnedToken::I32(u.arbitrary()?),
+            4 => OwnedToken::I64(u.arbitrary()?),
+            5 => OwnedToken::I128(u.arbitrary()?),
+            6 => OwnedToken::U8(u.arbitrary()?),
+            7 => OwnedToken::U16(u.arbitrary()?),
+            8 => OwnedToken::U32(u.arbitrary()?),
+            9 => OwnedToken::U64(u.arbitrary()?),
+            10 => OwnedToken::U128(u.arbitrary()?),
+            11 => OwnedToken::F32(u.arbitrary()?),
+            12 => OwnedToken::F64(u.arbitrary()?),
+            13 => OwnedToken::Char(u.arbitrary()?),
+            14 => OwnedToken::Str(u.arbitrary()?),
+            15 => OwnedToken::BorrowedStr(u.arbitrary()?),
+            16 => OwnedToken::String(u.arbitrary()?),
+            17 => OwnedToken::Bytes(u.arbitrary()?),
+            18 => OwnedToken::BorrowedBytes(u.arbitrary()?),
+            19 => OwnedToken::ByteBuf(u.arbitrary()?),
+            20 => OwnedToken::None,
+            21 => OwnedToken::Some,
+            22 => OwnedToken::Unit,
+            23 => OwnedToken::UnitStruct { name: name(u)? },
+            24 => OwnedToken::UnitVariant {
+                name: name(u)?,
+                variant: name(u)?,
+            },
+            25 => OwnedToken::NewtypeStruct { name: name(u)? },
+            26 => OwnedToken::NewtypeVariant {
+                name: name(u)?,
+                variant: name(u)?,
+            },
+            27 => OwnedToken::Seq {
+                len: u.arbitrary()?,
+            },
+            28 => OwnedToken::SeqEnd,
+            29 => OwnedToken::Tuple {
+                len: u.arbitrary()?,
+            },
+            30 => OwnedToken::TupleEnd,
+            31 => OwnedToken::TupleStruct {
+                name: name(u)?,
+                len: u.arbitrary()?,
+            },
+            32 => OwnedToken::TupleStructEnd,
+            33 => OwnedToken::TupleVariant {
+                name: name(u)?,
+                variant: name(u)?,
+                len: u.arbitrary()?,
+            },
+            34 => OwnedToken::TupleVariantEnd,
+            35 => OwnedToken::Map {
+                len: u.arbitrary()?,
+            },
+            36 => OwnedToken::MapEnd,
+            37 => OwnedToken::Struct {
+                name: name(u)?,
+                len: u.arbitrary()?,
+            },
+            38 => OwnedToken::StructEnd,
+            39 => OwnedToken::StructVariant {
+                name: name(u)?,
+                variant: name(u)?,
+                len: u.arbitrary()?,
+            },
+            40 => OwnedToken::StructVariantEnd,
+            41 => OwnedToken::SkipStructField { name: name(u)? },
+            42 => OwnedToken::Enum { name: name(u)? },
+            43 => OwnedToken::VariantIndex(u.arbitrary()?),
+            44 => OwnedToken::FieldIndex(u.arbitrary()?),
+            45 => OwnedToken::F32Bits(u.arbitrary()?),
+            46 => OwnedToken::F64Bits(u.arbitrary()?),
+            47 => OwnedToken::Isize(u.arbitrary()?),
+            48 => OwnedToken::Usize(u.arbitrary()?),
+            49 => OwnedToken::Readable(u.arbitrary()?),
+            50 => OwnedToken::CollectStr(u.arbitrary()?),
+            51 => OwnedToken::MapEntry,
+            52 => OwnedToken::Identifier(name(u)?),
+            53 => OwnedToken::IdentifierIndex(u.arbitrary()?),
+            _ => OwnedToken::Number(u.arbitrary()?),
+        };
+        Ok(token)
+    }
+}
+
+/// Deserializes `T` from a token stream built out of fuzzer input.
+///
+/// Errors are ignored, so only a panic (or a hang, or an allocation failure)
+/// is reported by the fuzzer. Names and variants are drawn from a small fixed
+/// set, so streams only rarely name the types of `T` exactly; this exercises
+/// the error paths more than the happy path.
+///
+/// A `cargo fuzz` target is a one-liner:
+///
+/// ```ignore
+/// #![no_main]
+///
+/// libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+///     serde_test::fuzz::fuzz_deserialize::<my_crate::Config>(data);
+/// });
+/// ```
+///
+/// Values nested deeper than [`NESTING_DEPTH`] fail with a
+/// [`LimitExceeded`](crate::ErrorKind::LimitExceeded) error, as in
+/// [`assert_survives_nesting`](crate::pathological::assert_survives_nesting),
+/// so that a long run of `Some` tokens cannot overflow the stack of a
+/// recursive type.
+///
+/// It can also be called directly, for example to replay a crash:
+///
+/// ```
+/// # use serde_test::fuzz::fuzz_deserialize;
+/// #
+/// fuzz_deserialize::<Vec<Option<String>>>(b"\x1b\x01\x02\x15\x0e\x03abc");
+///
+/// #[derive(serde::Deserialize)]
+/// #[serde(transparent)]
+/// struct Nested(Option<Box<Nested>>);
+///
+/// fuzz_deserialize::<Nested>(&[21; 1_000_000]);
+/// ```
+pub fn fuzz_deserialize<T>(data: &[u8])
+where
+    T: DeserializeOwned,
+{
+    let u = Unstructured::new(data);
+    let stream = match Vec::<OwnedToken>::arbitrary_take_rest(u) {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+    let tokens: Vec<Token<'_, '_>> = stream.iter().map(OwnedToken::as_token).collect();
+    let mut de = Deserializer::new(&tokens).max_depth(NESTING_DEPTH);
+    let _ = T::deserialize(&mut de);
+}
@@ -0,0 +1,641 @@
+//! Low-level helpers that find the values and compounds of a token stream.
+//!
+//! They are the building blocks of the crate's own assertions, for custom
+//! helpers that need to walk a stream value by value. Markers such as
+//! [`Token::Readable`] and [`Token::SkipStructField`] before a value are
+//! counted as part of it. Every helper returns `None` for a malformed stream
+//! rather than panicking, however deeply it is nested.
+//!
+//! [`TokenStream`] offers the same as methods, for navigating into nested
+//! values by field name.
+
+use crate::token::{EndToken, Token};
+use std::iter;
+use std::mem;
+use std::ops::Range;
+
+/// Returns the end token that closes the compound opened by `token`.
+///
+/// This is [`EndToken::for_header`].
+pub fn compound_end(token: Token<'_, '_>) -> Option<EndToken> {
+    EndToken::for_header(token)
+}
+
+pub(crate) fn skip_markers(tokens: &[Token<'_, '_>], mut i: usize) -> usize {
+    while let Some(Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry) =
+        tokens.get(i)
+    {
+        i += 1;
+    }
+    i
+}
+
+/// A walk over one value of a token stream, fed a token at a time.
+///
+/// Nesting is kept on a stack of its own rather than the call stack, so
+/// arbitrarily deep streams are walked without overflowing it. Everything in
+/// the crate that finds where values end steps through the tokens with one
+/// of these: [`validate_tokens`](crate::validate_tokens), the helpers of
+/// this module, [`TokenTree::from_tokens`](crate::TokenTree::from_tokens) and
+/// [`Deserializer::skip_value`](crate::de::Deserializer::skip_value).
+pub(crate) struct Walker {
+    stack: Vec<Frame>,
+    done: bool,
+    /// The starts of the values that the last token completed, innermost
+    /// first.
+    completed: Vec<usize>,
+}
+
+/// A value that has been opened but not yet completed.
+enum Frame {
+    /// A `Some`, newtype or enum header at `start` still owed `remaining`
+    /// values. The first value of an enum may be a bare
+    /// [`Token::VariantIndex`].
+    Wrapper {
+        start: usize,
+        remaining: usize,
+        variant: bool,
+    },
+    /// A compound opened at `start` and closed by `end`, which has contained
+    /// `values` values so far, counting keys and values of maps separately.
+    Compound {
+        start: usize,
+        end: EndToken,
+        len: Option<usize>,
+        pairs: bool,
+        values: usize,
+    },
+}
+
+impl Frame {
+    fn compound(start: usize, end: EndToken, len: Option<usize>, pairs: bool) -> Self {
+        Frame::Compound {
+            start,
+            end,
+            len,
+            pairs,
+            values: 0,
+        }
+    }
+}
+
+/// What a token did to the value being walked.
+pub(crate) enum Step {
+    /// A marker, which belongs to the value after it.
+    Marker,
+    /// A header, whose value the tokens after it complete.
+    Open,
+    /// A value on its own.
+    Value,
+    /// The end token of the compound opened at `start`, which declared `len`
+    /// and contained `count` elements, or entries if it is a map or struct.
+    Close {
+        start: usize,
+        len: Option<usize>,
+        count: usize,
+    },
+}
+
+/// Why a token cannot come next in the value being walked.
+pub(crate) enum Malformed {
+    /// An end token where a value was expected.
+    UnexpectedEnd(EndToken),
+    /// An end token closing a different kind of compound.
+    MismatchedEnd { expected: EndToken, found: EndToken },
+    /// A [`Token::OneOf`] listing no alternatives, or one that is not a
+    /// value on its own.
+    InvalidOneOf,
+}
+
+/// What the value being walked is owed when the tokens run out.
+pub(crate) enum Missing {
+    /// The end token of the compound opened at `start`.
+    End {
+        start: usize,
+        end: EndToken,
+    },
+    Value,
+}
+
+impl Walker {
+    pub(crate) fn new() -> Self {
+        Walker {
+            stack: Vec::new(),
+            done: false,
+            completed: Vec::new(),
+        }
+    }
+
+    /// Whether the value is complete. No more tokens may be stepped
+    /// through once it is.
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The number of values opened and not yet completed.
+    pub(crate) fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The starts of the values that the last token completed, innermost
+    /// first: the token itself or the compound it closed, then every
+    /// wrapper that was owed nothing more.
+    pub(crate) fn completed(&self) -> &[usize] {
+        &self.completed
+    }
+
+    pub(crate) fn missing(&self) -> Missing {
+        match self.stack.last() {
+            Some(&Frame::Compound {
+                start,
+                end,
+                pairs,
+                values,
+                ..
+            }) if !pairs || values % 2 == 0 => Missing::End { start, end },
+            _ => Missing::Value,
+        }
+    }
+
+    /// Steps through the token at `index`. A malformed token leaves the
+    /// walk as it was.
+    pub(crate) fn step(&mut self, index: usize, token: Token<'_, '_>) -> Result<Step, Malformed> {
+        debug_assert!(!self.done, "stepped past the end of the value");
+        self.completed.clear();
+        if let Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry = token {
+            return Ok(Step::Marker);
+        }
+
+        let variant = match self.stack.last_mut() {
+            Some(Frame::Wrapper { variant, .. }) => mem::replace(variant, false),
+            _ => false,
+        };
+        let (end, len, pairs) = match token {
+            Token::VariantIndex(_) if variant => {
+                self.complete(index);
+                return Ok(Step::Value);
+            }
+            Token::Some
+            | Token::NewtypeStruct { .. }
+            | Token::NewtypeVariant { .. }
+            | Token::VariantIndex(_) => {
+                self.stack.push(Frame::Wrapper {
+                    start: index,
+                    remaining: 1,
+                    variant: false,
+                });
+                return Ok(Step::Open);
+            }
+            Token::Enum { .. } => {
+                self.stack.push(Frame::Wrapper {
+                    start: index,
+                    remaining: 2,
+                    variant: true,
+                });
+                return Ok(Step::Open);
+            }
+            Token::Seq { len } => (EndToken::Seq, len, false),
+            Token::Tuple { len } => (EndToken::Tuple, Some(len), false),
+            Token::TupleStruct { len, .. } => (EndToken::TupleStruct, Some(len), false),
+            Token::TupleVariant { len, .. } => (EndToken::TupleVariant, Some(len), false),
+            Token::Map { len } => (EndToken::Map, len, true),
+            Token::Struct { .. } => (EndToken::Struct, None, true),
+            Token::StructVariant { .. } => (EndToken::StructVariant, None, true),
+            Token::OneOf(alternatives) => {
+                if alternatives.is_empty() || !alternatives.iter().all(|&t| standalone(t)) {
+                    return Err(Malformed::InvalidOneOf);
+                }
+                self.complete(index);
+                return Ok(Step::Value);
+            }
+            _ => match EndToken::from_token(token) {
+                Some(found) => return self.close(found),
+                None => {
+                    self.complete(index);
+                    return Ok(Step::Value);
+                }
+            },
+        };
+        self.stack.push(Frame::compound(index, end, len, pairs));
+        Ok(Step::Open)
+    }
+
+    /// Closes the innermost open value with the end token `found`.
+    fn close(&mut self, found: EndToken) -> Result<Step, Malformed> {
+        let (start, end, len, count) = match self.stack.last() {
+            Some(&Frame::Compound {
+                start,
+                end,
+                len,
+                pairs,
+                values,
+            }) if !pairs || values % 2 == 0 => {
+                (start, end, len, if pairs { values / 2 } else { values })
+            }
+            _ => return Err(Malformed::UnexpectedEnd(found)),
+        };
+        if found != end {
+            return Err(Malformed::MismatchedEnd {
+                expected: end,
+                found,
+            });
+        }
+        self.stack.pop();
+        self.complete(start);
+        Ok(Step::Close { start, len, count })
+    }
+
+    /// Counts the value at `start` as complete, completing every wrapper
+    /// that it was the last value owed to.
+    fn complete(&mut self, start: usize) {
+        self.completed.push(start);
+        while let Some(top) = self.stack.last_mut() {
+            match top {
+                Frame::Wrapper {
+                    start, remaining, ..
+                } => {
+                    *remaining -= 1;
+                    if *remaining > 0 {
+                        return;
+                    }
+                    self.completed.push(*start);
+                    self.stack.pop();
+                }
+                Frame::Compound { values, .. } => {
+                    *values += 1;
+                    return;
+                }
+            }
+        }
+        self.done = true;
+    }
+}
+
+/// Whether `token` is a complete value by itself, as an alternative of a
+/// [`Token::OneOf`] must be.
+fn standalone(token: Token<'_, '_>) -> bool {
+    !matches!(
+        token,
+        Token::Some
+            | Token::NewtypeStruct { .. }
+            | Token::NewtypeVariant { .. }
+            | Token::VariantIndex(_)
+            | Token::Enum { .. }
+            | Token::SkipStructField { .. }
+            | Token::Readable(_)
+            | Token::MapEntry
+            | Token::OneOf(_)
+    ) && compound_end(token).is_none()
+        && EndToken::from_token(token).is_none()
+}
+
+/// Returns the index one past the end of the value starting at `start`, or
+/// `None` if the stream is malformed or ends partway through the value.
+///
+/// ```
+/// # use serde_test::group::value_end;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Seq { len: Some(1) },
+///     Token::Some,
+///     Token::U8(0),
+///     Token::SeqEnd,
+///     Token::Bool(true),
+/// ];
+/// assert_eq!(value_end(&tokens, 0), Some(4));
+/// assert_eq!(value_end(&tokens, 1), Some(3));
+/// assert_eq!(value_end(&tokens, 3), None);
+///
+/// let mut deep = vec![Token::Some; 200_000];
+/// deep.push(Token::Unit);
+/// assert_eq!(value_end(&deep, 0), Some(200_001));
+/// ```
+pub fn value_end(tokens: &[Token<'_, '_>], start: usize) -> Option<usize> {
+    let mut walker = Walker::new();
+    for (i, &token) in tokens.iter().enumerate().skip(start) {
+        walker.step(i, token).ok()?;
+        if walker.is_done() {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Returns the index of the end token that closes the compound whose header
+/// is at `header`, or `None` if there is no compound there or it is not
+/// closed.
+///
+/// ```
+/// # use serde_test::group::matching_end;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Tuple { len: 2 },
+///     Token::Seq { len: Some(0) },
+///     Token::SeqEnd,
+///     Token::U8(0),
+///     Token::TupleEnd,
+/// ];
+/// assert_eq!(matching_end(&tokens, 0), Some(4));
+/// assert_eq!(matching_end(&tokens, 1), Some(2));
+/// assert_eq!(matching_end(&tokens, 3), None);
+/// ```
+pub fn matching_end(tokens: &[Token<'_, '_>], header: usize) -> Option<usize> {
+    EndToken::for_header(*tokens.get(header)?)?;
+    value_end(tokens, header).map(|end| end - 1)
+}
+
+/// Returns the tokens of the value starting at `start`, up to and including
+/// its end token if it is a compound.
+///
+/// ```
+/// # use serde_test::group::value;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [Token::Some, Token::Str("a"), Token::Unit];
+/// assert_eq!(value(&tokens, 0), Some(&tokens[..2]));
+/// ```
+pub fn value<'a, 'test, 'de>(
+    tokens: &'a [Token<'test, 'de>],
+    start: usize,
+) -> Option<&'a [Token<'test, 'de>]> {
+    Some(&tokens[start..value_end(tokens, start)?])
+}
+
+/// Returns the tokens between the header of the compound at `header` and its
+/// end token, both excluded.
+///
+/// ```
+/// # use serde_test::group::contents;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Map { len: Some(1) },
+///     Token::Str("a"),
+///     Token::U8(0),
+///     Token::MapEnd,
+/// ];
+/// assert_eq!(contents(&tokens, 0), Some(&tokens[1..3]));
+/// ```
+pub fn contents<'a, 'test, 'de>(
+    tokens: &'a [Token<'test, 'de>],
+    header: usize,
+) -> Option<&'a [Token<'test, 'de>]> {
+    Some(&tokens[header + 1..matching_end(tokens, header)?])
+}
+
+/// Returns the index range of each value directly inside the compound whose
+/// header is at `header`. The entries of a map or struct are returned as
+/// their keys and values in turn.
+///
+/// ```
+/// # use serde_test::group::elements;
+/// # use serde_test::Token;
+/// #
+/// let tokens = [
+///     Token::Seq { len: Some(2) },
+///     Token::Some,
+///     Token::U8(0),
+///     Token::None,
+///     Token::SeqEnd,
+/// ];
+/// assert_eq!(elements(&tokens, 0), Some(vec![1..3, 3..4]));
+/// ```
+pub fn elements(tokens: &[Token<'_, '_>], header: usize) -> Option<Vec<Range<usize>>> {
+    let end = matching_end(tokens, header)?;
+    let mut elements = Vec::new();
+    let mut i = header + 1;
+    while skip_markers(tokens, i) < end {
+        let value_end = value_end(tokens, i)?;
+        elements.push(i..value_end);
+        i = value_end;
+    }
+    Some(elements)
+}
+
+/// A key/value pair inside a map or struct.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Entry {
+    pub key: Range<usize>,
+    pub value: Range<usize>,
+}
+
+/// Returns the entries of the map or struct whose header is at `header`.
+pub(crate) fn entries(tokens: &[Token<'_, '_>], header: usize) -> Option<Vec<Entry>> {
+    let end = match tokens.get(header)? {
+        Token::Map { .. } => EndToken::Map,
+        Token::Struct { .. } => EndToken::Struct,
+        Token::StructVariant { .. } => EndToken::StructVariant,
+        _ => return None,
+    };
+    let mut entries = Vec::new();
+    let mut i = header + 1;
+    loop {
+        i = skip_markers(tokens, i);
+        if *tokens.get(i)? == end {
+            return Some(entries);
+        }
+        let key_end = value_end(tokens, i)?;
+        let value_start = skip_markers(tokens, key_end);
+        let value_end = value_end(tokens, value_start)?;
+        entries.push(Entry {
+            key: i..key_end,
+            value: value_start..value_end,
+        });
+        i = value_end;
+    }
+}
+
+/// Returns the entries of every map and struct in the stream, in order of
+/// their headers.
+pub(crate) fn all_entries(tokens: &[Token<'_, '_>]) -> Vec<Entry> {
+    // The end of every value, found in one walk so that nested maps are not
+    // walked again for each map around them.
+    let mut ends = vec![None; tokens.len()];
+    let mut walker = Walker::new();
+    for (i, &token) in tokens.iter().enumerate() {
+        if walker.is_done() {
+            walker = Walker::new();
+        }
+        if walker.step(i, token).is_err() {
+            break;
+        }
+        for &start in walker.completed() {
+            ends[start] = Some(i + 1);
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (header, &token) in tokens.iter().enumerate() {
+        if !matches!(
+            token,
+            Token::Map { .. } | Token::Struct { .. } | Token::StructVariant { .. }
+        ) || ends[header].is_none()
+        {
+            continue;
+        }
+        let mut i = skip_markers(tokens, header + 1);
+        while EndToken::from_token(tokens[i]).is_none() {
+            let key_end = ends[i].unwrap();
+            let value_start = skip_markers(tokens, key_end);
+            let value_end = ends[value_start].unwrap();
+            entries.push(Entry {
+                key: i..key_end,
+                value: value_start..value_end,
+            });
+            i = skip_markers(tokens, value_end);
+        }
+    }
+    entries
+}
+
+/// A token stream, or a value within one, with methods to navigate to the
+/// values inside it.
+///
+/// Each stream navigated to remembers its [`offset`](Self::offset) in the
+/// stream it came from, so derived test cases can be spliced together with
+/// the [`compose`](crate::compose) functions without counting indices by
+/// hand.
+///
+/// ```
+/// # use serde_test::compose::replace_at;
+/// # use serde_test::{Token, TokenStream};
+/// #
+/// let tokens = [
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("a"),
+///     Token::U8(1),
+///     Token::Str("b"),
+///     Token::Seq { len: Some(2) },
+///     Token::Bool(true),
+///     Token::Bool(false),
+///     Token::SeqEnd,
+///     Token::StructEnd,
+/// ];
+/// let stream = TokenStream::new(&tokens);
+///
+/// let b = stream.field("b").unwrap();
+/// assert_eq!(b.offset(), 4);
+/// assert_eq!(b.elements().unwrap()[1].tokens(), [Token::Bool(false)]);
+///
+/// let replaced = replace_at(&tokens[..], b.offset(), [Token::Unit]);
+/// assert_eq!(replaced.len(), 6);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct TokenStream<'a, 'test, 'de> {
+    tokens: &'a [Token<'test, 'de>],
+    offset: usize,
+}
+
+impl<'a, 'test, 'de> TokenStream<'a, 'test, 'de> {
+    pub fn new(tokens: &'a [Token<'test, 'de>]) -> Self {
+        TokenStream { tokens, offset: 0 }
+    }
+
+    pub fn tokens(&self) -> &'a [Token<'test, 'de>] {
+        self.tokens
+    }
+
+    /// Returns the index of the first token of this stream in the stream
+    /// given to [`new`](Self::new).
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the indices of this stream in the stream given to
+    /// [`new`](Self::new).
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.tokens.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn slice(&self, range: Range<usize>) -> Self {
+        TokenStream {
+            tokens: &self.tokens[range.clone()],
+            offset: self.offset + range.start,
+        }
+    }
+
+    /// The index of the header of the value at the start of this stream,
+    /// after any markers.
+    fn header(&self) -> usize {
+        skip_markers(self.tokens, 0)
+    }
+
+    /// Returns the index of the end token that closes the compound whose
+    /// header is at `header`, like [`matching_end`].
+    pub fn matching_end(&self, header: usize) -> Option<usize> {
+        matching_end(self.tokens, header)
+    }
+
+    /// Returns the whole value starting at `start`, like [`value`].
+    pub fn value(&self, start: usize) -> Option<Self> {
+        Some(self.slice(start..value_end(self.tokens, start)?))
+    }
+
+    /// Iterates over the values of this stream one after another, stopping
+    /// early if the stream is malformed.
+    ///
+    /// ```
+    /// # use serde_test::{Token, TokenStream};
+    /// #
+    /// let tokens = [Token::Some, Token::U8(1), Token::Unit];
+    /// let values: Vec<_> = TokenStream::new(&tokens).values().collect();
+    /// assert_eq!(values.len(), 2);
+    /// assert_eq!(values[1].offset(), 2);
+    /// ```
+    pub fn values(self) -> impl Iterator<Item = Self> + 'a {
+        let mut start = 0;
+        iter::from_fn(move || {
+            if start == self.tokens.len() {
+                return None;
+            }
+            let value = self.value(start)?;
+            start += value.len();
+            Some(value)
+        })
+    }
+
+    /// Returns the values directly inside the compound that starts this
+    /// stream, with the entries of a map or struct as their keys and values
+    /// in turn.
+    pub fn elements(&self) -> Option<Vec<Self>> {
+        let elements = elements(self.tokens, self.header())?;
+        Some(
+            elements
+                .into_iter()
+                .map(|range| self.slice(range))
+                .collect(),
+        )
+    }
+
+    /// Returns the key and value of each entry of the map or struct that
+    /// starts this stream.
+    pub fn entries(&self) -> Option<Vec<(Self, Self)>> {
+        let entries = entries(self.tokens, self.header())?;
+        Some(
+            entries
+                .into_iter()
+                .map(|entry| (self.slice(entry.key), self.slice(entry.value)))
+                .collect(),
+        )
+    }
+
+    /// Returns the value of the first entry whose key is the string `name`
+    /// in the map or struct that starts this stream.
+    pub fn field(&self, name: &str) -> Option<Self> {
+        let entries = self.entries()?;
+        entries.into_iter().find_map(|(key, value)| {
+            let key = key.tokens[key.header()];
+            (key.as_str() == Some(name)).then_some(value)
+        })
+    }
+}
@@ -0,0 +1,49 @@
+use crate::token::Token;
+
+/// Token streams accepted by the assert functions.
+///
+/// Implemented for anything that iterates over [`Token`]s, `&Token`s or
+/// `&OwnedToken`s, which covers slices, arrays and `Vec`s by reference or by
+/// value, as well as iterators.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens, assert_tokens, Token, TokenBuilder};
+/// # use std::iter;
+/// #
+/// assert_tokens(
+///     &vec![1u8, 2],
+///     vec![
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+/// );
+///
+/// let tokens = iter::once(Token::Seq { len: None })
+///     .chain((1..=3).map(Token::U8))
+///     .chain(iter::once(Token::SeqEnd));
+/// assert_de_tokens(&vec![1u8, 2, 3], tokens);
+///
+/// let tokens = TokenBuilder::new()
+///     .tuple(2)
+///     .token(Token::Bool(true))
+///     .token(Token::Char('x'))
+///     .end()
+///     .build();
+/// assert_tokens(&(true, 'x'), &tokens);
+/// ```
+pub trait IntoTokens<'test, 'de: 'test> {
+    /// Collects the token stream.
+    fn into_tokens(self) -> Vec<Token<'test, 'de>>;
+}
+
+impl<'test, 'de: 'test, I> IntoTokens<'test, 'de> for I
+where
+    I: IntoIterator,
+    I::Item: Into<Token<'test, 'de>>,
+{
+    fn into_tokens(self) -> Vec<Token<'test, 'de>> {
+        self.into_iter().map(Into::into).collect()
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversions between [`serde_json::Value`]s and token streams.
+//!
+//! Requires the `json` feature.
+//!
+//! [`tokens_from_json_value`] lets existing JSON fixtures drive the token
+//! deserializer, and [`json_value_from_tokens`] turns tokens, such as those
+//! from [`capture_tokens`](crate::capture_tokens), into JSON to compare
+//! against JSON golden files. [`assert_round_trip_json`] checks a value
+//! against the real JSON format.
+//!
+//! ```
+//! # use serde::Deserialize;
+//! # use serde_json::json;
+//! # use serde_test::assert_de_tokens;
+//! # use serde_test::json::tokens_from_json_value;
+//! #
+//! #[derive(Deserialize, PartialEq, Debug)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! let fixture = json!({ "x": 1, "y": -2 });
+//! assert_de_tokens(&Point { x: 1, y: -2 }, &tokens_from_json_value(&fixture));
+//! ```
+
+use crate::de::Deserializer;
+use crate::error::Error;
+use crate::into_tokens::IntoTokens;
+use crate::token::OwnedToken;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Debug;
+
+/// Returns the tokens that `value` serializes to.
+///
+/// Objects become [`Token::Map`](crate::Token::Map)s with
+/// [`Token::Str`](crate::Token::Str) keys, arrays become
+/// [`Token::Seq`](crate::Token::Seq)s, `null` becomes
+/// [`Token::Unit`](crate::Token::Unit), and numbers become
+/// [`Token::U64`](crate::Token::U64), [`Token::I64`](crate::Token::I64) or
+/// [`Token::F64`](crate::Token::F64) depending on how `serde_json` stores
+/// them.
+///
+/// ```
+/// # use serde_json::json;
+/// # use serde_test::json::tokens_from_json_value;
+/// # use serde_test::Token;
+/// #
+/// assert_eq!(
+///     tokens_from_json_value(&json!([null, 1, -1, 0.5])),
+///     [
+///         Token::Seq { len: Some(4) },
+///         Token::Unit,
+///         Token::U64(1),
+///         Token::I64(-1),
+///         Token::F64(0.5),
+///         Token::SeqEnd,
+///     ],
+/// );
+/// ```
+pub fn tokens_from_json_value(value: &Value) -> Vec<OwnedToken> {
+    let mut tokens = Vec::new();
+    push_value(&mut tokens, value);
+    tokens
+}
+
+fn push_value(tokens: &mut Vec<OwnedToken>, value: &Value) {
+    match value {
+        Value::Null => tokens.push(OwnedToken::Unit),
+        Value::Bool(v) => tokens.push(OwnedToken::Bool(*v)),
+        Value::Number(v) => tokens.push(if let Some(v) = v.as_u64() {
+            OwnedToken::U64(v)
+        } else if let Some(v) = v.as_i64() {
+            OwnedToken::I64(v)
+        } else {
+            OwnedToken::F64(v.as_f64().unwrap_or(f64::NAN))
+        }),
+        Value::String(v) => tokens.push(OwnedToken::Str(v.clone())),
+        Value::Array(elements) => {
+            tokens.push(OwnedToken::Seq {
+                len: Some(elements.len()),
+            });
+            for element in elements {
+                push_value(tokens, element);
+            }
+            tokens.push(OwnedToken::SeqEnd);
+        }
+        Value::Object(entries) => {
+            tokens.push(OwnedToken::Map {
+                len: Some(entries.len()),
+            });
+            for (key, value) in entries {
+                tokens.push(OwnedToken::Str(key.clone()));
+                push_value(tokens, value);
+            }
+            tokens.push(OwnedToken::MapEnd);
+        }
+    }
+}
+
+/// Deserializes `tokens` into a [`Value`], the way `serde_json` would read
+/// the JSON they describe.
+///
+/// Structs become objects and enums use the externally tagged form, as with
+/// `serde_json::to_value`. Fails if the tokens are malformed, are not one
+/// value, or hold something JSON cannot, such as a map with non-string keys.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_json::json;
+/// # use serde_test::capture_tokens;
+/// # use serde_test::json::json_value_from_tokens;
+/// #
+/// #[derive(Serialize)]
+/// enum Shape {
+///     Circle { r: u8 },
+/// }
+///
+/// let tokens = capture_tokens(&Shape::Circle { r: 2 }).unwrap();
+/// assert_eq!(
+///     json_value_from_tokens(&tokens).unwrap(),
+///     json!({ "Circle": { "r": 2 } }),
+/// );
+/// ```
+pub fn json_value_from_tokens<'test, 'de: 'test>(
+    tokens: impl IntoTokens<'test, 'de>,
+) -> Result<Value, Error> {
+    let tokens = tokens.into_tokens();
+    Value::deserialize(Deserializer::new(&tokens))
+}
+
+/// Asserts that `value` serializes to JSON and deserializes back to an equal
+/// value, to go with the token assertions that check the data model.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::json::assert_round_trip_json;
+/// # use serde_test::{assert_tokens, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Id(u32);
+///
+/// assert_tokens(&Id(7), &[Token::NewtypeStruct { name: "Id" }, Token::U32(7)]);
+/// assert_round_trip_json(&Id(7));
+/// ```
+#[track_caller]
+pub fn assert_round_trip_json<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = match serde_json::to_string(value) {
+        Ok(json) => json,
+        Err(err) => panic!("value failed to serialize to JSON: {}", err),
+    };
+    let back: T = match serde_json::from_str(&json) {
+        Ok(back) => back,
+        Err(err) => panic!("JSON failed to deserialize: {}\n  json: {}", err, json),
+    };
+    if back != *value {
+        panic!(
+            "value does not match after a JSON round trip\n     found: {:?}\n  expected: {:?}\n      json: {}",
+            back, value, json,
+        );
+    }
+}
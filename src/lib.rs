@@ -147,7 +147,12 @@
 // #![doc(html_root_url = "https://docs.rs/serde_test/1.0.176")] // FIXME
 #![deny(elided_lifetimes_in_paths)]
 // Ignored clippy lints
-#![allow(clippy::float_cmp, clippy::needless_doctest_main)]
+#![allow(
+    clippy::float_cmp,
+    clippy::multiple_bound_locations,
+    clippy::needless_doctest_main,
+    clippy::test_attr_in_doctest
+)]
 // Ignored clippy_pedantic lints
 #![allow(
     clippy::missing_panics_doc,
@@ -155,18 +160,86 @@
     clippy::too_many_lines
 )]
 
+pub mod compat;
+pub mod compose;
 pub mod de;
+#[cfg(feature = "erased-serde")]
+pub mod erased;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+pub mod group;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod mutate;
+pub mod pathological;
+pub mod pattern;
+pub mod pretty;
+pub mod repr;
+pub mod schema;
 pub mod ser;
+pub mod shrink;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "transcode")]
+pub mod transcode;
 
 mod assert;
+mod builder;
+mod capture;
+mod check;
 mod configure;
+mod diff;
+mod display;
 mod error;
+mod fixture;
+mod into_tokens;
+mod located;
+mod macros;
+mod parse;
+mod stats;
+mod tee;
 mod token;
+mod trace;
+mod tree;
+mod validate;
 
 pub use crate::assert::{
-    assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, assert_ser_tokens_error,
-    assert_tokens,
+    assert_de_aliases, assert_de_borrows, assert_de_duplicate_field, assert_de_error_deterministic,
+    assert_de_errors_table, assert_de_hints, assert_de_in_place_reuses_capacity,
+    assert_de_in_place_tokens, assert_de_missing_fields, assert_de_rejects,
+    assert_de_rejects_unknown_fields, assert_de_seed_tokens, assert_de_seed_tokens_error,
+    assert_de_tokens, assert_de_tokens_any_field_order, assert_de_tokens_error,
+    assert_de_tokens_error_at, assert_de_tokens_error_kind, assert_de_tokens_error_matches,
+    assert_de_tokens_error_remaining, assert_de_tokens_errors_then_continues,
+    assert_de_tokens_ignores_extra, assert_de_tokens_no_in_place, assert_de_tokens_opaque,
+    assert_de_tokens_owned, assert_de_tokens_table, assert_de_tokens_with, assert_de_untagged,
+    assert_de_with, assert_matches_schema, assert_readable_queries, assert_rejects_bytes,
+    assert_rejects_floats, assert_rejects_strings, assert_round_trip, assert_ser_de_symmetry,
+    assert_ser_never_calls, assert_ser_tokens, assert_ser_tokens_contains, assert_ser_tokens_error,
+    assert_ser_tokens_error_consumed, assert_ser_tokens_error_kind,
+    assert_ser_tokens_error_matches, assert_ser_tokens_excludes, assert_ser_tokens_once,
+    assert_ser_tokens_prefix, assert_ser_tokens_repeat, assert_ser_tokens_table,
+    assert_ser_tokens_via_wrappers, assert_ser_with, assert_tokens, assert_tokens_all_modes,
+    assert_tokens_asymmetric, assert_tokens_golden, assert_tokens_located, assert_tokens_opaque,
+    assert_tokens_table, assert_with, ErrorMessages, OnDuplicate, UntaggedVariant,
+};
+pub use crate::builder::TokenBuilder;
+pub use crate::capture::{
+    capture_de_tokens, capture_tokens, tokens_source_code, CaptureDeserializer,
 };
-pub use crate::configure::{Compact, Configure, Readable};
-pub use crate::error::{Error, TestResult};
-pub use crate::token::Token;
+pub use crate::check::{check_tokens, Mismatch, Phase, Report};
+pub use crate::configure::{with_human_readable, Compact, Configure, Readable};
+pub use crate::display::DisplayTokens;
+pub use crate::error::{Error, ErrorKind, TestResult};
+pub use crate::fixture::FixtureSet;
+pub use crate::group::TokenStream;
+pub use crate::into_tokens::IntoTokens;
+pub use crate::located::Located;
+pub use crate::mutate::assert_survives_mutations;
+pub use crate::parse::{parse_tokens, ParseError};
+pub use crate::pathological::{assert_survives_nesting, assert_survives_pathological};
+pub use crate::stats::TokenStats;
+pub use crate::tee::{Tee, TeeCompound};
+pub use crate::token::{EndToken, OwnedToken, Token, TokenKind};
+pub use crate::tree::TokenTree;
+pub use crate::validate::{validate_tokens, TokenStreamError};
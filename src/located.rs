@@ -0,0 +1,60 @@
+use crate::token::Token;
+use std::panic::Location;
+
+/// A [`Token`] along with where in the source it was written.
+///
+/// Made by [`Token::with_location`]. Streams of them are accepted by every
+/// assert function, and [`assert_tokens_located`](crate::assert_tokens_located)
+/// points its panics at the token that mismatched rather than at the call.
+#[derive(Copy, Clone, Debug)]
+pub struct Located<'test, 'de: 'test> {
+    token: Token<'test, 'de>,
+    location: &'static Location<'static>,
+}
+
+impl<'test, 'de> Token<'test, 'de> {
+    /// Records the `file:line:column` this is called at.
+    ///
+    /// Each call needs to be written out at the token itself, since a
+    /// macro can only see the location it was invoked at.
+    ///
+    /// ```
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Some.with_location(),
+    ///     Token::U8(0).with_location(),
+    /// ];
+    /// assert_eq!(tokens[1].token(), Token::U8(0));
+    /// assert_eq!(tokens[1].location().line(), tokens[0].location().line() + 1);
+    /// ```
+    #[track_caller]
+    pub fn with_location(self) -> Located<'test, 'de> {
+        Located {
+            token: self,
+            location: Location::caller(),
+        }
+    }
+}
+
+impl<'test, 'de> Located<'test, 'de> {
+    pub const fn token(&self) -> Token<'test, 'de> {
+        self.token
+    }
+
+    pub const fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+}
+
+impl<'test, 'de> From<Located<'test, 'de>> for Token<'test, 'de> {
+    fn from(located: Located<'test, 'de>) -> Self {
+        located.token
+    }
+}
+
+impl<'test, 'de> From<&Located<'test, 'de>> for Token<'test, 'de> {
+    fn from(located: &Located<'test, 'de>) -> Self {
+        located.token
+    }
+}
@@ -0,0 +1,271 @@
+/// Builds a flat token array from a nested description of a value.
+///
+/// Compounds are written with their contents in brackets and expand to the
+/// header token, the contents, and the matching end token, with `len`
+/// counted for you. Since every compound is closed by its own bracket, an
+/// unbalanced stream is a compile error.
+///
+/// | Syntax                                 | Expands to                                |
+/// |----------------------------------------|-------------------------------------------|
+/// | `Seq [a, b]`                           | `Seq { len: Some(2) }`, a, b, `SeqEnd`    |
+/// | `Tuple (a, b)`                         | `Tuple { len: 2 }`, a, b, `TupleEnd`      |
+/// | `TupleStruct("S") (a, b)`              | `TupleStruct { .. }`, a, b, `TupleStructEnd` |
+/// | `TupleVariant("E", "V") (a, b)`        | `TupleVariant { .. }`, a, b, `TupleVariantEnd` |
+/// | `Map { k => v }`                       | `Map { len: Some(1) }`, k, v, `MapEnd`    |
+/// | `Struct("S") { "a" => v }`             | `Struct { .. }`, `Str("a")`, v, `StructEnd` |
+/// | `StructVariant("E", "V") { "a" => v }` | `StructVariant { .. }`, `Str("a")`, v, `StructVariantEnd` |
+/// | `Some(v)`                              | `Some`, v                                 |
+/// | `NewtypeStruct("S") (v)`               | `NewtypeStruct { .. }`, v                 |
+/// | `NewtypeVariant("E", "V") (v)`         | `NewtypeVariant { .. }`, v                |
+/// | `"a"`                                  | `Str("a")`                                |
+///
+/// Anything else is a plain token written without the `Token::` prefix, such
+/// as `U8(0)`, `Unit` or `UnitVariant { name: "E", variant: "A" }`.
+///
+/// Each token costs a level of macro recursion, so very long streams may need
+/// a higher `#![recursion_limit]`.
+///
+/// ```
+/// # use serde_test::{tokens, Token};
+/// #
+/// let tokens = tokens![Struct("S") {
+///     "a" => U8(0),
+///     "b" => Seq [Some(Bool(true)), None],
+/// }];
+///
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::Str("b"),
+///         Token::Seq { len: Some(2) },
+///         Token::Some,
+///         Token::Bool(true),
+///         Token::None,
+///         Token::SeqEnd,
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[macro_export]
+macro_rules! tokens {
+    ($($input:tt)*) => {
+        $crate::__tokens!(@munch [] $($input)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __tokens {
+    // Done.
+    (@munch [$($out:tt)*]) => {
+        [$($out)*]
+    };
+
+    // Separators carry no tokens.
+    (@munch [$($out:tt)*] , $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*] $($rest)*)
+    };
+    (@munch [$($out:tt)*] => $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*] $($rest)*)
+    };
+
+    // Close a compound opened below.
+    (@munch [$($out:tt)*] @end($end:ident) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::$end,] $($rest)*)
+    };
+
+    // Compounds.
+    (@munch [$($out:tt)*] Seq [$($inner:tt)*] $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::Seq { len: ::std::option::Option::Some($crate::__tokens!(@count $($inner)*)) },
+        ] $($inner)* , @end(SeqEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] Tuple ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::Tuple { len: $crate::__tokens!(@count $($inner)*) },
+        ] $($inner)* , @end(TupleEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] TupleStruct($name:expr) ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::TupleStruct { name: $name, len: $crate::__tokens!(@count $($inner)*) },
+        ] $($inner)* , @end(TupleStructEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] TupleVariant($name:expr, $variant:expr) ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::TupleVariant {
+                name: $name,
+                variant: $variant,
+                len: $crate::__tokens!(@count $($inner)*),
+            },
+        ] $($inner)* , @end(TupleVariantEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] Map {$($inner:tt)*} $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::Map { len: ::std::option::Option::Some($crate::__tokens!(@count $($inner)*)) },
+        ] $($inner)* , @end(MapEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] Struct($name:expr) {$($inner:tt)*} $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::Struct { name: $name, len: $crate::__tokens!(@count $($inner)*) },
+        ] $($inner)* , @end(StructEnd) $($rest)*)
+    };
+    (@munch [$($out:tt)*] StructVariant($name:expr, $variant:expr) {$($inner:tt)*} $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::StructVariant {
+                name: $name,
+                variant: $variant,
+                len: $crate::__tokens!(@count $($inner)*),
+            },
+        ] $($inner)* , @end(StructVariantEnd) $($rest)*)
+    };
+
+    // Wrappers around a single value.
+    (@munch [$($out:tt)*] Some($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::Some,] $($inner)* , $($rest)*)
+    };
+    (@munch [$($out:tt)*] NewtypeStruct($name:expr) ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::NewtypeStruct { name: $name },
+        ] $($inner)* , $($rest)*)
+    };
+    (@munch [$($out:tt)*] NewtypeVariant($name:expr, $variant:expr) ($($inner:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)*
+            $crate::Token::NewtypeVariant { name: $name, variant: $variant },
+        ] $($inner)* , $($rest)*)
+    };
+
+    // Plain tokens.
+    (@munch [$($out:tt)*] $variant:ident {$($fields:tt)*} $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::$variant {$($fields)*},] $($rest)*)
+    };
+    (@munch [$($out:tt)*] $variant:ident ($($args:tt)*) $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::$variant($($args)*),] $($rest)*)
+    };
+    (@munch [$($out:tt)*] $variant:ident $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::$variant,] $($rest)*)
+    };
+    (@munch [$($out:tt)*] $key:literal $($rest:tt)*) => {
+        $crate::__tokens!(@munch [$($out)* $crate::Token::Str($key),] $($rest)*)
+    };
+
+    // Counts the comma-separated elements of a compound.
+    (@count) => {
+        0
+    };
+    (@count $($inner:tt)+) => {
+        $crate::__tokens!(@commas [1] $($inner)+)
+    };
+    (@commas [$($n:tt)*] ,) => {
+        $($n)*
+    };
+    (@commas [$($n:tt)*] , $($rest:tt)+) => {
+        $crate::__tokens!(@commas [$($n)* + 1] $($rest)+)
+    };
+    (@commas [$($n:tt)*] $other:tt $($rest:tt)*) => {
+        $crate::__tokens!(@commas [$($n)*] $($rest)*)
+    };
+    (@commas [$($n:tt)*]) => {
+        $($n)*
+    };
+}
+
+/// Defines `#[test]` functions that run [`assert_tokens`](crate::assert_tokens)
+/// on a value.
+///
+/// Each entry names a test, gives the value, and after `=>` the tokens it is
+/// expected to round trip through. Types with different human-readable and
+/// compact forms list the tokens of each under `readable:` and `compact:`,
+/// and are wrapped with [`Configure`](crate::Configure) for you. Attributes
+/// such as `#[ignore]` are applied to the test function.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{serde_tokens_test, tokens, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// serde_tokens_test! {
+///     none: None::<u8> => [Token::None];
+///     some: Some(1u8) => [Token::Some, Token::U8(1)];
+///     struct_s: S { a: 0 } => tokens![Struct("S") { "a" => U8(0) }];
+///     localhost: Ipv4Addr::LOCALHOST => {
+///         readable: [Token::Str("127.0.0.1")],
+///         compact: tokens![Tuple(U8(127), U8(0), U8(0), U8(1))],
+///     };
+/// }
+/// #
+/// # fn main() {}
+/// ```
+///
+/// The value expression is evaluated once for each form.
+#[macro_export]
+macro_rules! serde_tokens_test {
+    () => {};
+    (
+        $(#[$attr:meta])*
+        $name:ident: $value:expr => { $($form:ident: $tokens:expr),+ $(,)? }
+        $(; $($rest:tt)*)?
+    ) => {
+        $(#[$attr])*
+        #[test]
+        fn $name() {
+            $($crate::assert_tokens(&$crate::Configure::$form($value), $tokens);)+
+        }
+        $($crate::serde_tokens_test!($($rest)*);)?
+    };
+    (
+        $(#[$attr:meta])*
+        $name:ident: $value:expr => $tokens:expr
+        $(; $($rest:tt)*)?
+    ) => {
+        $(#[$attr])*
+        #[test]
+        fn $name() {
+            $crate::assert_tokens(&$value, $tokens);
+        }
+        $($crate::serde_tokens_test!($($rest)*);)?
+    };
+}
+
+/// Runs [`assert_with`](crate::assert_with) through the `serialize` and
+/// `deserialize` functions that `#[serde(remote = "...")]` generates on a
+/// shim type.
+///
+/// ```
+/// # use serde::{Deserialize, Serialize};
+/// # use serde_test::{assert_remote_tokens, Token};
+/// #
+/// mod other_crate {
+///     #[derive(PartialEq, Debug)]
+///     pub struct Point(pub u8, pub u8);
+/// }
+/// use other_crate::Point;
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(remote = "Point")]
+/// struct PointDef(u8, u8);
+///
+/// assert_remote_tokens!(
+///     PointDef,
+///     &Point(1, 2),
+///     &[
+///         Token::TupleStruct { name: "PointDef", len: 2 },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::TupleStructEnd,
+///     ],
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_remote_tokens {
+    ($shim:ty, $value:expr, $tokens:expr $(,)?) => {
+        $crate::assert_with($value, <$shim>::serialize, <$shim>::deserialize, $tokens)
+    };
+}
@@ -0,0 +1,261 @@
+//! Systematic perturbations of a valid token stream.
+//!
+//! Where the [`pathological`](crate::pathological) corpus is the same for
+//! every type, these mutants are derived from a stream the type accepts, so
+//! they reach deep into its `Deserialize` impl: the stream cut short, an end
+//! token dropped, a token swapped for one of another kind, or a declared
+//! length off by one.
+
+use crate::assert::assert_valid;
+use crate::de::Deserializer;
+use crate::into_tokens::IntoTokens;
+use crate::token::{EndToken, Token};
+use crate::validate::validate_tokens;
+use serde::Deserialize;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The way a [`Mutant`] was derived from the original stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mutation {
+    /// The stream ends early.
+    Truncation,
+    /// An end token was removed, leaving its compound unclosed.
+    EndRemoval,
+    /// A scalar token was replaced by one of a different kind.
+    KindSwap,
+    /// The `len` of a compound header is one more or one less than the
+    /// number of elements.
+    LengthLie,
+}
+
+impl Mutation {
+    /// Whether every mutant of this kind is malformed, so that no type can
+    /// accept it.
+    pub fn is_malformed(self) -> bool {
+        match self {
+            Mutation::Truncation | Mutation::EndRemoval => true,
+            Mutation::KindSwap | Mutation::LengthLie => false,
+        }
+    }
+}
+
+/// One perturbed copy of a stream.
+#[derive(Clone, Debug)]
+pub struct Mutant<'test, 'de> {
+    pub mutation: Mutation,
+    pub description: String,
+    pub tokens: Vec<Token<'test, 'de>>,
+}
+
+/// Returns every mutant of `tokens`.
+///
+/// ```
+/// # use serde_test::mutate::{mutants, Mutation};
+/// # use serde_test::Token;
+/// #
+/// let tokens = [Token::Seq { len: Some(1) }, Token::U8(0), Token::SeqEnd];
+/// let mutants = mutants(&tokens);
+///
+/// let truncations = mutants
+///     .iter()
+///     .filter(|mutant| mutant.mutation == Mutation::Truncation)
+///     .count();
+/// assert_eq!(truncations, 3);
+/// assert!(mutants.iter().any(|mutant| mutant.tokens
+///     == [Token::Seq { len: Some(2) }, Token::U8(0), Token::SeqEnd]));
+/// ```
+pub fn mutants<'test, 'de>(tokens: &[Token<'test, 'de>]) -> Vec<Mutant<'test, 'de>> {
+    let mut mutants = Vec::new();
+    let mut push = |mutation, description: String, tokens| {
+        mutants.push(Mutant {
+            mutation,
+            description,
+            tokens,
+        });
+    };
+
+    for len in 0..tokens.len() {
+        push(
+            Mutation::Truncation,
+            format!("truncated to {} tokens", len),
+            tokens[..len].to_vec(),
+        );
+    }
+    for (index, &token) in tokens.iter().enumerate() {
+        if EndToken::from_token(token).is_some() {
+            let mut mutant = tokens.to_vec();
+            mutant.remove(index);
+            push(
+                Mutation::EndRemoval,
+                format!("Token::{} at index {} removed", token, index),
+                mutant,
+            );
+        }
+        if let Some(swapped) = swap_kind(token) {
+            let mut mutant = tokens.to_vec();
+            mutant[index] = swapped;
+            push(
+                Mutation::KindSwap,
+                format!(
+                    "Token::{} at index {} swapped for Token::{}",
+                    token, index, swapped,
+                ),
+                mutant,
+            );
+        }
+        for more in [true, false] {
+            if let Some(lied) = lie_about_len(token, more) {
+                let mut mutant = tokens.to_vec();
+                mutant[index] = lied;
+                push(
+                    Mutation::LengthLie,
+                    format!(
+                        "Token::{} at index {} changed to Token::{}",
+                        token, index, lied
+                    ),
+                    mutant,
+                );
+            }
+        }
+    }
+    mutants
+}
+
+/// A token of a different kind than the scalar `token`, or `None` if it is
+/// not a scalar.
+fn swap_kind<'test, 'de>(token: Token<'test, 'de>) -> Option<Token<'test, 'de>> {
+    Some(match token {
+        Token::Bool(_) => Token::U8(0),
+        Token::I8(_)
+        | Token::I16(_)
+        | Token::I32(_)
+        | Token::I64(_)
+        | Token::I128(_)
+        | Token::Isize(_)
+        | Token::U8(_)
+        | Token::U16(_)
+        | Token::U32(_)
+        | Token::U64(_)
+        | Token::U128(_)
+        | Token::Usize(_)
+        | Token::Number(_)
+        | Token::F32(_)
+        | Token::F64(_) => Token::Bool(false),
+        Token::Char(_)
+        | Token::Str(_)
+        | Token::BorrowedStr(_)
+        | Token::String(_)
+        | Token::CollectStr(_) => Token::U32(0),
+        Token::Bytes(_) | Token::BorrowedBytes(_) | Token::ByteBuf(_) => Token::Str(""),
+        Token::None | Token::Unit => Token::Bool(false),
+        Token::UnitStruct { .. } | Token::UnitVariant { .. } => Token::Unit,
+        _ => return None,
+    })
+}
+
+/// `token` with its `len` one `more` or one less, or `None` if it declares
+/// no length or the length would overflow.
+pub(crate) fn lie_about_len<'test, 'de>(
+    token: Token<'test, 'de>,
+    more: bool,
+) -> Option<Token<'test, 'de>> {
+    let lied = |len: usize| {
+        if more {
+            len.checked_add(1)
+        } else {
+            len.checked_sub(1)
+        }
+    };
+    Some(match token {
+        Token::Seq { len: Some(len) } => Token::Seq {
+            len: Some(lied(len)?),
+        },
+        Token::Map { len: Some(len) } => Token::Map {
+            len: Some(lied(len)?),
+        },
+        Token::Tuple { len } => Token::Tuple { len: lied(len)? },
+        Token::TupleStruct { name, len } => Token::TupleStruct {
+            name,
+            len: lied(len)?,
+        },
+        Token::TupleVariant { name, variant, len } => Token::TupleVariant {
+            name,
+            variant,
+            len: lied(len)?,
+        },
+        Token::Struct { name, len } => Token::Struct {
+            name,
+            len: lied(len)?,
+        },
+        Token::StructVariant { name, variant, len } => Token::StructVariant {
+            name,
+            variant,
+            len: lied(len)?,
+        },
+        _ => return None,
+    })
+}
+
+/// Deserializes `T` from every [`mutant`](mutants) of `tokens`, asserting
+/// that none of them cause a panic and that the malformed ones are
+/// rejected with an error.
+///
+/// Kind swaps and length lies may still be accepted, since serde's
+/// visitors convert between many kinds and treat lengths as hints. Every
+/// offending mutant is reported together.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::mutate::assert_survives_mutations;
+/// # use serde_test::Token;
+/// #
+/// #[derive(Deserialize)]
+/// struct S {
+///     a: Vec<u8>,
+///     b: Option<char>,
+/// }
+///
+/// assert_survives_mutations::<S>(&[
+///     Token::Struct { name: "S", len: 2 },
+///     Token::Str("a"),
+///     Token::Seq { len: Some(1) },
+///     Token::U8(1),
+///     Token::SeqEnd,
+///     Token::Str("b"),
+///     Token::Some,
+///     Token::Char('x'),
+///     Token::StructEnd,
+/// ]);
+/// ```
+#[track_caller]
+pub fn assert_survives_mutations<'test, 'de: 'test, T>(tokens: impl IntoTokens<'test, 'de>)
+where
+    T: Deserialize<'de>,
+{
+    let tokens = tokens.into_tokens();
+    assert_valid(validate_tokens(&tokens));
+
+    let mut failures = Vec::new();
+    for mutant in mutants(&tokens) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut de = Deserializer::new(&mutant.tokens);
+            T::deserialize(&mut de).is_ok() && de.remaining() == 0
+        }));
+        match result {
+            Err(_) => failures.push(format!("{}: panicked", mutant.description)),
+            Ok(true) if mutant.mutation.is_malformed() => {
+                failures.push(format!("{}: deserialized successfully", mutant.description));
+            }
+            Ok(_) => {}
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} mutants of the token stream were mishandled:\n{}",
+            failures.len(),
+            failures.join("\n"),
+        );
+    }
+}
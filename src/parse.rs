@@ -0,0 +1,550 @@
+use crate::token::OwnedToken;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The reason text was rejected by [`parse_tokens`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    msg: String,
+}
+
+impl ParseError {
+    /// The 1-based line of the offending input.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The 1-based column, in chars, of the offending input.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// The description of the problem, without its location.
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.msg
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a token stream written the way tokens are printed by `Debug`.
+///
+/// The input is a comma-separated list of tokens, optionally wrapped in `[`
+/// `]`, with an optional `Token::` prefix on each token, a trailing comma,
+/// and `//` line comments. Payloads use Rust literal syntax: strings and
+/// chars accept the usual escapes, and bytes are written either as a list
+/// of integers, as `Debug` prints them, or as a `b"..."` literal. This makes
+/// it possible to keep fixtures in files shared between crates or produced
+/// by other tools.
+///
+/// ```
+/// # use serde_test::{parse_tokens, Token};
+/// #
+/// let tokens = parse_tokens(
+///     r#"
+///     Struct { name: "S", len: 2 },
+///     Str("a"), U8(0),
+///     // bytes as Debug prints them, or as a byte string
+///     Str("b"), Bytes([104, 105]),
+///     Token::StructEnd,
+///     "#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(tokens[0], Token::Struct { name: "S", len: 2 });
+/// assert_eq!(tokens[4], Token::Bytes(b"hi"));
+/// assert_eq!(tokens.len(), 6);
+///
+/// let err = parse_tokens("U8(256)").unwrap_err();
+/// assert_eq!(err.to_string(), "line 1, column 4: invalid U8 payload `256`");
+/// ```
+pub fn parse_tokens(input: &str) -> Result<Vec<OwnedToken>, ParseError> {
+    let mut parser = Parser { input, pos: 0 };
+    let bracketed = parser.eat('[');
+    let mut tokens = Vec::new();
+    loop {
+        parser.skip_ws();
+        if parser.peek().map_or(true, |c| c == ']') {
+            break;
+        }
+        tokens.push(parser.token()?);
+        if !parser.eat(',') {
+            break;
+        }
+    }
+    if bracketed {
+        parser.expect(']')?;
+    }
+    parser.skip_ws();
+    if parser.peek().is_some() {
+        return Err(parser.error("expected `,` or the end of the input"));
+    }
+    Ok(tokens)
+}
+
+/// Parses a single token in the syntax accepted by [`parse_tokens`].
+impl FromStr for OwnedToken {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let token = parser.token()?;
+        parser.skip_ws();
+        if parser.peek().is_some() {
+            return Err(parser.error("expected the end of the input"));
+        }
+        Ok(token)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: impl Display) -> ParseError {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at(&self, pos: usize, msg: impl Display) -> ParseError {
+        let before = &self.input[..pos];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            line,
+            column: before[line_start..].chars().count() + 1,
+            msg: msg.to_string(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format_args!("expected `{}`", c)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str, ParseError> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected an identifier"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Parses the text of a number or a word like `true` or `NaN`.
+    fn scalar<T>(&mut self, kind: &str) -> Result<T, ParseError>
+    where
+        T: FromStr,
+    {
+        self.skip_ws();
+        let start = self.pos;
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '+' | '-')))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        let text = &rest[..len];
+        text.replace('_', "")
+            .parse()
+            .map_err(|_| self.error_at(start, format_args!("invalid {} payload `{}`", kind, text)))
+    }
+
+    fn escape(&mut self, start: usize) -> Result<char, ParseError> {
+        let c = match self.bump() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '\'' | '"')) => c,
+            Some('x') => {
+                let hex = self.rest().get(..2).unwrap_or("");
+                self.pos += hex.len();
+                u8::from_str_radix(hex, 16)
+                    .map(char::from)
+                    .map_err(|_| self.error_at(start, "invalid `\\x` escape"))?
+            }
+            Some('u') => {
+                let rest = self.rest();
+                let digits = rest
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(digits, _)| digits)
+                    .ok_or_else(|| self.error_at(start, "invalid `\\u` escape"))?;
+                self.pos += digits.len() + 2;
+                u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error_at(start, "invalid `\\u` escape"))?
+            }
+            _ => return Err(self.error_at(start, "unknown escape")),
+        };
+        Ok(c)
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, ParseError> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.bump() != Some(quote) {
+            return Err(self.error_at(start, format_args!("expected `{}`", quote)));
+        }
+        let mut out = String::new();
+        loop {
+            let pos = self.pos;
+            match self.bump() {
+                Some(c) if c == quote => return Ok(out),
+                Some('\\') => out.push(self.escape(pos)?),
+                Some(c) => out.push(c),
+                None => return Err(self.error_at(start, "unterminated literal")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.quoted('"')
+    }
+
+    fn name(&mut self) -> Result<Cow<'static, str>, ParseError> {
+        self.string().map(Cow::Owned)
+    }
+
+    fn char(&mut self) -> Result<char, ParseError> {
+        let start = self.pos;
+        let s = self.quoted('\'')?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(self.error_at(start, "char literal must contain one char")),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, ParseError> {
+        self.skip_ws();
+        if self.rest().starts_with("b\"") {
+            self.pos += 1;
+            let start = self.pos;
+            let s = self.string()?;
+            return s
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)))
+                .collect::<Result<_, _>>()
+                .map_err(|_| self.error_at(start, "byte string must be ASCII or `\\x` escapes"));
+        }
+        self.expect('[')?;
+        let mut bytes = Vec::new();
+        while !self.eat(']') {
+            bytes.push(self.scalar("byte")?);
+            if !self.eat(',') {
+                self.expect(']')?;
+                break;
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn len(&mut self) -> Result<usize, ParseError> {
+        self.scalar("len")
+    }
+
+    fn optional_len(&mut self) -> Result<Option<usize>, ParseError> {
+        let start = self.pos;
+        match self.ident()? {
+            "None" => Ok(None),
+            "Some" => {
+                self.expect('(')?;
+                let len = self.len()?;
+                self.expect(')')?;
+                Ok(Some(len))
+            }
+            other => Err(self.error_at(
+                start,
+                format_args!("expected `Some` or `None`, found `{}`", other),
+            )),
+        }
+    }
+
+    /// Parses `( payload )`.
+    fn paren<T>(
+        &mut self,
+        payload: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.expect('(')?;
+        let value = payload(self)?;
+        self.expect(')')?;
+        Ok(value)
+    }
+
+    /// Parses `{ field: value, ... }`, handing each field name to `field`.
+    fn fields(
+        &mut self,
+        mut field: impl FnMut(&mut Self, &str) -> Result<bool, ParseError>,
+    ) -> Result<(), ParseError> {
+        self.expect('{')?;
+        while !self.eat('}') {
+            let start = self.pos;
+            let name = self.ident()?;
+            self.expect(':')?;
+            if !field(self, name)? {
+                return Err(self.error_at(start, format_args!("unknown field `{}`", name)));
+            }
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the fields of a token with some of `name`, `variant` and `len`.
+    fn named(&mut self, variant: bool, len: bool) -> Result<Named, ParseError> {
+        let start = self.pos;
+        let mut named = Named::default();
+        self.fields(|p, field| {
+            match field {
+                "name" => named.name = Some(p.name()?),
+                "variant" if variant => named.variant = Some(p.name()?),
+                "len" if len => named.len = Some(p.len()?),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        let missing = if named.name.is_none() {
+            Some("name")
+        } else if variant && named.variant.is_none() {
+            Some("variant")
+        } else if len && named.len.is_none() {
+            Some("len")
+        } else {
+            None
+        };
+        match missing {
+            Some(field) => Err(self.error_at(start, format_args!("missing field `{}`", field))),
+            None => Ok(named),
+        }
+    }
+
+    fn unnamed_len(&mut self) -> Result<Option<usize>, ParseError> {
+        let start = self.pos;
+        let mut len = None;
+        self.fields(|p, field| {
+            if field != "len" {
+                return Ok(false);
+            }
+            len = Some(p.optional_len()?);
+            Ok(true)
+        })?;
+        len.ok_or_else(|| self.error_at(start, "missing field `len`"))
+    }
+
+    fn token(&mut self) -> Result<OwnedToken, ParseError> {
+        self.skip_ws();
+        if self.rest().starts_with("Token::") {
+            self.pos += "Token::".len();
+        }
+        let start = self.pos;
+        let token = match self.ident()? {
+            "Bool" => OwnedToken::Bool(self.paren(|p| p.scalar("Bool"))?),
+            "I8" => OwnedToken::I8(self.paren(|p| p.scalar("I8"))?),
+            "I16" => OwnedToken::I16(self.paren(|p| p.scalar("I16"))?),
+            "I32" => OwnedToken::I32(self.paren(|p| p.scalar("I32"))?),
+            "I64" => OwnedToken::I64(self.paren(|p| p.scalar("I64"))?),
+            "I128" => OwnedToken::I128(self.paren(|p| p.scalar("I128"))?),
+            "U8" => OwnedToken::U8(self.paren(|p| p.scalar("U8"))?),
+            "U16" => OwnedToken::U16(self.paren(|p| p.scalar("U16"))?),
+            "U32" => OwnedToken::U32(self.paren(|p| p.scalar("U32"))?),
+            "U64" => OwnedToken::U64(self.paren(|p| p.scalar("U64"))?),
+            "U128" => OwnedToken::U128(self.paren(|p| p.scalar("U128"))?),
+            "F32" => OwnedToken::F32(self.paren(|p| p.scalar("F32"))?),
+            "F64" => OwnedToken::F64(self.paren(|p| p.scalar("F64"))?),
+            "F32Bits" => OwnedToken::F32Bits(self.paren(|p| p.scalar("F32Bits"))?),
+            "F64Bits" => OwnedToken::F64Bits(self.paren(|p| p.scalar("F64Bits"))?),
+            "Isize" => OwnedToken::Isize(self.paren(|p| p.scalar("Isize"))?),
+            "Usize" => OwnedToken::Usize(self.paren(|p| p.scalar("Usize"))?),
+            "Number" => OwnedToken::Number(self.paren(|p| p.scalar("Number"))?),
+            "Char" => OwnedToken::Char(self.paren(Self::char)?),
+            "Str" => OwnedToken::Str(self.paren(Self::string)?),
+            "BorrowedStr" => OwnedToken::BorrowedStr(self.paren(Self::string)?),
+            "String" => OwnedToken::String(self.paren(Self::string)?),
+            "CollectStr" => OwnedToken::CollectStr(self.paren(Self::string)?),
+            "Bytes" => OwnedToken::Bytes(self.paren(Self::bytes)?),
+            "BorrowedBytes" => OwnedToken::BorrowedBytes(self.paren(Self::bytes)?),
+            "ByteBuf" => OwnedToken::ByteBuf(self.paren(Self::bytes)?),
+            "None" => OwnedToken::None,
+            "Some" => OwnedToken::Some,
+            "Unit" => OwnedToken::Unit,
+            "UnitStruct" => OwnedToken::UnitStruct {
+                name: self.named(false, false)?.name(),
+            },
+            "UnitVariant" => {
+                let mut named = self.named(true, false)?;
+                OwnedToken::UnitVariant {
+                    name: named.name(),
+                    variant: named.variant(),
+                }
+            }
+            "NewtypeStruct" => OwnedToken::NewtypeStruct {
+                name: self.named(false, false)?.name(),
+            },
+            "NewtypeVariant" => {
+                let mut named = self.named(true, false)?;
+                OwnedToken::NewtypeVariant {
+                    name: named.name(),
+                    variant: named.variant(),
+                }
+            }
+            "Seq" => OwnedToken::Seq {
+                len: self.unnamed_len()?,
+            },
+            "SeqEnd" => OwnedToken::SeqEnd,
+            "Tuple" => {
+                let start = self.pos;
+                let mut len = None;
+                self.fields(|p, field| {
+                    if field != "len" {
+                        return Ok(false);
+                    }
+                    len = Some(p.len()?);
+                    Ok(true)
+                })?;
+                OwnedToken::Tuple {
+                    len: len.ok_or_else(|| self.error_at(start, "missing field `len`"))?,
+                }
+            }
+            "TupleEnd" => OwnedToken::TupleEnd,
+            "TupleStruct" => {
+                let mut named = self.named(false, true)?;
+                OwnedToken::TupleStruct {
+                    name: named.name(),
+                    len: named.len(),
+                }
+            }
+            "TupleStructEnd" => OwnedToken::TupleStructEnd,
+            "TupleVariant" => {
+                let mut named = self.named(true, true)?;
+                OwnedToken::TupleVariant {
+                    name: named.name(),
+                    variant: named.variant(),
+                    len: named.len(),
+                }
+            }
+            "TupleVariantEnd" => OwnedToken::TupleVariantEnd,
+            "Map" => OwnedToken::Map {
+                len: self.unnamed_len()?,
+            },
+            "MapEnd" => OwnedToken::MapEnd,
+            "MapEntry" => OwnedToken::MapEntry,
+            "Struct" => {
+                let mut named = self.named(false, true)?;
+                OwnedToken::Struct {
+                    name: named.name(),
+                    len: named.len(),
+                }
+            }
+            "StructEnd" => OwnedToken::StructEnd,
+            "StructVariant" => {
+                let mut named = self.named(true, true)?;
+                OwnedToken::StructVariant {
+                    name: named.name(),
+                    variant: named.variant(),
+                    len: named.len(),
+                }
+            }
+            "StructVariantEnd" => OwnedToken::StructVariantEnd,
+            "SkipStructField" => OwnedToken::SkipStructField {
+                name: self.named(false, false)?.name(),
+            },
+            "Enum" => OwnedToken::Enum {
+                name: self.named(false, false)?.name(),
+            },
+            "VariantIndex" => OwnedToken::VariantIndex(self.paren(|p| p.scalar("VariantIndex"))?),
+            "FieldIndex" => OwnedToken::FieldIndex(self.paren(|p| p.scalar("FieldIndex"))?),
+            "Identifier" => OwnedToken::Identifier(self.paren(Self::string)?.into()),
+            "IdentifierIndex" => {
+                OwnedToken::IdentifierIndex(self.paren(|p| p.scalar("IdentifierIndex"))?)
+            }
+            "Readable" => OwnedToken::Readable(self.paren(|p| p.scalar("Readable"))?),
+            other => return Err(self.error_at(start, format_args!("unknown token `{}`", other))),
+        };
+        Ok(token)
+    }
+}
+
+/// The fields of a named token. [`Parser::named`] guarantees that the
+/// requested ones are present.
+#[derive(Default)]
+struct Named {
+    name: Option<Cow<'static, str>>,
+    variant: Option<Cow<'static, str>>,
+    len: Option<usize>,
+}
+
+impl Named {
+    fn name(&mut self) -> Cow<'static, str> {
+        self.name.take().unwrap()
+    }
+
+    fn variant(&mut self) -> Cow<'static, str> {
+        self.variant.take().unwrap()
+    }
+
+    fn len(&self) -> usize {
+        self.len.unwrap()
+    }
+}
@@ -0,0 +1,322 @@
+//! A curated corpus of adversarial token streams.
+//!
+//! None of these streams describe a sensible value. They exist to check that
+//! a `Deserialize` impl rejects garbage with an error instead of panicking,
+//! overflowing, or trying to allocate according to an absurd length hint.
+//!
+//! [`assert_survives_nesting`] goes deeper than the corpus, wrapping a
+//! stream in as many levels of nesting as asked for.
+
+use crate::de::Deserializer;
+use crate::into_tokens::IntoTokens;
+use crate::token::Token;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::panic::{self, AssertUnwindSafe};
+
+/// How many levels deep the nested cases go.
+pub const NESTING_DEPTH: usize = 128;
+
+/// Length in bytes of the maximal-length string and bytes payloads.
+pub const PAYLOAD_LEN: usize = 1 << 16;
+
+static PAYLOAD: [u8; PAYLOAD_LEN] = [b'x'; PAYLOAD_LEN];
+
+/// One named stream of the corpus.
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub name: &'static str,
+    pub tokens: Vec<Token<'static, 'static>>,
+}
+
+impl Case {
+    fn new(name: &'static str, tokens: Vec<Token<'static, 'static>>) -> Self {
+        Case { name, tokens }
+    }
+}
+
+fn nested(
+    open: Token<'static, 'static>,
+    inner: Token<'static, 'static>,
+    close: Option<Token<'static, 'static>>,
+) -> Vec<Token<'static, 'static>> {
+    let mut tokens = vec![open; NESTING_DEPTH];
+    tokens.push(inner);
+    if let Some(close) = close {
+        tokens.extend(std::iter::repeat(close).take(NESTING_DEPTH));
+    }
+    tokens
+}
+
+/// Returns every stream in the corpus.
+pub fn corpus() -> Vec<Case> {
+    let long_str = std::str::from_utf8(&PAYLOAD).unwrap();
+    let long_bytes: &'static [u8] = &PAYLOAD;
+
+    vec![
+        // Empty payloads.
+        Case::new("empty stream", vec![]),
+        Case::new("empty str", vec![Token::Str("")]),
+        Case::new("empty borrowed str", vec![Token::BorrowedStr("")]),
+        Case::new("empty string", vec![Token::String("")]),
+        Case::new("empty bytes", vec![Token::Bytes(b"")]),
+        Case::new("empty borrowed bytes", vec![Token::BorrowedBytes(b"")]),
+        Case::new("empty byte buf", vec![Token::ByteBuf(b"")]),
+        Case::new("empty struct name", vec![Token::UnitStruct { name: "" }]),
+        Case::new(
+            "empty variant",
+            vec![Token::UnitVariant {
+                name: "",
+                variant: "",
+            }],
+        ),
+        // Maximal-length payloads.
+        Case::new("long str", vec![Token::Str(long_str)]),
+        Case::new("long borrowed str", vec![Token::BorrowedStr(long_str)]),
+        Case::new("long string", vec![Token::String(long_str)]),
+        Case::new("long bytes", vec![Token::Bytes(long_bytes)]),
+        Case::new(
+            "long borrowed bytes",
+            vec![Token::BorrowedBytes(long_bytes)],
+        ),
+        Case::new("long byte buf", vec![Token::ByteBuf(long_bytes)]),
+        // Extreme scalars.
+        Case::new("i128 min", vec![Token::I128(i128::MIN)]),
+        Case::new("u128 max", vec![Token::U128(u128::MAX)]),
+        Case::new("i64 min", vec![Token::I64(i64::MIN)]),
+        Case::new("u64 max", vec![Token::U64(u64::MAX)]),
+        Case::new("f64 nan", vec![Token::F64(f64::NAN)]),
+        Case::new("f64 infinity", vec![Token::F64(f64::INFINITY)]),
+        Case::new("f32 negative infinity", vec![Token::F32(f32::NEG_INFINITY)]),
+        Case::new("max char", vec![Token::Char(char::MAX)]),
+        // Absurd declared lens.
+        Case::new(
+            "seq with len usize::MAX",
+            vec![
+                Token::Seq {
+                    len: Some(usize::MAX),
+                },
+                Token::SeqEnd,
+            ],
+        ),
+        Case::new(
+            "map with len usize::MAX",
+            vec![
+                Token::Map {
+                    len: Some(usize::MAX),
+                },
+                Token::MapEnd,
+            ],
+        ),
+        Case::new(
+            "tuple with len usize::MAX",
+            vec![Token::Tuple { len: usize::MAX }, Token::TupleEnd],
+        ),
+        Case::new(
+            "struct with len usize::MAX",
+            vec![
+                Token::Struct {
+                    name: "",
+                    len: usize::MAX,
+                },
+                Token::StructEnd,
+            ],
+        ),
+        Case::new(
+            "seq with len 0 but elements",
+            vec![
+                Token::Seq { len: Some(0) },
+                Token::U8(0),
+                Token::U8(0),
+                Token::SeqEnd,
+            ],
+        ),
+        // Deep nesting.
+        Case::new(
+            "deeply nested seq",
+            nested(
+                Token::Seq { len: Some(1) },
+                Token::Unit,
+                Some(Token::SeqEnd),
+            ),
+        ),
+        Case::new(
+            "deeply nested option",
+            nested(Token::Some, Token::Unit, None),
+        ),
+        Case::new(
+            "deeply nested newtype",
+            nested(Token::NewtypeStruct { name: "" }, Token::Unit, None),
+        ),
+        Case::new(
+            "deeply nested unclosed seq",
+            nested(Token::Seq { len: None }, Token::Unit, None),
+        ),
+        // Unbalanced prefixes.
+        Case::new("lone seq", vec![Token::Seq { len: None }]),
+        Case::new("lone map", vec![Token::Map { len: None }]),
+        Case::new(
+            "map key without value",
+            vec![Token::Map { len: Some(1) }, Token::Str("")],
+        ),
+        Case::new("lone struct", vec![Token::Struct { name: "", len: 0 }]),
+        Case::new("lone some", vec![Token::Some]),
+        Case::new("lone enum", vec![Token::Enum { name: "" }]),
+        Case::new(
+            "enum without payload",
+            vec![Token::Enum { name: "" }, Token::Str("")],
+        ),
+        Case::new("lone seq end", vec![Token::SeqEnd]),
+        Case::new("lone map end", vec![Token::MapEnd]),
+        Case::new(
+            "mismatched end",
+            vec![Token::Seq { len: None }, Token::MapEnd],
+        ),
+    ]
+}
+
+/// Deserializes `T` from every stream in the [`corpus`], asserting that none
+/// of them cause a panic.
+///
+/// Errors are expected and ignored. If any stream panics, the names of all
+/// offending streams are reported together.
+///
+/// Types that query `is_human_readable` must be wrapped in
+/// [`Readable`](crate::Readable) or [`Compact`](crate::Compact).
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::assert_survives_pathological;
+/// #
+/// #[derive(Deserialize)]
+/// struct S {
+///     a: Vec<u8>,
+///     b: Option<String>,
+/// }
+///
+/// assert_survives_pathological::<S>();
+/// assert_survives_pathological::<Vec<Option<i64>>>();
+/// ```
+#[track_caller]
+pub fn assert_survives_pathological<T>()
+where
+    T: DeserializeOwned,
+{
+    let mut panicked = Vec::new();
+    for case in corpus() {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut de = Deserializer::new(&case.tokens);
+            let _ = T::deserialize(&mut de);
+        }));
+        if result.is_err() {
+            panicked.push(case.name);
+        }
+    }
+
+    if !panicked.is_empty() {
+        panic!(
+            "{} pathological token streams caused a panic: {}",
+            panicked.len(),
+            panicked.join(", "),
+        );
+    }
+}
+
+/// How [`nest`] wraps a token stream at each level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Nesting {
+    /// A [`Token::Seq`] of one element.
+    Seq,
+    /// A [`Token::Some`].
+    Some,
+    /// A [`Token::NewtypeStruct`] with this name.
+    NewtypeStruct { name: &'static str },
+    /// A [`Token::NewtypeVariant`] of this enum and variant.
+    NewtypeVariant {
+        name: &'static str,
+        variant: &'static str,
+    },
+}
+
+/// Wraps `tokens` in `depth` levels of `nesting`.
+///
+/// ```
+/// # use serde_test::pathological::{nest, Nesting};
+/// # use serde_test::Token;
+/// #
+/// assert_eq!(
+///     nest(&[Token::U8(1)], Nesting::Seq, 2),
+///     [
+///         Token::Seq { len: Some(1) },
+///         Token::Seq { len: Some(1) },
+///         Token::U8(1),
+///         Token::SeqEnd,
+///         Token::SeqEnd,
+///     ],
+/// );
+/// ```
+pub fn nest<'test, 'de>(
+    tokens: &[Token<'test, 'de>],
+    nesting: Nesting,
+    depth: usize,
+) -> Vec<Token<'test, 'de>> {
+    let (open, close) = match nesting {
+        Nesting::Seq => (Token::Seq { len: Some(1) }, Some(Token::SeqEnd)),
+        Nesting::Some => (Token::Some, None),
+        Nesting::NewtypeStruct { name } => (Token::NewtypeStruct { name }, None),
+        Nesting::NewtypeVariant { name, variant } => {
+            (Token::NewtypeVariant { name, variant }, None)
+        }
+    };
+    let mut nested = vec![open; depth];
+    nested.extend_from_slice(tokens);
+    if let Some(close) = close {
+        nested.extend(std::iter::repeat(close).take(depth));
+    }
+    nested
+}
+
+/// Deserializes `T` from `tokens` wrapped in `depth` levels of `nesting`,
+/// asserting that it either succeeds or fails with an error, but does not
+/// panic.
+///
+/// The deserializer rejects values nested deeper than [`NESTING_DEPTH`]
+/// with a [`LimitExceeded`](crate::ErrorKind::LimitExceeded) error, so a
+/// recursive type fails gracefully at that depth instead of overflowing the
+/// stack however large `depth` is.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::pathological::{assert_survives_nesting, Nesting};
+/// # use serde_test::Token;
+/// #
+/// #[derive(Deserialize)]
+/// enum List {
+///     Nil,
+///     Cons(Box<List>),
+/// }
+///
+/// let nil = [Token::UnitVariant { name: "List", variant: "Nil" }];
+/// let cons = Nesting::NewtypeVariant { name: "List", variant: "Cons" };
+/// assert_survives_nesting::<List>(&nil, cons, 100_000);
+/// ```
+#[track_caller]
+pub fn assert_survives_nesting<'test, 'de: 'test, T>(
+    tokens: impl IntoTokens<'test, 'de>,
+    nesting: Nesting,
+    depth: usize,
+) where
+    T: Deserialize<'de>,
+{
+    let tokens = nest(&tokens.into_tokens(), nesting, depth);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut de = Deserializer::new(&tokens).max_depth(NESTING_DEPTH);
+        let _ = T::deserialize(&mut de);
+    }));
+    if result.is_err() {
+        panic!(
+            "deserializing {} levels of {:?} nesting caused a panic",
+            depth, nesting,
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Patterns for matching error messages loosely.
+//!
+//! Used with [`assert_ser_tokens_error_matches`] and
+//! [`assert_de_tokens_error_matches`] when the exact wording of an error is
+//! not under the test's control, such as messages from dependencies that
+//! change between versions.
+//!
+//! [`assert_ser_tokens_error_matches`]: crate::assert_ser_tokens_error_matches
+//! [`assert_de_tokens_error_matches`]: crate::assert_de_tokens_error_matches
+
+/// Decides whether an error message is the expected one.
+///
+/// Implemented for closures taking the message, and by the patterns in this
+/// module.
+pub trait ErrorPattern {
+    fn matches(&self, msg: &str) -> bool;
+
+    /// Describes the pattern in failure messages.
+    fn describe(&self) -> String {
+        "the predicate".to_owned()
+    }
+}
+
+impl<F> ErrorPattern for F
+where
+    F: Fn(&str) -> bool,
+{
+    fn matches(&self, msg: &str) -> bool {
+        self(msg)
+    }
+}
+
+/// Matches messages containing a substring. See [`contains`].
+#[derive(Copy, Clone, Debug)]
+pub struct Contains<'a>(&'a str);
+
+/// Matches messages that contain `needle`.
+pub fn contains(needle: &str) -> Contains<'_> {
+    Contains(needle)
+}
+
+impl ErrorPattern for Contains<'_> {
+    fn matches(&self, msg: &str) -> bool {
+        msg.contains(self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("a message containing {:?}", self.0)
+    }
+}
+
+/// Matches messages with a prefix. See [`starts_with`].
+#[derive(Copy, Clone, Debug)]
+pub struct StartsWith<'a>(&'a str);
+
+/// Matches messages that start with `prefix`.
+pub fn starts_with(prefix: &str) -> StartsWith<'_> {
+    StartsWith(prefix)
+}
+
+impl ErrorPattern for StartsWith<'_> {
+    fn matches(&self, msg: &str) -> bool {
+        msg.starts_with(self.0)
+    }
+
+    fn describe(&self) -> String {
+        format!("a message starting with {:?}", self.0)
+    }
+}
+
+/// Matches messages against a regular expression. See [`regex`](mod@regex).
+///
+/// Requires the `regex` feature.
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+pub struct Regex(regex::Regex);
+
+/// Matches messages in which the regular expression `pattern` finds a match.
+/// Anchor it with `^` and `$` to match the whole message.
+///
+/// Panics if `pattern` is not a valid regular expression.
+///
+/// Requires the `regex` feature.
+///
+/// ```
+/// # use serde_test::pattern::regex;
+/// # use serde_test::{assert_de_tokens_error_matches, Token};
+/// #
+/// assert_de_tokens_error_matches::<u8, _>(
+///     &[Token::I32(-1)],
+///     regex(r"^invalid value: integer `-\d+`"),
+/// );
+/// ```
+#[cfg(feature = "regex")]
+#[track_caller]
+pub fn regex(pattern: &str) -> Regex {
+    match regex::Regex::new(pattern) {
+        Ok(regex) => Regex(regex),
+        Err(err) => panic!("invalid regex: {}", err),
+    }
+}
+
+#[cfg(feature = "regex")]
+impl ErrorPattern for Regex {
+    fn matches(&self, msg: &str) -> bool {
+        self.0.is_match(msg)
+    }
+
+    fn describe(&self) -> String {
+        format!("a message matching /{}/", self.0)
+    }
+}
+
+/// Panics unless `msg` matches `pattern`.
+#[track_caller]
+pub(crate) fn assert_matches<P>(msg: &str, pattern: &P)
+where
+    P: ?Sized + ErrorPattern,
+{
+    if !pattern.matches(msg) {
+        panic!(
+            "error does not match\n     found: {:?}\n  expected: {}",
+            msg,
+            pattern.describe(),
+        );
+    }
+}
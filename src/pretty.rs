@@ -0,0 +1,173 @@
+//! Indented rendering of token streams, for panic messages and debugging.
+//!
+//! [`Pretty`] lays a stream out one token per line, indenting the contents of
+//! every compound, and can number the lines, point at one token, and leave
+//! out the tokens far away from it.
+//!
+//! ```
+//! # use serde_test::pretty::Pretty;
+//! # use serde_test::Token;
+//! #
+//! let tokens = [
+//!     Token::Struct { name: "S", len: 2 },
+//!     Token::Str("a"),
+//!     Token::Seq { len: Some(1) },
+//!     Token::U8(1),
+//!     Token::SeqEnd,
+//!     Token::Str("b"),
+//!     Token::Bool(true),
+//!     Token::StructEnd,
+//! ];
+//!
+//! assert_eq!(
+//!     Pretty::new(&tokens).numbered().mark(3).context(1).to_string(),
+//!     "  ...
+//!   2 |     Seq { len: Some(1) },
+//! > 3 |         U8(1),
+//!   4 |     SeqEnd,
+//!   ...
+//! ",
+//! );
+//! ```
+
+use crate::display;
+use crate::group;
+use crate::token::{EndToken, Token};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+
+/// The environment variable setting how many tokens on either side of a
+/// failure the panic messages show.
+const CONTEXT_VAR: &str = "SERDE_TEST_CONTEXT";
+
+/// How many tokens on either side of a failure are shown if [`CONTEXT_VAR`]
+/// is not set to a number.
+const DEFAULT_CONTEXT: usize = 3;
+
+/// How many tokens on either side of a failure the panic messages show.
+pub(crate) fn failure_context() -> usize {
+    env::var(CONTEXT_VAR)
+        .ok()
+        .and_then(|lines| lines.trim().parse().ok())
+        .unwrap_or(DEFAULT_CONTEXT)
+}
+
+/// Renders a token stream with indentation reflecting compound nesting.
+///
+/// Without options this is the `{}` form of
+/// [`DisplayTokens`](crate::DisplayTokens).
+#[derive(Copy, Clone)]
+pub struct Pretty<'a, 'test, 'de> {
+    tokens: &'a [Token<'test, 'de>],
+    numbered: bool,
+    mark: Option<usize>,
+    context: Option<usize>,
+    rust: bool,
+}
+
+impl<'a, 'test, 'de> Pretty<'a, 'test, 'de> {
+    pub const fn new(tokens: &'a [Token<'test, 'de>]) -> Self {
+        Pretty {
+            tokens,
+            numbered: false,
+            mark: None,
+            context: None,
+            rust: false,
+        }
+    }
+
+    /// Prefixes every line with the index of its token.
+    pub const fn numbered(mut self) -> Self {
+        self.numbered = true;
+        self
+    }
+
+    /// Points at the token at `index`. An index one past the last token
+    /// points at the end of the stream.
+    pub const fn mark(mut self, index: usize) -> Self {
+        self.mark = Some(index);
+        self
+    }
+
+    /// Only shows the `lines` tokens on either side of the marked one.
+    ///
+    /// Has no effect unless a token is [`mark`](Pretty::mark)ed.
+    pub const fn context(mut self, lines: usize) -> Self {
+        self.context = Some(lines);
+        self
+    }
+
+    /// Writes each token as the Rust expression that constructs it,
+    /// `Token::` prefix included, ready to paste into a token array.
+    ///
+    /// ```
+    /// # use serde_test::pretty::Pretty;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Some, Token::Bytes(b"\x01\xff")];
+    /// assert_eq!(
+    ///     Pretty::new(&tokens).rust().to_string(),
+    ///     "Token::Some,\nToken::Bytes(&[1, 255]),\n",
+    /// );
+    /// ```
+    pub const fn rust(mut self) -> Self {
+        self.rust = true;
+        self
+    }
+
+    fn visible(&self, index: usize) -> bool {
+        match (self.mark, self.context) {
+            (Some(mark), Some(lines)) => {
+                index.saturating_add(lines) >= mark && index <= mark.saturating_add(lines)
+            }
+            _ => true,
+        }
+    }
+
+    fn gutter(&self, index: usize, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.mark.is_some() {
+            f.write_str(if self.mark == Some(index) { "> " } else { "  " })?;
+        }
+        if self.numbered {
+            let width = self.tokens.len().to_string().len();
+            write!(f, "{:>width$} | ", index, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Pretty<'_, '_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut depth = 0usize;
+        let mut elided = false;
+        for (index, token) in self.tokens.iter().enumerate() {
+            if EndToken::from_token(*token).is_some() {
+                depth = depth.saturating_sub(1);
+            }
+            if self.visible(index) {
+                self.gutter(index, f)?;
+                for _ in 0..depth {
+                    f.write_str("    ")?;
+                }
+                if self.rust {
+                    display::write_rust(*token, f)?;
+                } else {
+                    write!(f, "{:?}", token)?;
+                }
+                f.write_str(",\n")?;
+                elided = false;
+            } else if !elided {
+                f.write_str("  ...\n")?;
+                elided = true;
+            }
+            if group::compound_end(*token).is_some() {
+                depth += 1;
+            }
+        }
+        if self.mark == Some(self.tokens.len()) {
+            self.gutter(self.tokens.len(), f)?;
+            f.write_str("end of tokens\n")?;
+        }
+        Ok(())
+    }
+}
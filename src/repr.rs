@@ -0,0 +1,422 @@
+//! Token streams for each way serde can represent an enum.
+//!
+//! The tokens of a variant depend on the enum's representation, chosen with
+//! `#[serde(tag = "...")]`, `#[serde(tag = "...", content = "...")]` or
+//! `#[serde(untagged)]`. [`Repr`] writes them out from the variant's
+//! contents, the same way the derived `Serialize` impl does.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! # use serde_test::repr::Repr;
+//! # use serde_test::{assert_tokens, Token};
+//! #
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! #[serde(tag = "type")]
+//! enum E {
+//!     A,
+//!     B { b: u8 },
+//! }
+//!
+//! let repr = Repr::Internal { tag: "type" };
+//! assert_tokens(&E::A, &repr.unit("E", "A"));
+//! assert_tokens(&E::B { b: 1 }, &repr.struct_("E", "B", [("b", [Token::U8(1)])]));
+//! ```
+
+use crate::assert::assert_valid;
+use crate::into_tokens::IntoTokens;
+use crate::token::OwnedToken;
+use crate::validate::validate_tokens;
+
+/// An enum representation.
+///
+/// Variant contents are given as token streams: the value of a newtype
+/// variant, each element of a tuple variant, and the name and value of each
+/// field of a struct variant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Repr {
+    /// The default representation, `{ "V": ... }`.
+    External,
+    /// `#[serde(tag = "...")]`, with the variant name stored under `tag`
+    /// alongside the variant's fields, `{ "tag": "V", ... }`.
+    ///
+    /// Tuple variants cannot be represented this way, and newtype variants
+    /// only if they hold a struct, a map, a unit or another enum.
+    Internal { tag: &'static str },
+    /// `#[serde(tag = "...", content = "...")]`, with the variant name and
+    /// contents stored side by side, `{ "tag": "V", "content": ... }`.
+    Adjacent {
+        tag: &'static str,
+        content: &'static str,
+    },
+    /// `#[serde(untagged)]`, with only the variant's contents.
+    Untagged,
+}
+
+impl Repr {
+    /// The tokens of a unit variant.
+    ///
+    /// ```
+    /// # use serde_test::repr::Repr;
+    /// # use serde_test::Token;
+    /// #
+    /// assert_eq!(
+    ///     Repr::Adjacent { tag: "t", content: "c" }.unit("E", "A"),
+    ///     [
+    ///         Token::Struct { name: "E", len: 1 },
+    ///         Token::Str("t"),
+    ///         Token::UnitVariant { name: "E", variant: "A" },
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    pub fn unit(self, name: &'static str, variant: &'static str) -> Vec<OwnedToken> {
+        match self {
+            Repr::External => vec![OwnedToken::UnitVariant {
+                name: name.into(),
+                variant: variant.into(),
+            }],
+            Repr::Internal { tag } => vec![
+                OwnedToken::Struct {
+                    name: name.into(),
+                    len: 1,
+                },
+                key(tag),
+                key(variant),
+                OwnedToken::StructEnd,
+            ],
+            Repr::Adjacent { tag, .. } => vec![
+                OwnedToken::Struct {
+                    name: name.into(),
+                    len: 1,
+                },
+                key(tag),
+                OwnedToken::UnitVariant {
+                    name: name.into(),
+                    variant: variant.into(),
+                },
+                OwnedToken::StructEnd,
+            ],
+            Repr::Untagged => vec![OwnedToken::Unit],
+        }
+    }
+
+    /// The tokens of a newtype variant holding `value`.
+    ///
+    /// Panics if `value` is not exactly one value, or for [`Repr::Internal`]
+    /// if it is a value that cannot hold the tag.
+    ///
+    /// ```
+    /// # use serde_test::repr::Repr;
+    /// # use serde_test::Token;
+    /// #
+    /// let inner = [
+    ///     Token::Struct { name: "Inner", len: 1 },
+    ///     Token::Str("x"),
+    ///     Token::U8(0),
+    ///     Token::StructEnd,
+    /// ];
+    /// assert_eq!(
+    ///     Repr::Internal { tag: "t" }.newtype("E", "A", inner),
+    ///     [
+    ///         Token::Struct { name: "Inner", len: 2 },
+    ///         Token::Str("t"),
+    ///         Token::Str("A"),
+    ///         Token::Str("x"),
+    ///         Token::U8(0),
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    #[track_caller]
+    pub fn newtype<'test, 'de: 'test>(
+        self,
+        name: &'static str,
+        variant: &'static str,
+        value: impl IntoTokens<'test, 'de>,
+    ) -> Vec<OwnedToken> {
+        let value = value_tokens(value);
+        match self {
+            Repr::External => {
+                let mut tokens = vec![OwnedToken::NewtypeVariant {
+                    name: name.into(),
+                    variant: variant.into(),
+                }];
+                tokens.extend(value);
+                tokens
+            }
+            Repr::Internal { tag } => tag_newtype(tag, variant, value),
+            Repr::Adjacent { tag, content } => {
+                let mut tokens = vec![
+                    OwnedToken::Struct {
+                        name: name.into(),
+                        len: 2,
+                    },
+                    key(tag),
+                    OwnedToken::UnitVariant {
+                        name: name.into(),
+                        variant: variant.into(),
+                    },
+                    key(content),
+                ];
+                tokens.extend(value);
+                tokens.push(OwnedToken::StructEnd);
+                tokens
+            }
+            Repr::Untagged => value,
+        }
+    }
+
+    /// The tokens of a tuple variant with the given `elements`.
+    ///
+    /// Panics if an element is not exactly one value, or for
+    /// [`Repr::Internal`], which cannot represent tuple variants.
+    ///
+    /// ```
+    /// # use serde_test::repr::Repr;
+    /// # use serde_test::Token;
+    /// #
+    /// assert_eq!(
+    ///     Repr::Untagged.tuple("E", "A", [vec![Token::U8(0)], vec![Token::Some, Token::U8(1)]]),
+    ///     [
+    ///         Token::Tuple { len: 2 },
+    ///         Token::U8(0),
+    ///         Token::Some,
+    ///         Token::U8(1),
+    ///         Token::TupleEnd,
+    ///     ],
+    /// );
+    /// ```
+    #[track_caller]
+    pub fn tuple<'test, 'de: 'test, I>(
+        self,
+        name: &'static str,
+        variant: &'static str,
+        elements: I,
+    ) -> Vec<OwnedToken>
+    where
+        I: IntoIterator,
+        I::Item: IntoTokens<'test, 'de>,
+    {
+        let elements: Vec<Vec<OwnedToken>> = elements.into_iter().map(value_tokens).collect();
+        let len = elements.len();
+        let tuple = || {
+            let mut tokens = vec![OwnedToken::Tuple { len }];
+            tokens.extend(elements.iter().flatten().cloned());
+            tokens.push(OwnedToken::TupleEnd);
+            tokens
+        };
+        match self {
+            Repr::External => {
+                let mut tokens = vec![OwnedToken::TupleVariant {
+                    name: name.into(),
+                    variant: variant.into(),
+                    len,
+                }];
+                tokens.extend(elements.iter().flatten().cloned());
+                tokens.push(OwnedToken::TupleVariantEnd);
+                tokens
+            }
+            Repr::Internal { .. } => {
+                panic!("internally tagged enums cannot represent tuple variants")
+            }
+            Repr::Adjacent { tag, content } => {
+                let mut tokens = vec![
+                    OwnedToken::Struct {
+                        name: name.into(),
+                        len: 2,
+                    },
+                    key(tag),
+                    OwnedToken::UnitVariant {
+                        name: name.into(),
+                        variant: variant.into(),
+                    },
+                    key(content),
+                ];
+                tokens.extend(tuple());
+                tokens.push(OwnedToken::StructEnd);
+                tokens
+            }
+            Repr::Untagged => tuple(),
+        }
+    }
+
+    /// The tokens of a struct variant with the given `fields`, as pairs of
+    /// field name and value.
+    ///
+    /// Panics if a field value is not exactly one value.
+    ///
+    /// ```
+    /// # use serde_test::repr::Repr;
+    /// # use serde_test::Token;
+    /// #
+    /// assert_eq!(
+    ///     Repr::External.struct_("E", "A", [("a", [Token::Bool(true)])]),
+    ///     [
+    ///         Token::StructVariant { name: "E", variant: "A", len: 1 },
+    ///         Token::Str("a"),
+    ///         Token::Bool(true),
+    ///         Token::StructVariantEnd,
+    ///     ],
+    /// );
+    /// ```
+    #[track_caller]
+    pub fn struct_<'test, 'de: 'test, I, V>(
+        self,
+        name: &'static str,
+        variant: &'static str,
+        fields: I,
+    ) -> Vec<OwnedToken>
+    where
+        I: IntoIterator<Item = (&'static str, V)>,
+        V: IntoTokens<'test, 'de>,
+    {
+        let mut len = 0;
+        let mut body = Vec::new();
+        for (field, value) in fields {
+            len += 1;
+            body.push(key(field));
+            body.extend(value_tokens(value));
+        }
+
+        let (header, end) = match self {
+            Repr::External => (
+                vec![OwnedToken::StructVariant {
+                    name: name.into(),
+                    variant: variant.into(),
+                    len,
+                }],
+                OwnedToken::StructVariantEnd,
+            ),
+            Repr::Internal { tag } => (
+                vec![
+                    OwnedToken::Struct {
+                        name: name.into(),
+                        len: len + 1,
+                    },
+                    key(tag),
+                    key(variant),
+                ],
+                OwnedToken::StructEnd,
+            ),
+            Repr::Adjacent { tag, content } => {
+                let mut tokens = vec![
+                    OwnedToken::Struct {
+                        name: name.into(),
+                        len: 2,
+                    },
+                    key(tag),
+                    OwnedToken::UnitVariant {
+                        name: name.into(),
+                        variant: variant.into(),
+                    },
+                    key(content),
+                    OwnedToken::Struct {
+                        name: variant.into(),
+                        len,
+                    },
+                ];
+                tokens.extend(body);
+                tokens.push(OwnedToken::StructEnd);
+                tokens.push(OwnedToken::StructEnd);
+                return tokens;
+            }
+            Repr::Untagged => (
+                vec![OwnedToken::Struct {
+                    name: name.into(),
+                    len,
+                }],
+                OwnedToken::StructEnd,
+            ),
+        };
+        let mut tokens = header;
+        tokens.extend(body);
+        tokens.push(end);
+        tokens
+    }
+}
+
+fn key(key: &str) -> OwnedToken {
+    OwnedToken::Str(key.to_owned())
+}
+
+/// Collects `value`, which must be exactly one value.
+#[track_caller]
+fn value_tokens<'test, 'de: 'test>(value: impl IntoTokens<'test, 'de>) -> Vec<OwnedToken> {
+    let value = value.into_tokens();
+    assert_valid(validate_tokens(&value));
+    value.into_iter().map(OwnedToken::from).collect()
+}
+
+/// Adds the tag to the value of an internally tagged newtype variant, the
+/// way serde's `TaggedSerializer` does.
+#[track_caller]
+fn tag_newtype(tag: &str, variant: &str, mut value: Vec<OwnedToken>) -> Vec<OwnedToken> {
+    let entry = [key(tag), key(variant)];
+    // A map holding the tag entry and one more entry, whose value is `inner`.
+    let map_with = |inner_key: &str, inner: Vec<OwnedToken>| {
+        let mut tokens = vec![OwnedToken::Map { len: Some(2) }];
+        tokens.extend(entry.iter().cloned());
+        tokens.push(key(inner_key));
+        tokens.extend(inner);
+        tokens.push(OwnedToken::MapEnd);
+        tokens
+    };
+
+    match value[0].clone() {
+        OwnedToken::Struct { name, len } => {
+            value[0] = OwnedToken::Struct { name, len: len + 1 };
+            value.splice(1..1, entry);
+            value
+        }
+        OwnedToken::Map { len } => {
+            value[0] = OwnedToken::Map {
+                len: len.map(|len| len + 1),
+            };
+            value.splice(1..1, entry);
+            value
+        }
+        OwnedToken::Unit | OwnedToken::UnitStruct { .. } => {
+            let mut tokens = vec![OwnedToken::Map { len: Some(1) }];
+            tokens.extend(entry);
+            tokens.push(OwnedToken::MapEnd);
+            tokens
+        }
+        OwnedToken::NewtypeStruct { .. } => {
+            value.remove(0);
+            tag_newtype(tag, variant, value)
+        }
+        OwnedToken::UnitVariant { variant: inner, .. } => map_with(&inner, vec![OwnedToken::Unit]),
+        OwnedToken::NewtypeVariant { variant: inner, .. } => {
+            value.remove(0);
+            map_with(&inner, value)
+        }
+        OwnedToken::TupleVariant {
+            variant: inner,
+            len,
+            ..
+        } => {
+            value[0] = OwnedToken::TupleStruct {
+                name: inner.clone(),
+                len,
+            };
+            *value.last_mut().unwrap() = OwnedToken::TupleStructEnd;
+            map_with(&inner, value)
+        }
+        OwnedToken::StructVariant {
+            variant: inner,
+            len,
+            ..
+        } => {
+            value[0] = OwnedToken::Struct {
+                name: inner.clone(),
+                len,
+            };
+            *value.last_mut().unwrap() = OwnedToken::StructEnd;
+            map_with(&inner, value)
+        }
+        token => panic!(
+            "internally tagged newtype variants cannot hold Token::{}",
+            token,
+        ),
+    }
+}
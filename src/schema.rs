@@ -0,0 +1,650 @@
+//! The expected shape of a type's token stream.
+//!
+//! A [`Schema`] records what the `Serialize` impl of a type is expected to
+//! emit apart from its leaf values: container and variant names, field names
+//! in order, and the lengths that follow from them. Types describe
+//! themselves through [`TokenSchema`], after which
+//! [`assert_matches_schema`](crate::assert_matches_schema) only needs the
+//! leaf values.
+
+use crate::token::Token;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// The shape of a type's token stream, without its leaf values.
+///
+/// Leaves are the scalar tokens: booleans, numbers, chars, strings and
+/// bytes. Everything around them, down to the length of every tuple and
+/// struct, is checked against the schema.
+///
+/// ```
+/// # use serde_test::schema::{Schema, TokenSchema};
+/// #
+/// enum Shape {
+///     Circle { radius: f64 },
+///     Polygon(Vec<(f64, f64)>),
+///     Empty,
+/// }
+///
+/// impl TokenSchema for Shape {
+///     fn schema() -> Schema {
+///         Schema::enumeration("Shape")
+///             .struct_variant("Circle", vec![("radius", f64::schema())])
+///             .newtype_variant("Polygon", Vec::<(f64, f64)>::schema())
+///             .unit_variant("Empty")
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Schema {
+    shape: Shape,
+}
+
+#[derive(Clone, Debug)]
+enum Shape {
+    Leaf,
+    Unit,
+    Option(Box<Schema>),
+    UnitStruct(&'static str),
+    NewtypeStruct(&'static str, Box<Schema>),
+    Seq(Box<Schema>),
+    Tuple(Vec<Schema>),
+    TupleStruct(&'static str, Vec<Schema>),
+    Map(Box<Schema>, Box<Schema>),
+    Struct(&'static str, Vec<(&'static str, Schema)>),
+    Enum(&'static str, Vec<(&'static str, Variant)>),
+}
+
+#[derive(Clone, Debug)]
+enum Variant {
+    Unit,
+    Newtype(Schema),
+    Tuple(Vec<Schema>),
+    Struct(Vec<(&'static str, Schema)>),
+}
+
+impl Schema {
+    fn new(shape: Shape) -> Self {
+        Schema { shape }
+    }
+
+    /// A single scalar token, like [`Token::U8`] or [`Token::Str`].
+    pub fn leaf() -> Self {
+        Schema::new(Shape::Leaf)
+    }
+
+    /// [`Token::Unit`].
+    pub fn unit() -> Self {
+        Schema::new(Shape::Unit)
+    }
+
+    /// [`Token::None`], or [`Token::Some`] followed by `some`.
+    pub fn option(some: Schema) -> Self {
+        Schema::new(Shape::Option(Box::new(some)))
+    }
+
+    /// [`Token::UnitStruct`] named `name`.
+    pub fn unit_struct(name: &'static str) -> Self {
+        Schema::new(Shape::UnitStruct(name))
+    }
+
+    /// [`Token::NewtypeStruct`] named `name`, followed by `inner`.
+    pub fn newtype_struct(name: &'static str, inner: Schema) -> Self {
+        Schema::new(Shape::NewtypeStruct(name, Box::new(inner)))
+    }
+
+    /// A sequence of any number of `element`s.
+    pub fn seq(element: Schema) -> Self {
+        Schema::new(Shape::Seq(Box::new(element)))
+    }
+
+    /// A tuple of exactly `elements`.
+    pub fn tuple(elements: Vec<Schema>) -> Self {
+        Schema::new(Shape::Tuple(elements))
+    }
+
+    /// A tuple struct named `name` with exactly `fields`.
+    pub fn tuple_struct(name: &'static str, fields: Vec<Schema>) -> Self {
+        Schema::new(Shape::TupleStruct(name, fields))
+    }
+
+    /// A map of any number of `key` to `value` entries.
+    pub fn map(key: Schema, value: Schema) -> Self {
+        Schema::new(Shape::Map(Box::new(key), Box::new(value)))
+    }
+
+    /// A struct named `name` with no fields yet; add them with
+    /// [`field`](Self::field) in declaration order.
+    pub fn structure(name: &'static str) -> Self {
+        Schema::new(Shape::Struct(name, Vec::new()))
+    }
+
+    /// An enum named `name` with no variants yet; add them with the
+    /// `*_variant` methods.
+    pub fn enumeration(name: &'static str) -> Self {
+        Schema::new(Shape::Enum(name, Vec::new()))
+    }
+
+    /// Adds a field to a [`structure`](Self::structure).
+    ///
+    /// Fields may be skipped when serializing, but those that are
+    /// serialized must come in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// If this schema is not a struct.
+    #[track_caller]
+    pub fn field(mut self, name: &'static str, schema: Schema) -> Self {
+        match &mut self.shape {
+            Shape::Struct(_, fields) => fields.push((name, schema)),
+            _ => panic!("Schema::field called on a schema that is not a struct"),
+        }
+        self
+    }
+
+    /// Adds a unit variant to an [`enumeration`](Self::enumeration).
+    #[track_caller]
+    pub fn unit_variant(self, name: &'static str) -> Self {
+        self.variant(name, Variant::Unit)
+    }
+
+    /// Adds a newtype variant to an [`enumeration`](Self::enumeration).
+    #[track_caller]
+    pub fn newtype_variant(self, name: &'static str, inner: Schema) -> Self {
+        self.variant(name, Variant::Newtype(inner))
+    }
+
+    /// Adds a tuple variant to an [`enumeration`](Self::enumeration).
+    #[track_caller]
+    pub fn tuple_variant(self, name: &'static str, fields: Vec<Schema>) -> Self {
+        self.variant(name, Variant::Tuple(fields))
+    }
+
+    /// Adds a struct variant to an [`enumeration`](Self::enumeration), with
+    /// its fields in declaration order.
+    #[track_caller]
+    pub fn struct_variant(self, name: &'static str, fields: Vec<(&'static str, Schema)>) -> Self {
+        self.variant(name, Variant::Struct(fields))
+    }
+
+    #[track_caller]
+    fn variant(mut self, name: &'static str, variant: Variant) -> Self {
+        match &mut self.shape {
+            Shape::Enum(_, variants) => variants.push((name, variant)),
+            _ => panic!(
+                "Schema::{}_variant called on a schema that is not an enum",
+                {
+                    match variant {
+                        Variant::Unit => "unit",
+                        Variant::Newtype(_) => "newtype",
+                        Variant::Tuple(_) => "tuple",
+                        Variant::Struct(_) => "struct",
+                    }
+                }
+            ),
+        }
+        self
+    }
+}
+
+/// Types that can describe the shape of their token stream.
+///
+/// Implemented for the primitives, strings, options, tuples and the std
+/// collections, following their `Serialize` impls. Implement it for your own
+/// types with the [`Schema`] builders, naming things as the `Serialize` impl
+/// does (so after any `#[serde(rename)]`).
+pub trait TokenSchema {
+    fn schema() -> Schema;
+}
+
+macro_rules! leaf_schema {
+    ($($ty:ty)*) => {
+        $(
+            impl TokenSchema for $ty {
+                fn schema() -> Schema {
+                    Schema::leaf()
+                }
+            }
+        )*
+    };
+}
+
+leaf_schema! {
+    bool i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 char str String
+}
+
+impl TokenSchema for () {
+    fn schema() -> Schema {
+        Schema::unit()
+    }
+}
+
+impl<T: ?Sized + TokenSchema> TokenSchema for &T {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: ?Sized + TokenSchema> TokenSchema for Box<T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: TokenSchema> TokenSchema for Option<T> {
+    fn schema() -> Schema {
+        Schema::option(T::schema())
+    }
+}
+
+impl<T: TokenSchema> TokenSchema for [T] {
+    fn schema() -> Schema {
+        Schema::seq(T::schema())
+    }
+}
+
+impl<T: TokenSchema> TokenSchema for Vec<T> {
+    fn schema() -> Schema {
+        Schema::seq(T::schema())
+    }
+}
+
+impl<T: TokenSchema> TokenSchema for BTreeSet<T> {
+    fn schema() -> Schema {
+        Schema::seq(T::schema())
+    }
+}
+
+impl<T: TokenSchema, H> TokenSchema for HashSet<T, H> {
+    fn schema() -> Schema {
+        Schema::seq(T::schema())
+    }
+}
+
+impl<K: TokenSchema, V: TokenSchema> TokenSchema for BTreeMap<K, V> {
+    fn schema() -> Schema {
+        Schema::map(K::schema(), V::schema())
+    }
+}
+
+impl<K: TokenSchema, V: TokenSchema, H> TokenSchema for HashMap<K, V, H> {
+    fn schema() -> Schema {
+        Schema::map(K::schema(), V::schema())
+    }
+}
+
+macro_rules! tuple_schema {
+    ($($name:ident)+) => {
+        impl<$($name: TokenSchema),+> TokenSchema for ($($name,)+) {
+            fn schema() -> Schema {
+                Schema::tuple(vec![$($name::schema()),+])
+            }
+        }
+    };
+}
+
+tuple_schema!(A);
+tuple_schema!(A B);
+tuple_schema!(A B C);
+tuple_schema!(A B C D);
+tuple_schema!(A B C D E);
+tuple_schema!(A B C D E F);
+tuple_schema!(A B C D E F G);
+tuple_schema!(A B C D E F G H);
+
+/// Walks `tokens` against `schema`, comparing their leaves with `leaves` in
+/// order. Returns the index of the first mismatch and what was wrong there.
+pub(crate) fn check(
+    schema: &Schema,
+    tokens: &[Token<'_, '_>],
+    leaves: &[Token<'_, '_>],
+) -> Result<(), (usize, String)> {
+    let mut walk = Walk {
+        tokens,
+        leaves,
+        index: 0,
+        leaf: 0,
+    };
+    walk.value(schema)?;
+    if walk.index < tokens.len() {
+        return Err((
+            walk.index,
+            format!(
+                "expected the value to end but found Token::{:?} at index {}",
+                tokens[walk.index], walk.index,
+            ),
+        ));
+    }
+    if walk.leaf < leaves.len() {
+        return Err((
+            walk.index,
+            format!(
+                "only {} of the {} leaves were serialized, the next is Token::{:?}",
+                walk.leaf,
+                leaves.len(),
+                leaves[walk.leaf],
+            ),
+        ));
+    }
+    Ok(())
+}
+
+struct Walk<'a, 'test, 'de> {
+    tokens: &'a [Token<'test, 'de>],
+    leaves: &'a [Token<'test, 'de>],
+    index: usize,
+    leaf: usize,
+}
+
+impl<'test, 'de> Walk<'_, 'test, 'de> {
+    fn next(&mut self, expected: &str) -> Result<Token<'test, 'de>, (usize, String)> {
+        match self.tokens.get(self.index) {
+            Some(&token) => {
+                self.index += 1;
+                Ok(token)
+            }
+            None => Err((
+                self.index,
+                format!("expected {} but the tokens ended", expected),
+            )),
+        }
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T, (usize, String)> {
+        let index = self.index - 1;
+        Err((
+            index,
+            format!(
+                "expected {} but found Token::{:?} at index {}",
+                expected, self.tokens[index], index,
+            ),
+        ))
+    }
+
+    fn value(&mut self, schema: &Schema) -> Result<(), (usize, String)> {
+        match &schema.shape {
+            Shape::Leaf => {
+                let token = self.next("a leaf")?;
+                if !is_leaf(token) {
+                    return self.unexpected("a leaf");
+                }
+                self.leaf(token)
+            }
+            Shape::Unit => match self.next("Token::Unit")? {
+                Token::Unit => Ok(()),
+                _ => self.unexpected("Token::Unit"),
+            },
+            Shape::Option(some) => match self.next("Token::None or Token::Some")? {
+                Token::None => Ok(()),
+                Token::Some => self.value(some),
+                _ => self.unexpected("Token::None or Token::Some"),
+            },
+            Shape::UnitStruct(name) => {
+                let expected = format!("Token::UnitStruct {{ name: {:?} }}", name);
+                match self.next(&expected)? {
+                    Token::UnitStruct { name: found } if found == *name => Ok(()),
+                    _ => self.unexpected(&expected),
+                }
+            }
+            Shape::NewtypeStruct(name, inner) => {
+                let expected = format!("Token::NewtypeStruct {{ name: {:?} }}", name);
+                match self.next(&expected)? {
+                    Token::NewtypeStruct { name: found } if found == *name => self.value(inner),
+                    _ => self.unexpected(&expected),
+                }
+            }
+            Shape::Seq(element) => {
+                let len = match self.next("Token::Seq")? {
+                    Token::Seq { len } => len,
+                    _ => return self.unexpected("Token::Seq"),
+                };
+                let mut count = 0;
+                while !self.end(Token::SeqEnd)? {
+                    self.value(element)?;
+                    count += 1;
+                }
+                self.check_len(len, count)
+            }
+            Shape::Tuple(elements) => {
+                let expected = format!("Token::Tuple {{ len: {} }}", elements.len());
+                match self.next(&expected)? {
+                    Token::Tuple { len } if len == elements.len() => {}
+                    _ => return self.unexpected(&expected),
+                }
+                self.elements(elements, Token::TupleEnd)
+            }
+            Shape::TupleStruct(name, fields) => {
+                let expected = format!(
+                    "Token::TupleStruct {{ name: {:?}, len: {} }}",
+                    name,
+                    fields.len(),
+                );
+                match self.next(&expected)? {
+                    Token::TupleStruct { name: found, len }
+                        if found == *name && len == fields.len() => {}
+                    _ => return self.unexpected(&expected),
+                }
+                self.elements(fields, Token::TupleStructEnd)
+            }
+            Shape::Map(key, value) => {
+                let len = match self.next("Token::Map")? {
+                    Token::Map { len } => len,
+                    _ => return self.unexpected("Token::Map"),
+                };
+                let mut count = 0;
+                while !self.end(Token::MapEnd)? {
+                    self.value(key)?;
+                    self.value(value)?;
+                    count += 1;
+                }
+                self.check_len(len, count)
+            }
+            Shape::Struct(name, fields) => {
+                let expected = format!("Token::Struct {{ name: {:?}, .. }}", name);
+                let len = match self.next(&expected)? {
+                    Token::Struct { name: found, len } if found == *name => len,
+                    _ => return self.unexpected(&expected),
+                };
+                self.fields(fields, len, Token::StructEnd)
+            }
+            Shape::Enum(name, variants) => self.variant(name, variants),
+        }
+    }
+
+    fn variant(
+        &mut self,
+        name: &str,
+        variants: &[(&'static str, Variant)],
+    ) -> Result<(), (usize, String)> {
+        let expected = format!("a variant of {:?}", name);
+        let token = self.next(&expected)?;
+        let (found, variant) = match token {
+            Token::UnitVariant {
+                name: found,
+                variant,
+            }
+            | Token::NewtypeVariant {
+                name: found,
+                variant,
+            }
+            | Token::TupleVariant {
+                name: found,
+                variant,
+                ..
+            }
+            | Token::StructVariant {
+                name: found,
+                variant,
+                ..
+            } => (found, variant),
+            _ => return self.unexpected(&expected),
+        };
+        if found != name {
+            return self.unexpected(&expected);
+        }
+        let schema = match variants.iter().find(|(known, _)| *known == variant) {
+            Some((_, schema)) => schema,
+            None => {
+                let known: Vec<_> = variants.iter().map(|(known, _)| *known).collect();
+                return self.unexpected(&format!("one of the variants {:?}", known));
+            }
+        };
+        match (schema, token) {
+            (Variant::Unit, Token::UnitVariant { .. }) => Ok(()),
+            (Variant::Newtype(inner), Token::NewtypeVariant { .. }) => self.value(inner),
+            (Variant::Tuple(fields), Token::TupleVariant { len, .. }) if len == fields.len() => {
+                self.elements(fields, Token::TupleVariantEnd)
+            }
+            (Variant::Struct(fields), Token::StructVariant { len, .. }) => {
+                self.fields(fields, len, Token::StructVariantEnd)
+            }
+            _ => self.unexpected(&match schema {
+                Variant::Unit => format!("Token::UnitVariant for {:?}", variant),
+                Variant::Newtype(_) => format!("Token::NewtypeVariant for {:?}", variant),
+                Variant::Tuple(fields) => format!(
+                    "Token::TupleVariant with len {} for {:?}",
+                    fields.len(),
+                    variant,
+                ),
+                Variant::Struct(_) => format!("Token::StructVariant for {:?}", variant),
+            }),
+        }
+    }
+
+    fn elements(&mut self, elements: &[Schema], end: Token<'_, '_>) -> Result<(), (usize, String)> {
+        for element in elements {
+            self.value(element)?;
+        }
+        let expected = format!("Token::{:?}", end);
+        if self.next(&expected)? != end {
+            return self.unexpected(&expected);
+        }
+        Ok(())
+    }
+
+    fn fields(
+        &mut self,
+        fields: &[(&'static str, Schema)],
+        len: usize,
+        end: Token<'_, '_>,
+    ) -> Result<(), (usize, String)> {
+        let mut rest = fields;
+        let mut count = 0;
+        while !self.end(end)? {
+            let (key, skipped) = match self.next("a field name")? {
+                Token::Str(key) => (key, false),
+                Token::SkipStructField { name } => (name, true),
+                _ => return self.unexpected("a field name"),
+            };
+            let position = match rest.iter().position(|(name, _)| *name == key) {
+                Some(position) => position,
+                None => {
+                    let names: Vec<_> = rest.iter().map(|(name, _)| *name).collect();
+                    return self.unexpected(&format!("one of the fields {:?}", names));
+                }
+            };
+            let schema = &rest[position].1;
+            rest = &rest[position + 1..];
+            if !skipped {
+                self.value(schema)?;
+                count += 1;
+            }
+        }
+        if len != count {
+            let index = self.index - 1;
+            return Err((
+                index,
+                format!(
+                    "struct was serialized with len {} but has {} fields, ending at index {}",
+                    len, count, index,
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Consumes the next token if it is `end`, returning whether it was.
+    fn end(&mut self, end: Token<'_, '_>) -> Result<bool, (usize, String)> {
+        match self.tokens.get(self.index) {
+            Some(&token) if token == end => {
+                self.index += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err((
+                self.index,
+                format!("expected Token::{:?} but the tokens ended", end),
+            )),
+        }
+    }
+
+    fn check_len(&self, len: Option<usize>, count: usize) -> Result<(), (usize, String)> {
+        match len {
+            Some(len) if len != count => {
+                let index = self.index - 1;
+                Err((
+                    index,
+                    format!(
+                        "compound was serialized with len {} but has {}, ending at index {}",
+                        len, count, index,
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn leaf(&mut self, token: Token<'_, '_>) -> Result<(), (usize, String)> {
+        let index = self.index - 1;
+        match self.leaves.get(self.leaf) {
+            Some(&leaf) if leaf == token => {
+                self.leaf += 1;
+                Ok(())
+            }
+            Some(&leaf) => Err((
+                index,
+                format!(
+                    "expected leaf {} to be Token::{:?} but found Token::{:?} at index {}",
+                    self.leaf, leaf, token, index,
+                ),
+            )),
+            None => Err((
+                index,
+                format!(
+                    "found Token::{:?} at index {} after all {} leaves",
+                    token,
+                    index,
+                    self.leaves.len(),
+                ),
+            )),
+        }
+    }
+}
+
+fn is_leaf(token: Token<'_, '_>) -> bool {
+    matches!(
+        token,
+        Token::Bool(_)
+            | Token::I8(_)
+            | Token::I16(_)
+            | Token::I32(_)
+            | Token::I64(_)
+            | Token::I128(_)
+            | Token::Isize(_)
+            | Token::U8(_)
+            | Token::U16(_)
+            | Token::U32(_)
+            | Token::U64(_)
+            | Token::U128(_)
+            | Token::Usize(_)
+            | Token::Number(_)
+            | Token::F32(_)
+            | Token::F64(_)
+            | Token::Char(_)
+            | Token::Str(_)
+            | Token::BorrowedStr(_)
+            | Token::String(_)
+            | Token::Bytes(_)
+            | Token::BorrowedBytes(_)
+            | Token::ByteBuf(_)
+    )
+}
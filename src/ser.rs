@@ -1,81 +1,1090 @@
-use crate::error::Error;
-use crate::token::{EndToken, Token};
+use crate::configure;
+use crate::error::{Error, ErrorKind};
+use crate::pathological::NESTING_DEPTH;
+use crate::token::{EndToken, OwnedToken, Token};
+use crate::trace;
 use crate::TestResult;
 use serde::ser::{self, Serialize};
+use std::cell::Cell;
+use std::fmt::{self, Debug, Display};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::slice;
+use std::thread;
 
 /// A `Serializer` that ensures that a value serializes to a given list of
 /// tokens.
-#[derive(Debug)]
 pub struct Serializer<'test> {
     tokens: &'test [Token<'test, 'test>],
+    len: usize,
+    float_cmp: FloatCmp,
+    /// Whether strings and bytes only match `Str` and `Bytes`.
+    strict_strings: bool,
+    /// Whether bytes are expected as a sequence of `u8`.
+    bytes_as_seq: bool,
+    /// Whether chars are expected as a one-character string.
+    char_as_str: bool,
+    limits: Limits,
+    /// The number of compounds and wrappers currently open.
+    depth: usize,
+    observer: Option<Observer<'test>>,
+    /// Whether the last value serialized satisfied the [`Token::Matches`]
+    /// predicate expected for it.
+    matched: bool,
+    /// The answer to `is_human_readable`, which panics if there is none.
+    human_readable: Option<bool>,
+    /// The answer set by the last [`Token::Readable`] marker, which takes
+    /// precedence over `human_readable`.
+    marked_readable: Option<bool>,
+    readable_queries: Cell<usize>,
+    /// The first compound dropped without its `end` being called.
+    unended: Rc<Cell<Option<Unended>>>,
+    /// The tokens serialized so far, if they are being recorded.
+    emitted: Option<Vec<OwnedToken>>,
+    /// Whether a mismatch is kept instead of failing serialization.
+    past_mismatch: bool,
+    /// The first mismatch, if serialization continued past it.
+    mismatch: Option<Box<Error>>,
+    /// How many more tokens may be serialized past the first mismatch.
+    past_budget: usize,
+}
+
+/// The callback registered with [`Serializer::observe`].
+type Observer<'test> = Box<dyn FnMut(&Event<'_>) + 'test>;
+
+impl Debug for Serializer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Serializer")
+            .field("tokens", &self.tokens)
+            .field("len", &self.len)
+            .field("float_cmp", &self.float_cmp)
+            .field("strict_strings", &self.strict_strings)
+            .field("bytes_as_seq", &self.bytes_as_seq)
+            .field("char_as_str", &self.char_as_str)
+            .field("limits", &self.limits)
+            .field("depth", &self.depth)
+            .field("human_readable", &self.human_readable)
+            .field("marked_readable", &self.marked_readable)
+            .field("unended", &self.unended.get())
+            .field("observer", &self.observer.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// How many times the number of expected tokens a value may serialize past
+/// a mismatch, when serialization continues to record the rest of it.
+const PAST_MISMATCH_TOKENS: usize = 4;
+
+/// Limits that stop a runaway `Serialize` impl before it hangs the test or
+/// overflows the stack.
+#[derive(Copy, Clone, Debug, Default)]
+struct Limits {
+    tokens: Option<usize>,
+    depth: Option<usize>,
+}
+
+/// How a [`Serializer`] compares serialized floats against [`Token::F32`]
+/// and [`Token::F64`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FloatCmp {
+    /// IEEE equality, the default: NaN matches nothing, and `-0.0` matches
+    /// `0.0`.
+    Exact,
+    /// IEEE equality, except that any NaN matches any NaN.
+    NanEqual,
+    /// Matches values at most this far apart, and any NaN with any NaN.
+    Epsilon(f64),
+}
+
+impl FloatCmp {
+    fn matches(self, expected: f64, actual: f64) -> bool {
+        match self {
+            FloatCmp::Exact => expected == actual,
+            FloatCmp::NanEqual => expected == actual || expected.is_nan() && actual.is_nan(),
+            FloatCmp::Epsilon(epsilon) => {
+                (expected - actual).abs() <= epsilon || expected.is_nan() && actual.is_nan()
+            }
+        }
+    }
+}
+
+/// Serializes `value` against `tokens` the way the asserts do: once,
+/// recording what it serialized as and going on past the first mismatch,
+/// within limits, so that a failure can be shown against the whole value.
+/// Returns the serializer along with the first mismatch or error.
+pub(crate) fn serialize_recorded<'test, T>(
+    value: &T,
+    tokens: &'test [Token<'test, 'test>],
+    readable: Option<bool>,
+) -> (Serializer<'test>, TestResult)
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::new(tokens).record_emitted().past_mismatch();
+    if let Some(readable) = readable {
+        ser = ser.human_readable(readable);
+    }
+    let result = value.serialize(&mut ser);
+    let result = ser.take_mismatch().and(result);
+    (ser, result)
+}
+
+/// Whether `token` is a marker that [`serialize_recorded`] only records up
+/// to the first mismatch, and which is left out when comparing what was
+/// recorded with the expected tokens.
+pub(crate) fn is_marker(token: &Token<'_, '_>) -> bool {
+    matches!(
+        token,
+        Token::SkipStructField { .. } | Token::VariantIndex(_) | Token::Readable(_) | Token::MapEntry
+    )
+}
+
+/// Whether a value that serialized as `found` matches `expected` the way a
+/// [`Serializer`] with the default options accepts it: a [`Token::OneOf`]
+/// matches any of its alternatives, a [`Token::Matches`] whatever passes its
+/// predicate, a [`Token::Number`] an integer of any width with its value,
+/// floats by IEEE equality, and the owned and borrowed forms of a string or
+/// bytes the serialized [`Token::Str`] or [`Token::Bytes`].
+///
+/// For the asserts that compare tokens captured from a value rather than
+/// serializing it against the tokens.
+pub(crate) fn token_matches(expected: Token<'_, '_>, found: Token<'_, '_>) -> bool {
+    Matching {
+        float_cmp: FloatCmp::Exact,
+        strict_strings: false,
+        matched: None,
+    }
+    .matches(expected, found)
+}
+
+/// How a [`Serializer`] compares the token a value serialized as with the
+/// expected one.
+#[derive(Copy, Clone)]
+struct Matching {
+    float_cmp: FloatCmp,
+    strict_strings: bool,
+    /// Whether the `serialize_*` call itself satisfied the predicate of an
+    /// expected [`Token::Matches`], if it was tested against the call.
+    matched: Option<bool>,
+}
+
+impl Matching {
+    fn matches(self, expected: Token<'_, '_>, found: Token<'_, '_>) -> bool {
+        match (expected, found) {
+            (Token::OneOf(alternatives), _) => alternatives
+                .iter()
+                .any(|&alternative| self.matches(alternative, found)),
+            (Token::Matches(predicate), _) => match self.matched {
+                Some(matched) => matched,
+                None => Event::from_token(found).map_or(false, |event| predicate.test(&event)),
+            },
+            (Token::Number(expected), _) => integer(found) == Some(expected),
+            (Token::F32(expected), Token::F32(v)) => self.float_cmp.matches(expected.into(), v.into()),
+            (Token::F64(expected), Token::F64(v)) => self.float_cmp.matches(expected, v),
+            (Token::F32Bits(bits), Token::F32(v)) => bits == v.to_bits(),
+            (Token::F64Bits(bits), Token::F64(v)) => bits == v.to_bits(),
+            (Token::Isize(expected), Token::I64(v)) => expected as i64 == v,
+            (Token::Usize(expected), Token::U64(v)) => expected as u64 == v,
+            (Token::BorrowedStr(expected) | Token::String(expected), Token::Str(v))
+                if !self.strict_strings =>
+            {
+                expected == v
+            }
+            (Token::BorrowedBytes(expected) | Token::ByteBuf(expected), Token::Bytes(v))
+                if !self.strict_strings =>
+            {
+                expected == v
+            }
+            // A `Str` accepts a string however it was written.
+            (Token::Str(expected), Token::CollectStr(v) | Token::Identifier(v)) => expected == v,
+            _ => expected == found,
+        }
+    }
+}
+
+/// The value of an integer token of any width.
+fn integer(token: Token<'_, '_>) -> Option<i128> {
+    match token {
+        Token::I8(v) => Some(v.into()),
+        Token::I16(v) => Some(v.into()),
+        Token::I32(v) => Some(v.into()),
+        Token::I64(v) => Some(v.into()),
+        Token::I128(v) | Token::Number(v) => Some(v),
+        Token::Isize(v) => Some(v as i128),
+        Token::U8(v) => Some(v.into()),
+        Token::U16(v) => Some(v.into()),
+        Token::U32(v) => Some(v.into()),
+        Token::U64(v) => Some(v.into()),
+        Token::U128(v) => i128::try_from(v).ok(),
+        Token::Usize(v) => Some(v as i128),
+        _ => None,
+    }
+}
+
+/// A call made on a [`Serializer`] or one of its compounds, as passed to
+/// [`Serializer::observe`].
+///
+/// Each variant carries the arguments of the method it stands for, which is
+/// named by [`method`](Event::method).
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(&'a str),
+    Bytes(&'a [u8]),
+    None,
+    Some,
+    Unit,
+    UnitStruct {
+        name: &'static str,
+    },
+    UnitVariant {
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    },
+    NewtypeStruct {
+        name: &'static str,
+    },
+    NewtypeVariant {
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    },
+    Seq {
+        len: Option<usize>,
+    },
+    Tuple {
+        len: usize,
+    },
+    TupleStruct {
+        name: &'static str,
+        len: usize,
+    },
+    TupleVariant {
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    },
+    Map {
+        len: Option<usize>,
+    },
+    Struct {
+        name: &'static str,
+        len: usize,
+    },
+    StructVariant {
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    },
+    /// `serialize_element` on a sequence or tuple.
+    Element,
+    /// `serialize_field` on a tuple struct or variant, which has no `key`, or
+    /// on a struct or struct variant.
+    Field {
+        key: Option<&'static str>,
+    },
+    /// `skip_field` on a struct or struct variant.
+    SkipField {
+        key: &'static str,
+    },
+    /// `serialize_key` on a map.
+    Key,
+    /// `serialize_value` on a map.
+    Value,
+    /// `serialize_entry` on a map, followed by the events of the key and of
+    /// the value.
+    Entry,
+    /// `end` on any compound.
+    End,
+}
+
+impl Event<'_> {
+    /// Returns the name of the method that was called, such as
+    /// `"serialize_u8"` or `"end"`.
+    pub fn method(&self) -> &'static str {
+        match self {
+            Event::Bool(_) => "serialize_bool",
+            Event::I8(_) => "serialize_i8",
+            Event::I16(_) => "serialize_i16",
+            Event::I32(_) => "serialize_i32",
+            Event::I64(_) => "serialize_i64",
+            Event::I128(_) => "serialize_i128",
+            Event::U8(_) => "serialize_u8",
+            Event::U16(_) => "serialize_u16",
+            Event::U32(_) => "serialize_u32",
+            Event::U64(_) => "serialize_u64",
+            Event::U128(_) => "serialize_u128",
+            Event::F32(_) => "serialize_f32",
+            Event::F64(_) => "serialize_f64",
+            Event::Char(_) => "serialize_char",
+            Event::Str(_) => "serialize_str",
+            Event::Bytes(_) => "serialize_bytes",
+            Event::None => "serialize_none",
+            Event::Some => "serialize_some",
+            Event::Unit => "serialize_unit",
+            Event::UnitStruct { .. } => "serialize_unit_struct",
+            Event::UnitVariant { .. } => "serialize_unit_variant",
+            Event::NewtypeStruct { .. } => "serialize_newtype_struct",
+            Event::NewtypeVariant { .. } => "serialize_newtype_variant",
+            Event::Seq { .. } => "serialize_seq",
+            Event::Tuple { .. } => "serialize_tuple",
+            Event::TupleStruct { .. } => "serialize_tuple_struct",
+            Event::TupleVariant { .. } => "serialize_tuple_variant",
+            Event::Map { .. } => "serialize_map",
+            Event::Struct { .. } => "serialize_struct",
+            Event::StructVariant { .. } => "serialize_struct_variant",
+            Event::Element => "serialize_element",
+            Event::Field { .. } => "serialize_field",
+            Event::SkipField { .. } => "skip_field",
+            Event::Key => "serialize_key",
+            Event::Value => "serialize_value",
+            Event::Entry => "serialize_entry",
+            Event::End => "end",
+        }
+    }
+}
+
+impl<'a> Event<'a> {
+    /// The event of the `serialize_*` call that a value token stands for.
+    /// `None` for tokens that are not a whole value, and for unit structs,
+    /// unit variants and [`Token::Number`], whose tokens do not say
+    /// everything the call did.
+    fn from_token(token: Token<'a, 'a>) -> Option<Self> {
+        Some(match token {
+            Token::Bool(v) => Event::Bool(v),
+            Token::I8(v) => Event::I8(v),
+            Token::I16(v) => Event::I16(v),
+            Token::I32(v) => Event::I32(v),
+            Token::I64(v) => Event::I64(v),
+            Token::I128(v) => Event::I128(v),
+            Token::Isize(v) => Event::I64(v as i64),
+            Token::U8(v) => Event::U8(v),
+            Token::U16(v) => Event::U16(v),
+            Token::U32(v) => Event::U32(v),
+            Token::U64(v) => Event::U64(v),
+            Token::U128(v) => Event::U128(v),
+            Token::Usize(v) => Event::U64(v as u64),
+            Token::F32(v) => Event::F32(v),
+            Token::F64(v) => Event::F64(v),
+            Token::F32Bits(bits) => Event::F32(f32::from_bits(bits)),
+            Token::F64Bits(bits) => Event::F64(f64::from_bits(bits)),
+            Token::Char(v) => Event::Char(v),
+            Token::Str(v)
+            | Token::BorrowedStr(v)
+            | Token::String(v)
+            | Token::CollectStr(v)
+            | Token::Identifier(v) => Event::Str(v),
+            Token::Bytes(v) | Token::BorrowedBytes(v) | Token::ByteBuf(v) => Event::Bytes(v),
+            Token::None => Event::None,
+            Token::Unit => Event::Unit,
+            _ => return None,
+        })
+    }
+}
+
+impl Event<'_> {
+    /// Whether the event is the whole of a value, which a [`Predicate`] may
+    /// test, rather than the start of a compound or a part of one.
+    fn is_value(&self) -> bool {
+        matches!(
+            self,
+            Event::Bool(_)
+                | Event::I8(_)
+                | Event::I16(_)
+                | Event::I32(_)
+                | Event::I64(_)
+                | Event::I128(_)
+                | Event::U8(_)
+                | Event::U16(_)
+                | Event::U32(_)
+                | Event::U64(_)
+                | Event::U128(_)
+                | Event::F32(_)
+                | Event::F64(_)
+                | Event::Char(_)
+                | Event::Str(_)
+                | Event::Bytes(_)
+                | Event::None
+                | Event::Unit
+                | Event::UnitStruct { .. }
+                | Event::UnitVariant { .. }
+        )
+    }
+}
+
+/// A test of a single serialized value, matched by [`Token::Matches`].
+///
+/// The predicate is given the [`Event`] of the `serialize_*` call, which
+/// must be for a value without contents, such as a string, an integer or a
+/// unit variant.
+///
+/// ```
+/// # use serde_test::ser::{Event, Predicate};
+/// # use serde_test::{assert_ser_tokens, Token};
+/// #
+/// static HEX: Predicate = Predicate::new("hex", |event| {
+///     matches!(event, Event::Str(s) if s.chars().all(|c| c.is_ascii_hexdigit()))
+/// });
+///
+/// assert_ser_tokens(&"c0ffee", &[Token::Matches(&HEX)]);
+/// ```
+#[derive(Copy, Clone)]
+pub struct Predicate {
+    name: &'static str,
+    test: fn(&Event<'_>) -> bool,
+}
+
+impl Predicate {
+    /// Creates a predicate. Its `name` stands for it in messages, and two
+    /// predicates compare equal if their names do.
+    pub const fn new(name: &'static str, test: fn(&Event<'_>) -> bool) -> Self {
+        Predicate { name, test }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether `event` satisfies the predicate.
+    pub fn test(&self, event: &Event<'_>) -> bool {
+        (self.test)(event)
+    }
+}
+
+impl Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
 }
 
 impl<'test> Serializer<'test> {
     /// Creates the serializer.
     pub fn new(tokens: &'test [Token<'test, 'test>]) -> Self {
-        Serializer { tokens }
+        let mut ser = Serializer {
+            tokens,
+            len: tokens.len(),
+            float_cmp: FloatCmp::Exact,
+            strict_strings: false,
+            bytes_as_seq: false,
+            char_as_str: false,
+            limits: Limits::default(),
+            depth: 0,
+            observer: None,
+            matched: false,
+            human_readable: None,
+            marked_readable: None,
+            readable_queries: Cell::new(0),
+            unended: Rc::new(Cell::new(None)),
+            emitted: None,
+            past_mismatch: false,
+            mismatch: None,
+            past_budget: 0,
+        };
+        ser.take_readable_markers();
+        ser
+    }
+
+    /// Sets how serialized floats are compared against float tokens.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::{FloatCmp, Serializer};
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Tuple { len: 2 }, Token::F64(f64::NAN), Token::F32(0.3), Token::TupleEnd];
+    /// let mut ser = Serializer::new(&tokens).float_cmp(FloatCmp::Epsilon(1e-6));
+    /// (f64::NAN, 0.1f32 + 0.2).serialize(&mut ser).unwrap();
+    /// ```
+    pub fn float_cmp(mut self, cmp: FloatCmp) -> Self {
+        self.float_cmp = cmp;
+        self
+    }
+
+    /// Only matches `serialize_str` against [`Token::Str`] and
+    /// `serialize_bytes` against [`Token::Bytes`], the tokens for what those
+    /// methods are given, instead of also accepting the borrowed and owned
+    /// forms that exist for the deserializer's sake.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::BorrowedStr("a")];
+    /// let mut ser = Serializer::new(&tokens).strict_strings(true);
+    /// let err = "a".serialize(&mut ser).unwrap_err();
+    /// assert_eq!(
+    ///     err,
+    ///     r#"expected Token::BorrowedStr("a") at index 0 of 1 but serialized as Str("a")"#,
+    /// );
+    /// ```
+    pub fn strict_strings(mut self, strict: bool) -> Self {
+        self.strict_strings = strict;
+        self
+    }
+
+    /// Expects `serialize_bytes` as a [`Token::Seq`] of [`Token::U8`], the
+    /// way formats without a bytes type write them.
+    ///
+    /// ```
+    /// # use serde::Serializer as _;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::U8(b'a'),
+    ///     Token::U8(b'b'),
+    ///     Token::SeqEnd,
+    /// ];
+    /// let mut ser = Serializer::new(&tokens).bytes_as_seq(true);
+    /// (&mut ser).serialize_bytes(b"ab").unwrap();
+    /// assert_eq!(ser.remaining(), 0);
+    /// ```
+    pub fn bytes_as_seq(mut self, as_seq: bool) -> Self {
+        self.bytes_as_seq = as_seq;
+        self
+    }
+
+    /// Expects `serialize_char` as a one-character string, the way formats
+    /// like JSON write a `char`.
+    ///
+    /// The string matches [`Token::Str`], [`Token::BorrowedStr`] or
+    /// [`Token::String`] as for `serialize_str`.
+    ///
+    /// ```
+    /// # use serde::Serializer as _;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Str("x")];
+    /// let mut ser = Serializer::new(&tokens).char_as_str(true);
+    /// (&mut ser).serialize_char('x').unwrap();
+    /// assert_eq!(ser.remaining(), 0);
+    /// ```
+    pub fn char_as_str(mut self, as_str: bool) -> Self {
+        self.char_as_str = as_str;
+        self
+    }
+
+    /// Fails the serialization once more than `max` tokens have been
+    /// serialized, regardless of how many tokens are expected.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::{ErrorKind, Token};
+    /// #
+    /// let tokens = [Token::Tuple { len: 2 }, Token::U8(1), Token::U8(2), Token::TupleEnd];
+    /// let mut ser = Serializer::new(&tokens).max_tokens(2);
+    /// let err = (1u8, 2u8).serialize(&mut ser).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+    /// assert_eq!(err, "U8(2) at index 2 exceeds the limit of 2 tokens");
+    /// ```
+    pub fn max_tokens(mut self, max: usize) -> Self {
+        self.limits.tokens = Some(max);
+        self
+    }
+
+    /// Fails the serialization once compounds are nested more than `max`
+    /// levels deep.
+    ///
+    /// `Some` and newtype structs and variants count as a level too, since a
+    /// recursive type usually nests through them.
+    ///
+    /// ```
+    /// # use serde::ser::{Serialize, Serializer};
+    /// # use serde_test::ser;
+    /// # use serde_test::{ErrorKind, Token};
+    /// #
+    /// /// A buggy impl that wraps itself forever.
+    /// struct Recursive;
+    ///
+    /// impl Serialize for Recursive {
+    ///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    ///     where
+    ///         S: Serializer,
+    ///     {
+    ///         serializer.serialize_some(self)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [Token::Some; 8];
+    /// let mut ser = ser::Serializer::new(&tokens).max_depth(4);
+    /// let err = Recursive.serialize(&mut ser).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+    /// assert_eq!(err, "value at index 4 exceeds the nesting limit of 4");
+    /// ```
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.limits.depth = Some(max);
+        self
+    }
+
+    /// Calls `observer` with every [`Event`] as it happens, before the
+    /// serializer checks it against the tokens.
+    ///
+    /// The keys that the serializer writes on behalf of `serialize_field` are
+    /// not reported as `serialize_str` calls.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::U8(1),
+    ///     Token::U8(2),
+    ///     Token::SeqEnd,
+    /// ];
+    /// let mut methods = Vec::new();
+    /// let mut ser = Serializer::new(&tokens).observe(|event| methods.push(event.method()));
+    /// vec![1u8, 2].serialize(&mut ser).unwrap();
+    /// drop(ser);
+    ///
+    /// assert_eq!(
+    ///     methods,
+    ///     [
+    ///         "serialize_seq",
+    ///         "serialize_element",
+    ///         "serialize_u8",
+    ///         "serialize_element",
+    ///         "serialize_u8",
+    ///         "end",
+    ///     ],
+    /// );
+    /// ```
+    pub fn observe<F>(mut self, observer: F) -> Self
+    where
+        F: FnMut(&Event<'_>) + 'test,
+    {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Answers `is_human_readable` with `readable` instead of panicking, for
+    /// types that are meant to ask. Every query is counted either way; see
+    /// [`readable_queries`](Self::readable_queries).
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// let tokens = [Token::Str("127.0.0.1")];
+    /// let mut ser = Serializer::new(&tokens).human_readable(true);
+    /// Ipv4Addr::LOCALHOST.serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.readable_queries(), 1);
+    /// ```
+    pub fn human_readable(mut self, readable: bool) -> Self {
+        self.human_readable = Some(readable);
+        self
+    }
+
+    /// Returns how many times `is_human_readable` has been called.
+    pub fn readable_queries(&self) -> usize {
+        self.readable_queries.get()
+    }
+
+    fn query_readable(&self) -> bool {
+        self.readable_queries.set(self.readable_queries.get() + 1);
+        match self
+            .marked_readable
+            .or(self.human_readable)
+            .or_else(configure::default_readable)
+        {
+            Some(readable) => readable,
+            None => panic!(
+                "Types which have different human-readable and compact representations \
+                 must explicitly mark their test cases with `serde_test::Configure`"
+            ),
+        }
+    }
+
+    /// Records every token serialized from now on, including one that fails
+    /// to match, so that a failure can be explained without serializing the
+    /// value again.
+    fn record_emitted(mut self) -> Self {
+        self.emitted = Some(Vec::new());
+        self
+    }
+
+    /// The tokens recorded since [`record_emitted`](Self::record_emitted).
+    pub(crate) fn emitted(&self) -> &[OwnedToken] {
+        self.emitted.as_deref().unwrap_or_default()
+    }
+
+    /// Keeps serializing past a token that does not match, so that the
+    /// rest of the value is recorded by [`record_emitted`](Self::record_emitted)
+    /// without being checked. The mismatch is returned by
+    /// [`take_mismatch`](Self::take_mismatch) instead.
+    fn past_mismatch(mut self) -> Self {
+        self.past_mismatch = true;
+        self
+    }
+
+    /// Returns the first mismatch that serialization continued past.
+    fn take_mismatch(&mut self) -> TestResult {
+        match self.mismatch.take() {
+            Some(err) => Err(*err),
+            None => Ok(()),
+        }
+    }
+
+    fn record(&mut self, found: Token<'_, '_>) {
+        if let Some(emitted) = &mut self.emitted {
+            emitted.push(found.into());
+        }
+    }
+
+    /// Whether the tokens were set aside after a mismatch. Strings the value
+    /// wrote with `collect_str`, and the keys of its struct fields, are then
+    /// recorded as such, since the `Str` the rest of the tokens may have
+    /// expected matches them anyway.
+    fn set_aside(&self) -> bool {
+        self.mismatch.is_some()
+    }
+
+    /// How the next token is compared with the one the value serialized as.
+    fn matching(&mut self) -> Matching {
+        Matching {
+            float_cmp: self.float_cmp,
+            strict_strings: self.strict_strings,
+            matched: Some(mem::replace(&mut self.matched, false)),
+        }
+    }
+
+    /// Fails with `err`, unless serialization continues past mismatches, in
+    /// which case the first one is kept and the tokens are set aside so that
+    /// nothing after it is checked.
+    fn mismatch(&mut self, err: Error) -> TestResult {
+        if !self.past_mismatch {
+            return Err(err);
+        }
+        if self.mismatch.is_none() {
+            self.mismatch = Some(Box::new(err));
+            self.tokens = &[];
+            // Nothing is checked from here on, so a runaway `Serialize` impl
+            // is only stopped by these limits.
+            self.past_budget = PAST_MISMATCH_TOKENS * self.len.max(1);
+            if self.limits.depth.is_none() {
+                self.limits.depth = Some(self.depth + NESTING_DEPTH);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports `event` to the observer, if there is one, and tests it
+    /// against the next token if that is a [`Token::Matches`], or a
+    /// [`Token::OneOf`] with one among its alternatives.
+    fn emit(&mut self, event: Event<'_>) {
+        if let Some(observer) = &mut self.observer {
+            observer(&event);
+        }
+        let alternatives = match self.tokens.first() {
+            Some(Token::OneOf(alternatives)) => alternatives,
+            next => next.map(slice::from_ref).unwrap_or_default(),
+        };
+        // Any alternative matching is enough for a `OneOf`, so one answer
+        // serves every predicate among them.
+        self.matched = event.is_value()
+            && alternatives.iter().any(|alternative| match alternative {
+                Token::Matches(predicate) => predicate.test(&event),
+                _ => false,
+            });
+    }
+
+    /// Checks the token about to be serialized at `index` against the token
+    /// limit, and against the tokens left to serialize past a mismatch.
+    fn check_token_limit(&mut self, index: usize, found: Token<'_, '_>) -> TestResult {
+        if self.set_aside() {
+            if self.past_budget == 0 {
+                return Err(Error::with_kind(
+                    ErrorKind::LimitExceeded,
+                    format_args!(
+                        "{:?} exceeds the limit of {} tokens past the mismatch",
+                        found,
+                        PAST_MISMATCH_TOKENS * self.len.max(1),
+                    ),
+                )
+                .with_found(found));
+            }
+            self.past_budget -= 1;
+        }
+        match self.limits.tokens {
+            Some(max) if index >= max => Err(Error::with_kind(
+                ErrorKind::LimitExceeded,
+                format_args!(
+                    "{:?} at index {} exceeds the limit of {} tokens",
+                    found, index, max
+                ),
+            )
+            .at(index)
+            .with_found(found)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Opens a level of nesting, checking it against the depth limit.
+    fn enter(&mut self) -> TestResult {
+        self.depth += 1;
+        match self.limits.depth {
+            Some(max) if self.depth > max => {
+                let index = self.position() - 1;
+                Err(Error::with_kind(
+                    ErrorKind::LimitExceeded,
+                    format_args!(
+                        "value at index {} exceeds the nesting limit of {}",
+                        index, max,
+                    ),
+                )
+                .at(index))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a compound was dropped without its `end` being called, which
+    /// would otherwise only show up as tokens left over. Reports the first
+    /// such compound, by the name of its `Serialize*` trait and the index of
+    /// its header.
+    ///
+    /// The asserts run this check once a value has serialized successfully.
+    ///
+    /// ```
+    /// # use serde::Serializer as _;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Struct { name: "S", len: 0 }, Token::StructEnd];
+    /// let mut ser = Serializer::new(&tokens);
+    /// let state = (&mut ser).serialize_struct("S", 0).unwrap();
+    /// drop(state);
+    ///
+    /// assert_eq!(
+    ///     ser.check_ended().unwrap_err(),
+    ///     "SerializeStruct started at index 0 was dropped without calling end, so Token::StructEnd was never serialized",
+    /// );
+    /// ```
+    pub fn check_ended(&self) -> TestResult {
+        match self.unended.get() {
+            Some(Unended { compound, end, start }) => Err(Error::with_kind(
+                ErrorKind::Misuse,
+                format_args!(
+                    "{} started at index {} was dropped without calling end, so Token::{} was never serialized",
+                    compound, start, end,
+                ),
+            )
+            .at(start)),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes a level of nesting opened by [`enter`](Self::enter).
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Serializes `value` one level deeper.
+    fn nested<T>(&mut self, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.enter()?;
+        value.serialize(&mut *self)?;
+        self.leave();
+        Ok(())
     }
 
     /// Pulls the next token off of the serializer, ignoring it.
     fn next_token(&mut self) -> Option<Token<'test, 'test>> {
         if let Some((&first, rest)) = self.tokens.split_first() {
             self.tokens = rest;
+            self.take_readable_markers();
             Some(first)
         } else {
             None
         }
     }
 
+    /// Consumes any [`Token::Readable`] markers at the front of the stream,
+    /// so that the value after them sees their answer before it serializes
+    /// anything.
+    fn take_readable_markers(&mut self) {
+        while let Some((&Token::Readable(readable), rest)) = self.tokens.split_first() {
+            self.tokens = rest;
+            self.marked_readable = Some(readable);
+        }
+    }
+
+    /// Returns the next token without consuming it, or the first
+    /// alternative of a [`Token::OneOf`].
+    ///
+    /// Used to choose between the tokens a value may serialize as; any
+    /// alternative is then accepted.
+    fn peek(&self) -> Option<&Token<'test, 'test>> {
+        match self.tokens.first() {
+            Some(Token::OneOf(alternatives)) if !alternatives.is_empty() => alternatives.first(),
+            token => token,
+        }
+    }
+
+    /// Returns the number of tokens that have not been consumed yet.
     pub fn remaining(&self) -> usize {
         self.tokens.len()
     }
+
+    /// Returns the index of the next token to be consumed.
+    ///
+    /// Together with [`expected_next`](Self::expected_next) and
+    /// [`remaining_tokens`](Self::remaining_tokens), this lets a harness
+    /// wrapping the serializer report its progress in its own terms.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::Some, Token::U8(1), Token::Bool(true)];
+    /// let mut ser = Serializer::new(&tokens);
+    /// Some(1u8).serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.position(), 2);
+    /// assert_eq!(ser.len(), 3);
+    /// assert_eq!(ser.expected_next(), Some(Token::Bool(true)));
+    /// ```
+    pub fn position(&self) -> usize {
+        self.len - self.tokens.len()
+    }
+
+    /// Returns the total number of tokens, consumed or not.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the next token to be consumed, without consuming it.
+    ///
+    /// A [`Token::OneOf`] is returned as is, rather than one of its
+    /// alternatives.
+    pub fn expected_next(&self) -> Option<Token<'test, 'test>> {
+        self.tokens.first().copied()
+    }
+
+    /// Returns the tokens that have not been consumed yet.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::Serializer;
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens = [Token::U8(1), Token::Bool(true)];
+    /// let mut ser = Serializer::new(&tokens);
+    /// 1u8.serialize(&mut ser).unwrap();
+    /// assert_eq!(ser.remaining_tokens(), [Token::Bool(true)]);
+    /// ```
+    pub fn remaining_tokens(&self) -> &'test [Token<'test, 'test>] {
+        self.tokens
+    }
+
+    /// Checks that no tokens are left over once a serializer used by value
+    /// is done.
+    fn finish(&self) -> TestResult {
+        self.check_ended()?;
+        match self.tokens.first() {
+            Some(&token) => {
+                let index = self.position();
+                Err(Error::with_kind(
+                    ErrorKind::EndOfTokens,
+                    format_args!(
+                        "serialization ended at index {} of {} but Token::{} was expected",
+                        index, self.len, token,
+                    ),
+                )
+                .at(index)
+                .with_expected(token))
+            }
+            None => Ok(()),
+        }
+    }
 }
 
 macro_rules! assert_next_token {
     ($ser:expr, $actual:ident) => {{
-        assert_next_token!($ser, stringify!($actual), Token::$actual, true);
+        assert_next_token!($ser, Token::$actual);
     }};
     ($ser:expr, $actual:ident($v:expr)) => {{
-        assert_next_token!(
-            $ser,
-            format_args!(concat!(stringify!($actual), "({:?})"), $v),
-            Token::$actual(v),
-            v == $v
-        );
+        assert_next_token!($ser, Token::$actual($v));
     }};
     ($ser:expr, $actual:ident { $($k:ident),* }) => {{
-        let compare = ($($k,)*);
-        let field_format = || {
-            use std::fmt::Write;
-            let mut buffer = String::new();
-            $(
-                write!(&mut buffer, concat!(stringify!($k), ": {:?}, "), $k).unwrap();
-            )*
-            buffer
-        };
-        assert_next_token!(
-            $ser,
-            format_args!(concat!(stringify!($actual), " {{ {}}}"), field_format()),
-            Token::$actual { $($k),* },
-            ($($k,)*) == compare
-        );
+        assert_next_token!($ser, Token::$actual { $($k),* });
     }};
-    ($ser:expr, $actual:expr) => {
-        assert_next_token!($ser, $actual, expected, expected == $actual);
-    };
-    ($ser:expr, $actual:expr, $pat:pat, $guard:expr) => {
+    ($ser:expr, $found:expr) => {
+        let found = $found;
+        let index = $ser.position();
+        $ser.check_token_limit(index, found)?;
+        $ser.record(found);
+        let matching = $ser.matching();
         match $ser.next_token() {
-            Some($pat) if $guard => {}
-            Some(expected) => return Err(Error::new(
-                format_args!("expected Token::{} but serialized as {}", expected, $actual)
-            )),
-            None => return Err(Error::new(
-                format_args!("expected end of tokens, but {} was serialized", $actual)
-            )),
+            Some(expected) if matching.matches(expected, found) => {}
+            Some(expected) => {
+                let err = Error::with_kind(ErrorKind::Mismatch, format_args!(
+                    "expected Token::{} at index {} of {} but serialized as {}",
+                    expected, index, $ser.len(), found,
+                )).at(index).with_expected(expected).with_found(found);
+                $ser.mismatch(err)?;
+            }
+            None => $ser.mismatch(Error::with_kind(ErrorKind::EndOfTokens, format_args!(
+                "expected end of tokens after all {} tokens, but {} was serialized",
+                index, found,
+            )).at(index).with_found(found))?,
         }
+        trace::token("serialize", index, found);
     };
 }
 
+impl<'test> Serializer<'test> {
+    /// Checks `variant_index` against the [`Token::VariantIndex`] annotating
+    /// the next variant, if there is one.
+    fn assert_variant_index(&mut self, variant_index: u32) -> TestResult {
+        if let Some(Token::VariantIndex(_)) = self.peek() {
+            assert_next_token!(self, VariantIndex(variant_index));
+        }
+        Ok(())
+    }
+
+    /// Checks the variant identifier that follows a [`Token::Enum`], either
+    /// by name or by index, as a [`Token::VariantIndex`], `U32` or `U64`.
+    fn assert_variant(&mut self, variant_index: u32, variant: &'static str) -> TestResult {
+        match self.peek() {
+            Some(Token::VariantIndex(_)) => assert_next_token!(self, VariantIndex(variant_index)),
+            Some(Token::U32(_)) => assert_next_token!(self, U32(variant_index)),
+            Some(Token::U64(_)) => assert_next_token!(self, U64(u64::from(variant_index))),
+            _ => assert_next_token!(self, Str(variant)),
+        }
+        Ok(())
+    }
+}
+
 impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
     type Ok = ();
     type Error = Error;
@@ -89,94 +1098,191 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
     type SerializeStructVariant = ComplexSerializer<'a, 'test>;
 
     fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.emit(Event::Bool(v));
         assert_next_token!(self, Bool(v));
         Ok(())
     }
 
     fn serialize_i8(self, v: i8) -> Result<(), Error> {
-        assert_next_token!(self, I8(v));
+        self.emit(Event::I8(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, I8(v)),
+        }
         Ok(())
     }
 
     fn serialize_i16(self, v: i16) -> Result<(), Error> {
-        assert_next_token!(self, I16(v));
+        self.emit(Event::I16(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, I16(v)),
+        }
         Ok(())
     }
 
     fn serialize_i32(self, v: i32) -> Result<(), Error> {
-        assert_next_token!(self, I32(v));
+        self.emit(Event::I32(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, I32(v)),
+        }
         Ok(())
     }
 
     fn serialize_i64(self, v: i64) -> Result<(), Error> {
-        assert_next_token!(self, I64(v));
+        self.emit(Event::I64(v));
+        match (self.peek(), isize::try_from(v).ok()) {
+            (Some(Token::Number(_)), _) => assert_next_token!(self, Number(i128::from(v))),
+            (Some(Token::Isize(_)), Some(v)) => assert_next_token!(self, Isize(v)),
+            _ => assert_next_token!(self, I64(v)),
+        }
         Ok(())
     }
 
     fn serialize_i128(self, v: i128) -> TestResult {
-        assert_next_token!(self, I128(v));
+        self.emit(Event::I128(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(v)),
+            _ => assert_next_token!(self, I128(v)),
+        }
         Ok(())
     }
 
     fn serialize_u8(self, v: u8) -> Result<(), Error> {
-        assert_next_token!(self, U8(v));
+        self.emit(Event::U8(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, U8(v)),
+        }
         Ok(())
     }
 
     fn serialize_u16(self, v: u16) -> Result<(), Error> {
-        assert_next_token!(self, U16(v));
+        self.emit(Event::U16(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, U16(v)),
+        }
         Ok(())
     }
 
     fn serialize_u32(self, v: u32) -> Result<(), Error> {
-        assert_next_token!(self, U32(v));
+        self.emit(Event::U32(v));
+        match self.peek() {
+            Some(Token::Number(_)) => assert_next_token!(self, Number(i128::from(v))),
+            _ => assert_next_token!(self, U32(v)),
+        }
         Ok(())
     }
 
     fn serialize_u64(self, v: u64) -> Result<(), Error> {
-        assert_next_token!(self, U64(v));
+        self.emit(Event::U64(v));
+        match (self.peek(), usize::try_from(v).ok()) {
+            (Some(Token::Number(_)), _) => assert_next_token!(self, Number(i128::from(v))),
+            (Some(Token::Usize(_)), Some(v)) => assert_next_token!(self, Usize(v)),
+            _ => assert_next_token!(self, U64(v)),
+        }
         Ok(())
     }
 
     fn serialize_u128(self, v: u128) -> Result<(), Error> {
-        assert_next_token!(self, U128(v));
+        self.emit(Event::U128(v));
+        match (self.peek(), i128::try_from(v).ok()) {
+            (Some(Token::Number(_)), Some(v)) => assert_next_token!(self, Number(v)),
+            _ => assert_next_token!(self, U128(v)),
+        }
         Ok(())
     }
 
     fn serialize_f32(self, v: f32) -> Result<(), Error> {
-        assert_next_token!(self, F32(v));
+        self.emit(Event::F32(v));
+        if let Some(Token::F32Bits(_)) = self.peek() {
+            assert_next_token!(self, F32Bits(v.to_bits()));
+        } else {
+            assert_next_token!(self, F32(v));
+        }
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<(), Error> {
-        assert_next_token!(self, F64(v));
+        self.emit(Event::F64(v));
+        if let Some(Token::F64Bits(_)) = self.peek() {
+            assert_next_token!(self, F64Bits(v.to_bits()));
+        } else {
+            assert_next_token!(self, F64(v));
+        }
         Ok(())
     }
 
     fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.emit(Event::Char(v));
+        if self.char_as_str {
+            let mut buf = [0; 4];
+            let v = &*v.encode_utf8(&mut buf);
+            match self.peek() {
+                Some(Token::BorrowedStr(_)) if !self.strict_strings => {
+                    assert_next_token!(self, BorrowedStr(v));
+                }
+                Some(Token::String(_)) if !self.strict_strings => {
+                    assert_next_token!(self, String(v));
+                }
+                _ => assert_next_token!(self, Str(v)),
+            }
+            return Ok(());
+        }
         assert_next_token!(self, Char(v));
         Ok(())
     }
 
     fn serialize_str(self, v: &str) -> Result<(), Error> {
-        match self.tokens.first() {
-            Some(Token::BorrowedStr(_)) => assert_next_token!(self, BorrowedStr(v)),
-            Some(Token::String(_)) => assert_next_token!(self, String(v)),
+        self.emit(Event::Str(v));
+        match self.peek() {
+            Some(Token::BorrowedStr(_)) if !self.strict_strings => {
+                assert_next_token!(self, BorrowedStr(v));
+            }
+            Some(Token::String(_)) if !self.strict_strings => assert_next_token!(self, String(v)),
             _ => assert_next_token!(self, Str(v)),
         }
         Ok(())
     }
 
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Display,
+    {
+        let s = value.to_string();
+        if !matches!(self.peek(), Some(Token::CollectStr(_))) && !self.set_aside() {
+            return self.serialize_str(&s);
+        }
+        self.emit(Event::Str(&s));
+        assert_next_token!(self, CollectStr(&*s));
+        Ok(())
+    }
+
     fn serialize_bytes(self, v: &[u8]) -> Result<(), Self::Error> {
-        match self.tokens.first() {
-            Some(Token::BorrowedBytes(_)) => assert_next_token!(self, BorrowedBytes(v)),
-            Some(Token::ByteBuf(_)) => assert_next_token!(self, ByteBuf(v)),
+        self.emit(Event::Bytes(v));
+        if self.bytes_as_seq {
+            let len = Some(v.len());
+            assert_next_token!(self, Seq { len });
+            for &byte in v {
+                assert_next_token!(self, U8(byte));
+            }
+            assert_next_token!(self, SeqEnd);
+            return Ok(());
+        }
+        match self.peek() {
+            Some(Token::BorrowedBytes(_)) if !self.strict_strings => {
+                assert_next_token!(self, BorrowedBytes(v));
+            }
+            Some(Token::ByteBuf(_)) if !self.strict_strings => assert_next_token!(self, ByteBuf(v)),
             _ => assert_next_token!(self, Bytes(v)),
         }
         Ok(())
     }
 
     fn serialize_none(self) -> Result<(), Error> {
+        self.emit(Event::None);
         assert_next_token!(self, None);
         Ok(())
     }
@@ -185,16 +1291,19 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
     where
         T: Serialize,
     {
+        self.emit(Event::Some);
         assert_next_token!(self, Some);
-        value.serialize(self)
+        self.nested(value)
     }
 
     fn serialize_unit(self) -> Result<(), Error> {
+        self.emit(Event::Unit);
         assert_next_token!(self, Unit);
         Ok(())
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        self.emit(Event::UnitStruct { name });
         assert_next_token!(self, UnitStruct { name });
         Ok(())
     }
@@ -202,12 +1311,18 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
     fn serialize_unit_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
     ) -> Result<(), Error> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.emit(Event::UnitVariant {
+            name,
+            variant_index,
+            variant,
+        });
+        self.assert_variant_index(variant_index)?;
+        if self.peek() == Some(&Token::Enum { name }) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.assert_variant(variant_index, variant)?;
             assert_next_token!(self, Unit);
         } else {
             assert_next_token!(self, UnitVariant { name, variant });
@@ -219,45 +1334,48 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
     where
         T: Serialize,
     {
+        self.emit(Event::NewtypeStruct { name });
         assert_next_token!(self, NewtypeStruct { name });
-        value.serialize(self)
+        self.nested(value)
     }
 
     fn serialize_newtype_variant<T: ?Sized>(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<(), Error>
     where
         T: Serialize,
     {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.emit(Event::NewtypeVariant {
+            name,
+            variant_index,
+            variant,
+        });
+        self.assert_variant_index(variant_index)?;
+        if self.peek() == Some(&Token::Enum { name }) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.assert_variant(variant_index, variant)?;
         } else {
             assert_next_token!(self, NewtypeVariant { name, variant });
         }
-        value.serialize(self)
+        self.nested(value)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> TestResult<ComplexSerializer<'a, 'test>> {
+        self.emit(Event::Seq { len });
         assert_next_token!(self, Seq { len });
 
-        Ok(ComplexSerializer {
-            ser: self,
-            end: EndToken::Seq,
-        })
+        ComplexSerializer::new(self, "SerializeSeq", EndToken::Seq, len)
     }
 
     fn serialize_tuple(self, len: usize) -> TestResult<ComplexSerializer<'a, 'test>> {
+        self.emit(Event::Tuple { len });
         assert_next_token!(self, Tuple { len });
 
-        Ok(ComplexSerializer {
-            ser: self,
-            end: EndToken::Tuple,
-        })
+        ComplexSerializer::new(self, "SerializeTuple", EndToken::Tuple, Some(len))
     }
 
     fn serialize_tuple_struct(
@@ -265,48 +1383,45 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
         name: &'static str,
         len: usize,
     ) -> TestResult<ComplexSerializer<'a, 'test>> {
+        self.emit(Event::TupleStruct { name, len });
         assert_next_token!(self, TupleStruct { name, len });
 
-        Ok(ComplexSerializer {
-            ser: self,
-            end: EndToken::TupleStruct,
-        })
+        ComplexSerializer::new(self, "SerializeTupleStruct", EndToken::TupleStruct, Some(len))
     }
 
     fn serialize_tuple_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> TestResult<ComplexSerializer<'a, 'test>> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.emit(Event::TupleVariant {
+            name,
+            variant_index,
+            variant,
+            len,
+        });
+        self.assert_variant_index(variant_index)?;
+        if self.peek() == Some(&Token::Enum { name }) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.assert_variant(variant_index, variant)?;
             let len = Some(len);
             assert_next_token!(self, Seq { len });
 
-            Ok(ComplexSerializer {
-                ser: self,
-                end: EndToken::Seq,
-            })
+            ComplexSerializer::new(self, "SerializeTupleVariant", EndToken::Seq, len)
         } else {
             assert_next_token!(self, TupleVariant { name, variant, len });
 
-            Ok(ComplexSerializer {
-                ser: self,
-                end: EndToken::TupleVariant,
-            })
+            ComplexSerializer::new(self, "SerializeTupleVariant", EndToken::TupleVariant, Some(len))
         }
     }
 
     fn serialize_map(self, len: Option<usize>) -> TestResult<ComplexSerializer<'a, 'test>> {
+        self.emit(Event::Map { len });
         assert_next_token!(self, Map { len });
 
-        Ok(ComplexSerializer {
-            ser: self,
-            end: EndToken::Map,
-        })
+        ComplexSerializer::new(self, "SerializeMap", EndToken::Map, len)
     }
 
     fn serialize_struct(
@@ -314,72 +1429,434 @@ impl<'a, 'test: 'a> ser::Serializer for &'a mut Serializer<'test> {
         name: &'static str,
         len: usize,
     ) -> TestResult<ComplexSerializer<'a, 'test>> {
+        self.emit(Event::Struct { name, len });
         assert_next_token!(self, Struct { name, len });
 
-        Ok(ComplexSerializer {
-            ser: self,
-            end: EndToken::Struct,
-        })
+        ComplexSerializer::new(self, "SerializeStruct", EndToken::Struct, Some(len))
     }
 
     fn serialize_struct_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> TestResult<ComplexSerializer<'a, 'test>> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.emit(Event::StructVariant {
+            name,
+            variant_index,
+            variant,
+            len,
+        });
+        self.assert_variant_index(variant_index)?;
+        if self.peek() == Some(&Token::Enum { name }) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.assert_variant(variant_index, variant)?;
             let len = Some(len);
             assert_next_token!(self, Map { len });
 
-            Ok(ComplexSerializer {
-                ser: self,
-                end: EndToken::Map,
-            })
+            ComplexSerializer::new(self, "SerializeStructVariant", EndToken::Map, len)
         } else {
             assert_next_token!(self, StructVariant { name, variant, len });
 
+            ComplexSerializer::new(self, "SerializeStructVariant", EndToken::StructVariant, Some(len))
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.query_readable()
+    }
+}
+
+macro_rules! forward_by_value {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+        fn $method(mut self, $($arg: $ty),*) -> TestResult {
+            (&mut self).$method($($arg),*)?;
+            self.finish()
+        }
+        )*
+    };
+}
+
+macro_rules! forward_compound_by_value {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+        fn $method(mut self, $($arg: $ty),*) -> TestResult<ComplexSerializer<'test, 'test>> {
+            let ComplexSerializer { end, len, count, key_pending, span, guard, .. } =
+                (&mut self).$method($($arg),*)?;
             Ok(ComplexSerializer {
-                ser: self,
-                end: EndToken::StructVariant,
+                ser: SerializerRef::Owned(self),
+                end,
+                len,
+                count,
+                key_pending,
+                span,
+                guard,
             })
         }
+        )*
+    };
+}
+
+/// A serializer can also be used by value, for code that consumes it, such
+/// as `serialize_with` helpers. It then also checks that no tokens are left
+/// over once the value has been serialized.
+///
+/// ```
+/// # use serde::Serializer;
+/// # use serde_test::{ser, Token};
+/// #
+/// fn as_hex<S>(v: &u32, serializer: S) -> Result<S::Ok, S::Error>
+/// where
+///     S: Serializer,
+/// {
+///     serializer.collect_str(&format_args!("{:x}", v))
+/// }
+///
+/// let tokens = [Token::Str("ff")];
+/// as_hex(&255, ser::Serializer::new(&tokens)).unwrap();
+///
+/// let tokens = [Token::Str("ff"), Token::Str("ff")];
+/// let err = as_hex(&255, ser::Serializer::new(&tokens)).unwrap_err();
+/// assert_eq!(err, "serialization ended at index 1 of 2 but Token::Str(\"ff\") was expected");
+/// ```
+impl<'test> ser::Serializer for Serializer<'test> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = ComplexSerializer<'test, 'test>;
+    type SerializeTuple = ComplexSerializer<'test, 'test>;
+    type SerializeTupleStruct = ComplexSerializer<'test, 'test>;
+    type SerializeTupleVariant = ComplexSerializer<'test, 'test>;
+    type SerializeMap = ComplexSerializer<'test, 'test>;
+    type SerializeStruct = ComplexSerializer<'test, 'test>;
+    type SerializeStructVariant = ComplexSerializer<'test, 'test>;
+
+    forward_by_value! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
     }
 
-    fn is_human_readable(&self) -> bool {
-        panic!(
-            "Types which have different human-readable and compact representations \
-             must explicitly mark their test cases with `serde_test::Configure`"
+    fn serialize_some<T>(mut self, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        (&mut self).serialize_some(value)?;
+        self.finish()
+    }
+
+    fn collect_str<T>(mut self, value: &T) -> TestResult
+    where
+        T: ?Sized + Display,
+    {
+        (&mut self).collect_str(value)?;
+        self.finish()
+    }
+
+    fn serialize_newtype_struct<T>(mut self, name: &'static str, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        (&mut self).serialize_newtype_struct(name, value)?;
+        self.finish()
+    }
+
+    fn serialize_newtype_variant<T>(
+        mut self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        (&mut self).serialize_newtype_variant(name, variant_index, variant, value)?;
+        self.finish()
+    }
+
+    forward_compound_by_value! {
+        serialize_seq(len: Option<usize>);
+        serialize_tuple(len: usize);
+        serialize_tuple_struct(name: &'static str, len: usize);
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        );
+        serialize_map(len: Option<usize>);
+        serialize_struct(name: &'static str, len: usize);
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
         );
     }
+
+    fn is_human_readable(&self) -> bool {
+        self.query_readable()
+    }
 }
 
+/// The serializer for compound values, returned by the `serialize_seq`,
+/// `serialize_struct`, etc. methods of [`Serializer`].
+///
+/// Compounds with a fixed size, and sequences and maps that were given a
+/// length, check that the number of elements (or map entries) serialized
+/// matches the declared `len`, since length-prefixed formats rely on it.
+///
+/// ```
+/// # use serde::ser::{Serialize, SerializeStruct, Serializer};
+/// # use serde_test::{assert_ser_tokens_error, Token};
+/// #
+/// struct Lying;
+///
+/// impl Serialize for Lying {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let mut s = serializer.serialize_struct("Lying", 2)?;
+///         s.serialize_field("a", &0u8)?;
+///         s.end()
+///     }
+/// }
+///
+/// assert_ser_tokens_error(
+///     &Lying,
+///     &[
+///         Token::Struct { name: "Lying", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///     ],
+///     "compound declared len 2 but 1 elements were serialized before Token::StructEnd at index 3",
+/// );
+/// ```
+///
+/// ```
+/// # use serde::ser::{Serialize, SerializeSeq, Serializer};
+/// # use serde_test::{assert_ser_tokens_error, Token};
+/// #
+/// struct LyingHint;
+///
+/// impl Serialize for LyingHint {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let mut seq = serializer.serialize_seq(Some(3))?;
+///         seq.serialize_element(&'a')?;
+///         seq.end()
+///     }
+/// }
+///
+/// assert_ser_tokens_error(
+///     &LyingHint,
+///     &[Token::Seq { len: Some(3) }, Token::Char('a')],
+///     "compound declared len 3 but 1 elements were serialized before Token::SeqEnd at index 2",
+/// );
+/// ```
+///
+/// Maps also check that each `serialize_key` is followed by exactly one
+/// `serialize_value`, failing with [`ErrorKind::Misuse`] otherwise.
+///
+/// ```
+/// # use serde::ser::{Serialize, SerializeMap, Serializer};
+/// # use serde_test::{assert_ser_tokens_error, Token};
+/// #
+/// struct Keyless;
+///
+/// impl Serialize for Keyless {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         let mut map = serializer.serialize_map(None)?;
+///         map.serialize_value(&0u8)?;
+///         map.end()
+///     }
+/// }
+///
+/// assert_ser_tokens_error(
+///     &Keyless,
+///     &[Token::Map { len: None }],
+///     "serialize_value called at index 1 without a key",
+/// );
+/// ```
+///
+/// A compound dropped without its `end` being called fails the asserts
+/// naming the compound, rather than with its end token left over; see
+/// [`Serializer::check_ended`].
 pub struct ComplexSerializer<'a, 'test: 'a> {
-    ser: &'a mut Serializer<'test>,
+    ser: SerializerRef<'a, 'test>,
+    end: EndToken,
+    /// The declared number of elements, if it must be checked on `end`.
+    len: Option<usize>,
+    count: usize,
+    /// Whether `serialize_key` was called on a map and its value is still
+    /// due.
+    key_pending: bool,
+    span: trace::CompoundSpan,
+    guard: EndGuard,
+}
+
+/// A compound whose `end` was never called.
+#[derive(Copy, Clone, Debug)]
+struct Unended {
+    /// The name of the `Serialize*` trait the compound was serialized with.
+    compound: &'static str,
     end: EndToken,
+    /// The index of the compound's header token.
+    start: usize,
+}
+
+/// Records its compound in the serializer's `unended` if dropped before
+/// being disarmed by `end`.
+struct EndGuard {
+    unended: Rc<Cell<Option<Unended>>>,
+    compound: Unended,
+    armed: bool,
+}
+
+impl Drop for EndGuard {
+    fn drop(&mut self) {
+        // An error returned partway through the compound also drops it, but
+        // then the error is reported instead.
+        if self.armed && !thread::panicking() && self.unended.get().is_none() {
+            self.unended.set(Some(self.compound));
+        }
+    }
+}
+
+/// The serializer a [`ComplexSerializer`] writes to, which it owns if the
+/// [`Serializer`] was used by value.
+enum SerializerRef<'a, 'test> {
+    Borrowed(&'a mut Serializer<'test>),
+    Owned(Serializer<'test>),
+}
+
+impl<'test> Deref for SerializerRef<'_, 'test> {
+    type Target = Serializer<'test>;
+
+    fn deref(&self) -> &Serializer<'test> {
+        match self {
+            SerializerRef::Borrowed(ser) => ser,
+            SerializerRef::Owned(ser) => ser,
+        }
+    }
+}
+
+impl<'test> DerefMut for SerializerRef<'_, 'test> {
+    fn deref_mut(&mut self) -> &mut Serializer<'test> {
+        match self {
+            SerializerRef::Borrowed(ser) => ser,
+            SerializerRef::Owned(ser) => ser,
+        }
+    }
+}
+
+impl<'a, 'test> ComplexSerializer<'a, 'test> {
+    fn new(
+        ser: &'a mut Serializer<'test>,
+        compound: &'static str,
+        end: EndToken,
+        len: Option<usize>,
+    ) -> TestResult<Self> {
+        ser.enter()?;
+        let start = ser.position() - 1;
+        let span = trace::compound("serialize", start, end);
+        let guard = EndGuard {
+            unended: ser.unended.clone(),
+            compound: Unended {
+                compound,
+                end,
+                start,
+            },
+            armed: true,
+        };
+        Ok(ComplexSerializer {
+            ser: SerializerRef::Borrowed(ser),
+            end,
+            len,
+            count: 0,
+            key_pending: false,
+            span,
+            guard,
+        })
+    }
+
+    /// Reports a call to `method` that the map API does not allow here.
+    fn misuse(&self, method: &str, reason: &str) -> Error {
+        let index = self.ser.position();
+        Error::with_kind(
+            ErrorKind::Misuse,
+            format_args!("{} called at index {} {}", method, index, reason),
+        )
+        .at(index)
+    }
+
+    fn end(mut self) -> TestResult {
+        self.guard.armed = false;
+        self.ser.emit(Event::End);
+        if self.key_pending {
+            return Err(self.misuse("end", "before the value of the last map key"));
+        }
+        match self.len {
+            Some(len) if len != self.count => {
+                return Err(Error::with_kind(ErrorKind::Mismatch, format_args!(
+                    "compound declared len {} but {} elements were serialized before Token::{} at index {}",
+                    len, self.count, self.end, self.ser.position(),
+                )).at(self.ser.position()));
+            }
+            _ => {}
+        }
+        assert_next_token!(self.ser, self.end.token());
+        self.ser.leave();
+        if let SerializerRef::Owned(ser) = &self.ser {
+            ser.finish()?;
+        }
+        Ok(())
+    }
 }
 
 macro_rules! impl_complex_serialize {
-    ($tr:ident: $($method:ident),+) => {
+    ($tr:ident: $method:ident => $event:expr) => {
         impl ser::$tr for ComplexSerializer<'_, '_> {
             type Ok = ();
             type Error = Error;
 
-            $(
             fn $method<T: ?Sized>(&mut self, value: &T) -> TestResult
             where
                 T: Serialize,
             {
+                self.ser.emit($event);
+                self.count += 1;
                 value.serialize(&mut *self.ser)
             }
-            )+
 
             fn end(self) -> TestResult {
-                assert_next_token!(self.ser, self.end);
-                Ok(())
+                ComplexSerializer::end(self)
             }
         }
     };
@@ -393,11 +1870,25 @@ macro_rules! impl_complex_serialize {
             where
                 T: Serialize,
             {
-                key.serialize(&mut *self.ser)?;
+                self.ser.emit(Event::Field { key: Some(key) });
+                self.count += 1;
+                if let Some(Token::Identifier(_)) = self.ser.peek() {
+                    assert_next_token!(self.ser, Identifier(key));
+                } else if self.ser.set_aside() {
+                    assert_next_token!(self.ser, Identifier(key));
+                } else {
+                    // The key is written by the serializer, not the impl
+                    // under test, so it is hidden from the observer.
+                    let observer = self.ser.observer.take();
+                    let result = key.serialize(&mut *self.ser);
+                    self.ser.observer = observer;
+                    result?;
+                }
                 value.serialize(&mut *self.ser)
             }
 
             fn skip_field(&mut self, key: &'static str) -> TestResult {
+                self.ser.emit(Event::SkipField { key });
                 match self.ser.tokens.first() {
                     Some(Token::SkipStructField { .. }) => {
                         assert_next_token!(self.ser, Token::SkipStructField { name: key });
@@ -408,17 +1899,80 @@ macro_rules! impl_complex_serialize {
             }
 
             fn end(self) -> TestResult {
-                assert_next_token!(self.ser, self.end);
-                Ok(())
+                ComplexSerializer::end(self)
             }
         }
     };
 }
 
-impl_complex_serialize!(SerializeSeq: serialize_element);
-impl_complex_serialize!(SerializeTuple: serialize_element);
-impl_complex_serialize!(SerializeTupleStruct: serialize_field);
-impl_complex_serialize!(SerializeTupleVariant: serialize_field);
-impl_complex_serialize!(SerializeMap: serialize_key, serialize_value);
+impl_complex_serialize!(SerializeSeq: serialize_element => Event::Element);
+impl_complex_serialize!(SerializeTuple: serialize_element => Event::Element);
+impl_complex_serialize!(SerializeTupleStruct: serialize_field => Event::Field { key: None });
+impl_complex_serialize!(SerializeTupleVariant: serialize_field => Event::Field { key: None });
 impl_complex_serialize!(struct SerializeStruct: serialize_field);
 impl_complex_serialize!(struct SerializeStructVariant: serialize_field);
+
+impl ser::SerializeMap for ComplexSerializer<'_, '_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.emit(Event::Key);
+        if self.key_pending {
+            return Err(self.misuse("serialize_key", "before the value of the previous key"));
+        }
+        if let Some(Token::MapEntry) = self.ser.peek() {
+            let index = self.ser.position();
+            self.ser.next_token();
+            return Err(Error::with_kind(
+                ErrorKind::Mismatch,
+                format_args!(
+                    "expected Token::MapEntry at index {} of {} but serialize_key was called",
+                    index,
+                    self.ser.len(),
+                ),
+            )
+            .at(index)
+            .with_expected(Token::MapEntry));
+        }
+        self.count += 1;
+        self.key_pending = true;
+        key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> TestResult
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.emit(Event::Value);
+        if !self.key_pending {
+            return Err(self.misuse("serialize_value", "without a key"));
+        }
+        self.key_pending = false;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> TestResult
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        self.ser.emit(Event::Entry);
+        if self.key_pending {
+            return Err(self.misuse("serialize_entry", "before the value of the previous key"));
+        }
+        if let Some(Token::MapEntry) = self.ser.peek() {
+            assert_next_token!(self.ser, MapEntry);
+        }
+        self.count += 1;
+        key.serialize(&mut *self.ser)?;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> TestResult {
+        ComplexSerializer::end(self)
+    }
+}
@@ -0,0 +1,256 @@
+//! Minimizing a failing token stream.
+//!
+//! The streams that the `strategy` and `fuzz` modules find to break a
+//! `Deserialize` impl are often hundreds of tokens long. [`minimize`] cuts one down to a small stream that
+//! still fails, by repeatedly dropping elements, entries and tokens,
+//! replacing compounds with one of their elements, and zeroing scalars.
+
+use crate::de::Deserializer;
+use crate::group;
+use crate::mutate::lie_about_len;
+use crate::token::Token;
+use serde::Deserialize;
+use std::iter;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Returns a stream no larger than `tokens` for which `fails` still returns
+/// `true`.
+///
+/// Each step tries the candidates in order and keeps the first that fails,
+/// until none does. `fails` is called once per candidate, so it should not
+/// panic itself; wrap a panicking check in [`catch_unwind`] as
+/// [`minimize_panic`] does. The candidates need not be well formed, so
+/// `fails` decides whether a malformed stream counts.
+///
+/// # Panics
+///
+/// If `fails` returns `false` for `tokens` themselves.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::shrink::minimize;
+/// # use serde_test::de::Deserializer;
+/// # use serde_test::{validate_tokens, Token};
+/// #
+/// let tokens = [
+///     Token::Seq { len: Some(3) },
+///     Token::Str("padding"),
+///     Token::U64(500),
+///     Token::Str("more padding"),
+///     Token::SeqEnd,
+/// ];
+///
+/// // Only well-formed streams with a non-string element count.
+/// let minimal = minimize(&tokens, |tokens| {
+///     let mut de = Deserializer::new(tokens);
+///     validate_tokens(tokens).is_ok()
+///         && matches!(
+///             Vec::<String>::deserialize(&mut de),
+///             Err(err) if err.to_string().ends_with("expected a string"),
+///         )
+/// });
+///
+/// assert_eq!(
+///     minimal,
+///     [Token::Seq { len: Some(1) }, Token::U64(0), Token::SeqEnd],
+/// );
+/// ```
+///
+/// [`catch_unwind`]: std::panic::catch_unwind
+#[track_caller]
+pub fn minimize<'test, 'de, F>(tokens: &[Token<'test, 'de>], mut fails: F) -> Vec<Token<'test, 'de>>
+where
+    F: FnMut(&[Token<'test, 'de>]) -> bool,
+{
+    if !fails(tokens) {
+        panic!("the token stream to minimize does not fail");
+    }
+    let mut tokens = tokens.to_vec();
+    loop {
+        let smaller = candidates(&tokens).find(|candidate| fails(candidate));
+        match smaller {
+            Some(smaller) => tokens = smaller,
+            None => return tokens,
+        }
+    }
+}
+
+/// Returns a stream no larger than `tokens` on which deserializing `T` still
+/// panics.
+///
+/// Every candidate is deserialized under [`catch_unwind`], so the panic
+/// hook prints a message for each one that panics.
+///
+/// # Panics
+///
+/// If deserializing `T` from `tokens` does not panic.
+///
+/// ```
+/// # use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+/// # use serde_test::shrink::minimize_panic;
+/// # use serde_test::Token;
+/// # use std::fmt;
+/// #
+/// // Panics on a sequence whose elements add up to more than 255.
+/// struct Sum(u8);
+///
+/// impl<'de> Deserialize<'de> for Sum {
+///     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+///         struct SumVisitor;
+///
+///         impl<'de> Visitor<'de> for SumVisitor {
+///             type Value = Sum;
+///
+///             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+///                 formatter.write_str("a sequence of u8")
+///             }
+///
+///             fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Sum, A::Error> {
+///                 let mut sum = 0u8;
+///                 while let Some(n) = seq.next_element::<u8>()? {
+///                     sum = sum.checked_add(n).expect("overflow");
+///                 }
+///                 Ok(Sum(sum))
+///             }
+///         }
+///
+///         deserializer.deserialize_seq(SumVisitor)
+///     }
+/// }
+///
+/// let tokens = [
+///     Token::Seq { len: Some(4) },
+///     Token::U8(100),
+///     Token::U8(1),
+///     Token::U8(200),
+///     Token::U8(2),
+///     Token::SeqEnd,
+/// ];
+/// let minimal = minimize_panic::<Sum>(&tokens);
+///
+/// // It panics before reaching the missing end token.
+/// assert_eq!(
+///     minimal,
+///     [Token::Seq { len: Some(4) }, Token::U8(100), Token::U8(200)],
+/// );
+/// ```
+///
+/// [`catch_unwind`]: std::panic::catch_unwind
+#[track_caller]
+pub fn minimize_panic<'test, 'de: 'test, T>(tokens: &[Token<'test, 'de>]) -> Vec<Token<'test, 'de>>
+where
+    T: Deserialize<'de>,
+{
+    minimize(tokens, |tokens| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut de = Deserializer::new(tokens);
+            let _ = T::deserialize(&mut de);
+        }))
+        .is_err()
+    })
+}
+
+/// The streams to try in place of `tokens`, roughly largest cut first. Each
+/// one is shorter than `tokens` or has a scalar zeroed, so that minimizing
+/// terminates.
+fn candidates<'a, 'test, 'de>(
+    tokens: &'a [Token<'test, 'de>],
+) -> impl Iterator<Item = Vec<Token<'test, 'de>>> + 'a {
+    let truncations = (0..tokens.len()).map(move |len| tokens[..len].to_vec());
+    let removals = (0..tokens.len()).flat_map(move |header| {
+        let removed = match group::entries(tokens, header) {
+            Some(entries) => entries
+                .into_iter()
+                .map(|entry| entry.key.start..entry.value.end)
+                .collect(),
+            None => group::elements(tokens, header).unwrap_or_default(),
+        };
+        removed.into_iter().flat_map(move |range| {
+            let fewer = without(tokens, range);
+            let lied = lie_about_len(tokens[header], false).map(|len| {
+                let mut lied = fewer.clone();
+                lied[header] = len;
+                lied
+            });
+            lied.into_iter().chain(Some(fewer))
+        })
+    });
+    let hoists = (0..tokens.len()).flat_map(move |start| {
+        let end = group::value_end(tokens, start);
+        let inner = match (tokens[start], end) {
+            (
+                Token::Some | Token::NewtypeStruct { .. } | Token::NewtypeVariant { .. },
+                Some(end),
+            ) => iter::once(start + 1..end).collect(),
+            _ => group::elements(tokens, start).unwrap_or_default(),
+        };
+        inner
+            .into_iter()
+            .filter_map(move |element| Some(replaced(tokens, start..end?, &tokens[element])))
+    });
+    let removed_tokens = (0..tokens.len()).map(move |index| without(tokens, index..index + 1));
+    let zeroed = (0..tokens.len()).filter_map(move |index| {
+        let zero = zero(tokens[index])?;
+        Some(replaced(tokens, index..index + 1, &[zero]))
+    });
+    truncations
+        .chain(removals)
+        .chain(hoists)
+        .chain(removed_tokens)
+        .chain(zeroed)
+}
+
+fn without<'test, 'de>(
+    tokens: &[Token<'test, 'de>],
+    range: Range<usize>,
+) -> Vec<Token<'test, 'de>> {
+    replaced(tokens, range, &[])
+}
+
+fn replaced<'test, 'de>(
+    tokens: &[Token<'test, 'de>],
+    range: Range<usize>,
+    with: &[Token<'test, 'de>],
+) -> Vec<Token<'test, 'de>> {
+    let mut replaced = tokens[..range.start].to_vec();
+    replaced.extend_from_slice(with);
+    replaced.extend_from_slice(&tokens[range.end..]);
+    replaced
+}
+
+/// The zero value of the scalar `token`, or `None` if it is not a scalar or
+/// already zero.
+fn zero<'test, 'de>(token: Token<'test, 'de>) -> Option<Token<'test, 'de>> {
+    let zero = match token {
+        Token::Bool(_) => Token::Bool(false),
+        Token::I8(_) => Token::I8(0),
+        Token::I16(_) => Token::I16(0),
+        Token::I32(_) => Token::I32(0),
+        Token::I64(_) => Token::I64(0),
+        Token::I128(_) => Token::I128(0),
+        Token::Isize(_) => Token::Isize(0),
+        Token::U8(_) => Token::U8(0),
+        Token::U16(_) => Token::U16(0),
+        Token::U32(_) => Token::U32(0),
+        Token::U64(_) => Token::U64(0),
+        Token::U128(_) => Token::U128(0),
+        Token::Usize(_) => Token::Usize(0),
+        Token::Number(_) => Token::Number(0),
+        Token::F32(_) => Token::F32(0.0),
+        Token::F64(_) => Token::F64(0.0),
+        Token::Char(_) => Token::Char('\0'),
+        Token::Str(_) => Token::Str(""),
+        Token::BorrowedStr(_) => Token::BorrowedStr(""),
+        Token::String(_) => Token::String(""),
+        Token::Bytes(_) => Token::Bytes(&[]),
+        Token::BorrowedBytes(_) => Token::BorrowedBytes(&[]),
+        Token::ByteBuf(_) => Token::ByteBuf(&[]),
+        _ => return None,
+    };
+    if zero == token {
+        None
+    } else {
+        Some(zero)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::token::{Token, TokenKind};
+use std::collections::BTreeMap;
+
+/// Counts and sizes describing a token stream, for asserting properties of a
+/// representation rather than the exact tokens.
+///
+/// ```
+/// # use serde_test::{Token, TokenKind, TokenStats};
+/// #
+/// let tokens = [
+///     Token::Map { len: Some(2) },
+///     Token::Str("a"),
+///     Token::Seq { len: Some(2) },
+///     Token::U8(1),
+///     Token::U8(2),
+///     Token::SeqEnd,
+///     Token::Str("bc"),
+///     Token::Seq { len: Some(1) },
+///     Token::U8(3),
+///     Token::SeqEnd,
+///     Token::MapEnd,
+/// ];
+/// let stats = TokenStats::analyze(&tokens);
+///
+/// assert_eq!(stats.count(TokenKind::U8), 3);
+/// assert_eq!(stats.count(TokenKind::Seq), 2);
+/// assert_eq!(stats.max_depth(), 2);
+/// assert_eq!(stats.str_len(), 3);
+///
+/// stats.assert_none(TokenKind::String);
+/// stats.assert_max_depth(4);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenStats {
+    counts: BTreeMap<TokenKind, usize>,
+    max_depth: usize,
+    str_len: usize,
+    bytes_len: usize,
+}
+
+impl TokenStats {
+    /// Tallies the tokens of `tokens`.
+    ///
+    /// The depth counts the compounds open at once, so a flat sequence has a
+    /// depth of 1 and a scalar a depth of 0. The alternatives of a
+    /// [`Token::OneOf`] are not looked into.
+    pub fn analyze(tokens: &[Token<'_, '_>]) -> Self {
+        let mut stats = TokenStats::default();
+        let mut depth = 0usize;
+        for &token in tokens {
+            *stats.counts.entry(token.kind()).or_insert(0) += 1;
+            if token.is_compound_start() {
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+            } else if token.is_end() {
+                depth = depth.saturating_sub(1);
+            }
+            stats.str_len += token.as_str().map_or(0, str::len);
+            stats.bytes_len += token.as_bytes().map_or(0, <[u8]>::len);
+        }
+        stats
+    }
+
+    /// The number of tokens of the given kind.
+    pub fn count(&self, kind: TokenKind) -> usize {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    /// The number of tokens of each kind present.
+    pub fn counts(&self) -> &BTreeMap<TokenKind, usize> {
+        &self.counts
+    }
+
+    /// The total number of tokens.
+    pub fn len(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The greatest number of compounds open at once.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The total length in bytes of all strings.
+    pub fn str_len(&self) -> usize {
+        self.str_len
+    }
+
+    /// The total length of all byte arrays.
+    pub fn bytes_len(&self) -> usize {
+        self.bytes_len
+    }
+
+    /// Asserts that there are no tokens of the given kind.
+    #[track_caller]
+    pub fn assert_none(&self, kind: TokenKind) {
+        let count = self.count(kind);
+        if count != 0 {
+            panic!("expected no {} tokens, found {}", kind, count);
+        }
+    }
+
+    /// Asserts that no more than `max` compounds are open at once.
+    #[track_caller]
+    pub fn assert_max_depth(&self, max: usize) {
+        if self.max_depth > max {
+            panic!(
+                "expected a nesting depth of at most {}, found {}",
+                max, self.max_depth,
+            );
+        }
+    }
+
+    /// Asserts that strings and byte arrays total no more than `max` bytes.
+    #[track_caller]
+    pub fn assert_max_payload(&self, max: usize) {
+        let payload = self.str_len + self.bytes_len;
+        if payload > max {
+            panic!(
+                "expected at most {} bytes of strings and bytes, found {}",
+                max, payload,
+            );
+        }
+    }
+}
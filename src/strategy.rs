@@ -0,0 +1,266 @@
+//! [`proptest`](mod@proptest) strategies for token streams.
+//!
+//! Requires the `proptest` feature.
+//!
+//! [`token_stream`] generates streams that describe one well-formed value, and
+//! [`mutated_token_stream`] damages such streams so that they are usually, but
+//! not always, malformed. [`assert_never_panics`] feeds both into a
+//! `Deserialize` impl.
+
+use crate::de::Deserializer;
+use crate::token::{OwnedToken, Token};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::{select, Index};
+use proptest::test_runner::TestRunner;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+
+/// The names and variants that generated tokens are drawn from.
+const NAMES: &[&str] = &["A", "B", "S", "E"];
+
+/// The struct field names that generated tokens are drawn from.
+const FIELDS: &[&str] = &["a", "b", "c", "id", "value"];
+
+/// How many levels deep generated compounds nest.
+const DEPTH: u32 = 4;
+
+fn name() -> impl Strategy<Value = Cow<'static, str>> {
+    select(NAMES).prop_map(Cow::Borrowed)
+}
+
+fn string() -> impl Strategy<Value = String> {
+    vec(any::<char>(), 0..8).prop_map(|chars| chars.into_iter().collect())
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..8)
+}
+
+/// Generates any single token that is a complete value on its own.
+pub fn scalar_token() -> impl Strategy<Value = OwnedToken> {
+    prop_oneof![
+        any::<bool>().prop_map(OwnedToken::Bool),
+        any::<i8>().prop_map(OwnedToken::I8),
+        any::<i16>().prop_map(OwnedToken::I16),
+        any::<i32>().prop_map(OwnedToken::I32),
+        any::<i64>().prop_map(OwnedToken::I64),
+        any::<i128>().prop_map(OwnedToken::I128),
+        any::<u8>().prop_map(OwnedToken::U8),
+        any::<u16>().prop_map(OwnedToken::U16),
+        any::<u32>().prop_map(OwnedToken::U32),
+        any::<u64>().prop_map(OwnedToken::U64),
+        any::<u128>().prop_map(OwnedToken::U128),
+        any::<i128>().prop_map(OwnedToken::Number),
+        any::<f32>().prop_map(OwnedToken::F32),
+        any::<f64>().prop_map(OwnedToken::F64),
+        any::<char>().prop_map(OwnedToken::Char),
+        string().prop_map(OwnedToken::Str),
+        string().prop_map(OwnedToken::BorrowedStr),
+        string().prop_map(OwnedToken::String),
+        string().prop_map(OwnedToken::CollectStr),
+        bytes().prop_map(OwnedToken::Bytes),
+        bytes().prop_map(OwnedToken::BorrowedBytes),
+        bytes().prop_map(OwnedToken::ByteBuf),
+        Just(OwnedToken::None),
+        Just(OwnedToken::Unit),
+        name().prop_map(|name| OwnedToken::UnitStruct { name }),
+        (name(), name()).prop_map(|(name, variant)| OwnedToken::UnitVariant { name, variant }),
+    ]
+}
+
+fn wrap(header: OwnedToken, elements: Vec<Vec<OwnedToken>>, end: OwnedToken) -> Vec<OwnedToken> {
+    let mut tokens = vec![header];
+    tokens.extend(elements.into_iter().flatten());
+    tokens.push(end);
+    tokens
+}
+
+fn prefixed(header: OwnedToken, value: Vec<OwnedToken>) -> Vec<OwnedToken> {
+    let mut tokens = vec![header];
+    tokens.extend(value);
+    tokens
+}
+
+fn fields(
+    value: impl Strategy<Value = Vec<OwnedToken>>,
+) -> impl Strategy<Value = Vec<Vec<OwnedToken>>> {
+    vec((select(FIELDS), value), 0..4).prop_map(|fields| {
+        fields
+            .into_iter()
+            .map(|(key, value)| prefixed(OwnedToken::Str(key.to_owned()), value))
+            .collect()
+    })
+}
+
+/// Generates a stream describing exactly one well-formed value, as accepted
+/// by [`validate_tokens`](crate::validate_tokens).
+///
+/// ```
+/// # use proptest::prelude::*;
+/// # use serde_test::strategy::token_stream;
+/// # use serde_test::{validate_tokens, Token};
+/// #
+/// proptest!(|(stream in token_stream())| {
+///     let tokens: Vec<Token> = stream.iter().map(|t| t.as_token()).collect();
+///     prop_assert!(validate_tokens(&tokens).is_ok());
+/// });
+/// ```
+pub fn token_stream() -> impl Strategy<Value = Vec<OwnedToken>> {
+    let leaf = scalar_token().prop_map(|token| vec![token]);
+    leaf.prop_recursive(DEPTH, 64, 4, |value| {
+        let elements = || vec(value.clone(), 0..4);
+        prop_oneof![
+            value.clone().prop_map(|v| prefixed(OwnedToken::Some, v)),
+            (name(), value.clone())
+                .prop_map(|(name, v)| prefixed(OwnedToken::NewtypeStruct { name }, v)),
+            (name(), name(), value.clone()).prop_map(|(name, variant, v)| {
+                prefixed(OwnedToken::NewtypeVariant { name, variant }, v)
+            }),
+            (any::<bool>(), elements()).prop_map(|(declared, elements)| {
+                let len = Some(elements.len()).filter(|_| declared);
+                wrap(OwnedToken::Seq { len }, elements, OwnedToken::SeqEnd)
+            }),
+            elements().prop_map(|elements| {
+                let len = elements.len();
+                wrap(OwnedToken::Tuple { len }, elements, OwnedToken::TupleEnd)
+            }),
+            (name(), elements()).prop_map(|(name, elements)| {
+                let len = elements.len();
+                wrap(
+                    OwnedToken::TupleStruct { name, len },
+                    elements,
+                    OwnedToken::TupleStructEnd,
+                )
+            }),
+            (name(), name(), elements()).prop_map(|(name, variant, elements)| {
+                let len = elements.len();
+                wrap(
+                    OwnedToken::TupleVariant { name, variant, len },
+                    elements,
+                    OwnedToken::TupleVariantEnd,
+                )
+            }),
+            (any::<bool>(), vec((value.clone(), value.clone()), 0..4)).prop_map(
+                |(declared, entries)| {
+                    let len = Some(entries.len()).filter(|_| declared);
+                    let entries = entries
+                        .into_iter()
+                        .map(|(mut key, value)| {
+                            key.extend(value);
+                            key
+                        })
+                        .collect();
+                    wrap(OwnedToken::Map { len }, entries, OwnedToken::MapEnd)
+                }
+            ),
+            (name(), fields(value.clone())).prop_map(|(name, fields)| {
+                let len = fields.len();
+                wrap(
+                    OwnedToken::Struct { name, len },
+                    fields,
+                    OwnedToken::StructEnd,
+                )
+            }),
+            (name(), name(), fields(value.clone())).prop_map(|(name, variant, fields)| {
+                let len = fields.len();
+                wrap(
+                    OwnedToken::StructVariant { name, variant, len },
+                    fields,
+                    OwnedToken::StructVariantEnd,
+                )
+            }),
+        ]
+    })
+}
+
+/// A damage done to a stream by [`mutated_token_stream`].
+#[derive(Clone, Debug)]
+enum Mutation {
+    Remove(Index),
+    Duplicate(Index),
+    Insert(Index, OwnedToken),
+    Truncate(Index),
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    let end = prop_oneof![
+        Just(OwnedToken::SeqEnd),
+        Just(OwnedToken::TupleEnd),
+        Just(OwnedToken::MapEnd),
+        Just(OwnedToken::StructEnd),
+        Just(OwnedToken::StructVariantEnd),
+    ];
+    prop_oneof![
+        any::<Index>().prop_map(Mutation::Remove),
+        any::<Index>().prop_map(Mutation::Duplicate),
+        (any::<Index>(), prop_oneof![scalar_token(), end])
+            .prop_map(|(i, t)| Mutation::Insert(i, t)),
+        any::<Index>().prop_map(Mutation::Truncate),
+    ]
+}
+
+/// Generates a [`token_stream`] with one to three tokens removed, duplicated,
+/// inserted or cut off.
+///
+/// The result is usually malformed, but a mutation can happen to produce
+/// another valid stream.
+pub fn mutated_token_stream() -> impl Strategy<Value = Vec<OwnedToken>> {
+    (token_stream(), vec(mutation(), 1..=3)).prop_map(|(mut tokens, mutations)| {
+        for mutation in mutations {
+            match mutation {
+                Mutation::Remove(i) if !tokens.is_empty() => {
+                    tokens.remove(i.index(tokens.len()));
+                }
+                Mutation::Duplicate(i) if !tokens.is_empty() => {
+                    let i = i.index(tokens.len());
+                    tokens.insert(i, tokens[i].clone());
+                }
+                Mutation::Insert(i, token) => {
+                    tokens.insert(i.index(tokens.len() + 1), token);
+                }
+                Mutation::Truncate(i) => tokens.truncate(i.index(tokens.len() + 1)),
+                _ => {}
+            }
+        }
+        tokens
+    })
+}
+
+/// Deserializes `T` from generated well-formed and mutated streams, asserting
+/// that none of them cause a panic.
+///
+/// Errors are expected and ignored. A panic is reported together with the
+/// smallest stream proptest could shrink it to.
+///
+/// Types that query `is_human_readable` must be wrapped in
+/// [`Readable`](crate::Readable) or [`Compact`](crate::Compact).
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use serde_test::strategy::assert_never_panics;
+/// #
+/// #[derive(Deserialize)]
+/// enum E {
+///     A(u8),
+///     B { s: String },
+/// }
+///
+/// assert_never_panics::<Vec<Option<E>>>();
+/// ```
+#[track_caller]
+pub fn assert_never_panics<T>()
+where
+    T: DeserializeOwned,
+{
+    let streams = prop_oneof![token_stream(), mutated_token_stream()];
+    let result = TestRunner::default().run(&streams, |stream| {
+        let tokens: Vec<Token<'_, '_>> = stream.iter().map(OwnedToken::as_token).collect();
+        let mut de = Deserializer::new(&tokens);
+        let _ = T::deserialize(&mut de);
+        Ok(())
+    });
+    if let Err(err) = result {
+        panic!("deserialization panicked: {}", err);
+    }
+}
@@ -0,0 +1,712 @@
+use crate::ser::{ComplexSerializer, Serializer};
+use serde::ser::{
+    self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+
+/// A `serde::Serializer` that checks every call against the tokens of a
+/// [`Serializer`] while forwarding it to another serializer, and returns
+/// what that one returns.
+///
+/// The value is serialized only once, so a one-shot `Serialize` impl can be
+/// tested for both its tokens and the output of a real format. The first
+/// token mismatch is returned as an error of the other serializer, made
+/// with `ser::Error::custom`. Map entries serialized with `serialize_entry`
+/// are forwarded as `serialize_key` and `serialize_value`, and
+/// `is_human_readable` is answered by the other serializer.
+///
+/// ```
+/// # use serde::Serialize;
+/// # use serde_test::ser::Serializer;
+/// # use serde_test::{Tee, Token};
+/// #
+/// let tokens = [Token::Some, Token::Str("a")];
+///
+/// // Any serializer can be forwarded to, such as a second token serializer.
+/// let mut check = Serializer::new(&tokens);
+/// let mut other = Serializer::new(&tokens);
+/// Some("a").serialize(Tee::new(&mut check, &mut other)).unwrap();
+///
+/// assert_eq!(check.remaining(), 0);
+/// assert_eq!(other.remaining(), 0);
+/// ```
+///
+/// With the `json` feature, the same value can be checked against both its
+/// tokens and its JSON:
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # fn main() {
+/// # use serde::Serialize;
+/// # use serde_test::ser::Serializer;
+/// # use serde_test::{Tee, Token};
+/// #
+/// let tokens = [
+///     Token::Seq { len: Some(2) },
+///     Token::U8(1),
+///     Token::U8(2),
+///     Token::SeqEnd,
+/// ];
+/// let mut check = Serializer::new(&tokens);
+/// let mut json = Vec::new();
+/// vec![1u8, 2]
+///     .serialize(Tee::new(&mut check, &mut serde_json::Serializer::new(&mut json)))
+///     .unwrap();
+///
+/// assert_eq!(json, b"[1,2]");
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+pub struct Tee<'a, 'test, S> {
+    check: &'a mut Serializer<'test>,
+    inner: S,
+}
+
+impl<'a, 'test, S> Tee<'a, 'test, S> {
+    /// Creates a serializer checking the tokens of `check` and forwarding to
+    /// `inner`.
+    pub fn new(check: &'a mut Serializer<'test>, inner: S) -> Self {
+        Tee { check, inner }
+    }
+
+    fn dual(self) -> Dual<&'a mut Serializer<'test>, S> {
+        Dual {
+            a: self.check,
+            b: self.inner,
+        }
+    }
+}
+
+/// The compound of a [`Tee`], returned by `serialize_seq`,
+/// `serialize_struct`, etc.
+pub struct TeeCompound<'a, 'test, C> {
+    dual: DualCompound<ComplexSerializer<'a, 'test>, C>,
+}
+
+macro_rules! tee_methods {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<S::Ok, S::Error> {
+                self.dual().$method($($arg),*).map(|((), ok)| ok)
+            }
+        )*
+    };
+}
+
+macro_rules! tee_compounds {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $compound:ident;)*) => {
+        $(
+            fn $method(
+                self,
+                $($arg: $ty),*
+            ) -> Result<TeeCompound<'a, 'test, S::$compound>, S::Error> {
+                Ok(TeeCompound {
+                    dual: self.dual().$method($($arg),*)?,
+                })
+            }
+        )*
+    };
+}
+
+impl<'a, 'test, S> ser::Serializer for Tee<'a, 'test, S>
+where
+    S: ser::Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    type SerializeSeq = TeeCompound<'a, 'test, S::SerializeSeq>;
+    type SerializeTuple = TeeCompound<'a, 'test, S::SerializeTuple>;
+    type SerializeTupleStruct = TeeCompound<'a, 'test, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = TeeCompound<'a, 'test, S::SerializeTupleVariant>;
+    type SerializeMap = TeeCompound<'a, 'test, S::SerializeMap>;
+    type SerializeStruct = TeeCompound<'a, 'test, S::SerializeStruct>;
+    type SerializeStructVariant = TeeCompound<'a, 'test, S::SerializeStructVariant>;
+
+    tee_methods! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    tee_compounds! {
+        serialize_seq(len: Option<usize>) -> SerializeSeq;
+        serialize_tuple(len: usize) -> SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> SerializeStruct;
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.dual().serialize_some(value).map(|((), ok)| ok)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.dual()
+            .serialize_newtype_struct(name, value)
+            .map(|((), ok)| ok)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.dual()
+            .serialize_newtype_variant(name, variant_index, variant, value)
+            .map(|((), ok)| ok)
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Display,
+    {
+        self.dual().collect_str(value).map(|((), ok)| ok)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! tee_compound {
+    ($tr:ident { $($method:ident($($arg:ident: $ty:ty),*);)* } $($extra:tt)*) => {
+        impl<C> $tr for TeeCompound<'_, '_, C>
+        where
+            C: $tr,
+        {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            $(
+                fn $method<T>(&mut self, $($arg: $ty,)* value: &T) -> Result<(), C::Error>
+                where
+                    T: ?Sized + Serialize,
+                {
+                    self.dual.$method($($arg,)* value)
+                }
+            )*
+
+            $($extra)*
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.dual.end().map(|((), ok)| ok)
+            }
+        }
+    };
+}
+
+tee_compound!(SerializeSeq { serialize_element(); });
+tee_compound!(SerializeTuple { serialize_element(); });
+tee_compound!(SerializeTupleStruct { serialize_field(); });
+tee_compound!(SerializeTupleVariant { serialize_field(); });
+tee_compound!(SerializeMap { serialize_key(); serialize_value(); }
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), C::Error>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        self.dual.serialize_entry(key, value)
+    }
+);
+tee_compound!(SerializeStruct { serialize_field(key: &'static str); }
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.dual.skip_field(key)
+    }
+);
+tee_compound!(SerializeStructVariant { serialize_field(key: &'static str); }
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        self.dual.skip_field(key)
+    }
+);
+
+// `Dual` serializes to two serializers at once. A value nested in a
+// compound or wrapper can only be handed to each side through that side's
+// own `serialize_element`, `serialize_some` etc., so it is wrapped in an
+// `Outer` handed to side `a`, which from inside `Serialize::serialize` hands
+// an `Inner` to side `b`, which from inside its own `Serialize::serialize`
+// serializes the value to a `Dual` of the two nested serializers.
+//
+// Errors of side `a` are turned into errors of side `b`. When one side
+// fails, the other is failed with `STOPPED`, which is never reported.
+
+const STOPPED: &str = "serialization was stopped by the other serializer";
+
+struct Dual<A, B> {
+    a: A,
+    b: B,
+}
+
+struct DualCompound<A, B> {
+    a: A,
+    b: B,
+}
+
+/// A method serializing a value into `S`, so that the same method can be
+/// called on both sides of a [`Dual`].
+trait Forward<S> {
+    type Ok;
+    type Error: ser::Error;
+
+    fn forward<T>(&self, target: S, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize;
+}
+
+struct SomeValue;
+struct NewtypeStruct(&'static str);
+struct NewtypeVariant(&'static str, u32, &'static str);
+
+impl<S> Forward<S> for SomeValue
+where
+    S: ser::Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn forward<T>(&self, target: S, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        target.serialize_some(value)
+    }
+}
+
+impl<S> Forward<S> for NewtypeStruct
+where
+    S: ser::Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn forward<T>(&self, target: S, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        target.serialize_newtype_struct(self.0, value)
+    }
+}
+
+impl<S> Forward<S> for NewtypeVariant
+where
+    S: ser::Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn forward<T>(&self, target: S, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        target.serialize_newtype_variant(self.0, self.1, self.2, value)
+    }
+}
+
+macro_rules! forward_compound {
+    ($name:ident: $tr:ident::$method:ident) => {
+        struct $name;
+        forward_compound!(@impl $name: $tr, |_how, target, value| target.$method(value));
+    };
+    ($name:ident(key): $tr:ident::$method:ident) => {
+        struct $name(&'static str);
+        forward_compound!(@impl $name: $tr, |how, target, value| target.$method(how.0, value));
+    };
+    (@impl $name:ident: $tr:ident, |$how:ident, $target:ident, $value:ident| $call:expr) => {
+        impl<'c, 'm, C> Forward<&'c RefCell<&'m mut C>> for $name
+        where
+            C: $tr,
+        {
+            type Ok = ();
+            type Error = C::Error;
+
+            fn forward<T>(&self, target: &'c RefCell<&'m mut C>, value: &T) -> Result<(), C::Error>
+            where
+                T: ?Sized + Serialize,
+            {
+                let $how = self;
+                let $target = &mut **target.borrow_mut();
+                let $value = value;
+                $call
+            }
+        }
+    };
+}
+
+forward_compound!(SeqElement: SerializeSeq::serialize_element);
+forward_compound!(TupleElement: SerializeTuple::serialize_element);
+forward_compound!(TupleStructField: SerializeTupleStruct::serialize_field);
+forward_compound!(TupleVariantField: SerializeTupleVariant::serialize_field);
+forward_compound!(StructField(key): SerializeStruct::serialize_field);
+forward_compound!(StructVariantField(key): SerializeStructVariant::serialize_field);
+forward_compound!(MapKey: SerializeMap::serialize_key);
+forward_compound!(MapValue: SerializeMap::serialize_value);
+
+/// Handed to side `a`, and hands the value on to side `b` once `a` is
+/// ready for it.
+struct Outer<'v, T: ?Sized, B, F: Forward<B>> {
+    value: &'v T,
+    b: Cell<Option<B>>,
+    how: &'v F,
+    result: Cell<Option<Result<F::Ok, F::Error>>>,
+}
+
+impl<'v, T, B, F> Outer<'v, T, B, F>
+where
+    T: ?Sized,
+    F: Forward<B>,
+{
+    fn new(value: &'v T, b: B, how: &'v F) -> Self {
+        Outer {
+            value,
+            b: Cell::new(Some(b)),
+            how,
+            result: Cell::new(None),
+        }
+    }
+
+    /// Combines the result of side `a` with that of side `b`, preferring
+    /// the error of whichever side failed first.
+    fn settle<OA, EA>(self, a: Result<OA, EA>) -> Result<(OA, F::Ok), F::Error>
+    where
+        EA: Display,
+    {
+        match (a, self.result.into_inner()) {
+            (Ok(a), Some(Ok(b))) => Ok((a, b)),
+            (_, Some(Err(err))) => Err(err),
+            (Err(err), _) => Err(ser::Error::custom(err)),
+            (Ok(_), None) => Err(ser::Error::custom(
+                "the serializer returned without serializing the value",
+            )),
+        }
+    }
+}
+
+impl<T, B, F> Serialize for Outer<'_, T, B, F>
+where
+    T: ?Sized + Serialize,
+    F: Forward<B>,
+{
+    fn serialize<SA>(&self, a: SA) -> Result<SA::Ok, SA::Error>
+    where
+        SA: ser::Serializer,
+    {
+        let b = match self.b.take() {
+            Some(b) => b,
+            None => return Err(ser::Error::custom("the value was serialized twice")),
+        };
+        let inner = Inner {
+            value: self.value,
+            a: Cell::new(Some(a)),
+            result: Cell::new(None),
+        };
+        self.result.set(Some(self.how.forward(b, &inner)));
+        match inner.result.into_inner() {
+            Some(result) => result,
+            None => Err(ser::Error::custom(STOPPED)),
+        }
+    }
+}
+
+/// Handed to side `b` by an [`Outer`], with the serializer of side `a`.
+struct Inner<'v, T: ?Sized, SA: ser::Serializer> {
+    value: &'v T,
+    a: Cell<Option<SA>>,
+    result: Cell<Option<Result<SA::Ok, SA::Error>>>,
+}
+
+impl<T, SA> Serialize for Inner<'_, T, SA>
+where
+    T: ?Sized + Serialize,
+    SA: ser::Serializer,
+{
+    fn serialize<SB>(&self, b: SB) -> Result<SB::Ok, SB::Error>
+    where
+        SB: ser::Serializer,
+    {
+        let a = match self.a.take() {
+            Some(a) => a,
+            None => return Err(ser::Error::custom("the value was serialized twice")),
+        };
+        match self.value.serialize(Dual { a, b }) {
+            Ok((a, b)) => {
+                self.result.set(Some(Ok(a)));
+                Ok(b)
+            }
+            Err(err) => {
+                self.result.set(Some(Err(ser::Error::custom(STOPPED))));
+                Err(err)
+            }
+        }
+    }
+}
+
+/// The outputs of both sides of a [`Dual`] for a value forwarded through
+/// `F`, or the error of side `b`.
+type Forwarded<F, A, B> =
+    Result<(<F as Forward<A>>::Ok, <F as Forward<B>>::Ok), <F as Forward<B>>::Error>;
+
+/// Serializes `value` to both sides through the methods `how`.
+fn forward_value<A, B, F, T>(a: A, b: B, how: F, value: &T) -> Forwarded<F, A, B>
+where
+    F: Forward<A> + Forward<B>,
+    T: ?Sized + Serialize,
+{
+    let outer = Outer::new(value, b, &how);
+    let a = <F as Forward<A>>::forward(&how, a, &outer);
+    outer.settle(a)
+}
+
+macro_rules! dual_methods {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::Ok, B::Error> {
+                let a = self.a.$method($($arg),*).map_err(ser::Error::custom)?;
+                Ok((a, self.b.$method($($arg),*)?))
+            }
+        )*
+    };
+}
+
+macro_rules! dual_compounds {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $compound:ident;)*) => {
+        $(
+            fn $method(
+                self,
+                $($arg: $ty),*
+            ) -> Result<DualCompound<A::$compound, B::$compound>, B::Error> {
+                Ok(DualCompound {
+                    a: self.a.$method($($arg),*).map_err(ser::Error::custom)?,
+                    b: self.b.$method($($arg),*)?,
+                })
+            }
+        )*
+    };
+}
+
+impl<A, B> ser::Serializer for Dual<A, B>
+where
+    A: ser::Serializer,
+    B: ser::Serializer,
+{
+    type Ok = (A::Ok, B::Ok);
+    type Error = B::Error;
+
+    type SerializeSeq = DualCompound<A::SerializeSeq, B::SerializeSeq>;
+    type SerializeTuple = DualCompound<A::SerializeTuple, B::SerializeTuple>;
+    type SerializeTupleStruct = DualCompound<A::SerializeTupleStruct, B::SerializeTupleStruct>;
+    type SerializeTupleVariant = DualCompound<A::SerializeTupleVariant, B::SerializeTupleVariant>;
+    type SerializeMap = DualCompound<A::SerializeMap, B::SerializeMap>;
+    type SerializeStruct = DualCompound<A::SerializeStruct, B::SerializeStruct>;
+    type SerializeStructVariant =
+        DualCompound<A::SerializeStructVariant, B::SerializeStructVariant>;
+
+    dual_methods! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    dual_compounds! {
+        serialize_seq(len: Option<usize>) -> SerializeSeq;
+        serialize_tuple(len: usize) -> SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> SerializeStruct;
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, B::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        forward_value(self.a, self.b, SomeValue, value)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, B::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        forward_value(self.a, self.b, NewtypeStruct(name), value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, B::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let how = NewtypeVariant(name, variant_index, variant);
+        forward_value(self.a, self.b, how, value)
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok, B::Error>
+    where
+        T: ?Sized + Display,
+    {
+        let a = self.a.collect_str(value).map_err(ser::Error::custom)?;
+        Ok((a, self.b.collect_str(value)?))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.b.is_human_readable()
+    }
+}
+
+macro_rules! dual_compound {
+    ($tr:ident { $method:ident($($arg:ident: $ty:ty),*) => $how:expr; } $($extra:tt)*) => {
+        impl<A, B> $tr for DualCompound<A, B>
+        where
+            A: $tr,
+            B: $tr,
+        {
+            type Ok = (A::Ok, B::Ok);
+            type Error = B::Error;
+
+            fn $method<T>(&mut self, $($arg: $ty,)* value: &T) -> Result<(), B::Error>
+            where
+                T: ?Sized + Serialize,
+            {
+                let a = RefCell::new(&mut self.a);
+                let b = RefCell::new(&mut self.b);
+                forward_value(&a, &b, $how, value).map(drop)
+            }
+
+            $($extra)*
+
+            fn end(self) -> Result<Self::Ok, B::Error> {
+                let a = self.a.end().map_err(ser::Error::custom)?;
+                Ok((a, self.b.end()?))
+            }
+        }
+    };
+}
+
+dual_compound!(SerializeSeq { serialize_element() => SeqElement; });
+dual_compound!(SerializeTuple { serialize_element() => TupleElement; });
+dual_compound!(SerializeTupleStruct { serialize_field() => TupleStructField; });
+dual_compound!(SerializeTupleVariant { serialize_field() => TupleVariantField; });
+dual_compound!(SerializeStruct { serialize_field(key: &'static str) => StructField(key); }
+    fn skip_field(&mut self, key: &'static str) -> Result<(), B::Error> {
+        self.a.skip_field(key).map_err(ser::Error::custom)?;
+        self.b.skip_field(key)
+    }
+);
+dual_compound!(SerializeStructVariant {
+    serialize_field(key: &'static str) => StructVariantField(key);
+}
+    fn skip_field(&mut self, key: &'static str) -> Result<(), B::Error> {
+        self.a.skip_field(key).map_err(ser::Error::custom)?;
+        self.b.skip_field(key)
+    }
+);
+dual_compound!(SerializeMap { serialize_key() => MapKey; }
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), B::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let a = RefCell::new(&mut self.a);
+        let b = RefCell::new(&mut self.b);
+        forward_value(&a, &b, MapValue, value).map(drop)
+    }
+
+    // Side `a` sees `serialize_entry`, while side `b` is given the key and
+    // the value separately, since the entry cannot be handed to it while
+    // side `a` serializes the key and the value one after the other.
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), B::Error>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        let b = RefCell::new(&mut self.b);
+        let key = Outer::new(key, &b, &MapKey);
+        let value = Outer::new(value, &b, &MapValue);
+        let a = self.a.serialize_entry(&key, &value);
+        match (key.result.into_inner(), value.result.into_inner()) {
+            (Some(Err(err)), _) | (_, Some(Err(err))) => Err(err),
+            (Some(Ok(())), Some(Ok(()))) => a.map_err(ser::Error::custom),
+            _ => Err(ser::Error::custom(match a {
+                Err(err) => err.to_string(),
+                Ok(()) => "serialize_entry returned without serializing the entry".to_owned(),
+            })),
+        }
+    }
+);
@@ -1,6 +1,66 @@
+use crate::display;
+use crate::ser::Predicate;
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+/// A single serializer or deserializer event.
+///
+/// Tokens implement `Eq`, `Ord` and `Hash`, so streams can be deduplicated or
+/// used as keys. Floats compare by bit pattern: `F64(f64::NAN)` equals
+/// itself, and `F64(0.0)` differs from `F64(-0.0)`.
+///
+/// Every variant can be built in a const context, so streams shared between
+/// tests can be `static` or `const`, or built by `const fn` helpers.
+///
+/// ```
+/// # use serde_test::ser::{Event, Predicate};
+/// # use serde_test::{assert_ser_tokens, assert_tokens, Token};
+/// #
+/// const fn point(name: &'static str) -> [Token<'static, 'static>; 3] {
+///     [Token::Tuple { len: 2 }, Token::Str(name), Token::TupleEnd]
+/// }
+///
+/// const NONEMPTY: Predicate =
+///     Predicate::new("nonempty", |event| !matches!(event, Event::Str("")));
+///
+/// static SOME_ONE: [Token<'static, 'static>; 2] = [Token::Some, Token::U8(1)];
+/// const MATCHING: &[Token<'static, 'static>] = &[Token::Matches(&NONEMPTY)];
+/// const EITHER: &[Token<'static, 'static>] = &[Token::OneOf(&[Token::U8(1), Token::U16(1)])];
+///
+/// assert_tokens(&Some(1u8), &SOME_ONE);
+/// assert_ser_tokens(&"b", MATCHING);
+/// assert_ser_tokens(&1u16, EITHER);
+/// assert_eq!(point("p")[1], Token::Str("p"));
+/// ```
+///
+/// Struct, variant and field names borrow for `'test` like any other
+/// payload, so they can be built at runtime for types whose names are
+/// generated.
+///
+/// ```
+/// # use serde::ser::{Serialize, SerializeStruct, Serializer};
+/// # use serde_test::{assert_ser_tokens, Token};
+/// #
+/// struct Named(&'static str);
+///
+/// impl Serialize for Named {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         serializer.serialize_struct(self.0, 0)?.end()
+///     }
+/// }
+///
+/// let name = format!("Generated{}", 1);
+/// assert_ser_tokens(
+///     &Named("Generated1"),
+///     &[Token::Struct { name: &name, len: 0 }, Token::StructEnd],
+/// );
+/// ```
+#[derive(Copy, Clone, Debug)]
 #[non_exhaustive]
 pub enum Token<'test, 'de: 'test> {
     /// A serialized `bool`.
@@ -120,6 +180,93 @@ pub enum Token<'test, 'de: 'test> {
     /// ```
     F64(f64),
 
+    /// A serialized `f32`, matched by its exact bit pattern.
+    ///
+    /// Unlike [`F32`](Token::F32), this tells `-0.0` from `0.0` and one NaN
+    /// from another.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens, assert_ser_tokens_error, Token};
+    /// #
+    /// assert_ser_tokens(&f32::NAN, &[Token::F32Bits(f32::NAN.to_bits())]);
+    /// assert_ser_tokens_error(
+    ///     &-0.0f32,
+    ///     &[Token::F32Bits(0)],
+    ///     "expected Token::F32Bits(0) at index 0 of 1 but serialized as F32Bits(2147483648)",
+    /// );
+    /// ```
+    F32Bits(u32),
+
+    /// A serialized `f64`, matched by its exact bit pattern.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// assert_tokens(&-0.0f64, &[Token::F64Bits((-0.0f64).to_bits())]);
+    /// ```
+    F64Bits(u64),
+
+    /// A serialized `isize`.
+    ///
+    /// Serde writes an `isize` as an `i64` on every platform, which this
+    /// matches as long as the value fits in an `isize`. It deserializes as an
+    /// `i64`, so token lists need no casts or `cfg`s for lengths and indexes.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// assert_tokens(&-1_isize, &[Token::Isize(-1)]);
+    /// ```
+    Isize(isize),
+
+    /// A serialized `usize`.
+    ///
+    /// Like [`Isize`](Token::Isize), this matches a `u64`, and deserializes as
+    /// a `u64`.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// let v = vec!['a', 'b'];
+    /// assert_tokens(&v.len(), &[Token::Usize(v.len())]);
+    /// ```
+    Usize(usize),
+
+    /// An integer of any width, matched by its value alone.
+    ///
+    /// The serializer accepts any of `serialize_i8` through
+    /// `serialize_u128` for it, as long as the value is equal. The
+    /// deserializer visits it as a `u64` if it is not negative and as an
+    /// `i64` if it is, like a self-describing format would, falling back to
+    /// 128 bits for values too large. Choose another width with
+    /// [`Deserializer::number_kind`](crate::de::Deserializer::number_kind).
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens, assert_tokens, Token};
+    /// #
+    /// assert_ser_tokens(&300u16, &[Token::Number(300)]);
+    /// assert_ser_tokens(&300i64, &[Token::Number(300)]);
+    /// assert_tokens(&-1i8, &[Token::Number(-1)]);
+    /// ```
+    ///
+    /// It matches the same way wherever serialized tokens are compared, so a
+    /// [`check_tokens`](crate::check_tokens) report only blames the tokens
+    /// that differ.
+    ///
+    /// ```
+    /// # use serde_test::{check_tokens, Token};
+    /// #
+    /// let tokens = [
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::Number(3),
+    ///     Token::U8(5),
+    ///     Token::SeqEnd,
+    /// ];
+    /// let report = check_tokens(&vec![3u8, 4], &tokens);
+    /// assert_eq!(report.mismatches()[0].position(), Some(2));
+    /// ```
+    Number(i128),
+
     /// A serialized `char`.
     ///
     /// ```
@@ -151,6 +298,10 @@ pub enum Token<'test, 'de: 'test> {
 
     /// A serialized `String`.
     ///
+    /// The deserializer hands it to `visit_string` in a fresh allocation,
+    /// unless [`owned_as_transient`](crate::de::Deserializer::owned_as_transient)
+    /// is set.
+    ///
     /// ```
     /// # use serde_test::{assert_tokens, Token};
     /// #
@@ -159,6 +310,43 @@ pub enum Token<'test, 'de: 'test> {
     /// ```
     String(&'test str),
 
+    /// A string serialized through `collect_str`, which formats a `Display`
+    /// value without the caller allocating a `String` first.
+    ///
+    /// [`Str`](Token::Str) accepts either path, so this is only needed to
+    /// check that `collect_str` was used. Deserialized like `Str`.
+    ///
+    /// ```
+    /// # use serde::{Serialize, Serializer};
+    /// # use serde_test::{assert_ser_tokens, assert_ser_tokens_error, Token};
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// struct Collected(Ipv4Addr);
+    ///
+    /// impl Serialize for Collected {
+    ///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         serializer.collect_str(&self.0)
+    ///     }
+    /// }
+    ///
+    /// struct Allocated(Ipv4Addr);
+    ///
+    /// impl Serialize for Allocated {
+    ///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         serializer.serialize_str(&self.0.to_string())
+    ///     }
+    /// }
+    ///
+    /// let tokens = [Token::CollectStr("127.0.0.1")];
+    /// assert_ser_tokens(&Collected(Ipv4Addr::LOCALHOST), &tokens);
+    /// assert_ser_tokens_error(
+    ///     &Allocated(Ipv4Addr::LOCALHOST),
+    ///     &tokens,
+    ///     r#"expected Token::CollectStr("127.0.0.1") at index 0 of 1 but serialized as Str("127.0.0.1")"#,
+    /// );
+    /// ```
+    CollectStr(&'test str),
+
     /// A serialized `[u8]`
     Bytes(&'test [u8]),
 
@@ -166,6 +354,11 @@ pub enum Token<'test, 'de: 'test> {
     BorrowedBytes(&'de [u8]),
 
     /// A serialized `ByteBuf`
+    ///
+    /// Like [`String`](Token::String), it is handed to `visit_byte_buf` in a
+    /// fresh allocation, unless
+    /// [`owned_as_transient`](crate::de::Deserializer::owned_as_transient) is
+    /// set.
     ByteBuf(&'test [u8]),
 
     /// A serialized `Option<T>` containing none.
@@ -210,7 +403,7 @@ pub enum Token<'test, 'de: 'test> {
     ///
     /// assert_tokens(&X, &[Token::UnitStruct { name: "X" }]);
     /// ```
-    UnitStruct { name: &'static str },
+    UnitStruct { name: &'test str },
 
     /// A unit variant of an enum.
     ///
@@ -234,8 +427,8 @@ pub enum Token<'test, 'de: 'test> {
     /// # }
     /// ```
     UnitVariant {
-        name: &'static str,
-        variant: &'static str,
+        name: &'test str,
+        variant: &'test str,
     },
 
     /// The header to a serialized newtype struct of the given name.
@@ -257,7 +450,7 @@ pub enum Token<'test, 'de: 'test> {
     /// );
     /// # }
     /// ```
-    NewtypeStruct { name: &'static str },
+    NewtypeStruct { name: &'test str },
 
     /// The header to a newtype variant of an enum.
     ///
@@ -287,8 +480,8 @@ pub enum Token<'test, 'de: 'test> {
     /// # }
     /// ```
     NewtypeVariant {
-        name: &'static str,
-        variant: &'static str,
+        name: &'test str,
+        variant: &'test str,
     },
 
     /// The header to a sequence.
@@ -364,7 +557,7 @@ pub enum Token<'test, 'de: 'test> {
     /// );
     /// # }
     /// ```
-    TupleStruct { name: &'static str, len: usize },
+    TupleStruct { name: &'test str, len: usize },
 
     /// An indicator of the end of a tuple struct.
     TupleStructEnd,
@@ -401,8 +594,8 @@ pub enum Token<'test, 'de: 'test> {
     /// # }
     /// ```
     TupleVariant {
-        name: &'static str,
-        variant: &'static str,
+        name: &'test str,
+        variant: &'test str,
         len: usize,
     },
 
@@ -439,6 +632,49 @@ pub enum Token<'test, 'de: 'test> {
     /// An indicator of the end of a map.
     MapEnd,
 
+    /// A marker before a map key, requiring the entry to be serialized with
+    /// a single `serialize_entry` call rather than `serialize_key` followed
+    /// by `serialize_value`.
+    ///
+    /// Entries without it may use either. Ignored when deserializing.
+    ///
+    /// ```
+    /// # use serde::ser::{Serialize, SerializeMap, Serializer};
+    /// # use serde_test::{assert_ser_tokens, assert_ser_tokens_error, Token};
+    /// #
+    /// struct Pair(bool);
+    ///
+    /// impl Serialize for Pair {
+    ///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         let mut map = serializer.serialize_map(Some(1))?;
+    ///         if self.0 {
+    ///             map.serialize_entry("k", "v")?;
+    ///         } else {
+    ///             map.serialize_key("k")?;
+    ///             map.serialize_value("v")?;
+    ///         }
+    ///         map.end()
+    ///     }
+    /// }
+    ///
+    /// assert_ser_tokens(
+    ///     &Pair(true),
+    ///     &[
+    ///         Token::Map { len: Some(1) },
+    ///         Token::MapEntry,
+    ///         Token::Str("k"),
+    ///         Token::Str("v"),
+    ///         Token::MapEnd,
+    ///     ],
+    /// );
+    /// assert_ser_tokens_error(
+    ///     &Pair(false),
+    ///     &[Token::Map { len: Some(1) }, Token::MapEntry],
+    ///     "expected Token::MapEntry at index 1 of 2 but serialize_key was called",
+    /// );
+    /// ```
+    MapEntry,
+
     /// The header of a struct.
     ///
     /// After this header are the fields of the struct, followed by `StructEnd`.
@@ -468,7 +704,7 @@ pub enum Token<'test, 'de: 'test> {
     /// );
     /// # }
     /// ```
-    Struct { name: &'static str, len: usize },
+    Struct { name: &'test str, len: usize },
 
     /// An indicator of the end of a struct.
     StructEnd,
@@ -505,8 +741,8 @@ pub enum Token<'test, 'de: 'test> {
     /// # }
     /// ```
     StructVariant {
-        name: &'static str,
-        variant: &'static str,
+        name: &'test str,
+        variant: &'test str,
         len: usize,
     },
 
@@ -515,7 +751,7 @@ pub enum Token<'test, 'de: 'test> {
 
     /// optional indicator that a [`Struct`]/[`StructVariant`] field has been
     /// skipped.
-    SkipStructField { name: &'static str },
+    SkipStructField { name: &'test str },
 
     /// The header to an enum of the given name.
     ///
@@ -571,31 +807,956 @@ pub enum Token<'test, 'de: 'test> {
     /// );
     /// # }
     /// ```
-    Enum { name: &'static str },
+    ///
+    /// Through `deserialize_any`, an enum is visited as a map with a single
+    /// entry, keyed by the variant. The variant may be given as any string,
+    /// bytes or integer token.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens, Token};
+    /// # use std::collections::BTreeMap;
+    /// #
+    /// let map = BTreeMap::from([(-1i16, 7u8)]);
+    /// assert_de_tokens(
+    ///     &map,
+    ///     &[Token::Enum { name: "E" }, Token::I16(-1), Token::U8(7)],
+    /// );
+    /// ```
+    ///
+    /// To model formats that key externally tagged enums by variant index,
+    /// the variant may also be given as a `U32` or `U64` holding its index.
+    ///
+    /// ```
+    /// # use serde::{Deserialize, Serialize};
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     A,
+    ///     B(u8),
+    /// }
+    ///
+    /// assert_tokens(
+    ///     &E::B(0),
+    ///     &[Token::Enum { name: "E" }, Token::U32(1), Token::U8(0)],
+    /// );
+    /// assert_tokens(
+    ///     &E::A,
+    ///     &[Token::Enum { name: "E" }, Token::U64(0), Token::Unit],
+    /// );
+    /// ```
+    Enum { name: &'test str },
+
+    /// The index of an enum variant.
+    ///
+    /// Placed right before a [`UnitVariant`], [`NewtypeVariant`],
+    /// [`TupleVariant`], [`StructVariant`] or [`Enum`] token, it makes the
+    /// serializer check the `variant_index` of that variant as well as its
+    /// name, as index-based formats depend on it. Deserialization skips it.
+    ///
+    /// Placed right after an [`Enum`] token instead of the variant name, it
+    /// selects the variant by index alone. The deserializer visits it with
+    /// `visit_u32`.
+    ///
+    /// ```
+    /// # use serde::{Deserialize, Serialize};
+    /// # use serde_test::{assert_ser_tokens_error, assert_tokens, Token};
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     A,
+    ///     B(u8),
+    /// }
+    ///
+    /// assert_tokens(
+    ///     &E::B(0),
+    ///     &[
+    ///         Token::VariantIndex(1),
+    ///         Token::NewtypeVariant { name: "E", variant: "B" },
+    ///         Token::U8(0),
+    ///     ],
+    /// );
+    ///
+    /// assert_tokens(
+    ///     &E::B(0),
+    ///     &[
+    ///         Token::Enum { name: "E" },
+    ///         Token::VariantIndex(1),
+    ///         Token::U8(0),
+    ///     ],
+    /// );
+    ///
+    /// assert_ser_tokens_error(
+    ///     &E::A,
+    ///     &[Token::VariantIndex(1)],
+    ///     "expected Token::VariantIndex(1) at index 0 of 1 but serialized as VariantIndex(0)",
+    /// );
+    /// ```
+    ///
+    /// [`UnitVariant`]: Token::UnitVariant
+    /// [`NewtypeVariant`]: Token::NewtypeVariant
+    /// [`TupleVariant`]: Token::TupleVariant
+    /// [`StructVariant`]: Token::StructVariant
+    /// [`Enum`]: Token::Enum
+    VariantIndex(u32),
+
+    /// A struct field identified by its index instead of its name, as
+    /// index-based formats do. It is visited with `visit_u64`.
+    ///
+    /// Only understood by the deserializer.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::{assert_de_tokens, Token};
+    /// #
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// struct S {
+    ///     a: u8,
+    ///     b: u8,
+    /// }
+    ///
+    /// assert_de_tokens(
+    ///     &S { a: 0, b: 1 },
+    ///     &[
+    ///         Token::Struct { name: "S", len: 2 },
+    ///         Token::FieldIndex(1),
+    ///         Token::U8(1),
+    ///         Token::FieldIndex(0),
+    ///         Token::U8(0),
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    FieldIndex(u64),
+
+    /// A struct field or enum variant name that must be deserialized with
+    /// `deserialize_identifier`, where it is visited with `visit_str`. Any
+    /// other `deserialize_*` method fails on it, catching `Deserialize` impls
+    /// that read identifiers as ordinary strings.
+    ///
+    /// The serializer accepts it in place of a struct field name.
+    ///
+    /// ```
+    /// # use serde::{Deserialize, Serialize};
+    /// # use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// struct S {
+    ///     a: u8,
+    /// }
+    ///
+    /// assert_tokens(
+    ///     &S { a: 0 },
+    ///     &[
+    ///         Token::Struct { name: "S", len: 1 },
+    ///         Token::Identifier("a"),
+    ///         Token::U8(0),
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    ///
+    /// assert_de_tokens_error::<String>(
+    ///     &[Token::Identifier("a")],
+    ///     "Token::Identifier(\"a\") at index 0 of 1 is only accepted by deserialize_identifier",
+    /// );
+    /// ```
+    Identifier(&'test str),
+
+    /// A struct field or enum variant index that must be deserialized with
+    /// `deserialize_identifier`, where it is visited with `visit_u64`, like
+    /// [`Identifier`](Token::Identifier) is for names.
+    ///
+    /// Only understood by the deserializer.
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// # use serde_test::{assert_de_tokens, Token};
+    /// #
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     A,
+    ///     B,
+    /// }
+    ///
+    /// assert_de_tokens(
+    ///     &E::B,
+    ///     &[Token::Enum { name: "E" }, Token::IdentifierIndex(1), Token::Unit],
+    /// );
+    /// ```
+    IdentifierIndex(u64),
+
+    /// Any one of the listed tokens, for a position that may legitimately be
+    /// encoded in several ways, such as an `f32` that a format promotes to
+    /// `f64`.
+    ///
+    /// The serializer accepts a value matching any of the alternatives; the
+    /// deserializer presents the first one. Each alternative must be a
+    /// value on its own: not a compound, an end token, or a token like
+    /// [`Some`](Token::Some) that prefixes another value.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens, assert_ser_tokens, Token};
+    /// #
+    /// let either = Token::OneOf(&[Token::F64(1.5), Token::F32(1.5)]);
+    /// assert_ser_tokens(&1.5f32, &[either]);
+    /// assert_ser_tokens(&1.5f64, &[either]);
+    /// assert_de_tokens(&1.5f32, &[either]);
+    /// ```
+    OneOf(&'static [Token<'static, 'static>]),
+
+    /// Any value satisfying the [`Predicate`], for values such as
+    /// timestamps or random IDs that differ from run to run.
+    ///
+    /// Only understood by the serializer.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use serde_test::ser::{Event, Predicate};
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// #
+    /// #[derive(Serialize)]
+    /// struct Session {
+    ///     id: u64,
+    ///     user: &'static str,
+    /// }
+    ///
+    /// static NONZERO: Predicate =
+    ///     Predicate::new("nonzero u64", |event| matches!(event, Event::U64(v) if *v != 0));
+    ///
+    /// assert_ser_tokens(
+    ///     &Session { id: 0x5eed, user: "sky" },
+    ///     &[
+    ///         Token::Struct { name: "Session", len: 2 },
+    ///         Token::Str("id"),
+    ///         Token::Matches(&NONZERO),
+    ///         Token::Str("user"),
+    ///         Token::Str("sky"),
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    ///
+    /// It may also be one of the alternatives of a [`OneOf`](Token::OneOf).
+    ///
+    /// ```
+    /// # use serde_test::ser::{Event, Predicate};
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// #
+    /// static NONEMPTY: Predicate =
+    ///     Predicate::new("nonempty", |event| !matches!(event, Event::Str("")));
+    /// static ALT: [Token<'static, 'static>; 2] = [Token::Matches(&NONEMPTY), Token::U8(0)];
+    ///
+    /// assert_ser_tokens(&"b", &[Token::OneOf(&ALT)]);
+    /// assert_ser_tokens(&0u8, &[Token::OneOf(&ALT)]);
+    /// ```
+    Matches(&'static Predicate),
+
+    /// A marker that answers `is_human_readable` with the given value from
+    /// this point in the stream until the next marker, for types that
+    /// switch representation partway through, such as a compact blob inside
+    /// a readable struct.
+    ///
+    /// It is consumed as soon as the token before it is, so a value placed
+    /// after it sees the new answer when it asks. Like
+    /// [`SkipStructField`](Token::SkipStructField), it is not a value. The
+    /// [`Configure`](crate::Configure) wrappers answer `is_human_readable`
+    /// themselves, so markers have no effect on a wrapped value.
+    ///
+    /// ```
+    /// # use serde::{Deserialize, Serialize};
+    /// # use serde_test::{assert_tokens, Token};
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// struct Host {
+    ///     name: String,
+    ///     addr: Ipv4Addr,
+    /// }
+    ///
+    /// let host = Host {
+    ///     name: "localhost".to_owned(),
+    ///     addr: Ipv4Addr::LOCALHOST,
+    /// };
+    /// assert_tokens(
+    ///     &host,
+    ///     &[
+    ///         Token::Readable(true),
+    ///         Token::Struct { name: "Host", len: 2 },
+    ///         Token::Str("name"),
+    ///         Token::Str("localhost"),
+    ///         Token::Str("addr"),
+    ///         Token::Readable(false),
+    ///         Token::Tuple { len: 4 },
+    ///         Token::U8(127),
+    ///         Token::U8(0),
+    ///         Token::U8(0),
+    ///         Token::U8(1),
+    ///         Token::TupleEnd,
+    ///         Token::Readable(true),
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    Readable(bool),
 }
 
+/// `{}` renders a token like `Debug`. `{:#}` renders the compact one-line
+/// form used by [`DisplayTokens`](crate::DisplayTokens).
+///
+/// ```
+/// # use serde_test::Token;
+/// #
+/// let token = Token::TupleVariant { name: "E", variant: "V", len: 2 };
+/// assert_eq!(format!("{:#}", token), "tuple E::V(2)");
+/// assert_eq!(format!("{:#}", Token::U16(7)), "7u16");
+/// ```
 impl Display for Token<'_, '_> {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-        Debug::fmt(self, formatter)
+        if formatter.alternate() {
+            display::write_compact(*self, formatter)
+        } else {
+            Debug::fmt(self, formatter)
+        }
+    }
+}
+
+impl<'test, 'de> Token<'test, 'de> {
+    /// Returns the kind of this token, without its payload.
+    ///
+    /// ```
+    /// # use serde_test::{Token, TokenKind};
+    /// #
+    /// assert_eq!(Token::Str("a").kind(), TokenKind::Str);
+    /// assert_eq!(Token::Seq { len: None }.kind(), TokenKind::Seq);
+    /// ```
+    pub const fn kind(&self) -> TokenKind {
+        match *self {
+            Token::Bool(_) => TokenKind::Bool,
+            Token::I8(_) => TokenKind::I8,
+            Token::I16(_) => TokenKind::I16,
+            Token::I32(_) => TokenKind::I32,
+            Token::I64(_) => TokenKind::I64,
+            Token::I128(_) => TokenKind::I128,
+            Token::Isize(_) => TokenKind::Isize,
+            Token::U8(_) => TokenKind::U8,
+            Token::U16(_) => TokenKind::U16,
+            Token::U32(_) => TokenKind::U32,
+            Token::U64(_) => TokenKind::U64,
+            Token::U128(_) => TokenKind::U128,
+            Token::Usize(_) => TokenKind::Usize,
+            Token::Number(_) => TokenKind::Number,
+            Token::F32(_) => TokenKind::F32,
+            Token::F64(_) => TokenKind::F64,
+            Token::F32Bits(_) => TokenKind::F32Bits,
+            Token::F64Bits(_) => TokenKind::F64Bits,
+            Token::Char(_) => TokenKind::Char,
+            Token::Str(_) => TokenKind::Str,
+            Token::BorrowedStr(_) => TokenKind::BorrowedStr,
+            Token::String(_) => TokenKind::String,
+            Token::CollectStr(_) => TokenKind::CollectStr,
+            Token::Bytes(_) => TokenKind::Bytes,
+            Token::BorrowedBytes(_) => TokenKind::BorrowedBytes,
+            Token::ByteBuf(_) => TokenKind::ByteBuf,
+            Token::None => TokenKind::None,
+            Token::Some => TokenKind::Some,
+            Token::Unit => TokenKind::Unit,
+            Token::UnitStruct { .. } => TokenKind::UnitStruct,
+            Token::UnitVariant { .. } => TokenKind::UnitVariant,
+            Token::NewtypeStruct { .. } => TokenKind::NewtypeStruct,
+            Token::NewtypeVariant { .. } => TokenKind::NewtypeVariant,
+            Token::Seq { .. } => TokenKind::Seq,
+            Token::SeqEnd => TokenKind::SeqEnd,
+            Token::Tuple { .. } => TokenKind::Tuple,
+            Token::TupleEnd => TokenKind::TupleEnd,
+            Token::TupleStruct { .. } => TokenKind::TupleStruct,
+            Token::TupleStructEnd => TokenKind::TupleStructEnd,
+            Token::TupleVariant { .. } => TokenKind::TupleVariant,
+            Token::TupleVariantEnd => TokenKind::TupleVariantEnd,
+            Token::Map { .. } => TokenKind::Map,
+            Token::MapEnd => TokenKind::MapEnd,
+            Token::MapEntry => TokenKind::MapEntry,
+            Token::Struct { .. } => TokenKind::Struct,
+            Token::StructEnd => TokenKind::StructEnd,
+            Token::StructVariant { .. } => TokenKind::StructVariant,
+            Token::StructVariantEnd => TokenKind::StructVariantEnd,
+            Token::SkipStructField { .. } => TokenKind::SkipStructField,
+            Token::FieldIndex(_) => TokenKind::FieldIndex,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::IdentifierIndex(_) => TokenKind::IdentifierIndex,
+            Token::Enum { .. } => TokenKind::Enum,
+            Token::VariantIndex(_) => TokenKind::VariantIndex,
+            Token::OneOf(_) => TokenKind::OneOf,
+            Token::Matches(_) => TokenKind::Matches,
+            Token::Readable(_) => TokenKind::Readable,
+        }
+    }
+
+    /// Returns the string of a `Str`, `BorrowedStr`, `String` or
+    /// `CollectStr` token.
+    pub const fn as_str(&self) -> Option<&'test str> {
+        match *self {
+            Token::Str(v) | Token::BorrowedStr(v) | Token::String(v) | Token::CollectStr(v) => {
+                Some(v)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of a `Bytes`, `BorrowedBytes` or `ByteBuf` token.
+    pub const fn as_bytes(&self) -> Option<&'test [u8]> {
+        match *self {
+            Token::Bytes(v) | Token::BorrowedBytes(v) | Token::ByteBuf(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Whether this token closes a compound, like [`Token::SeqEnd`].
+    pub const fn is_end(&self) -> bool {
+        self.kind().is_end()
+    }
+
+    /// Whether this token opens a compound that an end token closes, like
+    /// [`Token::Seq`].
+    pub const fn is_compound_start(&self) -> bool {
+        self.kind().is_compound_start()
+    }
+}
+
+/// The payload of a token in a form that is totally ordered.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Key<'a> {
+    Empty,
+    Bool(bool),
+    Int(i128),
+    UInt(u128),
+    Float(i64),
+    Char(char),
+    Str(&'a str),
+    Bytes(&'a [u8]),
+    Len(Option<usize>),
+    Name(&'a str, Option<&'a str>, Option<usize>),
+    Tokens(&'a [Token<'a, 'a>]),
+}
+
+// Maps float bits onto integers whose order matches `total_cmp`.
+fn float_key(bits: i64) -> Key<'static> {
+    Key::Float(bits ^ (((bits >> 63) as u64) >> 1) as i64)
+}
+
+impl<'test> Token<'test, '_> {
+    fn key(&self) -> (u8, Key<'test>) {
+        match *self {
+            Token::Bool(v) => (0, Key::Bool(v)),
+            Token::I8(v) => (1, Key::Int(v.into())),
+            Token::I16(v) => (2, Key::Int(v.into())),
+            Token::I32(v) => (3, Key::Int(v.into())),
+            Token::I64(v) => (4, Key::Int(v.into())),
+            Token::I128(v) => (5, Key::Int(v)),
+            Token::U8(v) => (6, Key::UInt(v.into())),
+            Token::U16(v) => (7, Key::UInt(v.into())),
+            Token::U32(v) => (8, Key::UInt(v.into())),
+            Token::U64(v) => (9, Key::UInt(v.into())),
+            Token::U128(v) => (10, Key::UInt(v)),
+            Token::F32(v) => (11, float_key((v.to_bits() as i32).into())),
+            Token::F64(v) => (12, float_key(v.to_bits() as i64)),
+            Token::Char(v) => (13, Key::Char(v)),
+            Token::Str(v) => (14, Key::Str(v)),
+            Token::BorrowedStr(v) => (15, Key::Str(v)),
+            Token::String(v) => (16, Key::Str(v)),
+            Token::Bytes(v) => (17, Key::Bytes(v)),
+            Token::BorrowedBytes(v) => (18, Key::Bytes(v)),
+            Token::ByteBuf(v) => (19, Key::Bytes(v)),
+            Token::None => (20, Key::Empty),
+            Token::Some => (21, Key::Empty),
+            Token::Unit => (22, Key::Empty),
+            Token::UnitStruct { name } => (23, Key::Name(name, None, None)),
+            Token::UnitVariant { name, variant } => (24, Key::Name(name, Some(variant), None)),
+            Token::NewtypeStruct { name } => (25, Key::Name(name, None, None)),
+            Token::NewtypeVariant { name, variant } => (26, Key::Name(name, Some(variant), None)),
+            Token::Seq { len } => (27, Key::Len(len)),
+            Token::SeqEnd => (28, Key::Empty),
+            Token::Tuple { len } => (29, Key::Len(Some(len))),
+            Token::TupleEnd => (30, Key::Empty),
+            Token::TupleStruct { name, len } => (31, Key::Name(name, None, Some(len))),
+            Token::TupleStructEnd => (32, Key::Empty),
+            Token::TupleVariant { name, variant, len } => {
+                (33, Key::Name(name, Some(variant), Some(len)))
+            }
+            Token::TupleVariantEnd => (34, Key::Empty),
+            Token::Map { len } => (35, Key::Len(len)),
+            Token::MapEnd => (36, Key::Empty),
+            Token::Struct { name, len } => (37, Key::Name(name, None, Some(len))),
+            Token::StructEnd => (38, Key::Empty),
+            Token::StructVariant { name, variant, len } => {
+                (39, Key::Name(name, Some(variant), Some(len)))
+            }
+            Token::StructVariantEnd => (40, Key::Empty),
+            Token::SkipStructField { name } => (41, Key::Name(name, None, None)),
+            Token::Enum { name } => (42, Key::Name(name, None, None)),
+            Token::VariantIndex(v) => (43, Key::UInt(v.into())),
+            Token::FieldIndex(v) => (44, Key::UInt(v.into())),
+            Token::F32Bits(v) => (45, Key::UInt(v.into())),
+            Token::F64Bits(v) => (46, Key::UInt(v.into())),
+            Token::Isize(v) => (47, Key::Int(v as i128)),
+            Token::Usize(v) => (48, Key::UInt(v as u128)),
+            Token::OneOf(v) => (49, Key::Tokens(v)),
+            Token::Matches(v) => (50, Key::Name(v.name(), None, None)),
+            Token::Readable(v) => (51, Key::Bool(v)),
+            Token::CollectStr(v) => (52, Key::Str(v)),
+            Token::MapEntry => (53, Key::Empty),
+            Token::Identifier(v) => (54, Key::Str(v)),
+            Token::IdentifierIndex(v) => (55, Key::UInt(v.into())),
+            Token::Number(v) => (56, Key::Int(v)),
+        }
+    }
+}
+
+impl<'test, 'de> From<&Token<'test, 'de>> for Token<'test, 'de> {
+    fn from(token: &Token<'test, 'de>) -> Self {
+        *token
+    }
+}
+
+impl<'t, 'd> PartialEq<Token<'t, 'd>> for Token<'_, '_> {
+    fn eq(&self, other: &Token<'t, 'd>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Token<'_, '_> {}
+
+impl<'t, 'd> PartialOrd<Token<'t, 'd>> for Token<'_, '_> {
+    fn partial_cmp(&self, other: &Token<'t, 'd>) -> Option<Ordering> {
+        Some(self.key().cmp(&other.key()))
+    }
+}
+
+impl Ord for Token<'_, '_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for Token<'_, '_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// A [`Token`] that owns its string and bytes payloads, and its names unless
+/// they are `'static`.
+///
+/// Useful for streams that are generated at runtime or kept around in
+/// collections. Use [`OwnedToken::as_token`] to borrow it back as a `Token`,
+/// and `OwnedToken::from` to convert the other way.
+///
+/// ```
+/// # use serde_test::{OwnedToken, Token};
+/// #
+/// let name = String::from("generated");
+/// let owned = OwnedToken::from(Token::Str(&name));
+/// drop(name);
+///
+/// assert_eq!(owned, OwnedToken::Str("generated".to_owned()));
+/// assert_eq!(owned.as_token(), Token::Str("generated"));
+/// ```
+///
+/// The assert functions take a `&Vec<OwnedToken>` or `&[OwnedToken]` as
+/// readily as `Token`s, and a [`Deserializer`](crate::de::Deserializer) can
+/// read from one through [`from_source`](crate::de::Deserializer::from_source),
+/// so generated payloads never need to be leaked to outlive the stream.
+///
+/// ```
+/// # use serde_test::{assert_tokens, OwnedToken};
+/// #
+/// let words: Vec<String> = (1..=2).map(|n| "ab".repeat(n)).collect();
+///
+/// let mut tokens = vec![OwnedToken::Seq { len: Some(words.len()) }];
+/// tokens.extend(words.iter().cloned().map(OwnedToken::Str));
+/// tokens.push(OwnedToken::SeqEnd);
+///
+/// assert_tokens(&words, &tokens);
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum OwnedToken {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    F32Bits(u32),
+    F64Bits(u64),
+    Isize(isize),
+    Usize(usize),
+    Number(i128),
+    Char(char),
+    Str(String),
+    BorrowedStr(String),
+    String(String),
+    CollectStr(String),
+    Bytes(Vec<u8>),
+    BorrowedBytes(Vec<u8>),
+    ByteBuf(Vec<u8>),
+    None,
+    Some,
+    Unit,
+    UnitStruct {
+        name: Cow<'static, str>,
+    },
+    UnitVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+    },
+    NewtypeStruct {
+        name: Cow<'static, str>,
+    },
+    NewtypeVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+    },
+    Seq {
+        len: Option<usize>,
+    },
+    SeqEnd,
+    Tuple {
+        len: usize,
+    },
+    TupleEnd,
+    TupleStruct {
+        name: Cow<'static, str>,
+        len: usize,
+    },
+    TupleStructEnd,
+    TupleVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+        len: usize,
+    },
+    TupleVariantEnd,
+    Map {
+        len: Option<usize>,
+    },
+    MapEnd,
+    MapEntry,
+    Struct {
+        name: Cow<'static, str>,
+        len: usize,
+    },
+    StructEnd,
+    StructVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+        len: usize,
+    },
+    StructVariantEnd,
+    SkipStructField {
+        name: Cow<'static, str>,
+    },
+    Enum {
+        name: Cow<'static, str>,
+    },
+    VariantIndex(u32),
+    FieldIndex(u64),
+    Identifier(Cow<'static, str>),
+    IdentifierIndex(u64),
+    OneOf(&'static [Token<'static, 'static>]),
+    Matches(&'static Predicate),
+    Readable(bool),
+}
+
+impl OwnedToken {
+    /// Borrows this token as a [`Token`].
+    pub fn as_token(&self) -> Token<'_, '_> {
+        match *self {
+            OwnedToken::Bool(v) => Token::Bool(v),
+            OwnedToken::I8(v) => Token::I8(v),
+            OwnedToken::I16(v) => Token::I16(v),
+            OwnedToken::I32(v) => Token::I32(v),
+            OwnedToken::I64(v) => Token::I64(v),
+            OwnedToken::I128(v) => Token::I128(v),
+            OwnedToken::U8(v) => Token::U8(v),
+            OwnedToken::U16(v) => Token::U16(v),
+            OwnedToken::U32(v) => Token::U32(v),
+            OwnedToken::U64(v) => Token::U64(v),
+            OwnedToken::U128(v) => Token::U128(v),
+            OwnedToken::F32(v) => Token::F32(v),
+            OwnedToken::F64(v) => Token::F64(v),
+            OwnedToken::F32Bits(v) => Token::F32Bits(v),
+            OwnedToken::F64Bits(v) => Token::F64Bits(v),
+            OwnedToken::Isize(v) => Token::Isize(v),
+            OwnedToken::Usize(v) => Token::Usize(v),
+            OwnedToken::Number(v) => Token::Number(v),
+            OwnedToken::Char(v) => Token::Char(v),
+            OwnedToken::Str(ref v) => Token::Str(v),
+            OwnedToken::BorrowedStr(ref v) => Token::BorrowedStr(v),
+            OwnedToken::String(ref v) => Token::String(v),
+            OwnedToken::CollectStr(ref v) => Token::CollectStr(v),
+            OwnedToken::Bytes(ref v) => Token::Bytes(v),
+            OwnedToken::BorrowedBytes(ref v) => Token::BorrowedBytes(v),
+            OwnedToken::ByteBuf(ref v) => Token::ByteBuf(v),
+            OwnedToken::None => Token::None,
+            OwnedToken::Some => Token::Some,
+            OwnedToken::Unit => Token::Unit,
+            OwnedToken::UnitStruct { ref name } => Token::UnitStruct { name },
+            OwnedToken::UnitVariant {
+                ref name,
+                ref variant,
+            } => Token::UnitVariant { name, variant },
+            OwnedToken::NewtypeStruct { ref name } => Token::NewtypeStruct { name },
+            OwnedToken::NewtypeVariant {
+                ref name,
+                ref variant,
+            } => Token::NewtypeVariant { name, variant },
+            OwnedToken::Seq { len } => Token::Seq { len },
+            OwnedToken::SeqEnd => Token::SeqEnd,
+            OwnedToken::Tuple { len } => Token::Tuple { len },
+            OwnedToken::TupleEnd => Token::TupleEnd,
+            OwnedToken::TupleStruct { ref name, len } => Token::TupleStruct { name, len },
+            OwnedToken::TupleStructEnd => Token::TupleStructEnd,
+            OwnedToken::TupleVariant {
+                ref name,
+                ref variant,
+                len,
+            } => Token::TupleVariant { name, variant, len },
+            OwnedToken::TupleVariantEnd => Token::TupleVariantEnd,
+            OwnedToken::Map { len } => Token::Map { len },
+            OwnedToken::MapEnd => Token::MapEnd,
+            OwnedToken::MapEntry => Token::MapEntry,
+            OwnedToken::Struct { ref name, len } => Token::Struct { name, len },
+            OwnedToken::StructEnd => Token::StructEnd,
+            OwnedToken::StructVariant {
+                ref name,
+                ref variant,
+                len,
+            } => Token::StructVariant { name, variant, len },
+            OwnedToken::StructVariantEnd => Token::StructVariantEnd,
+            OwnedToken::SkipStructField { ref name } => Token::SkipStructField { name },
+            OwnedToken::Enum { ref name } => Token::Enum { name },
+            OwnedToken::VariantIndex(v) => Token::VariantIndex(v),
+            OwnedToken::FieldIndex(v) => Token::FieldIndex(v),
+            OwnedToken::Identifier(ref v) => Token::Identifier(v),
+            OwnedToken::IdentifierIndex(v) => Token::IdentifierIndex(v),
+            OwnedToken::OneOf(v) => Token::OneOf(v),
+            OwnedToken::Matches(v) => Token::Matches(v),
+            OwnedToken::Readable(v) => Token::Readable(v),
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedToken> for Token<'a, 'a> {
+    fn from(token: &'a OwnedToken) -> Self {
+        token.as_token()
+    }
+}
+
+impl From<Token<'_, '_>> for OwnedToken {
+    fn from(token: Token<'_, '_>) -> Self {
+        match token {
+            Token::Bool(v) => OwnedToken::Bool(v),
+            Token::I8(v) => OwnedToken::I8(v),
+            Token::I16(v) => OwnedToken::I16(v),
+            Token::I32(v) => OwnedToken::I32(v),
+            Token::I64(v) => OwnedToken::I64(v),
+            Token::I128(v) => OwnedToken::I128(v),
+            Token::U8(v) => OwnedToken::U8(v),
+            Token::U16(v) => OwnedToken::U16(v),
+            Token::U32(v) => OwnedToken::U32(v),
+            Token::U64(v) => OwnedToken::U64(v),
+            Token::U128(v) => OwnedToken::U128(v),
+            Token::F32(v) => OwnedToken::F32(v),
+            Token::F64(v) => OwnedToken::F64(v),
+            Token::F32Bits(v) => OwnedToken::F32Bits(v),
+            Token::F64Bits(v) => OwnedToken::F64Bits(v),
+            Token::Isize(v) => OwnedToken::Isize(v),
+            Token::Usize(v) => OwnedToken::Usize(v),
+            Token::Number(v) => OwnedToken::Number(v),
+            Token::Char(v) => OwnedToken::Char(v),
+            Token::Str(v) => OwnedToken::Str(v.to_owned()),
+            Token::BorrowedStr(v) => OwnedToken::BorrowedStr(v.to_owned()),
+            Token::String(v) => OwnedToken::String(v.to_owned()),
+            Token::CollectStr(v) => OwnedToken::CollectStr(v.to_owned()),
+            Token::Bytes(v) => OwnedToken::Bytes(v.to_vec()),
+            Token::BorrowedBytes(v) => OwnedToken::BorrowedBytes(v.to_vec()),
+            Token::ByteBuf(v) => OwnedToken::ByteBuf(v.to_vec()),
+            Token::None => OwnedToken::None,
+            Token::Some => OwnedToken::Some,
+            Token::Unit => OwnedToken::Unit,
+            Token::UnitStruct { name } => OwnedToken::UnitStruct { name: owned(name) },
+            Token::UnitVariant { name, variant } => OwnedToken::UnitVariant {
+                name: owned(name),
+                variant: owned(variant),
+            },
+            Token::NewtypeStruct { name } => OwnedToken::NewtypeStruct { name: owned(name) },
+            Token::NewtypeVariant { name, variant } => OwnedToken::NewtypeVariant {
+                name: owned(name),
+                variant: owned(variant),
+            },
+            Token::Seq { len } => OwnedToken::Seq { len },
+            Token::SeqEnd => OwnedToken::SeqEnd,
+            Token::Tuple { len } => OwnedToken::Tuple { len },
+            Token::TupleEnd => OwnedToken::TupleEnd,
+            Token::TupleStruct { name, len } => OwnedToken::TupleStruct {
+                name: owned(name),
+                len,
+            },
+            Token::TupleStructEnd => OwnedToken::TupleStructEnd,
+            Token::TupleVariant { name, variant, len } => OwnedToken::TupleVariant {
+                name: owned(name),
+                variant: owned(variant),
+                len,
+            },
+            Token::TupleVariantEnd => OwnedToken::TupleVariantEnd,
+            Token::Map { len } => OwnedToken::Map { len },
+            Token::MapEnd => OwnedToken::MapEnd,
+            Token::MapEntry => OwnedToken::MapEntry,
+            Token::Struct { name, len } => OwnedToken::Struct {
+                name: owned(name),
+                len,
+            },
+            Token::StructEnd => OwnedToken::StructEnd,
+            Token::StructVariant { name, variant, len } => OwnedToken::StructVariant {
+                name: owned(name),
+                variant: owned(variant),
+                len,
+            },
+            Token::StructVariantEnd => OwnedToken::StructVariantEnd,
+            Token::SkipStructField { name } => OwnedToken::SkipStructField { name: owned(name) },
+            Token::Enum { name } => OwnedToken::Enum { name: owned(name) },
+            Token::VariantIndex(v) => OwnedToken::VariantIndex(v),
+            Token::FieldIndex(v) => OwnedToken::FieldIndex(v),
+            Token::Identifier(v) => OwnedToken::Identifier(owned(v)),
+            Token::IdentifierIndex(v) => OwnedToken::IdentifierIndex(v),
+            Token::OneOf(v) => OwnedToken::OneOf(v),
+            Token::Matches(v) => OwnedToken::Matches(v),
+            Token::Readable(v) => OwnedToken::Readable(v),
+        }
+    }
+}
+
+fn owned(name: &str) -> Cow<'static, str> {
+    Cow::Owned(name.to_owned())
+}
+
+impl PartialEq for OwnedToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_token() == other.as_token()
+    }
+}
+
+impl Eq for OwnedToken {}
+
+impl PartialOrd for OwnedToken {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OwnedToken {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_token().cmp(&other.as_token())
+    }
+}
+
+impl Hash for OwnedToken {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_token().hash(state);
+    }
+}
+
+impl PartialEq<Token<'_, '_>> for OwnedToken {
+    fn eq(&self, other: &Token<'_, '_>) -> bool {
+        self.as_token() == *other
+    }
+}
+
+impl PartialEq<OwnedToken> for Token<'_, '_> {
+    fn eq(&self, other: &OwnedToken) -> bool {
+        *self == other.as_token()
+    }
+}
+
+impl Display for OwnedToken {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.as_token(), formatter)
     }
 }
 
 //
 
-#[derive(Copy, Clone, PartialEq)]
-pub(crate) enum EndToken {
+/// The token that closes a compound: a [`Token::SeqEnd`], [`Token::MapEnd`]
+/// or one of the other `*End` tokens.
+///
+/// A `Token` compares equal to the `EndToken` it is, so
+/// `token == EndToken::Seq` checks for a `Token::SeqEnd`.
+///
+/// ```
+/// # use serde_test::{EndToken, Token};
+/// #
+/// let header = Token::Map { len: None };
+/// assert_eq!(EndToken::for_header(header), Some(EndToken::Map));
+/// assert_eq!(EndToken::from_token(Token::MapEnd), Some(EndToken::Map));
+/// assert_eq!(EndToken::Map.token(), Token::MapEnd);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EndToken {
+    /// [`Token::SeqEnd`]
     Seq,
+    /// [`Token::TupleEnd`]
     Tuple,
+    /// [`Token::TupleStructEnd`]
     TupleStruct,
+    /// [`Token::TupleVariantEnd`]
     TupleVariant,
+    /// [`Token::MapEnd`]
     Map,
+    /// [`Token::StructEnd`]
     Struct,
+    /// [`Token::StructVariantEnd`]
     StructVariant,
 }
 
 impl EndToken {
-    // FIXME to_token? into_token?
-    pub(crate) fn token(self) -> Token<'static, 'static> {
+    /// Returns the `EndToken` that `token` is, or `None` if it does not
+    /// close a compound.
+    pub const fn from_token(token: Token<'_, '_>) -> Option<Self> {
+        match token {
+            Token::SeqEnd => Some(EndToken::Seq),
+            Token::TupleEnd => Some(EndToken::Tuple),
+            Token::TupleStructEnd => Some(EndToken::TupleStruct),
+            Token::TupleVariantEnd => Some(EndToken::TupleVariant),
+            Token::MapEnd => Some(EndToken::Map),
+            Token::StructEnd => Some(EndToken::Struct),
+            Token::StructVariantEnd => Some(EndToken::StructVariant),
+            _ => None,
+        }
+    }
+
+    /// Returns the `EndToken` that closes the compound opened by `header`,
+    /// or `None` if it does not open one.
+    pub const fn for_header(header: Token<'_, '_>) -> Option<Self> {
+        match header {
+            Token::Seq { .. } => Some(EndToken::Seq),
+            Token::Tuple { .. } => Some(EndToken::Tuple),
+            Token::TupleStruct { .. } => Some(EndToken::TupleStruct),
+            Token::TupleVariant { .. } => Some(EndToken::TupleVariant),
+            Token::Map { .. } => Some(EndToken::Map),
+            Token::Struct { .. } => Some(EndToken::Struct),
+            Token::StructVariant { .. } => Some(EndToken::StructVariant),
+            _ => None,
+        }
+    }
+
+    /// Returns the `Token` this end token is.
+    pub const fn token(self) -> Token<'static, 'static> {
         match self {
             EndToken::Seq => Token::SeqEnd,
             EndToken::Tuple => Token::TupleEnd,
@@ -625,3 +1786,235 @@ impl Display for EndToken {
         Display::fmt(&self.token(), f)
     }
 }
+
+/// The kind of a [`Token`]: its variant, without the payload.
+///
+/// Kinds are `Ord` and `Hash`, so tools can tally or filter tokens by kind
+/// without matching on every variant.
+///
+/// ```
+/// # use serde_test::{Token, TokenKind};
+/// #
+/// let kind = Token::MapEnd.kind();
+/// assert_eq!(kind, TokenKind::MapEnd);
+/// assert_eq!(kind.as_str(), "MapEnd");
+/// assert!(kind.is_end());
+/// assert!(TokenKind::Map.is_compound_start());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// [`Token::Bool`]
+    Bool,
+    /// [`Token::I8`]
+    I8,
+    /// [`Token::I16`]
+    I16,
+    /// [`Token::I32`]
+    I32,
+    /// [`Token::I64`]
+    I64,
+    /// [`Token::I128`]
+    I128,
+    /// [`Token::Isize`]
+    Isize,
+    /// [`Token::U8`]
+    U8,
+    /// [`Token::U16`]
+    U16,
+    /// [`Token::U32`]
+    U32,
+    /// [`Token::U64`]
+    U64,
+    /// [`Token::U128`]
+    U128,
+    /// [`Token::Usize`]
+    Usize,
+    /// [`Token::Number`]
+    Number,
+    /// [`Token::F32`]
+    F32,
+    /// [`Token::F64`]
+    F64,
+    /// [`Token::F32Bits`]
+    F32Bits,
+    /// [`Token::F64Bits`]
+    F64Bits,
+    /// [`Token::Char`]
+    Char,
+    /// [`Token::Str`]
+    Str,
+    /// [`Token::BorrowedStr`]
+    BorrowedStr,
+    /// [`Token::String`]
+    String,
+    /// [`Token::CollectStr`]
+    CollectStr,
+    /// [`Token::Bytes`]
+    Bytes,
+    /// [`Token::BorrowedBytes`]
+    BorrowedBytes,
+    /// [`Token::ByteBuf`]
+    ByteBuf,
+    /// [`Token::None`]
+    None,
+    /// [`Token::Some`]
+    Some,
+    /// [`Token::Unit`]
+    Unit,
+    /// [`Token::UnitStruct`]
+    UnitStruct,
+    /// [`Token::UnitVariant`]
+    UnitVariant,
+    /// [`Token::NewtypeStruct`]
+    NewtypeStruct,
+    /// [`Token::NewtypeVariant`]
+    NewtypeVariant,
+    /// [`Token::Seq`]
+    Seq,
+    /// [`Token::SeqEnd`]
+    SeqEnd,
+    /// [`Token::Tuple`]
+    Tuple,
+    /// [`Token::TupleEnd`]
+    TupleEnd,
+    /// [`Token::TupleStruct`]
+    TupleStruct,
+    /// [`Token::TupleStructEnd`]
+    TupleStructEnd,
+    /// [`Token::TupleVariant`]
+    TupleVariant,
+    /// [`Token::TupleVariantEnd`]
+    TupleVariantEnd,
+    /// [`Token::Map`]
+    Map,
+    /// [`Token::MapEnd`]
+    MapEnd,
+    /// [`Token::MapEntry`]
+    MapEntry,
+    /// [`Token::Struct`]
+    Struct,
+    /// [`Token::StructEnd`]
+    StructEnd,
+    /// [`Token::StructVariant`]
+    StructVariant,
+    /// [`Token::StructVariantEnd`]
+    StructVariantEnd,
+    /// [`Token::SkipStructField`]
+    SkipStructField,
+    /// [`Token::FieldIndex`]
+    FieldIndex,
+    /// [`Token::Identifier`]
+    Identifier,
+    /// [`Token::IdentifierIndex`]
+    IdentifierIndex,
+    /// [`Token::Enum`]
+    Enum,
+    /// [`Token::VariantIndex`]
+    VariantIndex,
+    /// [`Token::OneOf`]
+    OneOf,
+    /// [`Token::Matches`]
+    Matches,
+    /// [`Token::Readable`]
+    Readable,
+}
+
+impl TokenKind {
+    /// The name of the [`Token`] variant, such as `"U8"` or `"BorrowedStr"`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            TokenKind::Bool => "Bool",
+            TokenKind::I8 => "I8",
+            TokenKind::I16 => "I16",
+            TokenKind::I32 => "I32",
+            TokenKind::I64 => "I64",
+            TokenKind::I128 => "I128",
+            TokenKind::Isize => "Isize",
+            TokenKind::U8 => "U8",
+            TokenKind::U16 => "U16",
+            TokenKind::U32 => "U32",
+            TokenKind::U64 => "U64",
+            TokenKind::U128 => "U128",
+            TokenKind::Usize => "Usize",
+            TokenKind::Number => "Number",
+            TokenKind::F32 => "F32",
+            TokenKind::F64 => "F64",
+            TokenKind::F32Bits => "F32Bits",
+            TokenKind::F64Bits => "F64Bits",
+            TokenKind::Char => "Char",
+            TokenKind::Str => "Str",
+            TokenKind::BorrowedStr => "BorrowedStr",
+            TokenKind::String => "String",
+            TokenKind::CollectStr => "CollectStr",
+            TokenKind::Bytes => "Bytes",
+            TokenKind::BorrowedBytes => "BorrowedBytes",
+            TokenKind::ByteBuf => "ByteBuf",
+            TokenKind::None => "None",
+            TokenKind::Some => "Some",
+            TokenKind::Unit => "Unit",
+            TokenKind::UnitStruct => "UnitStruct",
+            TokenKind::UnitVariant => "UnitVariant",
+            TokenKind::NewtypeStruct => "NewtypeStruct",
+            TokenKind::NewtypeVariant => "NewtypeVariant",
+            TokenKind::Seq => "Seq",
+            TokenKind::SeqEnd => "SeqEnd",
+            TokenKind::Tuple => "Tuple",
+            TokenKind::TupleEnd => "TupleEnd",
+            TokenKind::TupleStruct => "TupleStruct",
+            TokenKind::TupleStructEnd => "TupleStructEnd",
+            TokenKind::TupleVariant => "TupleVariant",
+            TokenKind::TupleVariantEnd => "TupleVariantEnd",
+            TokenKind::Map => "Map",
+            TokenKind::MapEnd => "MapEnd",
+            TokenKind::MapEntry => "MapEntry",
+            TokenKind::Struct => "Struct",
+            TokenKind::StructEnd => "StructEnd",
+            TokenKind::StructVariant => "StructVariant",
+            TokenKind::StructVariantEnd => "StructVariantEnd",
+            TokenKind::SkipStructField => "SkipStructField",
+            TokenKind::FieldIndex => "FieldIndex",
+            TokenKind::Identifier => "Identifier",
+            TokenKind::IdentifierIndex => "IdentifierIndex",
+            TokenKind::Enum => "Enum",
+            TokenKind::VariantIndex => "VariantIndex",
+            TokenKind::OneOf => "OneOf",
+            TokenKind::Matches => "Matches",
+            TokenKind::Readable => "Readable",
+        }
+    }
+
+    /// Whether tokens of this kind close a compound.
+    pub const fn is_end(self) -> bool {
+        matches!(
+            self,
+            TokenKind::SeqEnd
+                | TokenKind::TupleEnd
+                | TokenKind::TupleStructEnd
+                | TokenKind::TupleVariantEnd
+                | TokenKind::MapEnd
+                | TokenKind::StructEnd
+                | TokenKind::StructVariantEnd
+        )
+    }
+
+    /// Whether tokens of this kind open a compound that an end token closes.
+    pub const fn is_compound_start(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Seq
+                | TokenKind::Tuple
+                | TokenKind::TupleStruct
+                | TokenKind::TupleVariant
+                | TokenKind::Map
+                | TokenKind::Struct
+                | TokenKind::StructVariant
+        )
+    }
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
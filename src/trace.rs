@@ -0,0 +1,30 @@
+//! Instrumentation with [`tracing`], which compiles to nothing without the
+//! `tracing` feature.
+//!
+//! Every compound gets a span and every token consumed an event, both at the
+//! `TRACE` level, so that a subscriber shows how far a failing value got.
+
+use crate::token::{EndToken, Token};
+
+/// The span of a compound, left when this is dropped.
+pub(crate) struct CompoundSpan {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+/// Enters the span of the compound whose header is at `start` and which is
+/// closed by `end`.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn compound(pass: &'static str, start: usize, end: EndToken) -> CompoundSpan {
+    CompoundSpan {
+        #[cfg(feature = "tracing")]
+        _span: tracing::trace_span!("compound", pass, start, end = %end).entered(),
+    }
+}
+
+/// Records that `pass` consumed `token`, at `index`.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn token(pass: &'static str, index: usize, token: Token<'_, '_>) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(pass, index, token = %token, "token");
+}
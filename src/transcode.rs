@@ -0,0 +1,119 @@
+//! Transcoding between token streams and other formats with
+//! [`serde_transcode`].
+//!
+//! Requires the `transcode` feature.
+//!
+//! [`transcode_tokens`] writes a token stream out through any `Serializer`,
+//! such as a JSON or CBOR one, and [`tokens_from_deserializer`] reads a
+//! format's input back as tokens. Together they turn format-specific fixtures
+//! into token tests and the other way around.
+//!
+//! Both go through `deserialize_any`, so only what the data itself describes
+//! survives: struct and variant names are lost, and structs come out of a
+//! self-describing format as maps.
+//!
+//! ```
+//! # #[cfg(feature = "json")]
+//! # fn main() {
+//! # use serde::Deserialize;
+//! # use serde_test::transcode::tokens_from_deserializer;
+//! # use serde_test::{assert_de_tokens, Token};
+//! #
+//! #[derive(Deserialize, PartialEq, Debug)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! let mut json = serde_json::Deserializer::from_str(r#"{ "x": 1, "y": -2 }"#);
+//! let tokens = tokens_from_deserializer(&mut json).unwrap();
+//! assert_eq!(
+//!     tokens,
+//!     [
+//!         Token::Map { len: None },
+//!         Token::Str("x"),
+//!         Token::U64(1),
+//!         Token::Str("y"),
+//!         Token::I64(-2),
+//!         Token::MapEnd,
+//!     ],
+//! );
+//!
+//! assert_de_tokens(&Point { x: 1, y: -2 }, &tokens);
+//! # }
+//! # #[cfg(not(feature = "json"))]
+//! # fn main() {}
+//! ```
+
+use crate::capture::capture_tokens;
+use crate::de::Deserializer;
+use crate::error::Error;
+use crate::into_tokens::IntoTokens;
+use crate::token::OwnedToken;
+use serde::ser::{self, Serializer};
+use serde_transcode::Transcoder;
+
+/// Serializes the value that `tokens` describe with `serializer`.
+///
+/// The tokens are read through `deserialize_any`, so structs are written as
+/// maps and enums in the externally tagged form. Fails with the serializer's
+/// error if the tokens are malformed, are not exactly one value, or hold
+/// something the format cannot write.
+///
+/// ```
+/// # #[cfg(feature = "json")]
+/// # fn main() {
+/// # use serde_test::transcode::transcode_tokens;
+/// # use serde_test::Token;
+/// #
+/// let mut json = Vec::new();
+/// transcode_tokens(
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::Seq { len: Some(1) },
+///         Token::U8(1),
+///         Token::SeqEnd,
+///         Token::Str("b"),
+///         Token::None,
+///         Token::StructEnd,
+///     ],
+///     &mut serde_json::Serializer::new(&mut json),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(json, br#"{"a":[1],"b":null}"#);
+/// # }
+/// # #[cfg(not(feature = "json"))]
+/// # fn main() {}
+/// ```
+pub fn transcode_tokens<'test, 'de: 'test, S>(
+    tokens: impl IntoTokens<'test, 'de>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let tokens = tokens.into_tokens();
+    let mut de = Deserializer::new(&tokens);
+    let ok = serde_transcode::transcode(&mut de, serializer)?;
+    de.end().map_err(ser::Error::custom)?;
+    Ok(ok)
+}
+
+/// Returns the tokens for the value that `deserializer` reads, such as one
+/// parsing a format's fixture.
+///
+/// The tokens are those a `Serialize` impl would emit for the data the
+/// format describes, as recorded by [`capture_tokens`]: strings become
+/// [`Token::Str`], and maps and sequences have the len the format gives up
+/// front, if any. Errors from `deserializer` are returned as an [`Error`]
+/// with their message.
+///
+/// [`Token::Str`]: crate::Token::Str
+pub fn tokens_from_deserializer<'de, D>(deserializer: D) -> Result<Vec<OwnedToken>, Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    capture_tokens(&Transcoder::new(deserializer))
+}
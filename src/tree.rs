@@ -0,0 +1,366 @@
+use crate::group::{Step, Walker};
+use crate::token::{OwnedToken, Token};
+use crate::validate::{validate_tokens, TokenStreamError};
+use std::borrow::Cow;
+
+/// A token stream shaped as a tree, with the contents of every compound
+/// nested inside it.
+///
+/// A tree cannot be unbalanced: [`to_tokens`](TokenTree::to_tokens) emits
+/// each end token and counts each `len` itself. Going the other way,
+/// [`from_tokens`](TokenTree::from_tokens) only succeeds on a stream that
+/// [`validate_tokens`] accepts.
+///
+/// The tree does not keep [`Token::SkipStructField`], [`Token::Readable`] or
+/// [`Token::MapEntry`] markers, or the declared `len` of structs, which it
+/// takes to be the number of fields.
+///
+/// ```
+/// # use serde_test::{assert_tokens, OwnedToken, Token, TokenTree};
+/// #
+/// let tree = TokenTree::Struct {
+///     name: "S".into(),
+///     fields: vec![
+///         (
+///             TokenTree::Token(OwnedToken::Str("a".to_owned())),
+///             TokenTree::Seq {
+///                 known_len: true,
+///                 elements: vec![TokenTree::Some(Box::new(TokenTree::Token(OwnedToken::U8(1))))],
+///             },
+///         ),
+///     ],
+/// };
+///
+/// let tokens = tree.to_tokens();
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::Seq { len: Some(1) },
+///         Token::Some,
+///         Token::U8(1),
+///         Token::SeqEnd,
+///         Token::StructEnd,
+///     ],
+/// );
+///
+/// let tokens: Vec<Token> = tokens.iter().map(OwnedToken::as_token).collect();
+/// assert_eq!(TokenTree::from_tokens(&tokens), Ok(tree));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TokenTree {
+    /// A token that is a complete value on its own, such as `U8` or
+    /// `UnitVariant`.
+    ///
+    /// Holding a compound header or end token here defeats the point of the
+    /// tree; [`to_tokens`](TokenTree::to_tokens) emits it as is.
+    Token(OwnedToken),
+    /// [`Token::Some`] followed by the value.
+    Some(Box<TokenTree>),
+    /// [`Token::NewtypeStruct`] followed by the value.
+    NewtypeStruct {
+        name: Cow<'static, str>,
+        value: Box<TokenTree>,
+    },
+    /// [`Token::NewtypeVariant`] followed by the value.
+    NewtypeVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+        value: Box<TokenTree>,
+    },
+    /// [`Token::VariantIndex`] followed by the variant it identifies.
+    VariantIndex {
+        index: u32,
+        value: Box<TokenTree>,
+    },
+    /// [`Token::Enum`] followed by the variant identifier and the variant's
+    /// value. An identifier given as a bare index is
+    /// `TokenTree::Token(OwnedToken::VariantIndex(index))`.
+    Enum {
+        name: Cow<'static, str>,
+        variant: Box<TokenTree>,
+        value: Box<TokenTree>,
+    },
+    /// A sequence, whose header declares its `len` if `known_len` is set.
+    Seq {
+        known_len: bool,
+        elements: Vec<TokenTree>,
+    },
+    Tuple {
+        elements: Vec<TokenTree>,
+    },
+    TupleStruct {
+        name: Cow<'static, str>,
+        elements: Vec<TokenTree>,
+    },
+    TupleVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+        elements: Vec<TokenTree>,
+    },
+    /// A map of key/value pairs, whose header declares its `len` if
+    /// `known_len` is set.
+    Map {
+        known_len: bool,
+        entries: Vec<(TokenTree, TokenTree)>,
+    },
+    Struct {
+        name: Cow<'static, str>,
+        fields: Vec<(TokenTree, TokenTree)>,
+    },
+    StructVariant {
+        name: Cow<'static, str>,
+        variant: Cow<'static, str>,
+        fields: Vec<(TokenTree, TokenTree)>,
+    },
+}
+
+impl TokenTree {
+    /// Builds the tree of a stream describing exactly one value.
+    ///
+    /// Fails with the same error as [`validate_tokens`] on a malformed stream.
+    ///
+    /// ```
+    /// # use serde_test::{Token, TokenStreamError, TokenTree};
+    /// #
+    /// assert_eq!(
+    ///     TokenTree::from_tokens(&[Token::Tuple { len: 1 }, Token::Unit]),
+    ///     Err(TokenStreamError::Unterminated {
+    ///         index: 0,
+    ///         expected: Token::TupleEnd,
+    ///     }),
+    /// );
+    /// ```
+    pub fn from_tokens(tokens: &[Token<'_, '_>]) -> Result<Self, TokenStreamError> {
+        validate_tokens(tokens)?;
+        Ok(build(tokens))
+    }
+
+    /// Flattens the tree into a token stream.
+    pub fn to_tokens(&self) -> Vec<OwnedToken> {
+        let mut tokens = Vec::new();
+        self.write(&mut tokens);
+        tokens
+    }
+
+    fn write(&self, out: &mut Vec<OwnedToken>) {
+        match self {
+            TokenTree::Token(token) => out.push(token.clone()),
+            TokenTree::Some(value) => {
+                out.push(OwnedToken::Some);
+                value.write(out);
+            }
+            TokenTree::NewtypeStruct { name, value } => {
+                out.push(OwnedToken::NewtypeStruct { name: name.clone() });
+                value.write(out);
+            }
+            TokenTree::NewtypeVariant {
+                name,
+                variant,
+                value,
+            } => {
+                out.push(OwnedToken::NewtypeVariant {
+                    name: name.clone(),
+                    variant: variant.clone(),
+                });
+                value.write(out);
+            }
+            TokenTree::VariantIndex { index, value } => {
+                out.push(OwnedToken::VariantIndex(*index));
+                value.write(out);
+            }
+            TokenTree::Enum {
+                name,
+                variant,
+                value,
+            } => {
+                out.push(OwnedToken::Enum { name: name.clone() });
+                variant.write(out);
+                value.write(out);
+            }
+            TokenTree::Seq {
+                known_len,
+                elements,
+            } => {
+                let len = Some(elements.len()).filter(|_| *known_len);
+                out.push(OwnedToken::Seq { len });
+                write_elements(elements, out);
+                out.push(OwnedToken::SeqEnd);
+            }
+            TokenTree::Tuple { elements } => {
+                out.push(OwnedToken::Tuple {
+                    len: elements.len(),
+                });
+                write_elements(elements, out);
+                out.push(OwnedToken::TupleEnd);
+            }
+            TokenTree::TupleStruct { name, elements } => {
+                out.push(OwnedToken::TupleStruct {
+                    name: name.clone(),
+                    len: elements.len(),
+                });
+                write_elements(elements, out);
+                out.push(OwnedToken::TupleStructEnd);
+            }
+            TokenTree::TupleVariant {
+                name,
+                variant,
+                elements,
+            } => {
+                out.push(OwnedToken::TupleVariant {
+                    name: name.clone(),
+                    variant: variant.clone(),
+                    len: elements.len(),
+                });
+                write_elements(elements, out);
+                out.push(OwnedToken::TupleVariantEnd);
+            }
+            TokenTree::Map { known_len, entries } => {
+                let len = Some(entries.len()).filter(|_| *known_len);
+                out.push(OwnedToken::Map { len });
+                write_entries(entries, out);
+                out.push(OwnedToken::MapEnd);
+            }
+            TokenTree::Struct { name, fields } => {
+                out.push(OwnedToken::Struct {
+                    name: name.clone(),
+                    len: fields.len(),
+                });
+                write_entries(fields, out);
+                out.push(OwnedToken::StructEnd);
+            }
+            TokenTree::StructVariant {
+                name,
+                variant,
+                fields,
+            } => {
+                out.push(OwnedToken::StructVariant {
+                    name: name.clone(),
+                    variant: variant.clone(),
+                    len: fields.len(),
+                });
+                write_entries(fields, out);
+                out.push(OwnedToken::StructVariantEnd);
+            }
+        }
+    }
+}
+
+fn write_elements(elements: &[TokenTree], out: &mut Vec<OwnedToken>) {
+    for element in elements {
+        element.write(out);
+    }
+}
+
+fn write_entries(entries: &[(TokenTree, TokenTree)], out: &mut Vec<OwnedToken>) {
+    for (key, value) in entries {
+        key.write(out);
+        value.write(out);
+    }
+}
+
+/// Builds a tree from a stream already checked by [`validate_tokens`].
+///
+/// The values still being read are kept on a stack of their own, in step
+/// with the walker, so that deep streams do not overflow the call stack.
+fn build(tokens: &[Token<'_, '_>]) -> TokenTree {
+    // The header of each open value, and the values read inside it so far.
+    let mut open: Vec<(Token<'_, '_>, Vec<TokenTree>)> = Vec::new();
+    let mut walker = Walker::new();
+    for (i, &token) in tokens.iter().enumerate() {
+        let mut value = match walker.step(i, token) {
+            Ok(Step::Marker) => continue,
+            Ok(Step::Open) => {
+                open.push((token, Vec::new()));
+                continue;
+            }
+            Ok(Step::Value) => TokenTree::Token(token.into()),
+            Ok(Step::Close { .. }) => {
+                let (header, values) = open.pop().unwrap();
+                finish(header, values)
+            }
+            Err(_) => unreachable!("the stream was validated"),
+        };
+        // The value completes every wrapper that was owed only it.
+        loop {
+            match open.last_mut() {
+                Some((_, values)) => values.push(value),
+                None => return value,
+            }
+            if open.len() == walker.depth() {
+                break;
+            }
+            let (header, values) = open.pop().unwrap();
+            value = finish(header, values);
+        }
+    }
+    unreachable!("the stream was validated")
+}
+
+/// The tree of the value opened by `header`, which contained `values`.
+fn finish(header: Token<'_, '_>, values: Vec<TokenTree>) -> TokenTree {
+    let mut values = values.into_iter();
+    let mut boxed = || Box::new(values.next().unwrap());
+    match header {
+        Token::Some => TokenTree::Some(boxed()),
+        Token::NewtypeStruct { name } => TokenTree::NewtypeStruct {
+            name: name.to_owned().into(),
+            value: boxed(),
+        },
+        Token::NewtypeVariant { name, variant } => TokenTree::NewtypeVariant {
+            name: name.to_owned().into(),
+            variant: variant.to_owned().into(),
+            value: boxed(),
+        },
+        Token::VariantIndex(index) => TokenTree::VariantIndex {
+            index,
+            value: boxed(),
+        },
+        Token::Enum { name } => TokenTree::Enum {
+            name: name.to_owned().into(),
+            variant: boxed(),
+            value: boxed(),
+        },
+        Token::Seq { len } => TokenTree::Seq {
+            known_len: len.is_some(),
+            elements: values.collect(),
+        },
+        Token::Tuple { .. } => TokenTree::Tuple {
+            elements: values.collect(),
+        },
+        Token::TupleStruct { name, .. } => TokenTree::TupleStruct {
+            name: name.to_owned().into(),
+            elements: values.collect(),
+        },
+        Token::TupleVariant { name, variant, .. } => TokenTree::TupleVariant {
+            name: name.to_owned().into(),
+            variant: variant.to_owned().into(),
+            elements: values.collect(),
+        },
+        Token::Map { len } => TokenTree::Map {
+            known_len: len.is_some(),
+            entries: pairs(values),
+        },
+        Token::Struct { name, .. } => TokenTree::Struct {
+            name: name.to_owned().into(),
+            fields: pairs(values),
+        },
+        Token::StructVariant { name, variant, .. } => TokenTree::StructVariant {
+            name: name.to_owned().into(),
+            variant: variant.to_owned().into(),
+            fields: pairs(values),
+        },
+        _ => unreachable!("only headers open a value"),
+    }
+}
+
+/// Pairs up the keys and values of a map or struct.
+fn pairs(mut values: impl Iterator<Item = TokenTree>) -> Vec<(TokenTree, TokenTree)> {
+    let mut entries = Vec::new();
+    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+        entries.push((key, value));
+    }
+    entries
+}
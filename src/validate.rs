@@ -0,0 +1,270 @@
+use crate::group::{Malformed, Missing, Step, Walker};
+use crate::token::Token;
+use std::fmt::{self, Display, Formatter};
+
+/// The reason a token stream was rejected by [`validate_tokens`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TokenStreamError {
+    /// An end token appeared where a value was expected.
+    UnexpectedEnd {
+        index: usize,
+        found: Token<'static, 'static>,
+    },
+    /// A compound was closed by the end token of a different kind of
+    /// compound.
+    MismatchedEnd {
+        index: usize,
+        expected: Token<'static, 'static>,
+        found: Token<'static, 'static>,
+    },
+    /// The stream ended before the compound starting at `index` was closed.
+    Unterminated {
+        index: usize,
+        expected: Token<'static, 'static>,
+    },
+    /// The stream ended where a value was expected.
+    MissingValue { index: usize },
+    /// The compound starting at `index` declares a `len` that differs from
+    /// the number of elements it contains.
+    LenMismatch {
+        index: usize,
+        declared: usize,
+        actual: usize,
+    },
+    /// Tokens remain after the first complete value.
+    TrailingTokens { index: usize, remaining: usize },
+    /// The [`Token::OneOf`] at `index` lists no alternatives, or one that is
+    /// not a value on its own.
+    InvalidOneOf { index: usize },
+}
+
+impl TokenStreamError {
+    /// The index of the token at which the problem was detected.
+    pub fn index(&self) -> usize {
+        match *self {
+            TokenStreamError::UnexpectedEnd { index, .. }
+            | TokenStreamError::MismatchedEnd { index, .. }
+            | TokenStreamError::Unterminated { index, .. }
+            | TokenStreamError::MissingValue { index }
+            | TokenStreamError::LenMismatch { index, .. }
+            | TokenStreamError::TrailingTokens { index, .. }
+            | TokenStreamError::InvalidOneOf { index } => index,
+        }
+    }
+}
+
+impl Display for TokenStreamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenStreamError::UnexpectedEnd { index, found } => {
+                write!(f, "unexpected Token::{} at index {}", found, index)
+            }
+            TokenStreamError::MismatchedEnd {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected Token::{} but found Token::{} at index {}",
+                expected, found, index,
+            ),
+            TokenStreamError::Unterminated { index, expected } => write!(
+                f,
+                "compound at index {} is never closed by Token::{}",
+                index, expected,
+            ),
+            TokenStreamError::MissingValue { index } => {
+                write!(
+                    f,
+                    "expected a value at index {} but the tokens ended",
+                    index
+                )
+            }
+            TokenStreamError::LenMismatch {
+                index,
+                declared,
+                actual,
+            } => write!(
+                f,
+                "compound at index {} declares len {} but contains {} elements",
+                index, declared, actual,
+            ),
+            TokenStreamError::TrailingTokens { index, remaining } => write!(
+                f,
+                "{} trailing tokens after the value ending at index {}",
+                remaining, index,
+            ),
+            TokenStreamError::InvalidOneOf { index } => write!(
+                f,
+                "Token::OneOf at index {} must list at least one token, each a value on its own",
+                index,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TokenStreamError {}
+
+/// Checks that `tokens` describe exactly one well-formed value.
+///
+/// Every compound must be closed by its matching end token, maps and structs
+/// must consist of key/value pairs, and the declared `len` of sequences,
+/// tuples and maps must match the number of elements present.
+/// [`Token::SkipStructField`], [`Token::Readable`] and [`Token::MapEntry`]
+/// markers are ignored.
+///
+/// The `assert_*` functions run this automatically, so a malformed stream is
+/// reported before any `Serialize` or `Deserialize` impl sees it. Nesting
+/// is tracked without recursion, so arbitrarily deep streams are fine.
+///
+/// ```
+/// # use serde_test::{validate_tokens, Token, TokenStreamError};
+/// #
+/// assert_eq!(
+///     validate_tokens(&[
+///         Token::Seq { len: Some(1) },
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ]),
+///     Err(TokenStreamError::MismatchedEnd {
+///         index: 2,
+///         expected: Token::SeqEnd,
+///         found: Token::StructEnd,
+///     }),
+/// );
+///
+/// let mut deep = vec![Token::Seq { len: Some(1) }; 200_000];
+/// deep.push(Token::Unit);
+/// assert_eq!(
+///     validate_tokens(&deep),
+///     Err(TokenStreamError::Unterminated {
+///         index: 199_999,
+///         expected: Token::SeqEnd,
+///     }),
+/// );
+/// ```
+pub fn validate_tokens(tokens: &[Token<'_, '_>]) -> Result<(), TokenStreamError> {
+    Validator::new(tokens, false).validate()
+}
+
+/// Like [`validate_tokens`], but accepts a stream that stops partway through
+/// a value and does not check declared lens. Used by the error assertions,
+/// whose streams only need to cover the tokens consumed before the failure.
+pub(crate) fn validate_token_prefix(tokens: &[Token<'_, '_>]) -> Result<(), TokenStreamError> {
+    Validator::new(tokens, true).validate()
+}
+
+enum Stop {
+    Truncated,
+    Invalid(TokenStreamError),
+}
+
+impl From<TokenStreamError> for Stop {
+    fn from(err: TokenStreamError) -> Self {
+        Stop::Invalid(err)
+    }
+}
+
+struct Validator<'a, 'test, 'de> {
+    tokens: &'a [Token<'test, 'de>],
+    pos: usize,
+    prefix: bool,
+}
+
+impl<'a, 'test, 'de> Validator<'a, 'test, 'de> {
+    fn new(tokens: &'a [Token<'test, 'de>], prefix: bool) -> Self {
+        Validator {
+            tokens,
+            pos: 0,
+            prefix,
+        }
+    }
+
+    fn validate(mut self) -> Result<(), TokenStreamError> {
+        if self.prefix && self.peek().is_none() {
+            return Ok(());
+        }
+        match self.value() {
+            Ok(()) => {}
+            Err(Stop::Truncated) => return Ok(()),
+            Err(Stop::Invalid(err)) => return Err(err),
+        }
+        let end = self.pos;
+        if self.peek().is_some() {
+            return Err(TokenStreamError::TrailingTokens {
+                index: end,
+                remaining: self.tokens.len() - end,
+            });
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Option<Token<'test, 'de>> {
+        while let Some(&token) = self.tokens.get(self.pos) {
+            if let Token::SkipStructField { .. } | Token::Readable(_) | Token::MapEntry = token {
+                self.pos += 1;
+            } else {
+                return Some(token);
+            }
+        }
+        None
+    }
+
+    fn truncated(&self, err: TokenStreamError) -> Stop {
+        if self.prefix {
+            Stop::Truncated
+        } else {
+            Stop::Invalid(err)
+        }
+    }
+
+    /// Reads one whole value, including everything nested inside it.
+    fn value(&mut self) -> Result<(), Stop> {
+        let mut walker = Walker::new();
+        while !walker.is_done() {
+            let index = self.pos;
+            let token = match self.tokens.get(index) {
+                Some(&token) => token,
+                None => {
+                    let err = match walker.missing() {
+                        Missing::End { start, end } => TokenStreamError::Unterminated {
+                            index: start,
+                            expected: end.token(),
+                        },
+                        Missing::Value => TokenStreamError::MissingValue { index },
+                    };
+                    return Err(self.truncated(err));
+                }
+            };
+            self.pos += 1;
+
+            let err = match walker.step(index, token) {
+                Ok(Step::Close {
+                    start,
+                    len: Some(declared),
+                    count,
+                }) if !self.prefix && declared != count => TokenStreamError::LenMismatch {
+                    index: start,
+                    declared,
+                    actual: count,
+                },
+                Ok(_) => continue,
+                Err(Malformed::UnexpectedEnd(found)) => TokenStreamError::UnexpectedEnd {
+                    index,
+                    found: found.token(),
+                },
+                Err(Malformed::MismatchedEnd { expected, found }) => {
+                    TokenStreamError::MismatchedEnd {
+                        index,
+                        expected: expected.token(),
+                        found: found.token(),
+                    }
+                }
+                Err(Malformed::InvalidOneOf) => TokenStreamError::InvalidOneOf { index },
+            };
+            return Err(Stop::Invalid(err));
+        }
+        Ok(())
+    }
+}